@@ -1,12 +1,9 @@
 //! Shared audio bus that owns the engine channels and singleton client access
 
 use crate::audio::{Client, Command, Snapshot};
+use crate::error::{MugenError, Result};
 use device_query::Keycode;
-use std::{
-    collections::HashSet,
-    error::Error,
-    io::{Error as IoError, ErrorKind},
-};
+use std::collections::HashSet;
 use tokio::sync::{Mutex, OnceCell, mpsc, watch};
 
 pub struct Bus {
@@ -19,11 +16,15 @@ pub struct Bus {
 static AUDIO: OnceCell<Bus> = OnceCell::const_new();
 
 pub async fn client() -> &'static Client {
+    init(Snapshot::default()).await
+}
+
+/// Creates the bus starting from `snapshot`; a no-op once the bus exists
+pub async fn init(snapshot: Snapshot) -> &'static Client {
     &AUDIO
         .get_or_init(|| async {
             let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
 
-            let snapshot = Snapshot::default();
             let (snapshot_tx, snapshot_rx) = watch::channel(snapshot);
             let (held_keys_tx, held_keys_rx) = watch::channel(HashSet::<Keycode>::new());
 
@@ -38,23 +39,18 @@ pub async fn client() -> &'static Client {
         .client
 }
 
-pub async fn take_runtime_channels() -> Result<
-    (
-        mpsc::UnboundedReceiver<Command>,
-        watch::Sender<Snapshot>,
-        watch::Sender<HashSet<Keycode>>,
-        Snapshot,
-    ),
-    Box<dyn Error + Send + Sync>,
-> {
+pub async fn take_runtime_channels() -> Result<(
+    mpsc::UnboundedReceiver<Command>,
+    watch::Sender<Snapshot>,
+    watch::Sender<HashSet<Keycode>>,
+    Snapshot,
+)> {
     let bus = AUDIO
         .get()
-        .ok_or_else(|| IoError::new(ErrorKind::NotFound, "call audio::client() first"))?;
+        .ok_or(MugenError::Engine("call audio::client() first"))?;
 
     let mut commands = bus.commands.lock().await;
-    let cmd_rx = commands
-        .take()
-        .ok_or_else(|| IoError::new(ErrorKind::AlreadyExists, "audio engine already taken"))?;
+    let cmd_rx = commands.take().ok_or(MugenError::Engine("already taken"))?;
 
     let snapshot = bus.snapshot_tx.borrow().clone();
 
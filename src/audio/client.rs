@@ -1,11 +1,37 @@
 //! Client API for sending commands to the audio engine and subscribing to state
 
 use crate::audio::{Command, Snapshot};
+use crate::patch::bend::BendReturn;
+use crate::patch::chain::Fx;
 use crate::patch::effects::adsr::Adsr;
+use crate::patch::effects::am::Am;
+use crate::patch::effects::bitcrush::Bitcrush;
+use crate::patch::effects::delay::Delay;
 use crate::patch::effects::gain::Gain;
+use crate::patch::effects::highpass::HighPass;
 use crate::patch::effects::lfo_amp::LfoAmp;
 use crate::patch::effects::lowpass::LowPass;
-use crate::patch::oscilators::basic::Wave;
+use crate::patch::effects::noise_gate::NoiseGate;
+use crate::patch::effects::pan::Pan;
+use crate::patch::effects::reverb::Reverb;
+use crate::patch::effects::ringmod::RingMod;
+use crate::patch::effects::sample_hold::SampleHold;
+use crate::patch::lfo::Lfo;
+use crate::patch::oscilators::basic::{Sub, Wave};
+use crate::patch::oscilators::fm::Fm;
+use crate::patch::oscilators::stack::Layer;
+use crate::patch::oscilators::wavetable::Wavetable;
+use crate::patch::unison::Unison;
+use crate::play::VoiceMode;
+use crate::play::arp::Arp;
+use crate::play::capture::Capture;
+use crate::play::chord::Chord;
+use crate::play::input::InputEngine;
+use crate::play::scale::Quantizer;
+use crate::play::sequencer::Sequence;
+use crate::play::tempo::Tempo;
+use crate::play::tuning::Tuning;
+use crate::play::velocity::Velocity;
 use device_query::Keycode;
 use std::collections::HashSet;
 use tokio::sync::{mpsc, watch};
@@ -15,6 +41,8 @@ pub struct Client {
     tx: mpsc::UnboundedSender<Command>,
     snapshot_rx: watch::Receiver<Snapshot>,
     held_keys_rx: watch::Receiver<HashSet<Keycode>>,
+    capture: Capture,
+    voice_capture: Capture,
 }
 
 impl Client {
@@ -27,6 +55,8 @@ impl Client {
             tx,
             snapshot_rx,
             held_keys_rx,
+            capture: Capture::new(),
+            voice_capture: Capture::new(),
         }
     }
 
@@ -47,6 +77,61 @@ impl Client {
         self.send(Command::SetWave(wave));
     }
 
+    pub fn set_crossfade(&self, crossfade_s: f32) {
+        self.send(Command::SetCrossfade(crossfade_s));
+    }
+
+    pub fn set_pulse_duty(&self, duty: f32) {
+        self.send(Command::SetPulseDuty(duty));
+    }
+
+    pub fn set_split(&self, split: bool) {
+        self.send(Command::SetSplit(split));
+    }
+
+    pub fn set_split_wave(&self, wave: Wave) {
+        self.send(Command::SetSplitWave(wave));
+    }
+
+    /// Envelope and effect changes go to the top-row zone while `edit_split`
+    pub fn set_edit_split(&self, edit_split: bool) {
+        self.send(Command::SetEditSplit(edit_split));
+    }
+
+    /// Pins the scope to the next sounding voice, after the newest it goes
+    /// back to the whole mix
+    pub fn pin_next_voice(&self) {
+        self.send(Command::PinNextVoice);
+    }
+
+    /// Releases notes held longer than `max_ms`, `None` disables the limit
+    pub fn set_max_note(&self, max_ms: Option<u64>) {
+        self.send(Command::SetMaxNote(max_ms));
+    }
+
+    pub fn set_layers(&self, layers: Vec<Layer>) {
+        self.send(Command::SetLayers(layers));
+    }
+
+    /// Table the `Table` wave plays
+    pub fn set_wavetable(&self, table: Wavetable) {
+        self.send(Command::SetWavetable(table));
+    }
+
+    /// Phase modulation of the patch oscillator, index 0 turns it off
+    pub fn set_fm(&self, fm: Fm) {
+        self.send(Command::SetFm(fm));
+    }
+
+    /// Oscillator an octave under every note, mixed in at `sub.level`
+    pub fn set_sub(&self, sub: Sub) {
+        self.send(Command::SetSub(sub));
+    }
+
+    pub fn set_unison(&self, unison: Unison) {
+        self.send(Command::SetUnison(unison));
+    }
+
     pub fn set_adsr(&self, adsr: Adsr) {
         self.send(Command::SetAdsr(adsr));
     }
@@ -59,20 +144,179 @@ impl Client {
         self.send(Command::SetLfoAmp(lfo_amp));
     }
 
+    pub fn set_lfo(&self, lfo: Lfo) {
+        self.send(Command::SetLfo(lfo));
+    }
+
+    pub fn set_am(&self, am: Am) {
+        self.send(Command::SetAm(am));
+    }
+
+    pub fn set_ringmod(&self, ringmod: RingMod) {
+        self.send(Command::SetRingMod(ringmod));
+    }
+
+    pub fn set_bitcrush(&self, bitcrush: Bitcrush) {
+        self.send(Command::SetBitcrush(bitcrush));
+    }
+
+    pub fn set_sample_hold(&self, sample_hold: SampleHold) {
+        self.send(Command::SetSampleHold(sample_hold));
+    }
+
+    pub fn set_highpass(&self, highpass: HighPass) {
+        self.send(Command::SetHighPass(highpass));
+    }
+
     pub fn set_lowpass(&self, lowpass: LowPass) {
         self.send(Command::SetLowPass(lowpass));
     }
 
+    pub fn set_delay(&self, delay: Delay) {
+        self.send(Command::SetDelay(delay));
+    }
+
+    pub fn set_reverb(&self, reverb: Reverb) {
+        self.send(Command::SetReverb(reverb));
+    }
+
+    pub fn set_noise_gate(&self, noise_gate: NoiseGate) {
+        self.send(Command::SetNoiseGate(noise_gate));
+    }
+
+    pub fn set_pan(&self, pan: Pan) {
+        self.send(Command::SetPan(pan));
+    }
+
+    /// Swaps the whole chain ahead of the envelope in one go, for a
+    /// reordered or trimmed chain. Notes already sounding keep their chain
+    pub fn set_fx_chain(&self, effects: Vec<Fx>) {
+        self.send(Command::SetFxChain(effects));
+    }
+
     pub fn set_octave(&self, octave: i32) {
         self.send(Command::SetOctave(octave));
     }
 
-    #[must_use] 
+    pub fn set_transpose(&self, semitones: i32) {
+        self.send(Command::SetTranspose(semitones));
+    }
+
+    pub fn set_tuning(&self, a4_hz: f32) {
+        self.send(Command::SetTuning(a4_hz));
+    }
+
+    /// Equal temperament or pure intervals up from the scale root
+    pub fn set_tuning_system(&self, tuning: Tuning) {
+        self.send(Command::SetTuningSystem(tuning));
+    }
+
+    /// MIDI note numbers, 60 is middle C
+    pub fn note_on(&self, note: u8, velocity: u8) {
+        self.send(Command::NoteOn { note, velocity });
+    }
+
+    pub fn note_off(&self, note: u8) {
+        self.send(Command::NoteOff(note));
+    }
+
+    /// While down, released notes keep ringing until the pedal comes up
+    pub fn set_sustain(&self, down: bool) {
+        self.send(Command::SetSustain(down));
+    }
+
+    /// Snaps the notes played from now on into a scale
+    pub fn set_quantizer(&self, quantizer: Quantizer) {
+        self.send(Command::SetQuantizer(quantizer));
+    }
+
+    /// In poly mode every key plays this chord on its note
+    pub fn set_chord(&self, chord: Chord) {
+        self.send(Command::SetChord(chord));
+    }
+
+    /// Velocity curve and the velocities computer keys play at
+    pub fn set_velocity(&self, velocity: Velocity) {
+        self.send(Command::SetVelocity(velocity));
+    }
+
+    pub fn set_arp(&self, arp: Arp) {
+        self.send(Command::SetArp(arp));
+    }
+
+    /// Shared by the arp and sequencer, the transport keeps its place
+    pub fn set_tempo(&self, tempo: Tempo) {
+        self.send(Command::SetTempo(tempo));
+    }
+
+    /// Steps and whether the sequencer is running
+    pub fn set_sequence(&self, sequence: Sequence) {
+        self.send(Command::SetSequence(sequence));
+    }
+
+    pub fn set_voice_mode(&self, mode: VoiceMode) {
+        self.send(Command::SetVoiceMode(mode));
+    }
+
+    /// Seconds the mono voice takes to slide to a new note
+    pub fn set_glide(&self, glide_s: f32) {
+        self.send(Command::SetGlide(glide_s));
+    }
+
+    pub fn set_bend(&self, amount: f32) {
+        self.send(Command::SetBend(amount));
+    }
+
+    /// Bends fully towards the sign of `amount` for as long as the key
+    /// keeps repeating, then returns to center
+    pub fn bend_key(&self, amount: f32) {
+        self.send(Command::BendKey(amount));
+    }
+
+    pub fn set_bend_range(&self, range_st: f32) {
+        self.send(Command::SetBendRange(range_st));
+    }
+
+    pub fn set_bend_return(&self, bend_return: BendReturn) {
+        self.send(Command::SetBendReturn(bend_return));
+    }
+
+    pub fn set_input_engine(&self, input: InputEngine) {
+        self.send(Command::SetInputEngine(input));
+    }
+
+    pub fn press_key(&self, keycode: Keycode, accent: bool) {
+        self.send(Command::PressKey { keycode, accent });
+    }
+
+    /// Starts a WAV recording of the output, or finishes the running one
+    pub fn toggle_recording(&self) {
+        self.send(Command::ToggleRecording);
+    }
+
+    /// Silences everything at once without a release, for stuck notes
+    pub fn all_notes_off(&self) {
+        self.send(Command::AllNotesOff);
+    }
+
+    #[must_use]
     pub fn subscribe(&self) -> watch::Receiver<Snapshot> {
         self.snapshot_rx.clone()
     }
 
-    #[must_use] 
+    /// Recent master output, filled once the engine has opened the stream
+    #[must_use]
+    pub fn capture(&self) -> &Capture {
+        &self.capture
+    }
+
+    /// The voice the scope is pinned to on its own, see `pin_next_voice`
+    #[must_use]
+    pub fn voice_capture(&self) -> &Capture {
+        &self.voice_capture
+    }
+
+    #[must_use]
     pub fn subscribe_held_keys(&self) -> watch::Receiver<HashSet<Keycode>> {
         self.held_keys_rx.clone()
     }
@@ -1,19 +1,105 @@
 //! Commands sent to the audio engine
 
+use crate::patch::bend::BendReturn;
+use crate::patch::chain::Fx;
 use crate::patch::effects::adsr::Adsr;
+use crate::patch::effects::am::Am;
+use crate::patch::effects::bitcrush::Bitcrush;
+use crate::patch::effects::delay::Delay;
 use crate::patch::effects::gain::Gain;
+use crate::patch::effects::highpass::HighPass;
 use crate::patch::effects::lfo_amp::LfoAmp;
 use crate::patch::effects::lowpass::LowPass;
-use crate::patch::oscilators::basic::Wave;
+use crate::patch::effects::noise_gate::NoiseGate;
+use crate::patch::effects::pan::Pan;
+use crate::patch::effects::reverb::Reverb;
+use crate::patch::effects::ringmod::RingMod;
+use crate::patch::effects::sample_hold::SampleHold;
+use crate::patch::lfo::Lfo;
+use crate::patch::oscilators::basic::{Sub, Wave};
+use crate::patch::oscilators::fm::Fm;
+use crate::patch::oscilators::stack::Layer;
+use crate::patch::oscilators::wavetable::Wavetable;
+use crate::patch::unison::Unison;
+use crate::play::VoiceMode;
+use crate::play::arp::Arp;
+use crate::play::chord::Chord;
+use crate::play::input::InputEngine;
+use crate::play::scale::Quantizer;
+use crate::play::sequencer::Sequence;
+use crate::play::tempo::Tempo;
+use crate::play::tuning::Tuning;
+use crate::play::velocity::Velocity;
+use device_query::Keycode;
 
 #[derive(Debug, Clone)]
 pub enum Command {
     SetVolume(f32),
     SetMuted(bool),
     SetWave(Wave),
+    SetCrossfade(f32),
+    SetPulseDuty(f32),
+    SetSplit(bool),
+    SetSplitWave(Wave),
+    /// Send envelope and effect changes to the split zone
+    SetEditSplit(bool),
+    SetLayers(Vec<Layer>),
+    SetSub(Sub),
+    SetWavetable(Wavetable),
+    SetFm(Fm),
+    SetUnison(Unison),
     SetAdsr(Adsr),
     SetGain(Gain),
     SetLfoAmp(LfoAmp),
+    SetLfo(Lfo),
+    SetAm(Am),
+    SetRingMod(RingMod),
+    SetBitcrush(Bitcrush),
+    SetSampleHold(SampleHold),
+    SetHighPass(HighPass),
     SetLowPass(LowPass),
+    SetDelay(Delay),
+    SetReverb(Reverb),
+    SetNoiseGate(NoiseGate),
+    SetPan(Pan),
+    /// Effects ahead of the envelope, in signal order
+    SetFxChain(Vec<Fx>),
     SetOctave(i32),
+    SetTranspose(i32),
+    SetTuning(f32),
+    /// Intervals are counted from the quantizer's root
+    SetTuningSystem(Tuning),
+    SetInputEngine(InputEngine),
+    SetMaxNote(Option<u64>),
+    PinNextVoice,
+    /// `accent` plays the note at the accent velocity
+    PressKey {
+        keycode: Keycode,
+        accent: bool,
+    },
+    NoteOn {
+        note: u8,
+        velocity: u8,
+    },
+    NoteOff(u8),
+    SetVoiceMode(VoiceMode),
+    SetGlide(f32),
+    /// Bend in -1..1 that stays until the next one, e.g. from a MIDI wheel
+    SetBend(f32),
+    /// Bend in -1..1 that returns to center shortly after the last one
+    BendKey(f32),
+    SetBendRange(f32),
+    SetBendReturn(BendReturn),
+    SetSustain(bool),
+    SetQuantizer(Quantizer),
+    SetChord(Chord),
+    SetVelocity(Velocity),
+    /// Tempo the arp and sequencer both follow
+    SetTempo(Tempo),
+    SetArp(Arp),
+    SetSequence(Sequence),
+    ToggleRecording,
+    /// Cuts every voice and forgets held keys, the pedal, the arp position
+    /// and the running sequence
+    AllNotesOff,
 }
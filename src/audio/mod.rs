@@ -7,9 +7,9 @@ mod runtime;
 mod snapshot;
 mod state;
 
-pub use bus::{Bus, client, take_runtime_channels};
+pub use bus::{Bus, client, init, take_runtime_channels};
 pub use client::Client;
 pub use command::Command;
-pub use runtime::run;
+pub use runtime::{run, test_tone};
 pub use snapshot::Snapshot;
 pub use state::State;
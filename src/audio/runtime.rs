@@ -1,26 +1,45 @@
 //! Audio engine runtime: polls input, handles commands, updates state, and controls playback
 
 use crate::audio::{self, Command, Snapshot, State};
-use crate::config::TICK;
-use crate::patch::Gate;
-use crate::play::Player;
-use crate::play::key::Key;
+use crate::config::{
+    A4_MAX_HZ, A4_MIN_HZ, ARP_SEED, ARP_STEPS_PER_BEAT, BEND_KEY_HOLD_MS, BEND_RANGE_MAX_ST,
+    BEND_RETURN_S, GLIDE_MAX_S, MIDI_NOTE_0_OCTAVE, OCTAVE_SHIFT_MAX, OCTAVE_SHIFT_MIN,
+    RECORD_STOP_TIMEOUT_MS, SEMITONES_PER_OCTAVE, SEQ_LENGTH, SEQ_STEPS_PER_BEAT, TEMPO_PPQ,
+    TERMINAL_HOLD_MS, TERMINAL_RELEASE_MS, TEST_TONE_TAIL_MS, TICK, TRANSPOSE_MAX,
+    VOICE_CUT_FADE_MS,
+};
+use crate::error::{MugenError, Result};
+use crate::patch::bend::BendReturn;
+use crate::patch::fade::{Cut, fade_out};
+use crate::patch::unison::Unison;
+use crate::patch::{Gate, Patch, Pitch, PitchTarget};
+use crate::play::arp::{Arp, Arpeggiator};
+use crate::play::input::{ControlKeys, InputEngine, KeyDebounce, StartupKeys, TerminalKeys};
+use crate::play::key::{Key, Note};
+use crate::play::recorder::Recorder;
+use crate::play::sequencer::Sequence;
+use crate::play::tempo::{self, Tempo};
+use crate::play::velocity::Velocity;
+use crate::play::{Player, StreamOptions, VoiceKey, VoiceMode};
 use device_query::{DeviceQuery, DeviceState, Keycode};
 use rodio::Sink;
-use std::error::Error;
 use std::{
     collections::HashSet,
+    io,
     sync::{
         Arc,
         atomic::{AtomicBool, Ordering},
     },
     thread::sleep,
-    time::Duration,
+    time::{Duration, Instant},
 };
-use tokio::{signal::ctrl_c, task};
+use tokio::sync::watch;
+use tokio::{signal::ctrl_c, task, time::interval};
 
 enum Event {
     KeysChanged(HashSet<Keycode>),
+    /// Tempo clock tick, with the transport position
+    Tick(u64),
     Exit,
 }
 
@@ -29,45 +48,419 @@ fn publish_snapshot(tx: &tokio::sync::watch::Sender<Snapshot>, state: &State) {
     let _ = tx.send(state.snapshot());
 }
 
-fn start_note(player: &mut Player, state: &State, keycode: Keycode) {
-    let Some(key) = Key::from_keycode(keycode) else {
+/// Frequency, patch and velocity gain for a held key, `None` if it plays no note
+fn note_for(state: &State, key: VoiceKey) -> Option<(f32, Patch, f32)> {
+    let (key, patch, gain) = key_for(state, key)?;
+    Some((state.frequency(key), patch, gain))
+}
+
+/// Note, patch and velocity gain for a held key. The note is snapped into
+/// the scale after shifting
+fn key_for(state: &State, key: VoiceKey) -> Option<(Key, Patch, f32)> {
+    match key {
+        VoiceKey::Key(keycode) => {
+            let semitones = state.octave * SEMITONES_PER_OCTAVE + state.transpose;
+            let key = Key::from_keycode(keycode)?.transpose(semitones);
+            let key = state.quantizer.quantize(key);
+
+            let gain = state.velocity.key_gain(state.accented.contains(&keycode));
+
+            Some((key, state.patch_for(keycode).clone(), gain))
+        }
+        // MIDI notes skip the keyboard octave shift but follow transpose and tuning
+        VoiceKey::Midi(note) => {
+            let velocity = *state.held_midi.get(&note)?;
+            let semitones = i32::from(note) + state.transpose;
+            let key = Key::new(Note::C, MIDI_NOTE_0_OCTAVE).transpose(semitones);
+            let key = state.quantizer.quantize(key);
+
+            Some((key, state.main.patch.clone(), state.velocity.gain(velocity)))
+        }
+        VoiceKey::Mono | VoiceKey::Arp | VoiceKey::Seq => None,
+    }
+}
+
+/// A voice per chord tone, all under `key` so letting go of it stops the
+/// whole chord
+fn start_chord(player: &mut Player, state: &State, key: VoiceKey) {
+    let Some((root, patch, gain)) = key_for(state, key) else {
         return;
     };
 
-    let freq = key.transpose(state.octave * 12).frequency();
-    let gate: Gate = Arc::new(AtomicBool::new(true));
+    for tone in state.chord.tones(root, state.quantizer) {
+        start_voice(player, state, key, &patch, state.frequency(tone), gain);
+    }
+}
+
+/// Poly gives every key its own voice, or chord, the mono modes route all
+/// keys through the one `VoiceKey::Mono` voice and remember the press order
+fn press_note(player: &mut Player, state: &mut State, key: VoiceKey) {
+    let Some((note, patch, gain)) = key_for(state, key) else {
+        return;
+    };
+    let freq = state.frequency(note);
+    state.last_note = Some(note);
+
+    let was_sustained = state.sustained.contains(&key);
+    state.sustained.retain(|queued| *queued != key);
 
-    let sink = Sink::connect_new(player.stream.mixer());
-    sink.set_volume(state.volume);
+    // the arp picks held notes up on its own clock
+    if state.arp.enabled {
+        return;
+    }
 
-    if state.muted {
-        sink.pause();
+    if state.voice_mode == VoiceMode::Poly {
+        // replay a note the pedal is holding instead of stacking a second voice
+        if was_sustained {
+            player.stop_note(key);
+        }
+        start_chord(player, state, key);
+        return;
     }
 
-    sink.append(state.patch.build_voice(freq, gate.clone()));
-    player.add_voice(keycode, sink, gate);
+    let legato = state.voice_mode == VoiceMode::Legato && !state.mono_keys.is_empty();
+    state.mono_keys.retain(|held| *held != key);
+    state.mono_keys.push(key);
+
+    play_mono(player, state, &patch, freq, gain, legato);
 }
 
-fn restart_held_notes(player: &mut Player, state: &State) {
-    let held: Vec<_> = state.held_keys.iter().copied().collect();
+/// In the mono modes, letting go of the newest key falls back to the most
+/// recent key that is still down. With the pedal down the release is queued
+fn release_note(player: &mut Player, state: &mut State, key: VoiceKey) {
+    if state.sustain {
+        if !state.sustained.contains(&key) {
+            state.sustained.push(key);
+        }
+        return;
+    }
+
+    if state.arp.enabled {
+        return;
+    }
+
+    if state.voice_mode == VoiceMode::Poly {
+        player.stop_note(key);
+        return;
+    }
+
+    let was_newest = state.mono_keys.last() == Some(&key);
+    state.mono_keys.retain(|held| *held != key);
+    if !was_newest {
+        return;
+    }
+
+    match state
+        .mono_keys
+        .last()
+        .and_then(|prev| note_for(state, *prev))
+    {
+        Some((freq, patch, gain)) => {
+            let legato = state.voice_mode == VoiceMode::Legato;
+            play_mono(player, state, &patch, freq, gain, legato);
+        }
+        None => player.stop_note(VoiceKey::Mono),
+    }
+}
 
+/// Keys and MIDI notes that should be sounding, held down or sustained
+fn held_notes(state: &State) -> Vec<VoiceKey> {
+    let held = state.held_keys.iter().copied().map(VoiceKey::Key);
+    let midi = state.held_midi.keys().copied().map(VoiceKey::Midi);
+    // sustained MIDI notes are still in `held_midi`
+    let sustained = state
+        .sustained
+        .iter()
+        .copied()
+        .filter(|key| matches!(key, VoiceKey::Key(_)));
+
+    held.chain(midi).chain(sustained).collect()
+}
+
+/// Rebuilds the mono press order from whatever is held, after it went stale
+fn reset_mono_keys(state: &mut State) {
+    state.mono_keys = state
+        .held_keys
+        .iter()
+        .copied()
+        .map(VoiceKey::Key)
+        .chain(state.held_midi.keys().copied().map(VoiceKey::Midi))
+        .filter(|key| note_for(state, *key).is_some())
+        .collect();
+}
+
+/// Keys still physically down sound again on their next change, the
+/// global engine reports them afresh
+fn all_notes_off(player: &mut Player, state: &mut State) {
     player.kill_all();
 
-    for key in held {
-        start_note(player, state, key);
+    state.held_keys.clear();
+    state.accented.clear();
+    state.held_midi.clear();
+    state.mono_keys.clear();
+    state.sustain = false;
+    state.sustained.clear();
+    state.sequence.playing = false;
+    state.seq_step = None;
+    state.bend.set_amount(0.0);
+}
+
+/// Swaps the arp note for the next held one, returning when it should stop
+fn arp_step(
+    player: &mut Player,
+    state: &mut State,
+    arpeggiator: &mut Arpeggiator,
+) -> Option<Instant> {
+    player.stop_note(VoiceKey::Arp);
+
+    let mut notes: Vec<_> = held_notes(state)
+        .into_iter()
+        .filter_map(|key| note_for(state, key))
+        .collect();
+    notes.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let index = arpeggiator.next(state.arp.pattern, notes.len())?;
+    let (freq, patch, gain) = notes.swap_remove(index);
+    state.last_note = Some(Key::nearest(freq, state.a4_hz));
+    start_voice(player, state, VoiceKey::Arp, &patch, freq, gain);
+
+    Some(Instant::now() + state.arp.note_length(state.tempo))
+}
+
+/// Moves the sequencer on a step and plays it, returning when the note
+/// should stop. Steps follow transpose, scale and tuning like live keys do
+fn seq_step(player: &mut Player, state: &mut State) -> Option<Instant> {
+    player.stop_note(VoiceKey::Seq);
+
+    let len = state.sequence.steps.len();
+    let step = state.seq_step.map_or(0, |step| (step + 1) % len.max(1));
+    state.seq_step = Some(step);
+
+    let key = state.sequence.steps.get(step).copied().flatten()?;
+    let key = state.quantizer.quantize(key.transpose(state.transpose));
+    let freq = state.frequency(key);
+    let patch = state.main.patch.clone();
+    let gain = state.velocity.key_gain(false);
+    state.last_note = Some(key);
+    start_voice(player, state, VoiceKey::Seq, &patch, freq, gain);
+
+    Some(Instant::now() + state.tempo.step(SEQ_STEPS_PER_BEAT).mul_f32(SEQ_LENGTH))
+}
+
+/// Names of the notes being held, low to high without repeats
+fn held_keys(player: &Player, state: &State) -> Vec<Key> {
+    let mut pitches = player.held_pitches();
+    pitches.sort_by(f32::total_cmp);
+
+    let mut keys: Vec<Key> = pitches
+        .into_iter()
+        .map(|hz| Key::nearest(hz, state.a4_hz))
+        .collect();
+    keys.dedup();
+    keys
+}
+
+/// Lets go of every note queued while the pedal was down
+fn release_sustained(player: &mut Player, state: &mut State) {
+    let mut queued = std::mem::take(&mut state.sustained);
+    // oldest first, so a mono voice only ever falls back to a key still down
+    queued.sort_by_key(|key| state.mono_keys.iter().position(|held| held == key));
+
+    for key in queued {
+        release_note(player, state, key);
+        if let VoiceKey::Midi(note) = key {
+            state.held_midi.remove(&note);
+        }
     }
 }
 
+/// Legato retunes the sounding voice, otherwise it is released and a new one
+/// starts with a fresh envelope. Either way the pitch glides over `glide_s`
+fn play_mono(
+    player: &mut Player,
+    state: &State,
+    patch: &Patch,
+    freq: f32,
+    gain: f32,
+    legato: bool,
+) {
+    let target = PitchTarget {
+        hz: freq,
+        glide_s: state.glide_s,
+    };
+
+    if legato {
+        player.retune(VoiceKey::Mono, target);
+        return;
+    }
+
+    // a retriggered note still slides in from the one before it
+    let from = if state.glide_s > 0.0 {
+        player.last_pitch(VoiceKey::Mono)
+    } else {
+        None
+    };
+
+    player.stop_note(VoiceKey::Mono);
+    start_voice(
+        player,
+        state,
+        VoiceKey::Mono,
+        patch,
+        from.unwrap_or(freq),
+        gain,
+    );
+    player.retune(VoiceKey::Mono, target);
+}
+
+/// One sink per unison copy, all sharing the same gate and pitch. `gain`
+/// is the velocity, the envelope peaks there
+fn start_voice(
+    player: &mut Player,
+    state: &State,
+    key: VoiceKey,
+    patch: &Patch,
+    freq: f32,
+    gain: f32,
+) {
+    let gate: Gate = Arc::new(AtomicBool::new(true));
+    let cut: Cut = Arc::new(AtomicBool::new(false));
+    let pitch = Pitch::new(PitchTarget {
+        hz: freq,
+        glide_s: state.glide_s,
+    });
+
+    let tap = player.voice_tap();
+    let sinks = state
+        .unison
+        .spreads()
+        .into_iter()
+        .enumerate()
+        .map(|(i, spread)| {
+            let sink = Sink::connect_new(&player.mixer);
+            sink.set_volume(state.volume);
+
+            if state.muted {
+                sink.pause();
+            }
+
+            let voice = patch.build_voice(pitch.clone(), gate.clone(), spread, gain);
+            let voice = fade_out(voice, cut.clone(), VOICE_CUT_FADE_MS);
+            sink.append(tap.apply(voice, i == 0));
+            sink
+        })
+        .collect();
+
+    player.add_voice(key, sinks, gate, pitch, cut, tap);
+}
+
+fn restart_held_notes(player: &mut Player, state: &State) {
+    player.kill_all();
+
+    if state.arp.enabled {
+        return;
+    }
+
+    if state.voice_mode != VoiceMode::Poly {
+        if let Some((freq, patch, gain)) =
+            state.mono_keys.last().and_then(|key| note_for(state, *key))
+        {
+            start_voice(player, state, VoiceKey::Mono, &patch, freq, gain);
+        }
+        return;
+    }
+
+    for key in held_notes(state) {
+        start_chord(player, state, key);
+    }
+}
+
+/// Moves a bend from the keys one tick back towards center, true once it is
+/// there
+fn return_bend(state: &State) -> bool {
+    let amount = state.bend.amount();
+    let next = match state.bend_return {
+        BendReturn::Snap => 0.0,
+        BendReturn::Smooth => {
+            let step = TICK as f32 / 1000.0 / BEND_RETURN_S.max(f32::EPSILON);
+            if amount > 0.0 {
+                (amount - step).max(0.0)
+            } else {
+                (amount + step).min(0.0)
+            }
+        }
+    };
+
+    state.bend.set_amount(next);
+    next == 0.0
+}
+
 #[inline]
 fn toggle_wave(state: &State) {
     state.toggle_wave();
 }
 
+fn apply_keys(
+    player: &mut Player,
+    state: &mut State,
+    snapshot_tx: &watch::Sender<Snapshot>,
+    held_keys_tx: &watch::Sender<HashSet<Keycode>>,
+    last_keys: &HashSet<Keycode>,
+    keys: &HashSet<Keycode>,
+) -> HashSet<Keycode> {
+    let toggle_b = keys.contains(&Keycode::B) && !last_keys.contains(&Keycode::B);
+
+    let now: HashSet<Keycode> = keys.iter().copied().filter(|k| *k != Keycode::B).collect();
+
+    let prev: HashSet<Keycode> = last_keys
+        .iter()
+        .copied()
+        .filter(|k| *k != Keycode::B)
+        .collect();
+
+    state.held_keys.clone_from(&now);
+    let _ = held_keys_tx.send(state.held_keys.clone());
+
+    if toggle_b {
+        toggle_wave(state);
+        publish_snapshot(snapshot_tx, state);
+    }
+
+    // the terminal engine has no shift key to watch, its presses say
+    // whether they were accented
+    if state.input == InputEngine::Global {
+        let accent = keys.contains(&Keycode::LShift) || keys.contains(&Keycode::RShift);
+        for key in now.difference(&prev) {
+            if accent {
+                state.accented.insert(*key);
+            } else {
+                state.accented.remove(key);
+            }
+        }
+    }
+
+    for key in now.difference(&prev) {
+        press_note(player, state, (*key).into());
+    }
+
+    for key in prev.difference(&now) {
+        release_note(player, state, (*key).into());
+    }
+
+    player.clear_finished();
+    keys.clone()
+}
+
+/// `debounce` is how long a polled key may drop out before it counts as released
 pub async fn run(
     mut shutdown: tokio::sync::watch::Receiver<bool>,
     focused: Arc<AtomicBool>,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let _ = audio::client().await;
+    stream: StreamOptions,
+    debounce: Duration,
+    ignore_held: bool,
+) -> Result<()> {
+    let capture = audio::client().await.capture().clone();
+    let voice_capture = audio::client().await.voice_capture().clone();
     let (mut cmd_rx, snapshot_tx, held_keys_tx, initial) =
         match audio::take_runtime_channels().await {
             Ok(a) => a,
@@ -75,15 +468,35 @@ pub async fn run(
         };
 
     let mut state = State::from_snapshot(initial);
-    let mut player = match Player::new() {
+    let mut player = match Player::new(&capture, &voice_capture, &state.master_bus(), &stream) {
         Ok(a) => a,
         Err(e) => return Err(e),
     };
+    player.set_max_voices(state.max_voices);
+    player
+        .mixer
+        .add(state.modulation.clock(player.sample_rate()));
+    if let Some(warning) = player.take_warning() {
+        state.report(warning);
+    }
     publish_snapshot(&snapshot_tx, &state);
 
     let stop_flag = Arc::new(AtomicBool::new(false));
     let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Event>();
 
+    // one clock for everything that keeps time, so nothing drifts apart
+    let (tempo_tx, tempo_rx) = watch::channel(state.tempo);
+    let clock_handle = {
+        let tx = tx.clone();
+        task::spawn(tempo::clock(tempo_rx, move |tick| {
+            tx.send(Event::Tick(tick)).is_ok()
+        }))
+    };
+    let mut arpeggiator = Arpeggiator::new(ARP_SEED);
+    let mut arp_release_at: Option<Instant> = None;
+    let mut seq_release_at: Option<Instant> = None;
+    let mut bend_release_at: Option<Instant> = None;
+
     let poll_handle = {
         let stop_flag = Arc::clone(&stop_flag);
         let focused = Arc::clone(&focused);
@@ -92,6 +505,9 @@ pub async fn run(
             let device_state = DeviceState::new();
             let mut last_keys = HashSet::new();
             let mut was_focused = true;
+            let mut startup = StartupKeys::new(ignore_held);
+            let mut debounce = KeyDebounce::new(debounce);
+            let mut control = ControlKeys::default();
 
             loop {
                 if stop_flag.load(Ordering::Relaxed) {
@@ -107,17 +523,21 @@ pub async fn run(
                         let _ = tx.send(Event::KeysChanged(HashSet::new()));
                         last_keys.clear();
                     }
+                    debounce.clear();
+                    control.clear();
                     was_focused = false;
                     continue;
                 }
 
                 if !was_focused {
-                    last_keys = device_state.get_keys().into_iter().collect();
+                    let keys = startup.filter(device_state.get_keys().into_iter().collect());
+                    last_keys = control.filter(debounce.filter(keys, Instant::now()));
                     was_focused = true;
                     continue;
                 }
 
-                let now: HashSet<Keycode> = device_state.get_keys().into_iter().collect();
+                let keys = startup.filter(device_state.get_keys().into_iter().collect());
+                let now = debounce.filter(keys, Instant::now());
 
                 if now.contains(&Keycode::Escape)
                     || (now.contains(&Keycode::C) && now.contains(&Keycode::LControl))
@@ -126,6 +546,7 @@ pub async fn run(
                     break;
                 }
 
+                let now = control.filter(now);
                 if now != last_keys {
                     let _ = tx.send(Event::KeysChanged(now.clone()));
                     last_keys = now;
@@ -138,6 +559,11 @@ pub async fn run(
     tokio::pin!(ctrl_c);
 
     let mut last_keys = HashSet::new();
+    let mut terminal_keys = TerminalKeys::new(
+        Duration::from_millis(TERMINAL_HOLD_MS),
+        Duration::from_millis(TERMINAL_RELEASE_MS),
+    );
+    let mut tick = interval(Duration::from_millis(TICK));
 
     loop {
         tokio::select! {
@@ -149,36 +575,93 @@ pub async fn run(
                 }
             }
 
-            msg = rx.recv() => match msg {
-                Some(Event::KeysChanged(now)) => {
-                    let toggle_b =
-                        now.contains(&Keycode::B) && !last_keys.contains(&Keycode::B);
+            _ = tick.tick() => {
+                if arp_release_at.is_some_and(|at| Instant::now() >= at) {
+                    player.stop_note(VoiceKey::Arp);
+                    arp_release_at = None;
+                }
+                if seq_release_at.is_some_and(|at| Instant::now() >= at) {
+                    player.stop_note(VoiceKey::Seq);
+                    seq_release_at = None;
+                }
+                if bend_release_at.is_some_and(|at| Instant::now() >= at) {
+                    if return_bend(&state) {
+                        bend_release_at = None;
+                    }
+                    publish_snapshot(&snapshot_tx, &state);
+                }
 
-                    let now: HashSet<Keycode> =
-                        now.iter().copied().filter(|k| *k != Keycode::B).collect();
+                if let Some(max_ms) = state.max_note_ms {
+                    player.release_expired(Duration::from_millis(max_ms), Instant::now());
+                }
 
-                    let prev: HashSet<Keycode> =
-                        last_keys.iter().copied().filter(|k| *k != Keycode::B).collect();
+                if let Some(done) = player.recorder.poll() {
+                    if let Err(err) = done {
+                        state.report(format!("failed to finish recording: {err}"));
+                    }
+                    // a take stopped by hand may finish after the next started
+                    state.recording = player.recorder.recording().cloned();
+                    publish_snapshot(&snapshot_tx, &state);
+                }
 
-                    state.held_keys.clone_from(&now);
-                    let _ = held_keys_tx.send(state.held_keys.clone());
+                player.clear_finished();
+                let keys = held_keys(&player, &state);
+                if player.voice_count() != state.active_voices
+                    || keys != state.active_keys
+                    || player.pinned_pitch() != state.scope_voice
+                {
+                    state.active_voices = player.voice_count();
+                    state.active_keys = keys;
+                    state.scope_voice = player.pinned_pitch();
+                    publish_snapshot(&snapshot_tx, &state);
+                }
 
-                    if toggle_b {
-                        toggle_wave(&state);
-                        publish_snapshot(&snapshot_tx, &state);
-                        restart_held_notes(&mut player, &state);
-                    }
+                if state.input == InputEngine::Terminal && terminal_keys.expire(Instant::now()) {
+                    last_keys = apply_keys(
+                        &mut player,
+                        &mut state,
+                        &snapshot_tx,
+                        &held_keys_tx,
+                        &last_keys,
+                        &terminal_keys.held(),
+                    );
+                }
+            }
 
-                    for key in now.difference(&prev) {
-                        start_note(&mut player, &state, *key);
+            msg = rx.recv() => match msg {
+                Some(Event::KeysChanged(now)) => {
+                    if state.input != InputEngine::Global {
+                        continue;
                     }
 
-                    for key in prev.difference(&now) {
-                        player.stop_note(*key);
+                    last_keys = apply_keys(
+                        &mut player,
+                        &mut state,
+                        &snapshot_tx,
+                        &held_keys_tx,
+                        &last_keys,
+                        &now,
+                    );
+                }
+                Some(Event::Tick(tick)) => {
+                    state.tick = tick;
+                    // the beat count alone is only published once a beat
+                    let mut publish = tick.is_multiple_of(u64::from(TEMPO_PPQ));
+
+                    if state.arp.enabled && tempo::on_step(tick, ARP_STEPS_PER_BEAT) {
+                        arp_release_at = arp_step(&mut player, &mut state, &mut arpeggiator);
+                        player.clear_finished();
+                        publish = true;
+                    }
+                    if state.sequence.playing && tempo::on_step(tick, SEQ_STEPS_PER_BEAT) {
+                        seq_release_at = seq_step(&mut player, &mut state);
+                        player.clear_finished();
+                        publish = true;
                     }
 
-                    player.clear_finished();
-                    last_keys = now;
+                    if publish {
+                        publish_snapshot(&snapshot_tx, &state);
+                    }
                 }
                 Some(Event::Exit) | None => break,
             },
@@ -188,7 +671,7 @@ pub async fn run(
 
                 match cmd {
                     Command::SetVolume(v) => {
-                        state.volume = v.clamp(0.0, 2.0);
+                        state.volume = v.clamp(0.0, 1.0);
                         player.set_volume(state.volume);
                     }
 
@@ -199,9 +682,46 @@ pub async fn run(
 
                     Command::SetWave(wave) => {
                         state.set_wave(wave);
+                    }
+
+                    Command::SetCrossfade(crossfade_s) => {
+                        state.set_crossfade(crossfade_s);
+                    }
+
+                    Command::SetPulseDuty(duty) => {
+                        state.set_pulse_duty(duty);
+                    }
+
+                    Command::SetSplit(split) => {
+                        state.split = split;
+                        state.edit_split &= split;
                         restart_held_notes(&mut player, &state);
                     }
 
+                    Command::SetSplitWave(wave) => {
+                        state.set_split_wave(wave);
+                    }
+
+                    Command::SetEditSplit(edit_split) => {
+                        state.edit_split = edit_split && state.split;
+                    }
+
+                    Command::SetLayers(layers) => {
+                        state.set_layers(&layers);
+                    }
+
+                    Command::SetSub(sub) => {
+                        state.set_sub(sub);
+                    }
+
+                    Command::SetWavetable(table) => {
+                        state.set_wavetable(&table);
+                    }
+
+                    Command::SetFm(fm) => {
+                        state.set_fm(&fm);
+                    }
+
                     Command::SetAdsr(adsr) => {
                         state.set_adsr(adsr);
                     }
@@ -214,14 +734,232 @@ pub async fn run(
                         state.set_lfo_amp(lfo_amp);
                     }
 
+                    Command::SetLfo(lfo) => {
+                        state.set_lfo(&lfo);
+                    }
+
+                    Command::SetAm(am) => {
+                        state.set_am(am);
+                    }
+
+                    Command::SetRingMod(ringmod) => {
+                        state.set_ringmod(&ringmod);
+                    }
+
+                    Command::SetSampleHold(sample_hold) => {
+                        state.set_sample_hold(sample_hold);
+                    }
+
+                    Command::SetHighPass(highpass) => {
+                        state.set_highpass(highpass);
+                    }
+
                     Command::SetLowPass(lowpass) => {
                         state.set_lowpass(lowpass);
                     }
 
+                    Command::SetBitcrush(bitcrush) => {
+                        state.set_bitcrush(bitcrush);
+                    }
+
+                    Command::SetDelay(delay) => {
+                        state.set_delay(delay);
+                    }
+
+                    Command::SetReverb(reverb) => {
+                        state.set_reverb(&reverb);
+                    }
+
+                    Command::SetNoiseGate(noise_gate) => {
+                        state.set_noise_gate(noise_gate);
+                    }
+
+                    Command::SetPan(pan) => {
+                        state.set_pan(pan);
+                    }
+
+                    Command::SetFxChain(effects) => {
+                        state.set_fx_chain(&effects);
+                    }
+
+                    Command::SetUnison(unison) => {
+                        state.unison = Unison::new(unison.voices, unison.detune_cents);
+                        restart_held_notes(&mut player, &state);
+                    }
+
                     Command::SetOctave(octave) => {
-                        state.octave = octave;
+                        state.octave = octave.clamp(OCTAVE_SHIFT_MIN, OCTAVE_SHIFT_MAX);
+                    }
+
+                    Command::SetTranspose(semitones) => {
+                        state.transpose = semitones.clamp(-TRANSPOSE_MAX, TRANSPOSE_MAX);
+                    }
+
+                    Command::SetTuning(a4_hz) => {
+                        state.a4_hz = a4_hz.clamp(A4_MIN_HZ, A4_MAX_HZ);
+                    }
+
+                    Command::SetTuningSystem(tuning) => {
+                        state.tuning = tuning;
+                    }
+
+                    Command::SetInputEngine(input) => {
+                        state.input = input;
+                        terminal_keys.clear();
+                        last_keys = apply_keys(
+                            &mut player,
+                            &mut state,
+                            &snapshot_tx,
+                            &held_keys_tx,
+                            &last_keys,
+                            &HashSet::new(),
+                        );
+                    }
+
+                    Command::SetMaxNote(max_ms) => {
+                        state.max_note_ms = max_ms;
+                    }
+
+                    Command::PinNextVoice => {
+                        player.clear_finished();
+                        player.pin_next_voice();
+                        state.scope_voice = player.pinned_pitch();
+                    }
+
+                    Command::ToggleRecording => {
+                        if let Err(err) = player.toggle_recording() {
+                            state.report(format!("recording failed: {err}"));
+                        }
+                        state.recording = player.recorder.recording().cloned();
+                    }
+
+                    Command::AllNotesOff => {
+                        all_notes_off(&mut player, &mut state);
+                        let _ = held_keys_tx.send(state.held_keys.clone());
+                        terminal_keys.clear();
+                        last_keys.clear();
+                        arpeggiator = Arpeggiator::new(ARP_SEED);
+                        arp_release_at = None;
+                        seq_release_at = None;
+                        bend_release_at = None;
+                    }
+
+                    Command::NoteOn { note, velocity } => {
+                        // a repeated note-on retriggers rather than stacking
+                        if state.voice_mode == VoiceMode::Poly {
+                            player.stop_note(VoiceKey::Midi(note));
+                        }
+                        state.held_midi.insert(note, velocity);
+                        press_note(&mut player, &mut state, VoiceKey::Midi(note));
+                        player.clear_finished();
+                    }
+
+                    Command::NoteOff(note) => {
+                        release_note(&mut player, &mut state, VoiceKey::Midi(note));
+                        // a sustained note keeps its velocity until the pedal is up
+                        if !state.sustain {
+                            state.held_midi.remove(&note);
+                        }
+                    }
+
+                    Command::SetSustain(down) => {
+                        state.sustain = down;
+                        if !down {
+                            release_sustained(&mut player, &mut state);
+                        }
+                    }
+
+                    Command::SetQuantizer(quantizer) => {
+                        state.quantizer = quantizer;
+                    }
+
+                    Command::SetChord(chord) => {
+                        state.chord = chord;
+                    }
+
+                    Command::SetVelocity(velocity) => {
+                        state.velocity = Velocity::new(velocity.curve, velocity.keys, velocity.accent);
+                    }
+
+                    Command::SetGlide(glide_s) => {
+                        state.glide_s = glide_s.clamp(0.0, GLIDE_MAX_S);
+                    }
+
+                    Command::SetBend(amount) => {
+                        state.bend.set_amount(amount);
+                        bend_release_at = None;
+                    }
+
+                    Command::BendKey(amount) => {
+                        state.bend.set_amount(amount);
+                        bend_release_at =
+                            Some(Instant::now() + Duration::from_millis(BEND_KEY_HOLD_MS));
+                    }
+
+                    Command::SetBendRange(range_st) => {
+                        state.bend.set_range_st(range_st.clamp(0.0, BEND_RANGE_MAX_ST));
+                    }
+
+                    Command::SetBendReturn(bend_return) => {
+                        state.bend_return = bend_return;
+                    }
+
+                    Command::SetTempo(tempo) => {
+                        state.tempo = Tempo::new(tempo.bpm);
+                        // only a real change restarts the tick timer
+                        tempo_tx.send_if_modified(|current| {
+                            let changed = *current != state.tempo;
+                            *current = state.tempo;
+                            changed
+                        });
+                    }
+
+                    Command::SetSequence(sequence) => {
+                        state.sequence = Sequence::new(sequence.steps, sequence.playing);
+
+                        if !state.sequence.playing {
+                            player.stop_note(VoiceKey::Seq);
+                            seq_release_at = None;
+                            state.seq_step = None;
+                        }
+                    }
+
+                    Command::SetVoiceMode(mode) => {
+                        state.voice_mode = mode;
+                        reset_mono_keys(&mut state);
                         restart_held_notes(&mut player, &state);
                     }
+
+                    Command::SetArp(arp) => {
+                        let toggled = arp.enabled != state.arp.enabled;
+                        state.arp = Arp::new(arp.enabled, arp.pattern, arp.length);
+
+                        if toggled {
+                            arp_release_at = None;
+                            reset_mono_keys(&mut state);
+                            restart_held_notes(&mut player, &state);
+                        }
+                    }
+
+                    Command::PressKey { keycode, accent } => {
+                        if state.input == InputEngine::Terminal
+                            && terminal_keys.press(keycode, Instant::now())
+                        {
+                            if accent {
+                                state.accented.insert(keycode);
+                            } else {
+                                state.accented.remove(&keycode);
+                            }
+                            last_keys = apply_keys(
+                                &mut player,
+                                &mut state,
+                                &snapshot_tx,
+                                &held_keys_tx,
+                                &last_keys,
+                                &terminal_keys.held(),
+                            );
+                        }
+                    }
                 }
 
                 publish_snapshot(&snapshot_tx, &state);
@@ -231,8 +969,77 @@ pub async fn run(
     }
 
     stop_flag.store(true, Ordering::Relaxed);
+    // the UI may still own the terminal, main prints this once it is restored
+    let finished = finish_recordings(&mut player.recorder)
+        .await
+        .map_err(MugenError::Recording);
     player.kill_all();
+    clock_handle.abort();
     let _ = poll_handle.await;
 
+    finished.map(drop)
+}
+
+/// Stops any recording and waits up to `RECORD_STOP_TIMEOUT_MS` for every
+/// stopped one to be finalized, without holding up the runtime. The first
+/// error wins
+async fn finish_recordings(recorder: &mut Recorder) -> io::Result<()> {
+    recorder.stop();
+    let deadline = Instant::now() + Duration::from_millis(RECORD_STOP_TIMEOUT_MS);
+    let mut res = Ok(());
+
+    while recorder.finishing() {
+        match recorder.poll() {
+            Some(Err(err)) if res.is_ok() => res = Err(err),
+            Some(_) => {}
+            None if Instant::now() >= deadline => {
+                let stalled = recorder.abandon();
+                res = res.and_then(|()| stalled.map_or(Ok(()), Err));
+            }
+            None => tokio::time::sleep(Duration::from_millis(1)).await,
+        }
+    }
+
+    res
+}
+
+/// Plays `key` through the starting patch for `hold`, then releases it and
+/// returns once the tail has died away. Nothing polls the keyboard and
+/// commands are ignored, so this only needs an output device
+pub async fn test_tone(key: Key, hold: Duration, stream: StreamOptions) -> Result<()> {
+    let capture = audio::client().await.capture().clone();
+    let voice_capture = audio::client().await.voice_capture().clone();
+    let (_cmd_rx, _snapshot_tx, _held_keys_tx, initial) = audio::take_runtime_channels().await?;
+
+    let state = State::from_snapshot(initial);
+    let mut player = Player::new(&capture, &voice_capture, &state.master_bus(), &stream)?;
+    player
+        .mixer
+        .add(state.modulation.clock(player.sample_rate()));
+    // no UI here, stderr is the terminal
+    if let Some(warning) = player.take_warning() {
+        eprintln!("mugen: {warning}");
+    }
+
+    start_voice(
+        &mut player,
+        &state,
+        VoiceKey::Mono,
+        &state.main.patch,
+        state.frequency(key),
+        state.velocity.key_gain(false),
+    );
+    tokio::time::sleep(hold).await;
+
+    player.stop_note(VoiceKey::Mono);
+    let deadline = Instant::now()
+        + Duration::from_secs_f32(state.adsr().release_s)
+        + Duration::from_millis(TEST_TONE_TAIL_MS);
+    while player.voice_count() > 0 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(TICK)).await;
+        player.clear_finished();
+    }
+
+    player.kill_all();
     Ok(())
 }
@@ -1,14 +1,49 @@
 //! Snapshot of the current audio engine state exposed to subscribers
 
 use crate::config::{
-    ADSR_ATTACK_S, ADSR_DECAY_S, ADSR_RELEASE_S, ADSR_SUSTAIN, CUTOFF, LFO_DEPTH, LFO_KIND,
-    LFO_RATE_HZ,
+    ADSR_ATTACK_S, ADSR_DECAY_S, ADSR_RELEASE_S, ADSR_SUSTAIN, AM_CARRIER_HZ, AM_DEPTH,
+    ARP_ENABLED, ARP_LENGTH, ARP_PATTERN, BASE_FREQ, BEND_RANGE_ST, BEND_RETURN, BITCRUSH_BITS,
+    BITCRUSH_DOWNSAMPLE, CHORD, CUTOFF, DELAY_FEEDBACK, DELAY_MIX, DELAY_TIME_MS, FM_INDEX,
+    FM_MODULATOR, FM_RATIO, GLIDE_S, HIGHPASS_CUTOFF, HIGHPASS_Q, INPUT_ENGINE, LFO_DEPTH,
+    LFO_KIND, LFO_MOD_DEPTH, LFO_MOD_RATE_HZ, LFO_MOD_TARGET, LFO_MOD_WAVE, LFO_RATE_HZ,
+    MAX_NOTE_MS, MAX_VOICES, NOISE_GATE_ATTACK_MS, NOISE_GATE_RELEASE_MS, NOISE_GATE_THRESHOLD_DB,
+    PAN, PULSE_DUTY, REVERB_DAMPING, REVERB_MIX, REVERB_ROOM, RINGMOD_FREQ_HZ, RINGMOD_MIX, SCALE,
+    SCALE_ROOT, SH_DEPTH, SH_RATE_HZ, SH_SEED, SH_TARGET, SUB_LEVEL, SUB_WAVE, TEMPO_BPM, TUNING,
+    UNISON_DETUNE_CENTS, UNISON_VOICES, VELOCITY_ACCENT, VELOCITY_CURVE, VELOCITY_KEYS, VOICE_MODE,
+    WAVE_CROSSFADE_S,
 };
+use crate::patch::bend::BendReturn;
+use crate::patch::chain::Fx;
 use crate::patch::effects::adsr::Adsr;
+use crate::patch::effects::am::Am;
+use crate::patch::effects::bitcrush::Bitcrush;
+use crate::patch::effects::delay::Delay;
 use crate::patch::effects::gain::Gain;
+use crate::patch::effects::highpass::HighPass;
 use crate::patch::effects::lfo_amp::LfoAmp;
 use crate::patch::effects::lowpass::LowPass;
-use crate::patch::oscilators::basic::Wave;
+use crate::patch::effects::noise_gate::NoiseGate;
+use crate::patch::effects::pan::Pan;
+use crate::patch::effects::reverb::Reverb;
+use crate::patch::effects::ringmod::RingMod;
+use crate::patch::effects::sample_hold::SampleHold;
+use crate::patch::lfo::Lfo;
+use crate::patch::oscilators::basic::{Sub, Wave};
+use crate::patch::oscilators::fm::Fm;
+use crate::patch::oscilators::stack::Layer;
+use crate::patch::oscilators::wavetable::Wavetable;
+use crate::patch::unison::Unison;
+use crate::play::VoiceMode;
+use crate::play::arp::Arp;
+use crate::play::chord::Chord;
+use crate::play::input::InputEngine;
+use crate::play::key::Key;
+use crate::play::recorder::Recording;
+use crate::play::scale::Quantizer;
+use crate::play::sequencer::Sequence;
+use crate::play::tempo::Tempo;
+use crate::play::tuning::Tuning;
+use crate::play::velocity::Velocity;
 use crate::presets::Preset;
 
 #[derive(Debug, Clone)]
@@ -16,23 +51,120 @@ pub struct Snapshot {
     pub volume: f32,
     pub muted: bool,
     pub wave: Wave,
+    pub crossfade_s: f32,
+    pub pulse_duty: f32,
+    pub sub: Sub,
+    pub wavetable: Wavetable,
+    pub fm: Fm,
     pub octave: i32,
+    pub transpose: i32,
+    pub a4_hz: f32,
+    pub tuning: Tuning,
+    pub input: InputEngine,
+    pub max_note_ms: Option<u64>,
+    pub max_voices: usize,
+    pub voice_mode: VoiceMode,
+    pub glide_s: f32,
+    /// Current bend in -1..1 of `bend_range_st`
+    pub bend: f32,
+    pub bend_range_st: f32,
+    pub bend_return: BendReturn,
+    pub sustain: bool,
+    pub quantizer: Quantizer,
+    pub chord: Chord,
+    pub velocity: Velocity,
+    pub tempo: Tempo,
+    /// Beats since the clock started
+    pub beat: u64,
+    pub arp: Arp,
+    pub sequence: Sequence,
+    /// Step the sequencer played last, `None` while stopped
+    pub seq_step: Option<usize>,
+    /// Notes sounding right now, including releasing ones
+    pub active_voices: usize,
+    /// Pitch of the voice the scope is pinned to, `None` while it shows the mix
+    pub scope_voice: Option<f32>,
+    /// Held notes low to high
+    pub active_keys: Vec<Key>,
+    /// Newest note-on, kept after it is let go so the readout stays put
+    pub last_note: Option<Key>,
+    /// Effect names in signal order
+    pub fx_chain: Vec<&'static str>,
+    /// Effects ahead of the envelope in the order new voices run them
+    pub effects: Vec<Fx>,
+    pub recording: Option<Recording>,
+    /// Newest engine error with how many there have been, so the status
+    /// line shows each one once
+    pub error: Option<(u64, String)>,
     pub patch_name: String,
+    pub split: bool,
+    pub split_wave: Wave,
+    /// The envelope and effects shown are the split zone's
+    pub edit_split: bool,
+    pub layers: Vec<Layer>,
+    pub unison: Unison,
     pub adsr: Adsr,
     pub gain: Gain,
     pub lfo_amp: LfoAmp,
+    pub lfo: Lfo,
+    pub am: Am,
+    pub ringmod: RingMod,
+    pub bitcrush: Bitcrush,
+    pub sample_hold: SampleHold,
+    pub highpass: HighPass,
     pub lowpass: LowPass,
+    pub delay: Delay,
+    pub reverb: Reverb,
+    pub noise_gate: NoiseGate,
+    pub pan: Pan,
 }
 
-impl Snapshot {
-    #[must_use]
-    pub fn default() -> Self {
+impl Default for Snapshot {
+    fn default() -> Self {
         Self {
             volume: 1.0,
             muted: false,
             wave: Wave::Sine,
+            crossfade_s: WAVE_CROSSFADE_S,
+            pulse_duty: PULSE_DUTY,
+            sub: Sub::new(SUB_WAVE, SUB_LEVEL),
+            wavetable: Wavetable::default(),
+            fm: Fm::new(FM_MODULATOR, FM_RATIO, FM_INDEX),
             octave: 0,
+            transpose: 0,
+            a4_hz: BASE_FREQ,
+            tuning: TUNING,
+            input: INPUT_ENGINE,
+            max_note_ms: MAX_NOTE_MS,
+            max_voices: MAX_VOICES,
+            voice_mode: VOICE_MODE,
+            glide_s: GLIDE_S,
+            bend: 0.0,
+            bend_range_st: BEND_RANGE_ST,
+            bend_return: BEND_RETURN,
+            sustain: false,
+            quantizer: Quantizer::new(SCALE, SCALE_ROOT),
+            chord: CHORD,
+            velocity: Velocity::new(VELOCITY_CURVE, VELOCITY_KEYS, VELOCITY_ACCENT),
+            tempo: Tempo::new(TEMPO_BPM),
+            beat: 0,
+            arp: Arp::new(ARP_ENABLED, ARP_PATTERN, ARP_LENGTH),
+            sequence: Sequence::empty(),
+            seq_step: None,
+            active_voices: 0,
+            scope_voice: None,
+            active_keys: Vec::new(),
+            last_note: None,
+            fx_chain: Vec::new(),
+            effects: Fx::ALL.to_vec(),
+            recording: None,
+            error: None,
             patch_name: Wave::Sine.name().to_string(),
+            split: false,
+            split_wave: Wave::Saw,
+            edit_split: false,
+            layers: Vec::new(),
+            unison: Unison::new(UNISON_VOICES, UNISON_DETUNE_CENTS),
             adsr: Adsr::new(ADSR_ATTACK_S, ADSR_DECAY_S, ADSR_SUSTAIN, ADSR_RELEASE_S),
             gain: Gain { amount: 1.0 },
             lfo_amp: LfoAmp {
@@ -41,16 +173,87 @@ impl Snapshot {
                 depth: LFO_DEPTH,
                 base_gain: 1.0,
             },
+            lfo: Lfo {
+                wave: LFO_MOD_WAVE,
+                rate_hz: LFO_MOD_RATE_HZ,
+                depth: LFO_MOD_DEPTH,
+                target: LFO_MOD_TARGET,
+            },
+            am: Am {
+                carrier_hz: AM_CARRIER_HZ,
+                depth: AM_DEPTH,
+            },
+            ringmod: RingMod::new(RINGMOD_FREQ_HZ, RINGMOD_MIX),
+            bitcrush: Bitcrush::new(BITCRUSH_BITS, BITCRUSH_DOWNSAMPLE),
+            sample_hold: SampleHold {
+                rate_hz: SH_RATE_HZ,
+                depth: SH_DEPTH,
+                target: SH_TARGET,
+                seed: SH_SEED,
+            },
+            highpass: HighPass {
+                cutoff_hz: HIGHPASS_CUTOFF,
+                q: HIGHPASS_Q,
+            },
             lowpass: LowPass { cutoff_hz: CUTOFF },
+            delay: Delay::new(DELAY_TIME_MS, DELAY_FEEDBACK, DELAY_MIX),
+            reverb: Reverb::new(REVERB_ROOM, REVERB_DAMPING, REVERB_MIX),
+            noise_gate: NoiseGate {
+                threshold_db: NOISE_GATE_THRESHOLD_DB,
+                attack_ms: NOISE_GATE_ATTACK_MS,
+                release_ms: NOISE_GATE_RELEASE_MS,
+            },
+            pan: Pan::new(PAN),
         }
     }
+}
+
+impl Snapshot {
     pub fn from_preset(preset: Preset) -> Self {
         Self {
             volume: 1.0,
             muted: false,
             wave: preset.wave,
+            crossfade_s: WAVE_CROSSFADE_S,
+            pulse_duty: PULSE_DUTY,
+            sub: Sub::new(SUB_WAVE, SUB_LEVEL),
+            wavetable: Wavetable::default(),
+            fm: Fm::new(FM_MODULATOR, FM_RATIO, FM_INDEX),
             octave: preset.octave_shift,
+            transpose: 0,
+            a4_hz: BASE_FREQ,
+            tuning: TUNING,
+            input: INPUT_ENGINE,
+            max_note_ms: MAX_NOTE_MS,
+            max_voices: MAX_VOICES,
+            voice_mode: VOICE_MODE,
+            glide_s: GLIDE_S,
+            bend: 0.0,
+            bend_range_st: BEND_RANGE_ST,
+            bend_return: BEND_RETURN,
+            sustain: false,
+            quantizer: Quantizer::new(SCALE, SCALE_ROOT),
+            chord: CHORD,
+            velocity: Velocity::new(VELOCITY_CURVE, VELOCITY_KEYS, VELOCITY_ACCENT),
+            tempo: Tempo::new(TEMPO_BPM),
+            beat: 0,
+            arp: Arp::new(ARP_ENABLED, ARP_PATTERN, ARP_LENGTH),
+            sequence: Sequence::empty(),
+            seq_step: None,
+            active_voices: 0,
+            scope_voice: None,
+            active_keys: Vec::new(),
+            last_note: None,
+            fx_chain: Vec::new(),
+            effects: Fx::ALL.to_vec(),
+            recording: None,
+            error: None,
             patch_name: preset.name,
+            split: false,
+            split_wave: Wave::Saw,
+            edit_split: false,
+            layers: Vec::new(),
+            unison: Unison::new(UNISON_VOICES, UNISON_DETUNE_CENTS),
             adsr: Adsr::new(preset.attack, preset.decay, preset.sustain, preset.release),
             gain: Gain { amount: 1.0 },
             lfo_amp: LfoAmp {
@@ -59,9 +262,39 @@ impl Snapshot {
                 depth: preset.lfo_depth,
                 base_gain: 1.0,
             },
+            lfo: Lfo {
+                wave: LFO_MOD_WAVE,
+                rate_hz: LFO_MOD_RATE_HZ,
+                depth: LFO_MOD_DEPTH,
+                target: LFO_MOD_TARGET,
+            },
+            am: Am {
+                carrier_hz: AM_CARRIER_HZ,
+                depth: AM_DEPTH,
+            },
+            ringmod: RingMod::new(RINGMOD_FREQ_HZ, RINGMOD_MIX),
+            bitcrush: Bitcrush::new(BITCRUSH_BITS, BITCRUSH_DOWNSAMPLE),
+            sample_hold: SampleHold {
+                rate_hz: SH_RATE_HZ,
+                depth: SH_DEPTH,
+                target: SH_TARGET,
+                seed: SH_SEED,
+            },
+            highpass: HighPass {
+                cutoff_hz: HIGHPASS_CUTOFF,
+                q: HIGHPASS_Q,
+            },
             lowpass: LowPass {
                 cutoff_hz: preset.cutoff,
             },
+            delay: Delay::new(DELAY_TIME_MS, DELAY_FEEDBACK, DELAY_MIX),
+            reverb: Reverb::new(REVERB_ROOM, REVERB_DAMPING, REVERB_MIX),
+            noise_gate: NoiseGate {
+                threshold_db: NOISE_GATE_THRESHOLD_DB,
+                attack_ms: NOISE_GATE_ATTACK_MS,
+                release_ms: NOISE_GATE_RELEASE_MS,
+            },
+            pan: Pan::new(PAN),
         }
     }
 }
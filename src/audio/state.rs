@@ -1,135 +1,742 @@
 //! Stores live engine parameters and patch handles
 
 use crate::audio::Snapshot;
+use crate::config::{FM_INDEX_MAX, FM_RATIO_MAX, TEMPO_PPQ};
+use crate::patch::Patch;
+use crate::patch::SharedEffect;
+use crate::patch::bend::{Bend, BendReturn};
+use crate::patch::chain::{Fx, FxChain};
 use crate::patch::effects::adsr::{Adsr, AdsrHandle, make_adsr};
+use crate::patch::effects::am::{Am, AmHandle, make_am};
+use crate::patch::effects::bitcrush::{Bitcrush, BitcrushHandle, make_bitcrush};
+use crate::patch::effects::delay::{Delay, DelayHandle, make_delay};
 use crate::patch::effects::gain::{Gain, GainHandle, make_gain};
+use crate::patch::effects::highpass::{HighPass, HighPassHandle, make_highpass};
 use crate::patch::effects::lfo_amp::{LfoAmp, LfoAmpHandle, make_lfo_amp};
-use crate::patch::effects::lowpass::{LowPass, LowPassHandle, make_lowpass};
-use crate::patch::oscilators::basic::{OscHandle, Wave, make_osc};
-use crate::patch::{Patch, SharedEffect};
+use crate::patch::effects::lowpass::{LowPass, LowPassHandle, ModLowPass, make_lowpass};
+use crate::patch::effects::noise_gate::{NoiseGate, NoiseGateHandle, make_noise_gate};
+use crate::patch::effects::pan::{Pan, PanHandle, make_pan};
+use crate::patch::effects::reverb::{Reverb, ReverbHandle, make_reverb};
+use crate::patch::effects::ringmod::{RingMod, RingModHandle, make_ringmod};
+use crate::patch::effects::sample_hold::{SampleHold, SampleHoldHandle, make_sample_hold};
+use crate::patch::lfo::{Lfo, Modulation};
+use crate::patch::oscilators::basic::{DUTY_MAX, DUTY_MIN, OscHandle, Sub, Wave, make_osc};
+use crate::patch::oscilators::fm::Fm;
+use crate::patch::oscilators::stack::{Layer, LayersHandle, clamp_layers, make_layers};
+use crate::patch::oscilators::wavetable::Wavetable;
+use crate::patch::unison::Unison;
+use crate::play::arp::Arp;
+use crate::play::chord::Chord;
+use crate::play::input::InputEngine;
+use crate::play::key::{Key, KeyRow};
+use crate::play::recorder::Recording;
+use crate::play::scale::Quantizer;
+use crate::play::sequencer::Sequence;
+use crate::play::tempo::Tempo;
+use crate::play::tuning::Tuning;
+use crate::play::velocity::Velocity;
+use crate::play::{VoiceKey, VoiceMode};
 use device_query::Keycode;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 pub struct State {
     pub volume: f32,
     pub muted: bool,
     pub octave: i32,
+    pub transpose: i32,
+    pub a4_hz: f32,
+    pub tuning: Tuning,
+    pub input: InputEngine,
+    pub max_note_ms: Option<u64>,
+    pub max_voices: usize,
+    pub voice_mode: VoiceMode,
+    pub glide_s: f32,
+    pub bend: Bend,
+    pub bend_return: BendReturn,
+    /// Keys down in the mono modes, newest last
+    pub mono_keys: Vec<VoiceKey>,
+    pub sustain: bool,
+    /// Keys let go of while the pedal was down, released when it comes up
+    pub sustained: Vec<VoiceKey>,
+    pub quantizer: Quantizer,
+    pub chord: Chord,
+    pub velocity: Velocity,
+    pub tempo: Tempo,
+    /// Transport position in clock ticks
+    pub tick: u64,
+    pub arp: Arp,
+    pub sequence: Sequence,
+    pub seq_step: Option<usize>,
+    pub active_voices: usize,
+    pub scope_voice: Option<f32>,
+    pub active_keys: Vec<Key>,
+    pub last_note: Option<Key>,
+    pub unison: Unison,
+    pub recording: Option<Recording>,
+    pub error: Option<(u64, String)>,
     pub held_keys: HashSet<Keycode>,
+    /// Held keys that went down with the accent key
+    pub accented: HashSet<Keycode>,
+    /// MIDI note numbers to their velocity
+    pub held_midi: HashMap<u8, u8>,
 
+    pub layers: LayersHandle,
+    pub modulation: Modulation,
+    /// Echo on the master bus after the voice mixer, so its repeats ring on
+    /// past the notes. Both zones play into it
+    pub delay: DelayHandle,
+    /// Room on the master bus after the delay, so the echoes sit in it too
+    pub reverb: ReverbHandle,
+
+    pub main: Zone,
+    pub split: bool,
+    pub split_zone: Zone,
+    /// Envelope and effect changes go to the split zone rather than the main one
+    pub edit_split: bool,
+}
+
+/// Oscillator, envelope and effects one keyboard zone plays through. Split
+/// mode gives the top row a zone of its own so it can sound nothing like
+/// the home row
+pub struct Zone {
     pub osc: OscHandle,
     pub adsr: AdsrHandle,
     pub gain: GainHandle,
     pub lfo_amp: LfoAmpHandle,
+    pub am: AmHandle,
+    pub ringmod: RingModHandle,
+    pub bitcrush: BitcrushHandle,
+    pub sample_hold: SampleHoldHandle,
+    pub highpass: HighPassHandle,
     pub lowpass: LowPassHandle,
+    pub noise_gate: NoiseGateHandle,
+    pub pan: PanHandle,
+    modulation: Modulation,
+    /// Effects ahead of the envelope in signal order
+    pub effects: Vec<Fx>,
 
     pub patch: Patch,
 }
 
+impl Zone {
+    /// Fresh handles set from `snapshot`, playing `wave`. The layers, LFO
+    /// and bend are shared by every zone
+    #[must_use]
+    fn new(
+        snapshot: &Snapshot,
+        wave: Wave,
+        layers: &LayersHandle,
+        modulation: &Modulation,
+        bend: &Bend,
+    ) -> Self {
+        let osc = make_osc(wave, snapshot.crossfade_s, snapshot.pulse_duty);
+        osc.update(|osc| {
+            osc.sub = snapshot.sub;
+            osc.table = snapshot.wavetable.clone();
+            osc.fm = snapshot.fm.clone();
+        });
+        let adsr = make_adsr(snapshot.adsr.clone());
+        let noise_gate = make_noise_gate(&snapshot.noise_gate);
+        let pan = make_pan(&snapshot.pan);
+
+        // after the envelope so release tails are gated too
+        let post_effects = FxChain::new(vec![Arc::new(noise_gate.clone()), Arc::new(pan.clone())]);
+
+        let patch = Patch::new(
+            osc.clone(),
+            layers.clone(),
+            adsr.clone(),
+            FxChain::default(),
+            post_effects,
+            modulation.clone(),
+            bend.clone(),
+        );
+
+        let mut zone = Self {
+            osc,
+            adsr,
+            gain: make_gain(snapshot.gain.amount),
+            lfo_amp: make_lfo_amp(snapshot.lfo_amp.clone()),
+            am: make_am(&snapshot.am),
+            ringmod: make_ringmod(&snapshot.ringmod),
+            bitcrush: make_bitcrush(&snapshot.bitcrush),
+            sample_hold: make_sample_hold(&snapshot.sample_hold),
+            highpass: make_highpass(&snapshot.highpass),
+            lowpass: make_lowpass(&snapshot.lowpass),
+            noise_gate,
+            pan,
+            modulation: modulation.clone(),
+            effects: Vec::new(),
+            patch,
+        };
+        zone.set_fx_chain(&snapshot.effects);
+
+        zone
+    }
+
+    /// Rebuilds the chain ahead of the envelope in the order of `effects`.
+    /// An effect can only appear once, repeats are dropped. Only voices
+    /// started afterwards run the new chain, held and releasing ones keep
+    /// the one they were built with
+    pub fn set_fx_chain(&mut self, effects: &[Fx]) {
+        let mut chain = FxChain::default();
+        self.effects.clear();
+
+        for &fx in effects {
+            if self.effects.contains(&fx) {
+                continue;
+            }
+            self.effects.push(fx);
+            chain.push(self.effect(fx));
+        }
+
+        self.patch.set_effects(chain);
+    }
+
+    fn effect(&self, fx: Fx) -> SharedEffect {
+        match fx {
+            Fx::SampleHold => Arc::new(self.sample_hold.clone()),
+            Fx::Gain => Arc::new(self.gain.clone()),
+            Fx::LfoAmp => Arc::new(self.lfo_amp.clone()),
+            Fx::Am => Arc::new(self.am.clone()),
+            Fx::RingMod => Arc::new(self.ringmod.clone()),
+            Fx::Bitcrush => Arc::new(self.bitcrush.clone()),
+            Fx::HighPass => Arc::new(self.highpass.clone()),
+            Fx::LowPass => Arc::new(ModLowPass {
+                lowpass: self.lowpass.clone(),
+                modulation: self.modulation.clone(),
+            }),
+        }
+    }
+}
+
 impl State {
-    #[must_use] 
+    #[must_use]
     pub fn from_snapshot(snapshot: Snapshot) -> Self {
-        let osc = make_osc(snapshot.wave);
-        let adsr = make_adsr(snapshot.adsr);
-        let gain = make_gain(snapshot.gain.amount);
-        let lfo_amp = make_lfo_amp(snapshot.lfo_amp);
-        let lowpass = make_lowpass(&snapshot.lowpass);
-
-        let effects: Vec<SharedEffect> = vec![
-            Arc::new(gain.clone()),
-            Arc::new(lfo_amp.clone()),
-            Arc::new(lowpass.clone()),
-        ];
+        let layers = make_layers(&snapshot.layers);
+        let modulation = Modulation::new(&snapshot.lfo);
+        let bend = Bend::new(snapshot.bend_range_st);
+        bend.set_amount(snapshot.bend);
 
-        let patch = Patch::new(osc.clone(), adsr.clone(), effects);
+        let main = Zone::new(
+            &snapshot,
+            snapshot.wave.clone(),
+            &layers,
+            &modulation,
+            &bend,
+        );
+        let split_zone = Zone::new(
+            &snapshot,
+            snapshot.split_wave.clone(),
+            &layers,
+            &modulation,
+            &bend,
+        );
 
         Self {
             volume: snapshot.volume,
             muted: snapshot.muted,
             octave: snapshot.octave,
+            transpose: snapshot.transpose,
+            a4_hz: snapshot.a4_hz,
+            tuning: snapshot.tuning,
+            input: snapshot.input,
+            max_note_ms: snapshot.max_note_ms,
+            max_voices: snapshot.max_voices,
+            voice_mode: snapshot.voice_mode,
+            glide_s: snapshot.glide_s,
+            bend,
+            bend_return: snapshot.bend_return,
+            mono_keys: Vec::new(),
+            sustain: false,
+            sustained: Vec::new(),
+            quantizer: snapshot.quantizer,
+            chord: snapshot.chord,
+            velocity: snapshot.velocity,
+            tempo: snapshot.tempo,
+            tick: 0,
+            arp: snapshot.arp,
+            sequence: snapshot.sequence,
+            seq_step: None,
+            active_voices: 0,
+            scope_voice: None,
+            active_keys: Vec::new(),
+            last_note: None,
+            unison: Unison::new(snapshot.unison.voices, snapshot.unison.detune_cents),
+            recording: None,
+            error: None,
             held_keys: HashSet::new(),
-            osc,
-            adsr,
-            gain,
-            lfo_amp,
-            lowpass,
-            patch,
+            accented: HashSet::new(),
+            held_midi: HashMap::new(),
+            layers,
+            modulation,
+            delay: make_delay(&snapshot.delay),
+            reverb: make_reverb(&snapshot.reverb),
+            main,
+            split: snapshot.split,
+            split_zone,
+            edit_split: snapshot.edit_split && snapshot.split,
         }
     }
 
+    /// The zone envelope and effect changes go to
     #[inline]
-    #[must_use] 
+    #[must_use]
+    pub fn zone(&self) -> &Zone {
+        if self.edit_split {
+            &self.split_zone
+        } else {
+            &self.main
+        }
+    }
+
+    #[inline]
+    fn oscs(&self) -> [&OscHandle; 2] {
+        [&self.main.osc, &self.split_zone.osc]
+    }
+
+    #[inline]
+    #[must_use]
     pub fn wave(&self) -> Wave {
-        self.osc.get().wave
+        self.main.osc.get().wave
     }
 
     #[inline]
     pub fn set_wave(&self, wave: Wave) {
-        self.osc.update(|osc| osc.wave = wave);
+        self.main.osc.update(|osc| osc.wave = wave);
     }
 
     #[inline]
     pub fn toggle_wave(&self) {
-        self.osc.update(|osc| osc.wave = osc.wave.toggle());
+        self.main.osc.update(|osc| osc.wave = osc.wave.toggle());
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn split_wave(&self) -> Wave {
+        self.split_zone.osc.get().wave
+    }
+
+    #[inline]
+    pub fn set_split_wave(&self, wave: Wave) {
+        self.split_zone.osc.update(|osc| osc.wave = wave);
+    }
+
+    /// Pitch of `key` at the current A4 and tuning system
+    #[inline]
+    #[must_use]
+    pub fn frequency(&self, key: Key) -> f32 {
+        self.tuning.frequency(key, self.quantizer.root, self.a4_hz)
+    }
+
+    /// Top-row keys play the split patch while split mode is on
+    #[inline]
+    #[must_use]
+    pub fn patch_for(&self, keycode: Keycode) -> &Patch {
+        if self.split && KeyRow::from_keycode(keycode) == Some(KeyRow::Top) {
+            &self.split_zone.patch
+        } else {
+            &self.main.patch
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn crossfade(&self) -> f32 {
+        self.main.osc.get().crossfade_s
+    }
+
+    #[inline]
+    pub fn set_crossfade(&self, crossfade_s: f32) {
+        let crossfade_s = crossfade_s.max(0.0);
+        for osc in self.oscs() {
+            osc.update(|osc| osc.crossfade_s = crossfade_s);
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn pulse_duty(&self) -> f32 {
+        self.main.osc.get().duty
+    }
+
+    #[inline]
+    pub fn set_pulse_duty(&self, duty: f32) {
+        let duty = duty.clamp(DUTY_MIN, DUTY_MAX);
+        for osc in self.oscs() {
+            osc.update(|osc| osc.duty = duty);
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn wavetable(&self) -> Wavetable {
+        self.main.osc.get().table
+    }
+
+    #[inline]
+    pub fn set_wavetable(&self, table: &Wavetable) {
+        for osc in self.oscs() {
+            osc.update(|osc| osc.table = table.clone());
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn fm(&self) -> Fm {
+        self.main.osc.get().fm
+    }
+
+    #[inline]
+    pub fn set_fm(&self, fm: &Fm) {
+        let fm = Fm::new(
+            fm.modulator.clone(),
+            fm.ratio.min(FM_RATIO_MAX),
+            fm.index.min(FM_INDEX_MAX),
+        );
+        for osc in self.oscs() {
+            osc.update(|osc| osc.fm = fm.clone());
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn sub(&self) -> Sub {
+        self.main.osc.get().sub
+    }
+
+    #[inline]
+    pub fn set_sub(&self, sub: Sub) {
+        let sub = Sub::new(sub.wave, sub.level);
+        for osc in self.oscs() {
+            osc.update(|osc| osc.sub = sub);
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn layers(&self) -> Vec<Layer> {
+        self.layers.get()
+    }
+
+    #[inline]
+    pub fn set_layers(&self, layers: &[Layer]) {
+        self.layers.set(clamp_layers(layers));
     }
 
     #[inline]
-    #[must_use] 
+    #[must_use]
     pub fn adsr(&self) -> Adsr {
-        self.adsr.get()
+        self.zone().adsr.get()
     }
 
     #[inline]
     pub fn set_adsr(&self, adsr: Adsr) {
-        self.adsr.set(adsr);
+        self.zone().adsr.set(adsr);
     }
 
     #[inline]
-    #[must_use] 
+    #[must_use]
     pub fn gain(&self) -> Gain {
-        self.gain.get()
+        self.zone().gain.get()
     }
 
     #[inline]
     pub fn set_gain(&self, gain: Gain) {
-        self.gain.set(gain);
+        self.zone().gain.set(gain);
     }
 
     #[inline]
-    #[must_use] 
+    #[must_use]
     pub fn lfo_amp(&self) -> LfoAmp {
-        self.lfo_amp.get()
+        self.zone().lfo_amp.get()
     }
 
     #[inline]
     pub fn set_lfo_amp(&self, lfo_amp: LfoAmp) {
-        self.lfo_amp.set(lfo_amp);
+        self.zone().lfo_amp.set(lfo_amp);
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn lfo(&self) -> Lfo {
+        self.modulation.lfo()
+    }
+
+    #[inline]
+    pub fn set_lfo(&self, lfo: &Lfo) {
+        self.modulation.set_lfo(lfo);
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn am(&self) -> Am {
+        self.zone().am.get()
+    }
+
+    #[inline]
+    pub fn set_am(&self, am: Am) {
+        self.zone().am.set(am);
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn ringmod(&self) -> RingMod {
+        self.zone().ringmod.get()
+    }
+
+    #[inline]
+    pub fn set_ringmod(&self, ringmod: &RingMod) {
+        self.zone()
+            .ringmod
+            .set(RingMod::new(ringmod.freq_hz, ringmod.mix));
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn bitcrush(&self) -> Bitcrush {
+        self.zone().bitcrush.get()
     }
 
     #[inline]
-    #[must_use] 
+    pub fn set_bitcrush(&self, bitcrush: Bitcrush) {
+        let bitcrush = Bitcrush::new(bitcrush.bits, bitcrush.downsample);
+        self.zone().bitcrush.set(bitcrush);
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn sample_hold(&self) -> SampleHold {
+        self.zone().sample_hold.get()
+    }
+
+    #[inline]
+    pub fn set_sample_hold(&self, sample_hold: SampleHold) {
+        self.zone().sample_hold.set(sample_hold);
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn highpass(&self) -> HighPass {
+        self.zone().highpass.get()
+    }
+
+    #[inline]
+    pub fn set_highpass(&self, highpass: HighPass) {
+        self.zone().highpass.set(highpass);
+    }
+
+    #[inline]
+    #[must_use]
     pub fn lowpass(&self) -> LowPass {
-        self.lowpass.get()
+        self.zone().lowpass.get()
     }
 
     #[inline]
     pub fn set_lowpass(&self, lowpass: LowPass) {
-        self.lowpass.set(lowpass);
+        self.zone().lowpass.set(lowpass);
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn delay(&self) -> Delay {
+        self.delay.get()
     }
 
     #[inline]
-    #[must_use] 
+    pub fn set_delay(&self, delay: Delay) {
+        self.delay.set(delay);
+    }
+
+    /// Effects on the whole mix after the voices, in signal order
+    #[must_use]
+    pub fn master_bus(&self) -> FxChain {
+        FxChain::new(vec![
+            Arc::new(self.delay.clone()),
+            Arc::new(self.reverb.clone()),
+        ])
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn reverb(&self) -> Reverb {
+        self.reverb.get()
+    }
+
+    #[inline]
+    pub fn set_reverb(&self, reverb: &Reverb) {
+        self.reverb
+            .set(Reverb::new(reverb.room, reverb.damping, reverb.mix));
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn noise_gate(&self) -> NoiseGate {
+        self.zone().noise_gate.get()
+    }
+
+    #[inline]
+    pub fn set_noise_gate(&self, noise_gate: NoiseGate) {
+        self.zone().noise_gate.set(noise_gate);
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn pan(&self) -> Pan {
+        self.zone().pan.get()
+    }
+
+    #[inline]
+    pub fn set_pan(&self, pan: Pan) {
+        self.zone().pan.set(pan);
+    }
+
+    /// Rebuilds the effects chain of the zone being edited
+    pub fn set_fx_chain(&mut self, effects: &[Fx]) {
+        if self.edit_split {
+            self.split_zone.set_fx_chain(effects);
+        } else {
+            self.main.set_fx_chain(effects);
+        }
+    }
+
+    /// Passes `err` on to the UI's status line
+    pub fn report(&mut self, err: impl std::fmt::Display) {
+        let count = self.error.as_ref().map_or(0, |(count, _)| *count);
+        self.error = Some((count + 1, err.to_string()));
+    }
+
+    #[inline]
+    #[must_use]
     pub fn snapshot(&self) -> Snapshot {
         Snapshot {
             volume: self.volume,
             muted: self.muted,
             wave: self.wave(),
+            crossfade_s: self.crossfade(),
+            pulse_duty: self.pulse_duty(),
+            sub: self.sub(),
+            wavetable: self.wavetable(),
+            fm: self.fm(),
             octave: self.octave,
-            patch_name: self.patch.name(),
+            transpose: self.transpose,
+            a4_hz: self.a4_hz,
+            tuning: self.tuning.clone(),
+            input: self.input,
+            max_note_ms: self.max_note_ms,
+            max_voices: self.max_voices,
+            voice_mode: self.voice_mode,
+            glide_s: self.glide_s,
+            bend: self.bend.amount(),
+            bend_range_st: self.bend.range_st(),
+            bend_return: self.bend_return,
+            sustain: self.sustain,
+            quantizer: self.quantizer,
+            chord: self.chord,
+            velocity: self.velocity,
+            tempo: self.tempo,
+            beat: self.tick / u64::from(TEMPO_PPQ),
+            arp: self.arp.clone(),
+            sequence: self.sequence.clone(),
+            seq_step: self.seq_step,
+            active_voices: self.active_voices,
+            scope_voice: self.scope_voice,
+            active_keys: self.active_keys.clone(),
+            last_note: self.last_note,
+            fx_chain: self.zone().patch.fx_names(),
+            effects: self.zone().effects.clone(),
+            recording: self.recording.clone(),
+            error: self.error.clone(),
+            patch_name: self.main.patch.name(),
+            split: self.split,
+            edit_split: self.edit_split,
+            split_wave: self.split_wave(),
+            layers: self.layers(),
+            unison: self.unison.clone(),
             adsr: self.adsr(),
             gain: self.gain(),
             lfo_amp: self.lfo_amp(),
+            lfo: self.lfo(),
+            am: self.am(),
+            ringmod: self.ringmod(),
+            bitcrush: self.bitcrush(),
+            sample_hold: self.sample_hold(),
+            highpass: self.highpass(),
             lowpass: self.lowpass(),
+            delay: self.delay(),
+            reverb: self.reverb(),
+            noise_gate: self.noise_gate(),
+            pan: self.pan(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::play::key::Note;
+
+    fn split_state() -> State {
+        State::from_snapshot(Snapshot {
+            wave: Wave::Sine,
+            split: true,
+            split_wave: Wave::Square,
+            ..Snapshot::default()
+        })
+    }
+
+    #[test]
+    fn rows_play_their_own_patch() {
+        let state = split_state();
+
+        assert_eq!(state.patch_for(Keycode::A).wave(), Wave::Sine);
+        assert_eq!(state.patch_for(Keycode::W).wave(), Wave::Square);
+
+        let off = State {
+            split: false,
+            ..split_state()
+        };
+        assert_eq!(off.patch_for(Keycode::W).wave(), Wave::Sine);
+    }
+
+    #[test]
+    fn zones_keep_their_own_envelope_and_effects() {
+        let mut state = split_state();
+        let home = state.adsr();
+        let home_fx = state.main.patch.fx_names();
+
+        state.edit_split = true;
+        state.set_adsr(Adsr {
+            attack_s: home.attack_s + 1.0,
+            ..home.clone()
+        });
+        state.set_fx_chain(&[Fx::Bitcrush]);
+        state.set_gain(Gain { amount: 0.25 });
+
+        let top = state.patch_for(Keycode::W);
+        assert!((top.adsr().attack_s - (home.attack_s + 1.0)).abs() < 1e-6);
+        assert_eq!(top.fx_names(), ["Bitcrush", "ADSR", "Gate", "Pan"]);
+
+        let main = state.patch_for(Keycode::A);
+        assert!((main.adsr().attack_s - home.attack_s).abs() < 1e-6);
+        assert_eq!(main.fx_names(), home_fx);
+        assert!((state.main.gain.get().amount - 0.25).abs() > 1e-6);
+
+        // the snapshot shows whichever zone is being edited
+        assert_eq!(state.snapshot().effects, vec![Fx::Bitcrush]);
+        state.edit_split = false;
+        assert_eq!(state.snapshot().effects, state.main.effects);
+    }
+
+    #[test]
+    fn a4_follows_the_reference_pitch() {
+        let a4 = Key::new(Note::A, 4);
+        let mut state = State::from_snapshot(Snapshot::default());
+        assert_eq!(state.frequency(a4), 440.0);
+
+        state.a4_hz = 432.0;
+        assert_eq!(state.frequency(a4), 432.0);
+        assert!((state.frequency(Key::new(Note::A, 5)) - 864.0).abs() < 1e-3);
+    }
+}
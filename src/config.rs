@@ -1,34 +1,275 @@
 //! Magic numbers and synth defaults
 
-use crate::patch::oscilators::basic::Wave;
+use crate::patch::bend::BendReturn;
+use crate::patch::effects::adsr::{EnvelopeCurve, EnvelopeMode};
+use crate::patch::effects::sample_hold::HoldTarget;
+use crate::patch::lfo::LfoTarget;
+use crate::patch::oscilators::basic::{SubWave, Wave};
+use crate::play::VoiceMode;
+use crate::play::arp::ArpPattern;
+use crate::play::chord::Chord;
+use crate::play::input::InputEngine;
+use crate::play::key::Note;
+use crate::play::scale::Scale;
+use crate::play::spectrum::Weighting;
+use crate::play::tuning::Tuning;
+use crate::play::velocity::VelocityCurve;
+use crate::settings::{IntroMode, Theme};
 use tokio::time::Duration;
 
 // play.rs
 pub const TICK: u64 = 10;
+pub const MAX_VOICES: usize = 16; // notes at once before the oldest is stolen, --max-voices
+pub const MAX_VOICES_LIMIT: usize = 128;
+pub const VOICE_MODE: VoiceMode = VoiceMode::Poly;
+pub const GLIDE_S: f32 = 0.0; // mono/legato portamento, 0 jumps straight to the note
+pub const GLIDE_MAX_S: f32 = 2.0;
+pub const BEND_RANGE_ST: f32 = 2.0; // full bend either way
+pub const BEND_RANGE_MAX_ST: f32 = 24.0;
+pub const BEND_RETURN: BendReturn = BendReturn::Smooth;
+pub const BEND_RETURN_S: f32 = 0.15; // smooth return from a full bend
+pub const BEND_KEY_HOLD_MS: u64 = 150; // a bend key press holds this long, repeats extend it
+
+// main.rs
+pub const UI_FPS: u32 = 60; // overridden by --fps
+pub const UI_FPS_MAX: u32 = 240;
+pub const UI_FPS_STEPS: [u32; 5] = [15, 30, 60, 120, 240]; // Ctrl+F cycles through these
+pub const FRAME_TIME_WINDOW: usize = 120; // frames the min/avg/max frame time is taken over
+pub const TEST_TONE_HOLD_S: f32 = 3.0; // --test-tone holds the note this long before releasing
+pub const TEST_TONE_TAIL_MS: u64 = 500; // waited past the release before giving up on the tail
+
+// settings.rs
+pub const SETTINGS_PATH: &str = "mugen/config.toml"; // under $XDG_CONFIG_HOME or ~/.config
+pub const SEQUENCE_PATH: &str = "mugen/sequence.toml";
+
+// patch/file.rs
+pub const PATCH_DIR: &str = "mugen/patches"; // under $XDG_CONFIG_HOME or ~/.config
+pub const PATCH_EXT: &str = "patch";
+
+// ui.rs
+pub const INTRO_MODE: IntroMode = IntroMode::Always; // overridden by `intro` in the settings file
+pub const UI_THEME: Theme = Theme::Dragon; // overridden by `theme` in the settings file
+pub const VOLUME_STEP: f32 = 0.05; // 9/0 keys
+pub const VELOCITY_STEP: u8 = 8;
+pub const ADSR_GRAPH_HOLD_S: f32 = 0.5; // nominal note length the graph sustains for
+pub const ADSR_PARAMS_WIDTH: u16 = 26;
+pub const ADSR_GRAPH_SIDE_MIN_WIDTH: u16 = 46; // narrower panes draw the graph under the values
+pub const ADSR_GRAPH_MIN_HEIGHT: u16 = 3;
+pub const ADSR_GRAPH_STAGE_POINTS: usize = 24; // samples per stage, enough for a smooth curve
+pub const INTRO_DURATION_MS: u64 = 1000;
+pub const INTRO_STAMP: &str = "mugen/intro_stamp"; // beside the settings file
+
+// capture.rs
+pub const CAPTURE_FRAMES: usize = 4096; // history kept for the scope and analyzer
+pub const VOICE_TAP_CHANNELS: usize = 8; // most channels a pinned voice's unison copies sum on, as many as --channels opens
+pub const SCOPE_FRAMES: usize = 1024;
+pub const SCOPE_TRIGGER_LEVEL: f32 = 0.0; // -1..1
+pub const SCOPE_AUTOSCALE: bool = false; // Ctrl+A toggles, Ctrl+Up/Down zoom by hand
+pub const SCOPE_AUTOSCALE_FILL: f32 = 0.8; // fraction of the height the peak is scaled to
+pub const SCOPE_AUTOSCALE_FLOOR: f32 = 1e-4; // quieter than this counts as silence
+pub const SCOPE_AUTOSCALE_TAU_S: f32 = 0.3; // how slowly it zooms in, zooming out is instant
+pub const SCOPE_SCALE_MIN: f32 = 0.5;
+pub const SCOPE_SCALE_MAX: f32 = 256.0;
+pub const SCOPE_GRID: bool = true; // Ctrl+R toggles
+pub const SCOPE_NOTES: bool = false; // each sounding note's pitch over the scope, Ctrl+Y toggles
+pub const SCOPE_GRID_LINES: usize = 2; // amplitude lines above zero, the step follows the zoom
+pub const SCOPE_GRID_DIVISIONS: usize = 8; // time divisions across the scope
+pub const SCOPE_AFTERGLOW: bool = false; // Ctrl+G toggles
+pub const SCOPE_AFTERGLOW_DEPTH: usize = 4; // earlier traces drawn fading out, Ctrl+Left/Right
+pub const SCOPE_AFTERGLOW_DEPTH_MAX: usize = 8;
+pub const SCOPE_SHADES: usize = 16; // colour steps for trails and the spectrogram, Ctrl+E
+pub const SCOPE_SHADE_STEPS: [usize; 4] = [2, 4, 8, 16];
+pub const SCOPE_CONTRAST: f32 = 1.0; // curve exponent, above 1 darkens quiet levels, Ctrl+K
+pub const SCOPE_CONTRAST_STEPS: [f32; 5] = [0.5, 0.75, 1.0, 1.5, 2.0];
+pub const SCOPE_MONO: bool = false; // black and white only, Ctrl+B
+pub const SPECTRUM_FRAMES: usize = 2048;
+pub const RESYNTH_PARTIALS: usize = 8; // strongest spectrum peaks Ctrl+X freezes into a wavetable
+pub const RESYNTH_RANGE_DB: f32 = 60.0; // peaks further below the loudest are left out
+pub const RESYNTH_MAX_HARMONIC: usize = 64;
+pub const RESYNTH_MIN_GAP_BINS: usize = 4; // closer peaks count as one, past the window's side lobes
+pub const SPECTRUM_WEIGHTING: Weighting = Weighting::Flat; // Ctrl+W cycles A and C weighting
+pub const SPECTROGRAM_ROW_MS: u64 = 40; // how often a new row scrolls in
+pub const CAPTURE_DC_BLOCK: bool = true; // strip DC from what the scope sees, Ctrl+D toggles
+pub const CAPTURE_DC_CUTOFF_HZ: f32 = 10.0; // corner of the DC blocker, under anything audible
+pub const CALLBACK_GAP_US: u64 = 200; // a pause this long between blocks starts a new callback
+pub const CALLBACK_TIMER_BLOCK: usize = 32; // samples between clock reads on the audio thread
+pub const CALLBACK_LATE: f32 = 1.5; // a callback this many of its own lengths late counts as an underrun
+pub const LOAD_SMOOTHING: f32 = 0.1; // how far each callback moves the shown load, 0..1
+pub const AUDIO_LOAD_WARN: f32 = 0.8; // the DSP readout turns to the accent colour from here
+
+// recorder.rs
+pub const RECORD_DIR: &str = "./recordings";
+pub const RECORD_CHUNK_FRAMES: usize = 1024; // frames per hand-off to the writer thread
+pub const RECORD_SPARE_CHUNKS: usize = 4; // buffers handed back and forth so the tap never allocates
+pub const RECORD_STOP_TIMEOUT_MS: u64 = 500; // longest shutdown waits for stopped recordings to be finalized
+pub const RECORD_LATENCY_MAX_MS: u32 = 1000; // --record-latency
+pub const RECORD_MAX_S: u32 = 3600; // longest recording before it stops itself, 0 for no limit, `record_max` in the settings file
+pub const RECORD_WARN_S: u64 = 10; // the UI counts down this close to the limit
 
 // key.rs
-pub const BASE_FREQ: f32 = 440.0;
+pub const BASE_FREQ: f32 = 440.0; // default A4, overridden by --a4
+pub const A4_MIN_HZ: f32 = 400.0;
+pub const A4_MAX_HZ: f32 = 480.0;
 pub const A4_SEMITONES: i32 = 57;
 pub const SEMITONES_PER_OCTAVE: i32 = 12;
 pub const KEYBOARD_BASE_OCTAVE: i32 = 4;
+pub const MIDI_NOTE_0_OCTAVE: i32 = -1; // MIDI note 0 is C-1, 60 is C4
+pub const OCTAVE_SHIFT_MIN: i32 = -4; // relative to KEYBOARD_BASE_OCTAVE
+pub const OCTAVE_SHIFT_MAX: i32 = 4;
+pub const TRANSPOSE_MAX: i32 = 12; // semitones either way
+
+// input.rs
+pub const INPUT_ENGINE: InputEngine = InputEngine::Global;
+pub const TERMINAL_HOLD_MS: u64 = 500; // covers the OS key-repeat delay
+pub const TERMINAL_RELEASE_MS: u64 = 120; // gap between repeats that counts as key-up
+pub const MAX_NOTE_MS: Option<u64> = None; // auto note-off, None holds forever, --max-note or `max_note_ms` in the settings file
+pub const MAX_NOTE_MAX_MS: u64 = 600_000;
+pub const IGNORE_HELD_AT_START: bool = true; // keys down at launch wait for a re-press, --play-held or `ignore_held` in the settings file
+pub const KEY_DEBOUNCE_MS: u64 = 30; // a held key may vanish from a poll this long
+pub const KEY_DEBOUNCE_MAX_MS: u64 = 250; // longest --debounce accepted
 
 // audio_source.rs
 pub const AMP_DEFAULT: f32 = 0.1;
+pub const WAVE_CROSSFADE_S: f32 = 0.03; //sec, `crossfade` in the settings file
+pub const WAVE_CROSSFADE_MAX_S: f32 = 0.5;
+pub const WAVE_CROSSFADE_STEP_S: f32 = 0.01; // </> in the waveforms pane
+pub const VOICE_CUT_FADE_MS: u64 = 5; // stolen and panicked voices ramp out instead of clicking
+pub const PULSE_DUTY: f32 = 0.25; //0.05..0.95
+
+// wavetable.rs
+pub const WAVETABLE_SIZE: usize = 2048; // samples in a built-in table
+pub const WAVETABLE_MAX_SIZE: usize = 4096; // a loaded WAV is resampled to at most this
+pub const WAVETABLE_MAX_FILE_BYTES: u64 = 16 << 20; // bigger WAVs are refused unread
+
+// stack.rs
+pub const LAYERS_MAX: usize = 2; // extra oscillators on top of the patch one
+pub const LAYER_DETUNE_CENTS: f32 = 7.0;
+pub const SUB_WAVE: SubWave = SubWave::Sine;
+pub const SUB_LEVEL: f32 = 0.0; // 0..1 against the main oscillator, 0 is off
+
+// fm.rs
+pub const FM_MODULATOR: Wave = Wave::Sine;
+pub const FM_RATIO: f32 = 2.0; // modulator frequency against the carrier
+pub const FM_RATIO_MAX: f32 = 16.0;
+pub const FM_INDEX: f32 = 0.0; // radians of phase deviation, 0 is off
+pub const FM_INDEX_MAX: f32 = 10.0;
+
+// tempo.rs
+pub const TEMPO_BPM: f32 = 120.0;
+pub const TEMPO_BPM_MIN: f32 = 30.0;
+pub const TEMPO_BPM_MAX: f32 = 300.0;
+pub const TEMPO_PPQ: u32 = 24; // clock ticks per beat, steps per beat must divide it
+pub const BEATS_PER_BAR: u64 = 4;
+pub const TAP_TEMPO_TAPS: usize = 4; // taps averaged for the tempo
+pub const TAP_TEMPO_RESET_MS: u64 = 2000; // a longer pause starts counting again
+
+// arp.rs
+pub const ARP_ENABLED: bool = false;
+pub const ARP_STEPS_PER_BEAT: u32 = 4; // sixteenth notes
+pub const ARP_PATTERN: ArpPattern = ArpPattern::Up;
+pub const ARP_LENGTH: f32 = 0.5; // fraction of a step
+pub const ARP_SEED: u64 = 0xA2B3_C4D5_E6F7_0819;
+
+// scale.rs
+pub const SCALE: Scale = Scale::Chromatic; // chromatic leaves notes alone
+pub const SCALE_ROOT: Note = Note::C;
+
+// tuning.rs
+pub const TUNING: Tuning = Tuning::Equal; // pure intervals count up from the scale root
+pub const TUNING_MAX_NOTES: usize = 128; // per period, for custom scales
+pub const TUNING_CUSTOM_ROOT_OCTAVE: i32 = 4; // custom scales count keys from this root
+
+// chord.rs
+pub const CHORD: Chord = Chord::Off;
+pub const CHORD_CUSTOM: &[i32] = &[0, 5, 10]; // semitones above the played note
+
+// velocity.rs
+pub const VELOCITY_CURVE: VelocityCurve = VelocityCurve::Linear;
+pub const VELOCITY_CURVE_EXPONENT: f32 = 2.0; // higher makes soft notes quieter
+pub const VELOCITY_KEYS: u8 = 100; // computer keys have no touch, they play at this
+pub const VELOCITY_ACCENT: u8 = 127; // computer keys pressed with shift held
+
+// sequencer.rs
+pub const SEQ_STEPS: usize = 16;
+pub const SEQ_STEPS_PER_BEAT: u32 = 4; // sixteenth notes
+pub const SEQ_LENGTH: f32 = 0.5; // fraction of a step each note sounds for
+
+// unison.rs
+pub const UNISON_VOICES: u8 = 1;
+pub const UNISON_MAX: u8 = 7;
+pub const UNISON_DETUNE_CENTS: f32 = 10.0;
 
 // atches
 pub const SAMPLE_RATE: u32 = 48_000;
 pub const ENDLESS: Duration = Duration::from_secs(3600);
 
+// slew.rs
+pub const PARAM_SLEW_S: f32 = 0.02; //sec, overridden by `slew` in the settings file or --slew
+pub const PARAM_SLEW_MAX_MS: u32 = 1000; // --slew and `slew` in the settings file
+
 // ADSR defaults
 pub const ADSR_ATTACK_S: f32 = 0.5; //sec
 pub const ADSR_DECAY_S: f32 = 0.5; //sec
 pub const ADSR_SUSTAIN: f32 = 0.4; //0..1
 pub const ADSR_RELEASE_S: f32 = 1.0; //sec
+pub const ADSR_MODE: EnvelopeMode = EnvelopeMode::Gate;
+pub const ADSR_CURVE: EnvelopeCurve = EnvelopeCurve::Linear;
+pub const ADSR_CURVE_EXPONENT: f32 = 3.0; // higher bends the exponential curve harder
 
 // LFO defaults
 pub const LFO_KIND: Wave = Wave::Sine;
 pub const LFO_RATE_HZ: f32 = 10.0;
 pub const LFO_DEPTH: f32 = 1.0;
 
+// Routed LFO defaults, full depth swings the cutoff and pitch this far
+pub const LFO_MOD_WAVE: Wave = Wave::Sine;
+pub const LFO_MOD_RATE_HZ: f32 = 5.0;
+pub const LFO_MOD_RATE_MAX_HZ: f32 = 40.0;
+pub const LFO_MOD_DEPTH: f32 = 0.5;
+pub const LFO_MOD_TARGET: LfoTarget = LfoTarget::Off;
+pub const LFO_CUTOFF_RANGE_OCT: f32 = 3.0;
+pub const LFO_PITCH_RANGE_ST: f32 = 1.0;
+
+// AM defaults
+pub const AM_CARRIER_HZ: f32 = 4.0;
+pub const AM_DEPTH: f32 = 0.0;
+
+// Ring mod defaults
+pub const RINGMOD_FREQ_HZ: f32 = 440.0;
+pub const RINGMOD_MIX: f32 = 0.0;
+
+// Sample & hold defaults
+pub const SH_RATE_HZ: f32 = 8.0;
+pub const SH_DEPTH: f32 = 0.0;
+pub const SH_TARGET: HoldTarget = HoldTarget::Pitch;
+pub const SH_SEED: u64 = 0x5EED_CAFE_F00D_1234;
+
+// HighPass defaults
+pub const HIGHPASS_CUTOFF: f32 = 20.0;
+pub const HIGHPASS_Q: f32 = 0.707;
+
 // LowPass default
 pub const CUTOFF: f32 = 20000.0;
+
+// Pan default
+pub const PAN: f32 = 0.0; //-1..1
+
+// Bitcrush defaults, 16 bits with no decimation is close to transparent
+pub const BITCRUSH_BITS: u8 = 16;
+pub const BITCRUSH_DOWNSAMPLE: u32 = 1;
+
+// Delay defaults
+pub const DELAY_TIME_MS: f32 = 300.0;
+pub const DELAY_FEEDBACK: f32 = 0.4;
+pub const DELAY_MIX: f32 = 0.0;
+pub const DELAY_MAX_MS: f32 = 2000.0;
+
+// Reverb defaults
+pub const REVERB_ROOM: f32 = 0.5;
+pub const REVERB_DAMPING: f32 = 0.5;
+pub const REVERB_MIX: f32 = 0.0;
+
+// Noise gate defaults, the threshold sits under anything audible so it starts open
+pub const NOISE_GATE_THRESHOLD_DB: f32 = -96.0;
+pub const NOISE_GATE_ATTACK_MS: f32 = 1.0;
+pub const NOISE_GATE_RELEASE_MS: f32 = 50.0;
@@ -0,0 +1,46 @@
+//! Errors the crate fails with, split by where they come from so a caller
+//! can tell a missing device from a bad flag
+
+use std::fmt::Display;
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MugenError {
+    /// Opening or running an audio or MIDI device
+    #[error("{0}")]
+    AudioDevice(String),
+    /// The audio engine was started twice, or before its client existed
+    #[error("audio engine: {0}")]
+    Engine(&'static str),
+    /// A command line value or setting that cannot be used
+    #[error("{0}")]
+    Config(String),
+    /// A patch, wavetable or tuning file that could not be read or written
+    #[error("{}: {source}", path.display())]
+    PatchIo {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// A recording whose file could not be finished
+    #[error("failed to finish recording: {0}")]
+    Recording(#[source] io::Error),
+    /// The preset database
+    #[error("presets: {0}")]
+    Presets(#[from] rusqlite::Error),
+    /// Setting up or drawing to the terminal
+    #[error("terminal: {0}")]
+    Terminal(#[source] io::Error),
+}
+
+impl MugenError {
+    /// Device errors come from cpal, rodio and midir in many types, only
+    /// their message is kept
+    pub fn audio_device(err: impl Display) -> Self {
+        Self::AudioDevice(err.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, MugenError>;
@@ -1,6 +1,8 @@
 pub mod audio;
 pub mod config;
+pub mod error;
 pub mod patch;
 pub mod play;
 pub mod presets;
+pub mod settings;
 pub mod ui;
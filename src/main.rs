@@ -1,39 +1,386 @@
+use clap::builder::RangedU64ValueParser;
+use clap::{Parser, value_parser};
+use std::path::PathBuf;
 use std::sync::{
     Arc,
     atomic::{AtomicBool, Ordering},
 };
-use synth_rs::{audio::client, audio::run, ui::run_ui};
+use std::time::Duration;
+use synth_rs::audio::{Snapshot, init, run, test_tone};
+use synth_rs::config::{
+    A4_MAX_HZ, A4_MIN_HZ, IGNORE_HELD_AT_START, INTRO_MODE, KEY_DEBOUNCE_MAX_MS, KEY_DEBOUNCE_MS,
+    MAX_NOTE_MAX_MS, MAX_VOICES_LIMIT, PARAM_SLEW_MAX_MS, RECORD_LATENCY_MAX_MS, TEST_TONE_HOLD_S,
+    UI_FPS, UI_FPS_MAX, UI_THEME,
+};
+use synth_rs::error::{MugenError, Result};
+use synth_rs::patch::oscilators::basic::Wave;
+use synth_rs::patch::oscilators::wavetable::Wavetable;
+use synth_rs::patch::slew::set_param_slew;
+use synth_rs::play::StreamOptions;
+use synth_rs::play::input::InputEngine;
+use synth_rs::play::key::{Key, set_keymap};
+use synth_rs::play::midi::{self, MidiEvent};
+use synth_rs::play::sequencer::Sequence;
+use synth_rs::play::tuning::Tuning;
+use synth_rs::presets::start_patch;
+use synth_rs::settings::Settings;
+use synth_rs::ui::run_ui;
 use tokio::sync::watch;
 
+/// A software synthesizer played from the computer keyboard
+#[derive(Parser)]
+#[command(name = "mugen", bin_name = "mugen")]
+struct Args {
+    /// Start on a patch or preset by name, or on a patch file with its
+    /// effect chain
+    #[arg(long, value_name = "NAME|FILE")]
+    patch: Option<String>,
+    /// Output device, defaults to the system default
+    #[arg(long, value_name = "NAME")]
+    device: Option<String>,
+    /// Output sample rate
+    #[arg(long, value_name = "HZ", value_parser = value_parser!(u32).range(1..=384_000))]
+    sample_rate: Option<u32>,
+    /// Output channels, falls back to the device default if they cannot be
+    /// opened
+    #[arg(long, value_name = "N", value_parser = value_parser!(u16).range(1..=8))]
+    channels: Option<u16>,
+    /// Sum the output to one channel
+    #[arg(long)]
+    mono: bool,
+    /// Output buffer size
+    #[arg(long, value_name = "FRAMES", value_parser = value_parser!(u32).range(16..=65_536))]
+    buffer_size: Option<u32>,
+    /// Start recordings this far back so they line up with what was heard,
+    /// defaults to the buffer size
+    #[arg(
+        long,
+        value_name = "MS",
+        value_parser = value_parser!(u32).range(0..=i64::from(RECORD_LATENCY_MAX_MS))
+    )]
+    record_latency: Option<u32>,
+    /// Input the scope shows on Ctrl+L, defaults to the system default
+    #[arg(long, value_name = "NAME")]
+    input_device: Option<String>,
+    /// UI refresh rate
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = UI_FPS,
+        value_parser = value_parser!(u32).range(1..=i64::from(UI_FPS_MAX))
+    )]
+    fps: u32,
+    /// Concert pitch, 400..=480 [default: 440]
+    #[arg(long = "a4", value_name = "HZ", value_parser = parse_a4)]
+    a4_hz: Option<f32>,
+    /// Notes at once before the oldest is stolen [default: 16]
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..=MAX_VOICES_LIMIT as u64)
+    )]
+    max_voices: Option<usize>,
+    /// Release notes held longer than this, 0 holds them as long as the key
+    /// [default: 0]
+    #[arg(
+        long = "max-note",
+        value_name = "MS",
+        value_parser = value_parser!(u64).range(0..=MAX_NOTE_MAX_MS)
+    )]
+    max_note_ms: Option<u64>,
+    /// MIDI input whose name contains NAME, defaults to the first one found
+    #[arg(long, value_name = "NAME")]
+    midi_port: Option<String>,
+    /// How long a held key may drop out of a poll before its note stops
+    #[arg(
+        long = "debounce",
+        value_name = "MS",
+        default_value_t = KEY_DEBOUNCE_MS,
+        value_parser = value_parser!(u64).range(0..=KEY_DEBOUNCE_MAX_MS)
+    )]
+    debounce_ms: u64,
+    /// Play keys already held at launch instead of waiting for them to be
+    /// pressed again
+    #[arg(long)]
+    play_held: bool,
+    /// How long live gain, pan, cutoff and mix changes take to settle
+    /// [default: 20]
+    #[arg(
+        long = "slew",
+        value_name = "MS",
+        value_parser = value_parser!(u32).range(0..=i64::from(PARAM_SLEW_MAX_MS))
+    )]
+    slew_ms: Option<u32>,
+    /// Play a single-cycle WAV as the wavetable
+    #[arg(long, value_name = "PATH")]
+    wavetable: Option<PathBuf>,
+    /// Custom scale from a Scala .scl file, or any other file as a list of
+    /// cents, one key per degree counting from the scale root
+    #[arg(long, value_name = "PATH")]
+    tuning: Option<PathBuf>,
+    /// Play one note (e.g. A4) through the patch for a few seconds and exit,
+    /// no UI or keyboard
+    #[arg(long, value_name = "NOTE")]
+    test_tone: Option<Key>,
+    /// Play without the terminal UI, keys are read globally, Ctrl+C quits
+    #[arg(long)]
+    headless: bool,
+}
+
+impl Args {
+    fn stream(&self) -> StreamOptions {
+        StreamOptions {
+            device: self.device.clone(),
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            buffer_size: self.buffer_size,
+            mono: self.mono,
+            record_latency_ms: self.record_latency,
+            ..StreamOptions::default()
+        }
+    }
+}
+
+/// Concert pitch within `A4_MIN_HZ..=A4_MAX_HZ`, clap only ranges integers
+fn parse_a4(value: &str) -> std::result::Result<f32, String> {
+    let hz = value
+        .parse::<f32>()
+        .map_err(|_| format!("expected a number, got {value:?}"))?;
+
+    if (A4_MIN_HZ..=A4_MAX_HZ).contains(&hz) {
+        Ok(hz)
+    } else {
+        Err(format!("{hz} is out of range ({A4_MIN_HZ}..={A4_MAX_HZ})"))
+    }
+}
+
+/// Runs `task` and then raises the shutdown flag however it ended, so a
+/// failed output stream takes the UI down with it and the terminal is back
+/// to normal before the error is printed
+async fn shutting_down(
+    shutdown_tx: watch::Sender<bool>,
+    task: impl Future<Output = Result<()>>,
+) -> Result<()> {
+    let res = task.await;
+    // stored even before the UI has subscribed
+    shutdown_tx.send_replace(true);
+
+    res
+}
+
+/// Prints a failed task and exits, called once the terminal is restored
+fn exit_on_error(task: &str, res: Result<()>) {
+    if let Err(err) = res {
+        eprintln!("mugen: {task}: {err}");
+        std::process::exit(1);
+    }
+}
+
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let audio = client().await.clone();
+async fn main() -> Result<()> {
+    let mut args = Args::parse();
+    let mut stream = args.stream();
+
+    let mut settings = Settings::load();
+    if let Some(keymap) = settings.keymap.take() {
+        set_keymap(keymap);
+    }
+
+    stream.record_max_s = settings.record_max_s;
+    let theme = settings.theme.unwrap_or(UI_THEME);
+    let intro = settings.intro.unwrap_or(INTRO_MODE);
+    if let Some(slew_s) = args
+        .slew_ms
+        .map(|ms| ms as f32 / 1000.0)
+        .or(settings.slew_s)
+    {
+        set_param_slew(slew_s);
+    }
+
+    let ignore_held = args
+        .play_held
+        .then_some(false)
+        .or(settings.ignore_held)
+        .unwrap_or(IGNORE_HELD_AT_START);
+
+    let patch = args.patch.take().or_else(|| settings.patch.take());
+    let mut snapshot = match &patch {
+        Some(patch) => match start_patch(patch).await {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                // the patch may come from the settings file as well as --patch
+                eprintln!("mugen: {err}");
+                std::process::exit(2);
+            }
+        },
+        None => Snapshot::default(),
+    };
+    settings.apply(&mut snapshot, patch.is_some());
+    if let Some((sequence, tempo)) = Sequence::load() {
+        snapshot.sequence = sequence;
+        snapshot.tempo = tempo;
+    }
+    if let Some(a4_hz) = args.a4_hz {
+        snapshot.a4_hz = a4_hz;
+    }
+    if let Some(max_voices) = args.max_voices {
+        snapshot.max_voices = max_voices;
+    }
+    if let Some(max_ms) = args.max_note_ms.or(settings.max_note_ms) {
+        snapshot.max_note_ms = (max_ms > 0).then_some(max_ms);
+    }
+    if let Some(path) = &args.wavetable {
+        match Wavetable::load(path) {
+            Ok(table) => {
+                snapshot.wavetable = table;
+                snapshot.wave = Wave::Wavetable;
+                snapshot.patch_name = Wave::Wavetable.name().to_string();
+            }
+            Err(source) => {
+                let err = MugenError::PatchIo {
+                    path: path.clone(),
+                    source,
+                };
+                eprintln!("mugen: --wavetable {err}");
+                std::process::exit(2);
+            }
+        }
+    }
+    if let Some(path) = &args.tuning {
+        match Tuning::load(path) {
+            Ok(tuning) => snapshot.tuning = tuning,
+            Err(source) => {
+                let err = MugenError::PatchIo {
+                    path: path.clone(),
+                    source,
+                };
+                eprintln!("mugen: --tuning {err}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // the terminal engine needs the UI to read keys
+    if args.headless {
+        snapshot.input = InputEngine::Global;
+    }
+
+    if let Some(key) = args.test_tone {
+        println!(
+            "mugen: test tone {key} at {:.2} Hz",
+            snapshot
+                .tuning
+                .frequency(key, snapshot.quantizer.root, snapshot.a4_hz)
+        );
+        init(snapshot).await;
+        let hold = Duration::from_secs_f32(TEST_TONE_HOLD_S);
+        exit_on_error("audio", test_tone(key, hold, stream).await);
+
+        return Ok(());
+    }
+
+    let audio = init(snapshot).await.clone();
+
+    // no MIDI inputs just leaves this dormant
+    let _midi = {
+        let audio = audio.clone();
+        let conn = midi::connect(args.midi_port.as_deref(), move |event| match event {
+            MidiEvent::NoteOn { note, velocity } => audio.note_on(note, velocity),
+            MidiEvent::NoteOff { note } => audio.note_off(note),
+            MidiEvent::Sustain { down } => audio.set_sustain(down),
+            MidiEvent::PitchBend { value } => audio.set_bend(midi::bend_amount(value)),
+        });
+        match conn {
+            Ok(conn) => conn,
+            Err(err) => {
+                eprintln!("mugen: midi: {err}");
+                std::process::exit(2);
+            }
+        }
+    };
     let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
     let focused = Arc::new(AtomicBool::new(true));
     focused.store(true, Ordering::Relaxed);
 
+    let debounce = Duration::from_millis(args.debounce_ms);
+    let engine = shutting_down(
+        shutdown_tx.clone(),
+        run(shutdown_rx, focused.clone(), stream, debounce, ignore_held),
+    );
+
+    if args.headless {
+        eprintln!("mugen: playing headless, Ctrl+C to quit");
+        tokio::pin!(engine);
+
+        // Ctrl+C still waits for the engine, so notes are released and a
+        // recording gets its header finished
+        let res = tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                let _ = shutdown_tx.send(true);
+                engine.await
+            }
+            res = &mut engine => res,
+        };
+        exit_on_error("audio", res);
+
+        return Ok(());
+    }
+
     let ui = {
         let shutdown_tx = shutdown_tx.clone();
         let audio = audio.clone();
         let focused = focused.clone();
 
         async move {
-            let res = run_ui(audio, shutdown_tx.clone(), focused).await;
+            let res = run_ui(
+                audio,
+                shutdown_tx.clone(),
+                focused,
+                args.fps,
+                theme,
+                intro,
+                args.input_device,
+            )
+            .await;
             let _ = shutdown_tx.send(true);
 
             res
         }
     };
 
-    let audio = run(shutdown_rx, focused.clone());
+    let both = async { tokio::join!(engine, ui) };
+    tokio::pin!(both);
 
-    tokio::select! {
+    let (engine, ui) = tokio::select! {
         _ = tokio::signal::ctrl_c() => {
             let _ = shutdown_tx.send(true);
+            both.await
         }
-        _ = async { tokio::join!(audio, ui) } => {}
-    }
+        res = &mut both => res,
+    };
+    exit_on_error("audio", engine);
+    exit_on_error("ui", ui);
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn a_failed_engine_raises_the_shutdown_flag() {
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        // the UI has not subscribed yet
+        drop(shutdown_rx);
+
+        let res = shutting_down(shutdown_tx.clone(), async {
+            Err(MugenError::AudioDevice("no output device".into()))
+        })
+        .await;
+
+        assert!(matches!(res, Err(MugenError::AudioDevice(_))));
+        assert!(*shutdown_tx.subscribe().borrow());
+    }
+}
@@ -0,0 +1,113 @@
+//! Pitch bend shared by every sounding voice
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// How a bend from the keys comes back to center once they are let go, a
+/// MIDI wheel springs back by itself
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BendReturn {
+    Snap,
+    Smooth,
+}
+
+impl BendReturn {
+    pub const ALL: [Self; 2] = [Self::Snap, Self::Smooth];
+
+    #[inline]
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Snap => "Snap",
+            Self::Smooth => "Smooth",
+        }
+    }
+}
+
+/// Frequency multiplier for `amount` in -1..1 of a `range_st` semitone bend
+#[inline]
+#[must_use]
+pub fn bend_ratio(amount: f32, range_st: f32) -> f32 {
+    (amount.clamp(-1.0, 1.0) * range_st / 12.0).exp2()
+}
+
+/// Bend amount in -1..1 and its range in semitones, voices read both every
+/// sample. Cloning shares them
+#[derive(Debug, Clone)]
+pub struct Bend {
+    amount: Arc<AtomicU32>,
+    range_st: Arc<AtomicU32>,
+}
+
+impl Bend {
+    #[must_use]
+    pub fn new(range_st: f32) -> Self {
+        Self {
+            amount: Arc::new(AtomicU32::new(0f32.to_bits())),
+            range_st: Arc::new(AtomicU32::new(range_st.max(0.0).to_bits())),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn amount(&self) -> f32 {
+        f32::from_bits(self.amount.load(Ordering::Relaxed))
+    }
+
+    #[inline]
+    pub fn set_amount(&self, amount: f32) {
+        self.amount
+            .store(amount.clamp(-1.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn range_st(&self) -> f32 {
+        f32::from_bits(self.range_st.load(Ordering::Relaxed))
+    }
+
+    #[inline]
+    pub fn set_range_st(&self, range_st: f32) {
+        self.range_st
+            .store(range_st.max(0.0).to_bits(), Ordering::Relaxed);
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn ratio(&self) -> f32 {
+        bend_ratio(self.amount(), self.range_st())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_bend_reaches_the_range() {
+        let bend = Bend::new(2.0);
+        assert_eq!(bend.ratio(), 1.0);
+
+        bend.set_amount(1.0);
+        assert!((bend.ratio() - (2.0_f32 / 12.0).exp2()).abs() < 1e-6);
+        bend.set_amount(-0.5);
+        assert!((bend.ratio() - (-1.0_f32 / 12.0).exp2()).abs() < 1e-6);
+
+        // past the ends it stays at the range
+        bend.set_amount(3.0);
+        assert_eq!(bend.amount(), 1.0);
+        bend.set_range_st(12.0);
+        assert!((bend.ratio() - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn clones_share_the_bend() {
+        let bend = Bend::new(2.0);
+        let voice = bend.clone();
+
+        bend.set_amount(0.25);
+        bend.set_range_st(7.0);
+        assert_eq!(voice.amount(), 0.25);
+        assert_eq!(voice.range_st(), 7.0);
+    }
+}
@@ -0,0 +1,153 @@
+//! Ordered effect chain folded over a voice source
+
+use crate::patch::{PatchSource, SharedEffect};
+use serde::{Deserialize, Serialize};
+
+/// Effects that can sit in the chain ahead of the envelope, each one backed
+/// by the engine's handle of the same name so its settings survive a reorder.
+/// Patch files name them like their sections
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fx {
+    SampleHold,
+    Gain,
+    LfoAmp,
+    Am,
+    #[serde(rename = "ringmod")]
+    RingMod,
+    Bitcrush,
+    #[serde(rename = "highpass")]
+    HighPass,
+    #[serde(rename = "lowpass")]
+    LowPass,
+}
+
+impl Fx {
+    /// The starting chain, in signal order
+    pub const ALL: [Self; 8] = [
+        Self::SampleHold,
+        Self::Gain,
+        Self::LfoAmp,
+        Self::Am,
+        Self::RingMod,
+        Self::Bitcrush,
+        Self::HighPass,
+        Self::LowPass,
+    ];
+
+    /// Matches the name the effect itself reports
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SampleHold => "S&H",
+            Self::Gain => "Gain",
+            Self::LfoAmp => "LFO Amp",
+            Self::Am => "AM",
+            Self::RingMod => "Ring Mod",
+            Self::Bitcrush => "Bitcrush",
+            Self::HighPass => "HighPass",
+            Self::LowPass => "LowPass",
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct FxChain {
+    effects: Vec<SharedEffect>,
+}
+
+impl FxChain {
+    #[must_use]
+    pub fn new(effects: Vec<SharedEffect>) -> Self {
+        Self { effects }
+    }
+
+    pub fn push(&mut self, effect: SharedEffect) {
+        self.effects.push(effect);
+    }
+
+    /// Removes the effect at `index`, `None` if there is none
+    pub fn remove(&mut self, index: usize) -> Option<SharedEffect> {
+        (index < self.effects.len()).then(|| self.effects.remove(index))
+    }
+
+    /// Moves the effect at `from` so it ends up at `to`, out of range is a no-op
+    pub fn reorder(&mut self, from: usize, to: usize) {
+        if from >= self.effects.len() || to >= self.effects.len() {
+            return;
+        }
+
+        let effect = self.effects.remove(from);
+        self.effects.insert(to, effect);
+    }
+
+    /// Wraps `input` in every effect, first to last
+    #[inline]
+    pub fn apply(&self, input: PatchSource) -> PatchSource {
+        self.effects
+            .iter()
+            .fold(input, |source, effect| effect.apply(source))
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn names(&self) -> Vec<&'static str> {
+        self.effects.iter().map(|effect| effect.name()).collect()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.effects.len()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::effects::bitcrush::{Bitcrush, make_bitcrush};
+    use crate::patch::effects::gain::make_gain;
+    use rodio::buffer::SamplesBuffer;
+    use std::sync::Arc;
+
+    fn run(chain: &FxChain, input: Vec<f32>) -> Vec<f32> {
+        chain
+            .apply(Box::new(SamplesBuffer::new(1, 48_000, input)))
+            .collect()
+    }
+
+    #[test]
+    fn order_matters() {
+        // a 16 bit crush is all but transparent, apart from clipping at 1.0
+        let gain: SharedEffect = Arc::new(make_gain(2.0));
+        let clip: SharedEffect = Arc::new(make_bitcrush(&Bitcrush::new(16, 1)));
+
+        let mut chain = FxChain::new(vec![gain, clip]);
+        assert_eq!(chain.names(), ["Gain", "Bitcrush"]);
+        let gain_then_clip = run(&chain, vec![0.8; 4]);
+
+        chain.reorder(1, 0);
+        assert_eq!(chain.names(), ["Bitcrush", "Gain"]);
+        let clip_then_gain = run(&chain, vec![0.8; 4]);
+
+        assert!(gain_then_clip.iter().all(|s| (s - 1.0).abs() < 1e-3));
+        assert!(clip_then_gain.iter().all(|s| (s - 1.6).abs() < 1e-3));
+    }
+
+    #[test]
+    fn remove_and_reorder_out_of_range_do_nothing() {
+        let mut chain = FxChain::new(vec![Arc::new(make_gain(1.0))]);
+
+        chain.reorder(0, 3);
+        assert!(chain.remove(1).is_none());
+        assert_eq!(chain.len(), 1);
+        assert!(chain.remove(0).is_some());
+        assert!(chain.is_empty());
+    }
+}
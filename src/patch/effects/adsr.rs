@@ -1,16 +1,78 @@
 //! Shapes note amplitude over time using gate-controlled stages
 
+use crate::config::{ADSR_CURVE, ADSR_CURVE_EXPONENT, ADSR_MODE};
 use crate::patch::shared::Shared;
 use crate::patch::{Gate, PatchSource};
 use rodio::Source;
+use serde::{Deserialize, Serialize};
 use std::sync::atomic::Ordering;
 
-#[derive(Clone, Debug)]
+/// How the envelope reacts to the gate: `Gate` sustains while held, `Trigger`
+/// runs attack -> decay -> release regardless of hold length, `Loop` repeats
+/// attack -> decay until the gate drops
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvelopeMode {
+    Gate,
+    Trigger,
+    Loop,
+}
+
+impl EnvelopeMode {
+    pub const ALL: [Self; 3] = [Self::Gate, Self::Trigger, Self::Loop];
+
+    #[inline]
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Gate => "Gate",
+            Self::Trigger => "Trigger",
+            Self::Loop => "Loop",
+        }
+    }
+}
+
+/// How each stage moves between its levels: `Exponential` covers most of
+/// the way early and eases into the target, like an analog envelope
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvelopeCurve {
+    Linear,
+    Exponential,
+}
+
+impl EnvelopeCurve {
+    pub const ALL: [Self; 2] = [Self::Linear, Self::Exponential];
+
+    #[inline]
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Linear => "Linear",
+            Self::Exponential => "Exp",
+        }
+    }
+
+    /// Fraction of the way to the target after `progress` (0..1) of a stage
+    #[inline]
+    #[must_use]
+    pub fn shape(self, progress: f32) -> f32 {
+        let progress = progress.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => progress,
+            Self::Exponential => 1.0 - (1.0 - progress).powf(ADSR_CURVE_EXPONENT),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Adsr {
     pub attack_s: f32,
     pub decay_s: f32,
     pub sustain: f32,
     pub release_s: f32,
+    pub mode: EnvelopeMode,
+    pub curve: EnvelopeCurve,
 }
 
 impl Adsr {
@@ -22,6 +84,8 @@ impl Adsr {
             decay_s,
             sustain,
             release_s,
+            mode: ADSR_MODE,
+            curve: ADSR_CURVE,
         }
     }
 }
@@ -34,10 +98,12 @@ pub fn make_adsr(adsr: Adsr) -> AdsrHandle {
     Shared::new(adsr)
 }
 
+/// The envelope peaks at `velocity` rather than full level, sustain scales
+/// along with it
 #[inline]
-pub fn adsr(input: PatchSource, adsr: AdsrHandle, gate: Gate) -> PatchSource {
+pub fn adsr(input: PatchSource, adsr: AdsrHandle, gate: Gate, velocity: f32) -> PatchSource {
     let sr = input.sample_rate().max(1);
-    Box::new(AdsrSource::new(input, adsr, gate, sr))
+    Box::new(AdsrSource::new(input, adsr, gate, sr, velocity))
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -49,68 +115,96 @@ enum Stage {
     Done,
 }
 
+/// Each stage runs `progress` from 0 to 1 over its length and moves `amp`
+/// from the level it started at, `from`, towards its target along the curve
 struct AdsrSource {
     input: PatchSource,
     adsr: AdsrHandle,
     gate: Gate,
     sample_rate: u32,
+    velocity: f32,
     stage: Stage,
     amp: f32,
-    release_step: f32,
+    from: f32,
+    progress: f32,
 }
 
 impl AdsrSource {
-    fn new(input: PatchSource, adsr: AdsrHandle, gate: Gate, sample_rate: u32) -> Self {
+    fn new(
+        input: PatchSource,
+        adsr: AdsrHandle,
+        gate: Gate,
+        sample_rate: u32,
+        velocity: f32,
+    ) -> Self {
         Self {
             input,
             adsr,
             gate,
             sample_rate,
+            velocity: velocity.clamp(0.0, 1.0),
             stage: Stage::Attack,
             amp: 0.0,
-            release_step: 0.0,
+            from: 0.0,
+            progress: 0.0,
+        }
+    }
+
+    fn enter(&mut self, stage: Stage) {
+        self.stage = stage;
+        self.from = self.amp;
+        self.progress = 0.0;
+    }
+
+    /// Advances the stage by one sample of a `time_s` long stage, true once
+    /// it has reached `target`
+    fn ramp(&mut self, curve: EnvelopeCurve, time_s: f32, target: f32) -> bool {
+        self.progress += 1.0 / (time_s.max(0.0) * self.sample_rate as f32).max(1.0);
+        self.amp = self.from + (target - self.from) * curve.shape(self.progress);
+
+        if self.progress >= 1.0 {
+            self.amp = target;
+            true
+        } else {
+            false
         }
     }
 
     fn step(&mut self) -> f32 {
         let adsr = self.adsr.get();
-        let sr = self.sample_rate as f32;
-
         let sustain = adsr.sustain.clamp(0.0, 1.0);
-        let attack_step = 1.0 / (adsr.attack_s.max(0.0) * sr).max(1.0);
-        let decay_step = (1.0 - sustain) / (adsr.decay_s.max(0.0) * sr).max(1.0);
 
-        if !self.gate.load(Ordering::Relaxed)
+        if adsr.mode != EnvelopeMode::Trigger
+            && !self.gate.load(Ordering::Relaxed)
             && self.stage != Stage::Release
             && self.stage != Stage::Done
         {
-            self.stage = Stage::Release;
-            let release_samples = (adsr.release_s.max(0.0) * sr).max(1.0);
-            self.release_step = self.amp / release_samples;
+            self.enter(Stage::Release);
         }
 
         match self.stage {
             Stage::Attack => {
-                self.amp += attack_step;
-                if self.amp >= 1.0 {
-                    self.amp = 1.0;
-                    self.stage = Stage::Decay;
+                // the rise keeps the slope of a full attack when a loop
+                // restarts it from the sustain level
+                let time_s = adsr.attack_s * (1.0 - self.from);
+                if self.ramp(adsr.curve, time_s, 1.0) {
+                    self.enter(Stage::Decay);
                 }
             }
             Stage::Decay => {
-                self.amp -= decay_step;
-                if self.amp <= sustain {
-                    self.amp = sustain;
-                    self.stage = Stage::Sustain;
+                if self.ramp(adsr.curve, adsr.decay_s, sustain) {
+                    match adsr.mode {
+                        EnvelopeMode::Gate => self.enter(Stage::Sustain),
+                        EnvelopeMode::Trigger => self.enter(Stage::Release),
+                        EnvelopeMode::Loop => self.enter(Stage::Attack),
+                    }
                 }
             }
             Stage::Sustain => {
                 self.amp = sustain;
             }
             Stage::Release => {
-                self.amp -= self.release_step;
-                if self.amp <= 0.0 {
-                    self.amp = 0.0;
+                if self.ramp(adsr.curve, adsr.release_s, 0.0) {
                     self.stage = Stage::Done;
                 }
             }
@@ -138,8 +232,176 @@ impl Iterator for AdsrSource {
             return None;
         }
 
-        Some(x * env)
+        Some(x * env * self.velocity)
     }
 }
 
 crate::impl_source_passthrough!(AdsrSource, input);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SAMPLE_RATE;
+    use rodio::buffer::SamplesBuffer;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    // 10 ms stages, 480 samples each
+    const STAGE: usize = SAMPLE_RATE as usize / 100;
+
+    fn envelope(mode: EnvelopeMode) -> AdsrHandle {
+        make_adsr(Adsr {
+            mode,
+            curve: EnvelopeCurve::Linear,
+            ..Adsr::new(0.01, 0.01, 0.5, 0.01)
+        })
+    }
+
+    /// The envelope over a steady full-scale input, the gate dropped after
+    /// `held` samples
+    fn run(mode: EnvelopeMode, held: usize, samples: usize) -> Vec<f32> {
+        run_at(mode, 1.0, held, samples)
+    }
+
+    /// `run` with the note played at `velocity`
+    fn run_at(mode: EnvelopeMode, velocity: f32, held: usize, samples: usize) -> Vec<f32> {
+        let ones = Box::new(SamplesBuffer::new(1, SAMPLE_RATE, vec![1.0; samples]));
+        let gate: Gate = Arc::new(AtomicBool::new(true));
+        let source = adsr(ones, envelope(mode), gate.clone(), velocity);
+
+        let mut out = Vec::new();
+        for x in source {
+            out.push(x);
+            if out.len() == held {
+                gate.store(false, Ordering::Relaxed);
+            }
+        }
+        out
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().copied().fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn trigger_runs_the_whole_envelope_however_long_the_gate() {
+        let tapped = run(EnvelopeMode::Trigger, 10, STAGE * 10);
+        let held = run(EnvelopeMode::Trigger, usize::MAX, STAGE * 10);
+
+        for out in [&tapped, &held] {
+            assert!((peak(out) - 1.0).abs() < 1e-3, "peak {}", peak(out));
+            assert!(
+                (out[STAGE * 2] - 0.5).abs() < 0.01,
+                "decayed to {}",
+                out[STAGE * 2]
+            );
+            assert!(
+                out.len().abs_diff(STAGE * 3) <= 2,
+                "ended after {}",
+                out.len()
+            );
+        }
+        assert_eq!(tapped, held);
+    }
+
+    #[test]
+    fn gate_mode_holds_sustain_then_releases() {
+        let out = run(EnvelopeMode::Gate, STAGE * 5, STAGE * 10);
+
+        assert!(
+            (out[STAGE * 4] - 0.5).abs() < 1e-6,
+            "sustain {}",
+            out[STAGE * 4]
+        );
+        assert!(
+            out.len().abs_diff(STAGE * 6) <= 2,
+            "ended after {}",
+            out.len()
+        );
+    }
+
+    #[test]
+    fn every_sample_follows_the_stages_at_any_velocity() {
+        let held = STAGE * 4;
+        // attack to 1, decay to 0.5, hold, then release from there
+        let shape = |i: usize| {
+            let t = |from: usize| (i - from) as f32 / STAGE as f32;
+            match i {
+                i if i < STAGE => t(0),
+                i if i < STAGE * 2 => 1.0 - 0.5 * t(STAGE),
+                i if i < held => 0.5,
+                _ => 0.5 * (1.0 - t(held)),
+            }
+        };
+
+        for velocity in [1.0, 0.6, 0.25] {
+            let out = run_at(EnvelopeMode::Gate, velocity, held, STAGE * 10);
+            assert!(
+                out.len().abs_diff(held + STAGE) <= 2,
+                "ended after {}",
+                out.len()
+            );
+            for (i, x) in out.iter().enumerate() {
+                let want = velocity * shape(i);
+                assert!(
+                    (x - want).abs() < 0.01,
+                    "at {velocity}, sample {i} is {x} not {want}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn loop_mode_restarts_after_the_decay() {
+        let out = run(EnvelopeMode::Loop, usize::MAX, STAGE * 6);
+
+        assert_eq!(out.len(), STAGE * 6, "a held loop never ends");
+        assert!(
+            (out[STAGE * 2] - 0.5).abs() < 0.01,
+            "decayed to {}",
+            out[STAGE * 2]
+        );
+        // rising again from sustain, so back at the peak well before another full attack
+        let second = peak(&out[STAGE * 2 + 10..STAGE * 3]);
+        assert!((second - 1.0).abs() < 1e-3, "second peak {second}");
+        assert!(out[STAGE * 2 + 10] > out[STAGE * 2], "no restart");
+    }
+
+    #[test]
+    fn curves_share_their_ends_and_exponential_leads() {
+        for curve in EnvelopeCurve::ALL {
+            assert_eq!(curve.shape(0.0), 0.0);
+            assert_eq!(curve.shape(1.0), 1.0);
+            let points: Vec<f32> = (0..=100).map(|i| curve.shape(i as f32 / 100.0)).collect();
+            assert!(
+                points.windows(2).all(|pair| pair[0] <= pair[1]),
+                "{curve:?} rises"
+            );
+        }
+
+        assert_eq!(EnvelopeCurve::Linear.shape(0.5), 0.5);
+        let half = 1.0 - 0.5_f32.powf(ADSR_CURVE_EXPONENT);
+        assert!((EnvelopeCurve::Exponential.shape(0.5) - half).abs() < 1e-6);
+    }
+
+    #[test]
+    fn exponential_envelope_moves_early_and_keeps_its_timing() {
+        let ones = Box::new(SamplesBuffer::new(1, SAMPLE_RATE, vec![1.0; STAGE * 10]));
+        let gate: Gate = Arc::new(AtomicBool::new(true));
+        let envelope = make_adsr(Adsr {
+            curve: EnvelopeCurve::Exponential,
+            ..envelope(EnvelopeMode::Trigger).get()
+        });
+        let out: Vec<f32> = adsr(ones, envelope, gate, 1.0).collect();
+
+        let half = 1.0 - 0.5_f32.powf(ADSR_CURVE_EXPONENT);
+        assert!((out[STAGE / 2] - half).abs() < 0.01, "{}", out[STAGE / 2]);
+        // the decay falls most of the way to sustain early on too
+        assert!(out[STAGE + STAGE / 2] < 0.5 + 0.5 * (1.0 - half) + 0.01);
+        assert!(
+            out.len().abs_diff(STAGE * 3) <= 2,
+            "ended after {}",
+            out.len()
+        );
+    }
+}
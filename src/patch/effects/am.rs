@@ -0,0 +1,140 @@
+//! Amplitude modulation with a sine carrier from sub-audio to audio rate
+
+use crate::patch::shared::Shared;
+use crate::patch::{Effect, PatchSource};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Am {
+    pub carrier_hz: f32,
+    pub depth: f32,
+}
+
+pub type AmHandle = Shared<Am>;
+
+#[inline]
+#[must_use]
+pub fn make_am(am: &Am) -> AmHandle {
+    Shared::new(Am {
+        carrier_hz: am.carrier_hz.max(0.0),
+        depth: am.depth.clamp(0.0, 1.0),
+    })
+}
+
+struct AmSource {
+    input: PatchSource,
+    am: AmHandle,
+    phase: f32,
+}
+
+impl Iterator for AmSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.input.next()?;
+        let cfg = self.am.get();
+        let sr = self.input.sample_rate().max(1) as f32;
+        let depth = cfg.depth.clamp(0.0, 1.0);
+
+        // unipolar: 1 + depth * m, scaled back so the peak never exceeds the input
+        let m = (TAU * self.phase).sin();
+        let gain = (1.0 + depth * m) / (1.0 + depth);
+
+        self.phase += cfg.carrier_hz.max(0.0) / sr;
+        if self.phase >= 1.0 {
+            self.phase -= self.phase.floor();
+        }
+
+        Some(x * gain)
+    }
+}
+
+crate::impl_source_passthrough!(AmSource, input);
+
+impl Effect for Shared<Am> {
+    fn name(&self) -> &'static str {
+        "AM"
+    }
+
+    fn apply(&self, input: PatchSource) -> PatchSource {
+        Box::new(AmSource {
+            input,
+            am: self.clone(),
+            phase: 0.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SAMPLE_RATE;
+    use crate::patch::oscilators::basic::{Osc, Wave, osc_source};
+    use crate::play::spectrum::magnitudes_db;
+    use rodio::buffer::SamplesBuffer;
+
+    const N: usize = 8192;
+    // a bin of an N-point spectrum, so tones land on bins exactly
+    const BIN_HZ: f32 = SAMPLE_RATE as f32 / N as f32;
+
+    fn am(carrier_hz: f32, input: PatchSource) -> Vec<f32> {
+        let handle = make_am(&Am {
+            carrier_hz,
+            depth: 1.0,
+        });
+        handle.apply(input).take(N).collect()
+    }
+
+    #[test]
+    fn sub_audio_carrier_swells_slowly() {
+        let ones: PatchSource = Box::new(SamplesBuffer::new(1, SAMPLE_RATE, vec![1.0; N * 6]));
+        let out: Vec<f32> = make_am(&Am {
+            carrier_hz: 2.0,
+            depth: 1.0,
+        })
+        .apply(ones)
+        .collect();
+
+        let at = |s: f32| out[(s * SAMPLE_RATE as f32) as usize];
+        assert!((at(0.125) - 1.0).abs() < 1e-3, "peak {}", at(0.125));
+        assert!(at(0.375).abs() < 1e-3, "trough {}", at(0.375));
+        // no step between neighbours bigger than a 2 Hz sine allows
+        let max_step = out
+            .windows(2)
+            .map(|w| (w[1] - w[0]).abs())
+            .fold(0.0, f32::max);
+        assert!(max_step < 1e-3, "step {max_step}");
+    }
+
+    #[test]
+    fn audio_rate_carrier_adds_sidebands() {
+        let signal = 192.0 * BIN_HZ;
+        let carrier = 32.0 * BIN_HZ;
+        let osc = Shared::new(Osc {
+            wave: Wave::Sine,
+            amplitude: 1.0,
+            ..Osc::default()
+        });
+        let spectrum = magnitudes_db(&am(carrier, Box::new(osc_source(signal, osc))));
+
+        // (1 + sin c) sin s / 2 leaves s at half level and c either side at a quarter
+        assert!(
+            (spectrum[192] + 6.0).abs() < 0.5,
+            "signal {}",
+            spectrum[192]
+        );
+        assert!(
+            (spectrum[160] + 12.0).abs() < 0.5,
+            "lower {}",
+            spectrum[160]
+        );
+        assert!(
+            (spectrum[224] + 12.0).abs() < 0.5,
+            "upper {}",
+            spectrum[224]
+        );
+        assert!(spectrum[176] < -60.0, "between {}", spectrum[176]);
+        assert!(spectrum[32] < -60.0, "carrier leaked {}", spectrum[32]);
+    }
+}
@@ -0,0 +1,130 @@
+//! Lo-fi crunch from coarse amplitude steps and a held, decimated output
+
+use crate::patch::shared::Shared;
+use crate::patch::{Effect, PatchSource};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bitcrush {
+    pub bits: u8,
+    /// Each output sample is held for this many input samples
+    pub downsample: u32,
+}
+
+impl Bitcrush {
+    #[inline]
+    #[must_use]
+    pub fn new(bits: u8, downsample: u32) -> Self {
+        Self {
+            bits: bits.clamp(1, 16),
+            downsample: downsample.clamp(1, 64),
+        }
+    }
+}
+
+pub type BitcrushHandle = Shared<Bitcrush>;
+
+#[inline]
+#[must_use]
+pub fn make_bitcrush(bitcrush: &Bitcrush) -> BitcrushHandle {
+    Shared::new(Bitcrush::new(bitcrush.bits, bitcrush.downsample))
+}
+
+/// Snaps `x` in -1..1 onto `2^bits` evenly spaced levels, so one bit leaves
+/// only -1 and 1
+#[inline]
+fn quantize(x: f32, bits: u8) -> f32 {
+    let steps = ((1u32 << bits.clamp(1, 16)) - 1) as f32;
+    let x = x.clamp(-1.0, 1.0);
+
+    ((x + 1.0) * 0.5 * steps).round() / steps * 2.0 - 1.0
+}
+
+struct BitcrushSource {
+    input: PatchSource,
+    bitcrush: BitcrushHandle,
+    held: f32,
+    count: u32,
+}
+
+impl Iterator for BitcrushSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.input.next()?;
+        let cfg = self.bitcrush.get();
+
+        if self.count == 0 {
+            self.held = quantize(x, cfg.bits);
+        }
+        self.count = (self.count + 1) % cfg.downsample.max(1);
+
+        Some(self.held)
+    }
+}
+
+crate::impl_source_passthrough!(BitcrushSource, input);
+
+impl Effect for Shared<Bitcrush> {
+    fn name(&self) -> &'static str {
+        "Bitcrush"
+    }
+
+    fn apply(&self, input: PatchSource) -> PatchSource {
+        Box::new(BitcrushSource {
+            input,
+            bitcrush: self.clone(),
+            held: 0.0,
+            count: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+    use std::f32::consts::TAU;
+
+    fn sine(n: usize) -> Vec<f32> {
+        (0..n)
+            .map(|i| 0.9 * (TAU * i as f32 / 97.0).sin())
+            .collect()
+    }
+
+    fn crush(bitcrush: Bitcrush, input: Vec<f32>) -> Vec<f32> {
+        make_bitcrush(&bitcrush)
+            .apply(Box::new(SamplesBuffer::new(1, 48_000, input)))
+            .collect()
+    }
+
+    #[test]
+    fn sixteen_bits_without_downsampling_is_nearly_transparent() {
+        let input = sine(1_000);
+        let out = crush(Bitcrush::new(16, 1), input.clone());
+
+        let error = input
+            .iter()
+            .zip(&out)
+            .fold(0.0_f32, |worst, (x, y)| worst.max((x - y).abs()));
+        // half a step of 2^16 levels over -1..1
+        assert!(error <= 1.0 / 65_535.0 + 1e-6, "worst error {error}");
+    }
+
+    #[test]
+    fn one_bit_leaves_two_levels() {
+        let out = crush(Bitcrush::new(1, 1), sine(1_000));
+
+        assert!(out.iter().all(|s| *s == 1.0 || *s == -1.0));
+        assert!(out.contains(&1.0) && out.contains(&-1.0));
+    }
+
+    #[test]
+    fn downsampling_holds_each_sample() {
+        let out = crush(Bitcrush::new(16, 4), sine(1_000));
+
+        for chunk in out.chunks(4) {
+            assert!(chunk.iter().all(|s| *s == chunk[0]), "{chunk:?}");
+        }
+    }
+}
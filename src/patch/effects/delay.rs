@@ -0,0 +1,163 @@
+//! Feedback echo over a ring buffer sized from the delay time, each channel
+//! echoes itself
+
+use crate::config::DELAY_MAX_MS;
+use crate::patch::shared::Shared;
+use crate::patch::slew::Slew;
+use crate::patch::{Effect, PatchSource};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Delay {
+    pub time_ms: f32,
+    pub feedback: f32,
+    pub mix: f32,
+}
+
+impl Delay {
+    #[inline]
+    #[must_use]
+    pub fn new(time_ms: f32, feedback: f32, mix: f32) -> Self {
+        Self {
+            time_ms: time_ms.clamp(1.0, DELAY_MAX_MS),
+            // at 1.0 every repeat is as loud as the last and the tail never dies
+            feedback: feedback.clamp(0.0, 0.95),
+            mix: mix.clamp(0.0, 1.0),
+        }
+    }
+}
+
+pub type DelayHandle = Shared<Delay>;
+
+#[inline]
+#[must_use]
+pub fn make_delay(delay: &Delay) -> DelayHandle {
+    Shared::new(Delay::new(delay.time_ms, delay.feedback, delay.mix))
+}
+
+/// Ring length in samples, whole frames so each channel reads back its own
+#[inline]
+fn delay_samples(time_ms: f32, sample_rate: u32, channels: u16) -> usize {
+    let frames = time_ms.clamp(1.0, DELAY_MAX_MS) * sample_rate as f32 / 1000.0;
+    (frames.round() as usize).max(1) * usize::from(channels.max(1))
+}
+
+struct DelaySource {
+    input: PatchSource,
+    delay: DelayHandle,
+    buffer: Vec<f32>,
+    pos: usize,
+    mix: Slew,
+}
+
+impl Iterator for DelaySource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.input.next()?;
+        let cfg = self.delay.get();
+        let len = delay_samples(
+            cfg.time_ms,
+            self.input.sample_rate().max(1),
+            self.input.channels(),
+        );
+
+        // the time can change live, so the ring follows it. Whole frames keep
+        // `pos` on the same channel
+        if len != self.buffer.len() {
+            self.buffer.resize(len, 0.0);
+            self.pos %= len;
+        }
+
+        let delayed = self.buffer[self.pos];
+        self.buffer[self.pos] = x + cfg.feedback.clamp(0.0, 0.95) * delayed;
+        self.pos = (self.pos + 1) % len;
+
+        let mix = self
+            .mix
+            .param(cfg.mix.clamp(0.0, 1.0), self.input.sample_rate());
+        Some(x + mix * delayed)
+    }
+}
+
+crate::impl_source_passthrough!(DelaySource, input);
+
+impl Effect for Shared<Delay> {
+    fn name(&self) -> &'static str {
+        "Delay"
+    }
+
+    fn apply(&self, input: PatchSource) -> PatchSource {
+        let len = delay_samples(
+            self.get().time_ms,
+            input.sample_rate().max(1),
+            input.channels(),
+        );
+
+        Box::new(DelaySource {
+            input,
+            delay: self.clone(),
+            buffer: vec![0.0; len],
+            pos: 0,
+            mix: Slew::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    fn echo(delay: Delay, channels: u16, input: Vec<f32>) -> Vec<f32> {
+        make_delay(&delay)
+            .apply(Box::new(SamplesBuffer::new(channels, 1_000, input)))
+            .collect()
+    }
+
+    #[test]
+    fn impulse_repeats_decay_at_the_delay_time() {
+        // 10 ms at 1 kHz is 10 samples
+        let mut input = vec![0.0; 60];
+        input[0] = 1.0;
+        let out = echo(Delay::new(10.0, 0.5, 0.8), 1, input);
+
+        assert!((out[0] - 1.0).abs() < 1e-6, "the dry impulse {}", out[0]);
+        for k in 1..6 {
+            let expected = 0.8 * 0.5_f32.powi(k - 1);
+            let at = 10 * k as usize;
+            assert!(
+                (out[at] - expected).abs() < 1e-6,
+                "repeat {k} at {at}: {} vs {expected}",
+                out[at]
+            );
+        }
+
+        let between = out
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i % 10 != 0)
+            .fold(0.0_f32, |peak, (_, s)| peak.max(s.abs()));
+        assert!(between < 1e-6, "nothing between the repeats {between}");
+    }
+
+    #[test]
+    fn each_channel_echoes_itself() {
+        // 10 frames of stereo, the impulse only on the left
+        let mut input = vec![0.0; 60];
+        input[0] = 1.0;
+        let out = echo(Delay::new(10.0, 0.5, 1.0), 2, input);
+
+        assert!(
+            (out[20] - 1.0).abs() < 1e-6,
+            "left repeat a frame-time later"
+        );
+        assert!((out[40] - 0.5).abs() < 1e-6, "second left repeat");
+        let right = out
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .fold(0.0_f32, |p, s| p.max(s.abs()));
+        assert!(right < 1e-6, "the right channel stays silent {right}");
+    }
+}
@@ -1,9 +1,11 @@
 //! Scales signal amplitude using shared live control
 
 use crate::patch::shared::Shared;
+use crate::patch::slew::Slew;
 use crate::patch::{Effect, PatchSource};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Gain {
     pub amount: f32,
 }
@@ -11,7 +13,7 @@ pub struct Gain {
 pub type GainHandle = Shared<Gain>;
 
 #[inline]
-#[must_use] 
+#[must_use]
 pub fn make_gain(amount: f32) -> GainHandle {
     Shared::new(Gain {
         amount: amount.max(0.0),
@@ -21,6 +23,7 @@ pub fn make_gain(amount: f32) -> GainHandle {
 struct GainSource {
     input: PatchSource,
     gain: GainHandle,
+    slew: Slew,
 }
 
 impl Iterator for GainSource {
@@ -28,7 +31,8 @@ impl Iterator for GainSource {
 
     fn next(&mut self) -> Option<Self::Item> {
         let x = self.input.next()?;
-        let g = self.gain.get().amount.max(0.0);
+        let target = self.gain.get().amount.max(0.0);
+        let g = self.slew.param(target, self.input.sample_rate());
         Some(x * g)
     }
 }
@@ -44,6 +48,28 @@ impl Effect for Shared<Gain> {
         Box::new(GainSource {
             input,
             gain: self.clone(),
+            slew: Slew::new(),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PARAM_SLEW_MAX_MS;
+    use rodio::buffer::SamplesBuffer;
+
+    #[test]
+    fn changing_the_shared_gain_changes_later_samples() {
+        let gain = make_gain(1.0);
+        let mut out = gain.apply(Box::new(SamplesBuffer::new(1, 1_000, vec![0.5; 4_000])));
+
+        let before: Vec<f32> = out.by_ref().take(10).collect();
+        assert!(before.iter().all(|s| (s - 0.5).abs() < 1e-6), "{before:?}");
+
+        gain.set(Gain { amount: 0.25 });
+        // past the longest slew the new gain has fully taken over
+        let after: Vec<f32> = out.skip(PARAM_SLEW_MAX_MS as usize).collect();
+        assert!(after.iter().all(|s| (s - 0.125).abs() < 1e-6));
+    }
+}
@@ -0,0 +1,172 @@
+//! Removes low frequencies with a resonant biquad high-pass
+
+use crate::patch::shared::Shared;
+use crate::patch::slew::Slew;
+use crate::patch::{Effect, PatchSource};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighPass {
+    pub cutoff_hz: f32,
+    pub q: f32,
+}
+
+pub type HighPassHandle = Shared<HighPass>;
+
+#[inline]
+#[must_use]
+pub fn make_highpass(highpass: &HighPass) -> HighPassHandle {
+    Shared::new(HighPass {
+        cutoff_hz: highpass.cutoff_hz.max(1.0),
+        q: highpass.q.max(0.1),
+    })
+}
+
+/// Normalized RBJ cookbook coefficients `[b0, b1, b2, a1, a2]`
+fn coefficients(sample_rate: f32, cutoff_hz: f32, q: f32) -> [f32; 5] {
+    // keep w0 clear of Nyquist where cos(w0) -> -1 and the poles crowd the unit circle
+    let cutoff_hz = cutoff_hz.clamp(1.0, sample_rate * 0.45);
+    let w0 = TAU * cutoff_hz / sample_rate;
+    let (sin, cos) = w0.sin_cos();
+    let alpha = sin / (2.0 * q.max(0.1));
+
+    let a0 = 1.0 + alpha;
+    let b0 = (1.0 + cos) / 2.0 / a0;
+    let b1 = -(1.0 + cos) / a0;
+    let a1 = -2.0 * cos / a0;
+    let a2 = (1.0 - alpha) / a0;
+
+    [b0, b1, b0, a1, a2]
+}
+
+struct HighPassSource {
+    input: PatchSource,
+    highpass: HighPassHandle,
+    slew: Slew,
+    q_slew: Slew,
+    // (sample rate, cutoff, q) the coefficients were built for
+    tuned: (u32, f32, f32),
+    coeffs: [f32; 5],
+    z1: f32,
+    z2: f32,
+}
+
+impl Iterator for HighPassSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.input.next()?;
+        let sr = self.input.sample_rate().max(1);
+        let cfg = self.highpass.get();
+        let cutoff = self.slew.param(cfg.cutoff_hz, sr);
+        let q = self.q_slew.param(cfg.q, sr);
+
+        if self.tuned != (sr, cutoff, q) {
+            self.tuned = (sr, cutoff, q);
+            self.coeffs = coefficients(sr as f32, cutoff, q);
+        }
+
+        // transposed direct form II
+        let [b0, b1, b2, a1, a2] = self.coeffs;
+        let y = b0 * x + self.z1;
+        self.z1 = b1 * x - a1 * y + self.z2;
+        self.z2 = b2 * x - a2 * y;
+
+        Some(y)
+    }
+}
+
+crate::impl_source_passthrough!(HighPassSource, input);
+
+impl Effect for Shared<HighPass> {
+    fn name(&self) -> &'static str {
+        "HighPass"
+    }
+
+    fn apply(&self, input: PatchSource) -> PatchSource {
+        let cfg = self.get();
+        let sr = input.sample_rate().max(1);
+
+        Box::new(HighPassSource {
+            input,
+            highpass: self.clone(),
+            slew: Slew::new(),
+            q_slew: Slew::new(),
+            tuned: (sr, cfg.cutoff_hz, cfg.q),
+            coeffs: coefficients(sr as f32, cfg.cutoff_hz, cfg.q),
+            z1: 0.0,
+            z2: 0.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    fn filter(highpass: HighPass, sample_rate: u32, input: Vec<f32>) -> Vec<f32> {
+        make_highpass(&highpass)
+            .apply(Box::new(SamplesBuffer::new(1, sample_rate, input)))
+            .collect()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn dc_settles_to_zero() {
+        let out = filter(
+            HighPass {
+                cutoff_hz: 200.0,
+                q: 0.707,
+            },
+            48_000,
+            vec![1.0; 48_000],
+        );
+
+        assert!(out[0] > 0.9, "the step passes first {}", out[0]);
+        assert!(peak(&out[24_000..]) < 1e-4);
+    }
+
+    #[test]
+    fn cutoff_is_3db_down_at_any_sample_rate() {
+        for sample_rate in [22_050, 44_100, 96_000] {
+            let sr = sample_rate as f32;
+            let tone = (0..sample_rate)
+                .map(|i| (TAU * 1000.0 * i as f32 / sr).sin())
+                .collect();
+            let out = filter(
+                HighPass {
+                    cutoff_hz: 1000.0,
+                    q: std::f32::consts::FRAC_1_SQRT_2,
+                },
+                sample_rate,
+                tone,
+            );
+
+            let gain = peak(&out[out.len() / 2..]);
+            assert!((gain - 0.707).abs() < 0.01, "{sample_rate} Hz: {gain}");
+        }
+    }
+
+    #[test]
+    fn cutoff_at_nyquist_stays_stable() {
+        let noise: Vec<f32> = (0..48_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 16) as f32 / 32_768.0 - 1.0)
+            .collect();
+        let out = filter(
+            HighPass {
+                cutoff_hz: 24_000.0,
+                q: 10.0,
+            },
+            48_000,
+            noise,
+        );
+
+        assert!(out.iter().all(|s| s.is_finite()));
+        assert!(peak(&out) < 20.0, "{}", peak(&out));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::config::PULSE_DUTY;
 use crate::patch::oscilators::basic::Wave;
 use std::f32::consts::TAU;
 
@@ -12,7 +13,7 @@ pub struct LfoOsc {
 }
 
 impl LfoOsc {
-    #[must_use] 
+    #[must_use]
     pub fn new(wave: Wave, rate_hz: f32, sample_rate: u32) -> Self {
         let mut osc = Self {
             wave,
@@ -77,7 +78,8 @@ impl LfoOsc {
 
     pub fn next_value(&mut self) -> f32 {
         match self.wave {
-            Wave::Sine => (TAU * self.step_phase()).sin(),
+            // the LFO has no table of its own
+            Wave::Sine | Wave::Wavetable => (TAU * self.step_phase()).sin(),
             Wave::Square => {
                 if self.step_phase() < 0.5 {
                     1.0
@@ -85,6 +87,13 @@ impl LfoOsc {
                     -1.0
                 }
             }
+            Wave::Pulse => {
+                if self.step_phase() < PULSE_DUTY {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
             Wave::Triangle => {
                 let p = self.step_phase();
                 if p < 0.5 {
@@ -94,7 +103,7 @@ impl LfoOsc {
                 }
             }
             Wave::Saw => 2.0 * self.step_phase() - 1.0,
-            Wave::Noise => self.next_noise(),
+            Wave::Noise | Wave::PinkNoise | Wave::BrownNoise => self.next_noise(),
         }
     }
 }
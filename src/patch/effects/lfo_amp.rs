@@ -4,8 +4,9 @@ use crate::patch::effects::lfo::LfoOsc;
 use crate::patch::oscilators::basic::Wave;
 use crate::patch::shared::Shared;
 use crate::patch::{Effect, PatchSource};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LfoAmp {
     pub wave: Wave,
     pub rate_hz: f32,
@@ -16,7 +17,7 @@ pub struct LfoAmp {
 pub type LfoAmpHandle = Shared<LfoAmp>;
 
 #[inline]
-#[must_use] 
+#[must_use]
 pub fn make_lfo_amp(lfo: LfoAmp) -> LfoAmpHandle {
     Shared::new(LfoAmp {
         wave: lfo.wave,
@@ -68,3 +69,42 @@ impl Effect for Shared<LfoAmp> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    /// Upward crossings of the resting gain, one per LFO cycle
+    fn cycles(wave: Wave, rate_hz: f32, sample_rate: u32, seconds: u32) -> usize {
+        let lfo = make_lfo_amp(LfoAmp {
+            wave,
+            rate_hz,
+            depth: 0.5,
+            base_gain: 1.0,
+        });
+        let dc = vec![1.0; (sample_rate * seconds) as usize];
+        let out: Vec<f32> = lfo
+            .apply(Box::new(SamplesBuffer::new(1, sample_rate, dc)))
+            .collect();
+
+        assert!(out.iter().all(|s| (0.5 - 1e-3..=1.5 + 1e-3).contains(s)));
+        out.windows(2)
+            .filter(|pair| pair[0] < 1.0 && pair[1] >= 1.0)
+            .count()
+    }
+
+    #[test]
+    fn envelope_follows_the_rate_at_any_sample_rate() {
+        for wave in [Wave::Sine, Wave::Triangle] {
+            for sample_rate in [22_050, 48_000] {
+                let n = cycles(wave.clone(), 5.0, sample_rate, 2);
+                assert!(
+                    (9..=10).contains(&n),
+                    "{} at {sample_rate}: {n}",
+                    wave.name()
+                );
+            }
+        }
+    }
+}
@@ -1,25 +1,35 @@
 //! Attenuates high frequencies with shared cutoff control
 
+use crate::patch::lfo::Modulation;
 use crate::patch::shared::Shared;
+use crate::patch::slew::Slew;
 use crate::patch::{Effect, PatchSource};
+use serde::{Deserialize, Serialize};
 use std::f32::consts::TAU;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LowPass {
     pub cutoff_hz: f32,
 }
 
 pub type LowPassHandle = Shared<LowPass>;
 
+/// Low-pass whose cutoff follows the LFO while it is routed to the cutoff
+#[derive(Clone)]
+pub struct ModLowPass {
+    pub lowpass: LowPassHandle,
+    pub modulation: Modulation,
+}
+
 #[inline]
-#[must_use] 
+#[must_use]
 pub fn make_lowpass(lowpass: &LowPass) -> LowPassHandle {
     Shared::new(LowPass {
         cutoff_hz: lowpass.cutoff_hz.max(1.0),
     })
 }
 
-fn alpha(sample_rate: f32, cutoff_hz: f32) -> f32 {
+pub(crate) fn alpha(sample_rate: f32, cutoff_hz: f32) -> f32 {
     let cutoff_hz = cutoff_hz.clamp(1.0, sample_rate * 0.45);
     let dt = 1.0 / sample_rate;
     let tau = 1.0 / (TAU * cutoff_hz);
@@ -29,7 +39,9 @@ fn alpha(sample_rate: f32, cutoff_hz: f32) -> f32 {
 struct LowPassSource {
     input: PatchSource,
     lowpass: LowPassHandle,
+    modulation: Option<Modulation>,
     prev_y: f32,
+    slew: Slew,
 }
 
 impl Iterator for LowPassSource {
@@ -37,9 +49,13 @@ impl Iterator for LowPassSource {
 
     fn next(&mut self) -> Option<Self::Item> {
         let x = self.input.next()?;
-        let sr = self.input.sample_rate() as f32;
-        let cutoff = self.lowpass.get().cutoff_hz;
-        let a = alpha(sr, cutoff);
+        let sr = self.input.sample_rate();
+        let mut cutoff = self.slew.param(self.lowpass.get().cutoff_hz, sr);
+        // after the slew, which would otherwise smear fast modulation
+        if let Some(modulation) = &self.modulation {
+            cutoff *= modulation.cutoff_ratio();
+        }
+        let a = alpha(sr as f32, cutoff);
 
         let y = a * x + (1.0 - a) * self.prev_y;
         self.prev_y = y;
@@ -59,7 +75,69 @@ impl Effect for Shared<LowPass> {
         Box::new(LowPassSource {
             input,
             lowpass: self.clone(),
+            modulation: None,
+            prev_y: 0.0,
+            slew: Slew::new(),
+        })
+    }
+}
+
+impl Effect for ModLowPass {
+    fn name(&self) -> &'static str {
+        "LowPass"
+    }
+
+    fn apply(&self, input: PatchSource) -> PatchSource {
+        Box::new(LowPassSource {
+            input,
+            lowpass: self.lowpass.clone(),
+            modulation: Some(self.modulation.clone()),
             prev_y: 0.0,
+            slew: Slew::new(),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SAMPLE_RATE;
+    use crate::patch::slew::param_slew;
+    use rodio::buffer::SamplesBuffer;
+
+    /// Steady level a one-pole at `cutoff_hz` leaves of a full-scale Nyquist tone
+    fn nyquist_level(cutoff_hz: f32) -> f32 {
+        let a = alpha(SAMPLE_RATE as f32, cutoff_hz);
+        a / (2.0 - a)
+    }
+
+    #[test]
+    fn cutoff_step_ramps_over_the_slew_time() {
+        let ramp = (param_slew() * SAMPLE_RATE as f32) as usize;
+        let tone: Vec<f32> = (0..ramp * 4)
+            .map(|i| if i % 2 == 0 { 1.0 } else { -1.0 })
+            .collect();
+        let handle = make_lowpass(&LowPass { cutoff_hz: 200.0 });
+        let mut out = handle.apply(Box::new(SamplesBuffer::new(1, SAMPLE_RATE, tone)));
+
+        let mut level = |samples: usize| out.by_ref().take(samples).last().map_or(0.0, |y| y.abs());
+        let low = level(ramp);
+        assert!((low - nyquist_level(200.0)).abs() < 1e-3, "before {low}");
+
+        handle.set(LowPass { cutoff_hz: 8000.0 });
+        let first = level(2);
+        assert!(first < low * 1.5, "jumped to {first}");
+
+        let halfway = level(ramp / 2 - 2);
+        assert!(
+            halfway > low * 2.0 && halfway < nyquist_level(8000.0) * 0.8,
+            "halfway {halfway}"
+        );
+
+        let settled = level(ramp);
+        assert!(
+            (settled - nyquist_level(8000.0)).abs() < 1e-3,
+            "settled {settled}"
+        );
+    }
+}
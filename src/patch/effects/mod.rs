@@ -1,5 +1,14 @@
 pub mod adsr;
+pub mod am;
+pub mod bitcrush;
+pub mod delay;
 pub mod gain;
+pub mod highpass;
 pub mod lfo;
 pub mod lfo_amp;
 pub mod lowpass;
+pub mod noise_gate;
+pub mod pan;
+pub mod reverb;
+pub mod ringmod;
+pub mod sample_hold;
@@ -0,0 +1,205 @@
+//! Silences the signal while its level sits below a threshold
+
+use crate::patch::shared::Shared;
+use crate::patch::{Effect, PatchSource};
+use serde::{Deserialize, Serialize};
+
+// the level detector holds a peak this long, enough to ride over the zero
+// crossings of a low note, and then falls away quickly
+const DETECTOR_HOLD_S: f32 = 0.02;
+const DETECTOR_RELEASE_S: f32 = 0.003;
+// the gate closes this far under the threshold so a level hovering right at
+// it does not chatter
+const HYSTERESIS_DB: f32 = 3.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoiseGate {
+    pub threshold_db: f32,
+    pub attack_ms: f32,
+    pub release_ms: f32,
+}
+
+pub type NoiseGateHandle = Shared<NoiseGate>;
+
+#[inline]
+#[must_use]
+pub fn make_noise_gate(noise_gate: &NoiseGate) -> NoiseGateHandle {
+    Shared::new(NoiseGate {
+        threshold_db: noise_gate.threshold_db.min(0.0),
+        attack_ms: noise_gate.attack_ms.max(0.0),
+        release_ms: noise_gate.release_ms.max(0.0),
+    })
+}
+
+/// Per-sample smoothing coefficient reaching ~63% of a step in `time_s`
+fn coefficient(time_s: f32, sample_rate: u32) -> f32 {
+    let samples = time_s * sample_rate.max(1) as f32;
+    if samples < 1.0 {
+        0.0
+    } else {
+        (-1.0 / samples).exp()
+    }
+}
+
+/// Follows the peak level, opens once it crosses the threshold and closes
+/// once it drops `HYSTERESIS_DB` below it, the gain ramps over attack/release
+struct NoiseGateSource {
+    input: PatchSource,
+    noise_gate: NoiseGateHandle,
+    // (sample rate, attack, release) the coefficients were built for
+    tuned: (u32, f32, f32),
+    // detector, attack, release
+    coeffs: [f32; 3],
+    level: f32,
+    // samples left before the held peak starts to fall
+    hold: u32,
+    open: bool,
+    gain: f32,
+}
+
+/// Detector, attack and release coefficients
+fn coefficients(sample_rate: u32, attack_ms: f32, release_ms: f32) -> [f32; 3] {
+    [
+        coefficient(DETECTOR_RELEASE_S, sample_rate),
+        coefficient(attack_ms / 1000.0, sample_rate),
+        coefficient(release_ms / 1000.0, sample_rate),
+    ]
+}
+
+impl Iterator for NoiseGateSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.input.next()?;
+        let sr = self.input.sample_rate();
+        let cfg = self.noise_gate.get();
+
+        if self.tuned != (sr, cfg.attack_ms, cfg.release_ms) {
+            self.tuned = (sr, cfg.attack_ms, cfg.release_ms);
+            self.coeffs = coefficients(sr, cfg.attack_ms, cfg.release_ms);
+        }
+        let [detector, attack, release] = self.coeffs;
+
+        let peak = x.abs();
+        if peak >= self.level {
+            self.level = peak;
+            self.hold = (DETECTOR_HOLD_S * sr as f32) as u32;
+        } else if self.hold > 0 {
+            self.hold -= 1;
+        } else {
+            self.level *= detector;
+        }
+
+        let level_db = 20.0 * self.level.max(1e-9).log10();
+        if level_db >= cfg.threshold_db {
+            self.open = true;
+        } else if level_db < cfg.threshold_db - HYSTERESIS_DB {
+            self.open = false;
+        }
+
+        let (target, k) = if self.open {
+            (1.0, attack)
+        } else {
+            (0.0, release)
+        };
+        self.gain = target + (self.gain - target) * k;
+
+        Some(x * self.gain)
+    }
+}
+
+crate::impl_source_passthrough!(NoiseGateSource, input);
+
+impl Effect for Shared<NoiseGate> {
+    fn name(&self) -> &'static str {
+        "Gate"
+    }
+
+    fn apply(&self, input: PatchSource) -> PatchSource {
+        let cfg = self.get();
+        let sr = input.sample_rate();
+
+        Box::new(NoiseGateSource {
+            input,
+            noise_gate: self.clone(),
+            tuned: (sr, cfg.attack_ms, cfg.release_ms),
+            coeffs: coefficients(sr, cfg.attack_ms, cfg.release_ms),
+            level: 0.0,
+            hold: 0,
+            // starts open so the attack of the first note is not cut
+            open: true,
+            gain: 1.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+    use std::f32::consts::TAU;
+
+    const RATE: u32 = 48_000;
+    const MS: usize = RATE as usize / 1000;
+
+    /// A 200 Hz sine at each `(amplitude, ms)` in turn
+    fn tone(parts: &[(f32, usize)]) -> Vec<f32> {
+        let mut i = 0;
+        let mut out = Vec::new();
+        for &(amp, ms) in parts {
+            for _ in 0..ms * MS {
+                out.push(amp * (TAU * 200.0 * i as f32 / RATE as f32).sin());
+                i += 1;
+            }
+        }
+        out
+    }
+
+    fn gate(input: Vec<f32>) -> Vec<f32> {
+        make_noise_gate(&NoiseGate {
+            threshold_db: -20.0,
+            attack_ms: 1.0,
+            release_ms: 10.0,
+        })
+        .apply(Box::new(SamplesBuffer::new(1, RATE, input)))
+        .collect()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn quiet_passages_are_silenced_and_loud_ones_pass() {
+        let input = tone(&[(0.5, 100), (0.01, 200), (0.5, 100)]);
+        let out = gate(input.clone());
+
+        assert!((peak(&out[..100 * MS]) - 0.5).abs() < 1e-3);
+        // past the detector hold and the release the noise floor is gone
+        assert!(
+            peak(&out[200 * MS..300 * MS]) < 1e-4,
+            "{}",
+            peak(&out[200 * MS..300 * MS])
+        );
+        // and a loud note opens it again within the attack
+        let back = &out[310 * MS..];
+        let dry = &input[310 * MS..];
+        assert!(back.iter().zip(dry).all(|(y, x)| (y - x).abs() < 1e-3));
+    }
+
+    #[test]
+    fn hysteresis_keeps_a_level_just_under_the_threshold_open() {
+        // 0.09 is about 1 dB under -20 dB, inside the 3 dB window, while 0.05
+        // is well past it
+        let steps = |level: f32| {
+            let mut input = vec![0.12; 50 * MS];
+            input.extend(vec![level; 100 * MS]);
+            gate(input)
+        };
+
+        let hovering = steps(0.09);
+        assert!(hovering[60 * MS..].iter().all(|s| (s - 0.09).abs() < 1e-4));
+        let quiet = steps(0.05);
+        assert!(peak(&quiet[140 * MS..]) < 1e-4);
+    }
+}
@@ -0,0 +1,184 @@
+//! Places the voice in the stereo field with equal-power panning
+
+use crate::patch::shared::Shared;
+use crate::patch::slew::Slew;
+use crate::patch::{Effect, PatchSource};
+use rodio::Source;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{FRAC_PI_4, SQRT_2};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pan {
+    /// -1.0 is hard left, 1.0 is hard right
+    pub position: f32,
+}
+
+impl Pan {
+    #[inline]
+    #[must_use]
+    pub fn new(position: f32) -> Self {
+        Self {
+            position: position.clamp(-1.0, 1.0),
+        }
+    }
+
+    /// Left and right gains, their squares always sum to 2 so the centre
+    /// plays both sides at unity, as loud as before there was a pan
+    #[inline]
+    #[must_use]
+    pub fn gains(&self) -> (f32, f32) {
+        let angle = (self.position.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+        (angle.cos() * SQRT_2, angle.sin() * SQRT_2)
+    }
+}
+
+pub type PanHandle = Shared<Pan>;
+
+#[inline]
+#[must_use]
+pub fn make_pan(pan: &Pan) -> PanHandle {
+    Shared::new(Pan::new(pan.position))
+}
+
+/// Mono input is spread over two interleaved channels, stereo input gets the
+/// left/right gain per sample, any other layout passes through untouched
+struct PanSource {
+    input: PatchSource,
+    pan: PanHandle,
+    slew: Slew,
+    right: Option<f32>,
+    /// Right gain of the stereo frame in progress
+    right_gain: f32,
+    channel: u16,
+}
+
+impl PanSource {
+    /// Gains for the next frame, the position slewed so moves do not click
+    fn gains(&mut self) -> (f32, f32) {
+        let position = self
+            .slew
+            .param(self.pan.get().position, self.input.sample_rate());
+        Pan::new(position).gains()
+    }
+}
+
+impl Iterator for PanSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.input.channels() {
+            1 => {
+                if let Some(r) = self.right.take() {
+                    return Some(r);
+                }
+
+                let x = self.input.next()?;
+                let (l, r) = self.gains();
+                self.right = Some(x * r);
+
+                Some(x * l)
+            }
+            2 => {
+                let x = self.input.next()?;
+                // one step per frame, on its left sample
+                let g = if self.channel == 0 {
+                    let (l, r) = self.gains();
+                    self.right_gain = r;
+                    l
+                } else {
+                    self.right_gain
+                };
+                self.channel ^= 1;
+
+                Some(x * g)
+            }
+            _ => self.input.next(),
+        }
+    }
+}
+
+impl Source for PanSource {
+    fn current_span_len(&self) -> Option<usize> {
+        let len = self.input.current_span_len()?;
+
+        if self.input.channels() == 1 {
+            Some(len * 2 + usize::from(self.right.is_some()))
+        } else {
+            Some(len)
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        match self.input.channels() {
+            1 => 2,
+            n => n,
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+impl Effect for Shared<Pan> {
+    fn name(&self) -> &'static str {
+        "Pan"
+    }
+
+    fn apply(&self, input: PatchSource) -> PatchSource {
+        Box::new(PanSource {
+            input,
+            pan: self.clone(),
+            slew: Slew::new(),
+            right: None,
+            right_gain: 0.0,
+            channel: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    fn pan(position: f32, channels: u16, input: Vec<f32>) -> Vec<f32> {
+        make_pan(&Pan::new(position))
+            .apply(Box::new(SamplesBuffer::new(channels, 48_000, input)))
+            .collect()
+    }
+
+    #[test]
+    fn centre_keeps_the_unpanned_level() {
+        let (l, r) = Pan::new(0.0).gains();
+        assert!((l - 1.0).abs() < 1e-6 && (r - 1.0).abs() < 1e-6, "{l} {r}");
+
+        let out = pan(0.0, 1, vec![0.5; 8]);
+        assert_eq!(out.len(), 16);
+        assert!(out.iter().all(|s| (s - 0.5).abs() < 1e-6), "{out:?}");
+    }
+
+    #[test]
+    fn full_left_silences_the_right() {
+        let mono = pan(-1.0, 1, vec![0.5; 8]);
+        let stereo = pan(-1.0, 2, vec![0.5; 16]);
+
+        for out in [mono, stereo] {
+            let right = out
+                .iter()
+                .skip(1)
+                .step_by(2)
+                .fold(0.0_f32, |p, s| p.max(s.abs()));
+            assert!(right < 1e-6, "right channel {right}");
+            assert!(
+                out.iter().step_by(2).all(|s| *s > 0.5),
+                "left gets the power"
+            );
+        }
+    }
+}
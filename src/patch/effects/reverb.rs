@@ -0,0 +1,253 @@
+//! Freeverb-style reverb, parallel damped combs into a chain of allpasses.
+//! Each channel has its own, a little longer on every channel after the
+//! first so the sides do not ring in step
+
+use crate::patch::shared::Shared;
+use crate::patch::slew::Slew;
+use crate::patch::{Effect, PatchSource};
+use serde::{Deserialize, Serialize};
+
+/// Comb and allpass lengths from Freeverb, in samples at 44.1 kHz. They are
+/// mutually prime-ish so the echoes never line up into a pitched ring
+const COMB_TUNING: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+const TUNING_RATE: f32 = 44_100.0;
+// Freeverb's offset between its left and right tanks, also at 44.1 kHz
+const STEREO_SPREAD: usize = 23;
+
+const ALLPASS_FEEDBACK: f32 = 0.5;
+// eight combs in parallel add up, this brings the tail back near unity
+const INPUT_GAIN: f32 = 0.015;
+const WET_GAIN: f32 = 3.0;
+
+/// `room` sets how long the tail rings, `damping` how quickly its highs die
+/// away, both 0..1
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reverb {
+    pub room: f32,
+    pub damping: f32,
+    pub mix: f32,
+}
+
+impl Reverb {
+    #[inline]
+    #[must_use]
+    pub fn new(room: f32, damping: f32, mix: f32) -> Self {
+        Self {
+            room: room.clamp(0.0, 1.0),
+            damping: damping.clamp(0.0, 1.0),
+            mix: mix.clamp(0.0, 1.0),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    fn feedback(&self) -> f32 {
+        0.7 + 0.28 * self.room.clamp(0.0, 1.0)
+    }
+}
+
+pub type ReverbHandle = Shared<Reverb>;
+
+#[inline]
+#[must_use]
+pub fn make_reverb(reverb: &Reverb) -> ReverbHandle {
+    Shared::new(Reverb::new(reverb.room, reverb.damping, reverb.mix))
+}
+
+/// Feedback comb with a one-pole lowpass in the loop
+struct Comb {
+    buffer: Vec<f32>,
+    pos: usize,
+    store: f32,
+}
+
+impl Comb {
+    #[inline]
+    fn process(&mut self, x: f32, feedback: f32, damp: f32) -> f32 {
+        let y = self.buffer[self.pos];
+        self.store = y * (1.0 - damp) + self.store * damp;
+        self.buffer[self.pos] = x + self.store * feedback;
+        self.pos = (self.pos + 1) % self.buffer.len();
+        y
+    }
+}
+
+struct Allpass {
+    buffer: Vec<f32>,
+    pos: usize,
+}
+
+impl Allpass {
+    #[inline]
+    fn process(&mut self, x: f32) -> f32 {
+        let delayed = self.buffer[self.pos];
+        self.buffer[self.pos] = x + delayed * ALLPASS_FEEDBACK;
+        self.pos = (self.pos + 1) % self.buffer.len();
+        delayed - x
+    }
+}
+
+#[inline]
+fn scaled_len(samples: usize, sample_rate: u32) -> usize {
+    ((samples as f32 * sample_rate as f32 / TUNING_RATE).round() as usize).max(1)
+}
+
+/// The combs and allpasses one channel runs through
+struct Tank {
+    combs: Vec<Comb>,
+    allpasses: Vec<Allpass>,
+}
+
+impl Tank {
+    fn new(spread: usize, sample_rate: u32) -> Self {
+        Self {
+            combs: COMB_TUNING
+                .iter()
+                .map(|&len| Comb {
+                    buffer: vec![0.0; scaled_len(len + spread, sample_rate)],
+                    pos: 0,
+                    store: 0.0,
+                })
+                .collect(),
+            allpasses: ALLPASS_TUNING
+                .iter()
+                .map(|&len| Allpass {
+                    buffer: vec![0.0; scaled_len(len + spread, sample_rate)],
+                    pos: 0,
+                })
+                .collect(),
+        }
+    }
+
+    #[inline]
+    fn process(&mut self, x: f32, feedback: f32, damp: f32) -> f32 {
+        let fed = x * INPUT_GAIN;
+        let mut wet: f32 = self
+            .combs
+            .iter_mut()
+            .map(|comb| comb.process(fed, feedback, damp))
+            .sum();
+        for allpass in &mut self.allpasses {
+            wet = allpass.process(wet);
+        }
+        wet
+    }
+}
+
+struct ReverbSource {
+    input: PatchSource,
+    reverb: ReverbHandle,
+    /// One per channel, samples come interleaved
+    tanks: Vec<Tank>,
+    channel: usize,
+    mix: Slew,
+}
+
+impl Iterator for ReverbSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.input.next()?;
+        let cfg = self.reverb.get();
+        let feedback = cfg.feedback();
+        let damp = cfg.damping.clamp(0.0, 1.0) * 0.4;
+        let mix = self
+            .mix
+            .param(cfg.mix.clamp(0.0, 1.0), self.input.sample_rate());
+
+        let wet = self.tanks[self.channel].process(x, feedback, damp);
+        self.channel = (self.channel + 1) % self.tanks.len();
+
+        Some(x * (1.0 - mix) + wet * WET_GAIN * mix)
+    }
+}
+
+crate::impl_source_passthrough!(ReverbSource, input);
+
+impl Effect for Shared<Reverb> {
+    fn name(&self) -> &'static str {
+        "Reverb"
+    }
+
+    fn apply(&self, input: PatchSource) -> PatchSource {
+        let sample_rate = input.sample_rate().max(1);
+        let tanks = (0..usize::from(input.channels().max(1)))
+            .map(|channel| Tank::new(channel * STEREO_SPREAD, sample_rate))
+            .collect();
+
+        Box::new(ReverbSource {
+            input,
+            reverb: self.clone(),
+            tanks,
+            channel: 0,
+            mix: Slew::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    const SR: u32 = 44_100;
+
+    fn impulse(reverb: Reverb, channels: u16, seconds: usize) -> Vec<f32> {
+        let mut input = vec![0.0; SR as usize * usize::from(channels) * seconds];
+        input[0] = 1.0;
+        make_reverb(&reverb)
+            .apply(Box::new(SamplesBuffer::new(channels, SR, input)))
+            .collect()
+    }
+
+    /// Energy of each tenth of a second
+    fn energy(samples: &[f32]) -> Vec<f32> {
+        samples
+            .chunks(SR as usize / 10)
+            .map(|chunk| chunk.iter().map(|s| s * s).sum())
+            .collect()
+    }
+
+    #[test]
+    fn impulse_rings_on_and_decays() {
+        let out = impulse(Reverb::new(0.5, 0.5, 1.0), 1, 2);
+        let energy = energy(&out);
+
+        // nothing comes back before the shortest comb
+        assert!(out[1..COMB_TUNING[0]].iter().all(|s| *s == 0.0));
+        assert!(energy[1] > 0.0 && energy[10] > 0.0, "a tail {energy:?}");
+        assert!(energy[15] < energy[2] / 10.0, "that dies away {energy:?}");
+        assert!(out.iter().all(|s| s.abs() < 1.0), "no blow up");
+    }
+
+    #[test]
+    fn bigger_room_rings_longer() {
+        let small = energy(&impulse(Reverb::new(0.1, 0.5, 1.0), 1, 2));
+        let big = energy(&impulse(Reverb::new(0.9, 0.5, 1.0), 1, 2));
+
+        assert!(big[15] > small[15] * 10.0, "{} vs {}", big[15], small[15]);
+    }
+
+    #[test]
+    fn dry_passes_the_input_through() {
+        let out = impulse(Reverb::new(0.9, 0.5, 0.0), 1, 1);
+
+        assert_eq!(out[0], 1.0);
+        assert!(out[1..].iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn each_channel_reverberates_itself() {
+        // the impulse only on the left
+        let out = impulse(Reverb::new(0.5, 0.5, 1.0), 2, 1);
+        let left: Vec<f32> = out.iter().step_by(2).copied().collect();
+        let right: Vec<f32> = out.iter().skip(1).step_by(2).copied().collect();
+
+        assert!(energy(&left)[1] > 0.0);
+        assert!(right.iter().all(|s| *s == 0.0), "the right stays silent");
+        // the tail lands a comb length later in frames, not samples
+        assert!(left[1..COMB_TUNING[0]].iter().all(|s| *s == 0.0));
+        assert!(left[COMB_TUNING[0]] != 0.0);
+    }
+}
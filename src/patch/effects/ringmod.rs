@@ -0,0 +1,133 @@
+//! Ring modulation, the input times an audio-rate sine for inharmonic tones
+
+use crate::patch::shared::Shared;
+use crate::patch::slew::Slew;
+use crate::patch::{Effect, PatchSource};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// Unlike AM the carrier is bipolar and the dry signal is not kept, so a
+/// note at `f` comes out as `f + freq_hz` and `f - freq_hz`. `mix` blends
+/// that back with the dry input
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RingMod {
+    pub freq_hz: f32,
+    pub mix: f32,
+}
+
+impl RingMod {
+    #[inline]
+    #[must_use]
+    pub fn new(freq_hz: f32, mix: f32) -> Self {
+        Self {
+            freq_hz: freq_hz.max(0.0),
+            mix: mix.clamp(0.0, 1.0),
+        }
+    }
+}
+
+pub type RingModHandle = Shared<RingMod>;
+
+#[inline]
+#[must_use]
+pub fn make_ringmod(ringmod: &RingMod) -> RingModHandle {
+    Shared::new(RingMod::new(ringmod.freq_hz, ringmod.mix))
+}
+
+struct RingModSource {
+    input: PatchSource,
+    ringmod: RingModHandle,
+    phase: f32,
+    mix: Slew,
+}
+
+impl Iterator for RingModSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.input.next()?;
+        let cfg = self.ringmod.get();
+        let sr = self.input.sample_rate().max(1) as f32;
+        let mix = self
+            .mix
+            .param(cfg.mix.clamp(0.0, 1.0), self.input.sample_rate());
+
+        let wet = x * (TAU * self.phase).sin();
+
+        self.phase += cfg.freq_hz.max(0.0) / sr;
+        if self.phase >= 1.0 {
+            self.phase -= self.phase.floor();
+        }
+
+        Some(x * (1.0 - mix) + wet * mix)
+    }
+}
+
+crate::impl_source_passthrough!(RingModSource, input);
+
+impl Effect for Shared<RingMod> {
+    fn name(&self) -> &'static str {
+        "Ring Mod"
+    }
+
+    fn apply(&self, input: PatchSource) -> PatchSource {
+        Box::new(RingModSource {
+            input,
+            ringmod: self.clone(),
+            phase: 0.0,
+            mix: Slew::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SAMPLE_RATE;
+    use crate::play::spectrum::level;
+    use rodio::buffer::SamplesBuffer;
+
+    fn sine(hz: f32, n: usize) -> Vec<f32> {
+        (0..n)
+            .map(|i| (TAU * hz * i as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    fn ring(ringmod: RingMod, input: Vec<f32>) -> Vec<f32> {
+        make_ringmod(&ringmod)
+            .apply(Box::new(SamplesBuffer::new(1, SAMPLE_RATE, input)))
+            .collect()
+    }
+
+    #[test]
+    fn fully_wet_moves_a_tone_to_the_sum_and_difference() {
+        let out = ring(RingMod::new(100.0, 1.0), sine(440.0, SAMPLE_RATE as usize));
+
+        assert!(level(&out, 440.0) < 0.01, "the input is gone");
+        assert!((level(&out, 340.0) - 0.5).abs() < 0.01);
+        assert!((level(&out, 540.0) - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn dry_passes_the_input_through() {
+        let input = sine(440.0, 1_000);
+
+        assert_eq!(ring(RingMod::new(100.0, 0.0), input.clone()), input);
+    }
+
+    #[test]
+    fn half_mix_keeps_half_the_input() {
+        let out = ring(RingMod::new(100.0, 0.5), sine(440.0, SAMPLE_RATE as usize));
+
+        assert!((level(&out, 440.0) - 0.5).abs() < 0.01);
+        assert!((level(&out, 340.0) - 0.25).abs() < 0.01);
+    }
+
+    #[test]
+    fn new_clamps_the_settings() {
+        let ringmod = RingMod::new(-5.0, 2.0);
+
+        assert_eq!(ringmod.freq_hz, 0.0);
+        assert_eq!(ringmod.mix, 1.0);
+    }
+}
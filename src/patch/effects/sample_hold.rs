@@ -0,0 +1,243 @@
+//! Sample-and-hold random modulation routed to pitch, cutoff, or amplitude
+
+use crate::patch::effects::lowpass::alpha;
+use crate::patch::shared::Shared;
+use crate::patch::{Effect, PatchSource};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const PITCH_RANGE_ST: f32 = 12.0;
+const CUTOFF_MAX_HZ: f32 = 20_000.0;
+const CUTOFF_RANGE_OCT: f32 = 8.0;
+
+/// Voices started so far, each one's sequence is seeded from its number so
+/// chords do not step in lockstep
+static VOICES: AtomicU64 = AtomicU64::new(0);
+
+/// Seed for voice number `voice` of a patch seeded with `seed`, splitmix64
+/// so neighbouring voices start far apart
+#[inline]
+#[must_use]
+fn voice_seed(seed: u64, voice: u64) -> u64 {
+    let mut z = seed.wrapping_add(voice.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldTarget {
+    Pitch,
+    Cutoff,
+    Amp,
+}
+
+impl HoldTarget {
+    pub const ALL: [Self; 3] = [Self::Pitch, Self::Cutoff, Self::Amp];
+
+    #[inline]
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Pitch => "Pitch",
+            Self::Cutoff => "Cutoff",
+            Self::Amp => "Amp",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SampleHold {
+    pub rate_hz: f32,
+    pub depth: f32,
+    pub target: HoldTarget,
+    pub seed: u64,
+}
+
+pub type SampleHoldHandle = Shared<SampleHold>;
+
+#[inline]
+#[must_use]
+pub fn make_sample_hold(sample_hold: &SampleHold) -> SampleHoldHandle {
+    Shared::new(SampleHold {
+        rate_hz: sample_hold.rate_hz.max(0.0),
+        depth: sample_hold.depth.clamp(0.0, 1.0),
+        target: sample_hold.target,
+        seed: sample_hold.seed,
+    })
+}
+
+/// Latches a new value in -1..1 from a seeded xorshift every `1 / rate_hz`
+/// seconds and holds it in between
+#[derive(Debug, Clone)]
+struct HoldOsc {
+    rng: u64,
+    phase: f32,
+    held: f32,
+}
+
+impl HoldOsc {
+    #[must_use]
+    fn new(seed: u64) -> Self {
+        let mut osc = Self {
+            // xorshift gets stuck on zero
+            rng: seed.max(1),
+            phase: 0.0,
+            held: 0.0,
+        };
+        osc.held = osc.next_random();
+        osc
+    }
+
+    fn next_random(&mut self) -> f32 {
+        let mut x = self.rng;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rng = x;
+
+        let y = x.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        let u = (y >> 40) as u32;
+        let f = u as f32 / ((1u32 << 24) as f32);
+
+        2.0 * f - 1.0
+    }
+
+    fn next_value(&mut self, rate_hz: f32, sample_rate: u32) -> f32 {
+        let value = self.held;
+
+        self.phase += rate_hz.max(0.0) / sample_rate.max(1) as f32;
+        if self.phase >= 1.0 {
+            self.phase -= self.phase.floor();
+            self.held = self.next_random();
+        }
+
+        value
+    }
+}
+
+struct SampleHoldSource {
+    input: PatchSource,
+    sample_hold: SampleHoldHandle,
+    hold: HoldOsc,
+    // pitch: linear interpolation between the last two input samples
+    pos: f32,
+    prev_x: f32,
+    next_x: f32,
+    // cutoff: one-pole lowpass state
+    prev_y: f32,
+}
+
+impl SampleHoldSource {
+    fn resample(&mut self, ratio: f32) -> Option<f32> {
+        self.pos += ratio;
+        while self.pos >= 1.0 {
+            self.prev_x = self.next_x;
+            self.next_x = self.input.next()?;
+            self.pos -= 1.0;
+        }
+
+        Some(self.prev_x + (self.next_x - self.prev_x) * self.pos)
+    }
+}
+
+impl Iterator for SampleHoldSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cfg = self.sample_hold.get();
+        let sr = self.input.sample_rate();
+        let depth = cfg.depth.clamp(0.0, 1.0);
+        let v = self.hold.next_value(cfg.rate_hz, sr);
+        let unipolar = 0.5 * (v + 1.0);
+
+        match cfg.target {
+            HoldTarget::Pitch => {
+                let ratio = (depth * v * PITCH_RANGE_ST / 12.0).exp2();
+                self.resample(ratio)
+            }
+            HoldTarget::Cutoff => {
+                let x = self.input.next()?;
+                let cutoff = CUTOFF_MAX_HZ * (-depth * unipolar * CUTOFF_RANGE_OCT).exp2();
+                let a = alpha(sr.max(1) as f32, cutoff);
+
+                let y = a * x + (1.0 - a) * self.prev_y;
+                self.prev_y = y;
+
+                Some(y)
+            }
+            HoldTarget::Amp => {
+                let x = self.input.next()?;
+                Some(x * (1.0 - depth * unipolar))
+            }
+        }
+    }
+}
+
+crate::impl_source_passthrough!(SampleHoldSource, input);
+
+impl Effect for Shared<SampleHold> {
+    fn name(&self) -> &'static str {
+        "S&H"
+    }
+
+    fn apply(&self, input: PatchSource) -> PatchSource {
+        let seed = voice_seed(self.get().seed, VOICES.fetch_add(1, Ordering::Relaxed));
+
+        Box::new(SampleHoldSource {
+            input,
+            sample_hold: self.clone(),
+            hold: HoldOsc::new(seed),
+            pos: 0.0,
+            prev_x: 0.0,
+            next_x: 0.0,
+            prev_y: 0.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SAMPLE_RATE;
+    use rodio::buffer::SamplesBuffer;
+
+    // a power of two, so the phase lands on each step exactly
+    const STEP: usize = 512;
+
+    fn handle() -> SampleHoldHandle {
+        make_sample_hold(&SampleHold {
+            rate_hz: SAMPLE_RATE as f32 / STEP as f32,
+            depth: 1.0,
+            target: HoldTarget::Amp,
+            seed: 7,
+        })
+    }
+
+    /// The held level of one voice, read off a steady input
+    fn run(handle: &SampleHoldHandle) -> Vec<f32> {
+        let ones = Box::new(SamplesBuffer::new(1, SAMPLE_RATE, vec![1.0; STEP * 8]));
+        handle.apply(ones).collect()
+    }
+
+    #[test]
+    fn holds_between_steps_and_changes_on_them() {
+        let out = run(&handle());
+
+        for step in out.chunks(STEP) {
+            assert!(step.iter().all(|&x| x == step[0]), "moved within a step");
+        }
+        for pair in out.chunks(STEP).collect::<Vec<_>>().windows(2) {
+            assert_ne!(pair[0][0], pair[1][0], "no new value at a step");
+        }
+    }
+
+    #[test]
+    fn each_voice_gets_its_own_sequence() {
+        let handle = handle();
+        assert_ne!(run(&handle), run(&handle));
+        assert_ne!(voice_seed(7, 0), voice_seed(7, 1));
+        assert_eq!(voice_seed(7, 3), voice_seed(7, 3));
+    }
+}
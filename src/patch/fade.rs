@@ -0,0 +1,116 @@
+//! Short linear fade for voices cut off before their envelope has finished
+
+use crate::patch::PatchSource;
+use rodio::Source;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set once to fade a voice out, shared by every sink the voice plays through
+pub type Cut = Arc<AtomicBool>;
+
+struct FadeOut {
+    input: PatchSource,
+    cut: Cut,
+    channels: usize,
+    /// Channel the next sample belongs to, the ramp only starts on a frame
+    channel: usize,
+    /// Frames the ramp lasts
+    frames: usize,
+    /// Frames left in the ramp, counting down once `cut` is seen
+    left: Option<usize>,
+}
+
+impl Iterator for FadeOut {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.input.next()?;
+
+        if self.channel == 0 {
+            match self.left {
+                None if self.cut.load(Ordering::Relaxed) => self.left = Some(self.frames),
+                Some(0) => return None,
+                Some(left) => self.left = Some(left - 1),
+                None => {}
+            }
+        }
+        self.channel = (self.channel + 1) % self.channels;
+
+        let Some(left) = self.left else {
+            return Some(x);
+        };
+        Some(x * left as f32 / self.frames as f32)
+    }
+}
+
+crate::impl_source_passthrough!(FadeOut, input);
+
+/// Passes `input` through until `cut` is set, then ramps it down to silence
+/// over `fade_ms` and ends, so a stolen voice stops without a click
+#[must_use]
+pub fn fade_out(input: PatchSource, cut: Cut, fade_ms: u64) -> PatchSource {
+    let frames = fade_ms * u64::from(input.sample_rate()) / 1000;
+
+    Box::new(FadeOut {
+        channels: usize::from(input.channels().max(1)),
+        input,
+        cut,
+        channel: 0,
+        frames: usize::try_from(frames).unwrap_or(usize::MAX).max(1),
+        left: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    /// 10 ms at 1 kHz, a full-scale constant until cut
+    fn voice(channels: u16, cut: &Cut) -> PatchSource {
+        let input = SamplesBuffer::new(channels, 1_000, vec![1.0; 1_000]);
+        fade_out(Box::new(input), cut.clone(), 10)
+    }
+
+    #[test]
+    fn uncut_passes_through() {
+        let cut = Cut::default();
+
+        assert!(voice(1, &cut).all(|s| s == 1.0));
+    }
+
+    #[test]
+    fn cut_ramps_monotonically_to_zero_and_ends() {
+        let cut = Cut::default();
+        let mut source = voice(1, &cut);
+        assert!(source.by_ref().take(100).all(|s| s == 1.0));
+
+        cut.store(true, Ordering::Relaxed);
+        let tail: Vec<f32> = source.collect();
+
+        assert_eq!(tail.len(), 11, "the fade, then the source ends");
+        assert!(tail.windows(2).all(|pair| pair[1] < pair[0]), "{tail:?}");
+        assert_eq!(tail.last(), Some(&0.0));
+        // no step into the ramp
+        assert!(1.0 - tail[0] <= 0.1, "{tail:?}");
+    }
+
+    #[test]
+    fn channels_fade_together() {
+        let cut = Cut::default();
+        let mut source = voice(2, &cut);
+        // half a frame in, the ramp waits for the next frame
+        assert_eq!(source.next(), Some(1.0));
+
+        cut.store(true, Ordering::Relaxed);
+        let tail: Vec<f32> = source.collect();
+
+        assert_eq!(tail[0], 1.0, "the rest of the frame");
+        let frames: Vec<&[f32]> = tail[1..].chunks(2).collect();
+        assert_eq!(frames.len(), 11);
+        assert!(
+            frames.iter().all(|frame| frame[0] == frame[1]),
+            "{frames:?}"
+        );
+    }
+}
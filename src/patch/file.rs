@@ -0,0 +1,217 @@
+//! Patches saved as TOML under `~/.config/mugen/patches`, the sound only:
+//! oscillator, envelope, effects and volume. Playing state such as the
+//! octave, arp or sequence is left out
+//!
+//! ```toml
+//! volume = 0.8
+//! wave = "saw"
+//! # ahead of the envelope, in signal order
+//! effects = ["gain", "lowpass", "highpass"]
+//!
+//! [adsr]
+//! attack_s = 0.01
+//! decay_s = 0.2
+//! sustain = 0.7
+//! release_s = 0.4
+//! mode = "gate"
+//! curve = "exponential"
+//!
+//! [lowpass]
+//! cutoff_hz = 1800.0
+//! ```
+
+use crate::audio::Snapshot;
+use crate::config::{PATCH_DIR, PATCH_EXT};
+use crate::patch::chain::Fx;
+use crate::patch::effects::adsr::Adsr;
+use crate::patch::effects::am::Am;
+use crate::patch::effects::bitcrush::Bitcrush;
+use crate::patch::effects::delay::Delay;
+use crate::patch::effects::gain::Gain;
+use crate::patch::effects::highpass::HighPass;
+use crate::patch::effects::lfo_amp::LfoAmp;
+use crate::patch::effects::lowpass::LowPass;
+use crate::patch::effects::noise_gate::NoiseGate;
+use crate::patch::effects::pan::Pan;
+use crate::patch::effects::reverb::Reverb;
+use crate::patch::effects::ringmod::RingMod;
+use crate::patch::effects::sample_hold::SampleHold;
+use crate::patch::lfo::Lfo;
+use crate::patch::oscilators::basic::{Sub, Wave};
+use crate::patch::oscilators::fm::Fm;
+use crate::play::recorder::create_stamped;
+use crate::settings::config_path;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Sections missing from a file keep the built-in defaults, so a patch
+/// saved before an effect existed still loads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PatchFile {
+    pub volume: f32,
+    pub wave: Wave,
+    pub pulse_duty: f32,
+    /// Chain ahead of the envelope, ahead of the sections so it saves as a
+    /// plain key
+    pub effects: Vec<Fx>,
+    pub sub: Sub,
+    pub fm: Fm,
+    pub adsr: Adsr,
+    pub gain: Gain,
+    pub lfo_amp: LfoAmp,
+    pub lfo: Lfo,
+    pub am: Am,
+    pub ringmod: RingMod,
+    pub bitcrush: Bitcrush,
+    pub sample_hold: SampleHold,
+    pub highpass: HighPass,
+    pub lowpass: LowPass,
+    pub delay: Delay,
+    pub reverb: Reverb,
+    pub noise_gate: NoiseGate,
+    pub pan: Pan,
+}
+
+impl Default for PatchFile {
+    fn default() -> Self {
+        Self::from_snapshot(&Snapshot::default())
+    }
+}
+
+impl PatchFile {
+    #[must_use]
+    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
+        Self {
+            volume: snapshot.volume,
+            wave: snapshot.wave.clone(),
+            pulse_duty: snapshot.pulse_duty,
+            effects: snapshot.effects.clone(),
+            sub: snapshot.sub,
+            fm: snapshot.fm.clone(),
+            adsr: snapshot.adsr.clone(),
+            gain: snapshot.gain.clone(),
+            lfo_amp: snapshot.lfo_amp.clone(),
+            lfo: snapshot.lfo.clone(),
+            am: snapshot.am.clone(),
+            ringmod: snapshot.ringmod.clone(),
+            bitcrush: snapshot.bitcrush.clone(),
+            sample_hold: snapshot.sample_hold.clone(),
+            highpass: snapshot.highpass.clone(),
+            lowpass: snapshot.lowpass.clone(),
+            delay: snapshot.delay.clone(),
+            reverb: snapshot.reverb.clone(),
+            noise_gate: snapshot.noise_gate.clone(),
+            pan: snapshot.pan.clone(),
+        }
+    }
+
+    /// Puts the patch into a snapshot the engine starts from
+    pub fn apply(self, snapshot: &mut Snapshot) {
+        snapshot.volume = self.volume;
+        snapshot.wave = self.wave;
+        snapshot.pulse_duty = self.pulse_duty;
+        snapshot.effects = self.effects;
+        snapshot.sub = self.sub;
+        snapshot.fm = self.fm;
+        snapshot.adsr = self.adsr;
+        snapshot.gain = self.gain;
+        snapshot.lfo_amp = self.lfo_amp;
+        snapshot.lfo = self.lfo;
+        snapshot.am = self.am;
+        snapshot.ringmod = self.ringmod;
+        snapshot.bitcrush = self.bitcrush;
+        snapshot.sample_hold = self.sample_hold;
+        snapshot.highpass = self.highpass;
+        snapshot.lowpass = self.lowpass;
+        snapshot.delay = self.delay;
+        snapshot.reverb = self.reverb;
+        snapshot.noise_gate = self.noise_gate;
+        snapshot.pan = self.pan;
+    }
+
+    /// Reads a patch, see `parse`
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Values out of range are pulled back in. Text that does not parse is
+    /// an `InvalidData` error naming the line
+    pub fn parse(text: &str) -> io::Result<Self> {
+        match toml::from_str::<Self>(text) {
+            Ok(file) => Ok(file.clamped()),
+            Err(err) => {
+                let line = err
+                    .span()
+                    .map(|span| text[..span.start.min(text.len())].lines().count().max(1));
+                let msg = match line {
+                    Some(line) => format!("line {line}: {}", err.message()),
+                    None => err.message().to_string(),
+                };
+                Err(io::Error::new(io::ErrorKind::InvalidData, msg))
+            }
+        }
+    }
+
+    /// Writes a new `mugen-<unix time>.patch` under `PATCH_DIR`, never
+    /// over one saved earlier
+    pub fn save(&self) -> io::Result<PathBuf> {
+        let dir = patch_dir()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        fs::create_dir_all(&dir)?;
+
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        let (path, mut file) = create_stamped(&dir, PATCH_EXT)?;
+        file.write_all(text.as_bytes())?;
+        Ok(path)
+    }
+
+    #[must_use]
+    fn clamped(self) -> Self {
+        Self {
+            adsr: Adsr {
+                attack_s: self.adsr.attack_s.max(0.0),
+                decay_s: self.adsr.decay_s.max(0.0),
+                sustain: self.adsr.sustain.clamp(0.0, 1.0),
+                release_s: self.adsr.release_s.max(0.0),
+                ..self.adsr
+            },
+            ringmod: RingMod::new(self.ringmod.freq_hz, self.ringmod.mix),
+            delay: Delay::new(self.delay.time_ms, self.delay.feedback, self.delay.mix),
+            reverb: Reverb::new(self.reverb.room, self.reverb.damping, self.reverb.mix),
+            pan: Pan::new(self.pan.position),
+            ..self
+        }
+    }
+}
+
+/// `PATCH_DIR` under the config directory
+#[must_use]
+pub fn patch_dir() -> Option<PathBuf> {
+    config_path(PATCH_DIR)
+}
+
+/// The patch file written last, `None` when there are none yet
+pub fn latest_patch() -> io::Result<Option<PathBuf>> {
+    let Some(dir) = patch_dir() else {
+        return Ok(None);
+    };
+
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    let latest = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == PATCH_EXT))
+        .filter_map(|path| Some((fs::metadata(&path).ok()?.modified().ok()?, path)))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path);
+
+    Ok(latest)
+}
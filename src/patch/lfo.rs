@@ -0,0 +1,291 @@
+//! Global LFO routed to the filter cutoff, oscillator pitch or amplitude.
+//! One oscillator runs on the audio clock and every voice reads the same
+//! value, so all notes move together
+
+use crate::config::{LFO_CUTOFF_RANGE_OCT, LFO_PITCH_RANGE_ST};
+use crate::patch::effects::lfo::LfoOsc;
+use crate::patch::oscilators::basic::Wave;
+use crate::patch::shared::Shared;
+use crate::patch::{PatchSource, Sample};
+use rodio::Source;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+// rate and wave edits are picked up at control rate rather than every sample
+const CONTROL_SAMPLES: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LfoTarget {
+    Off,
+    Cutoff,
+    Pitch,
+    Amp,
+}
+
+impl LfoTarget {
+    pub const ALL: [Self; 4] = [Self::Off, Self::Cutoff, Self::Pitch, Self::Amp];
+
+    #[inline]
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Cutoff => "Cutoff",
+            Self::Pitch => "Pitch",
+            Self::Amp => "Amp",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lfo {
+    pub wave: Wave,
+    pub rate_hz: f32,
+    pub depth: f32,
+    pub target: LfoTarget,
+}
+
+pub type LfoHandle = Shared<Lfo>;
+
+/// The LFO settings together with its latest output in -1..1, cloning shares
+/// both
+#[derive(Debug, Clone)]
+pub struct Modulation {
+    lfo: LfoHandle,
+    value: Arc<AtomicU32>,
+}
+
+impl Modulation {
+    #[must_use]
+    pub fn new(lfo: &Lfo) -> Self {
+        Self {
+            lfo: Shared::new(clamp_lfo(lfo)),
+            value: Arc::new(AtomicU32::new(0f32.to_bits())),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn lfo(&self) -> Lfo {
+        self.lfo.get()
+    }
+
+    #[inline]
+    pub fn set_lfo(&self, lfo: &Lfo) {
+        self.lfo.set(clamp_lfo(lfo));
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn value(&self) -> f32 {
+        f32::from_bits(self.value.load(Ordering::Relaxed))
+    }
+
+    /// Depth scaled output when the LFO is routed to `target`, zero otherwise
+    #[inline]
+    #[must_use]
+    pub fn amount(&self, target: LfoTarget) -> f32 {
+        let lfo = self.lfo.get();
+        if lfo.target == target {
+            lfo.depth * self.value()
+        } else {
+            0.0
+        }
+    }
+
+    /// Multiplier for a filter cutoff, up to `LFO_CUTOFF_RANGE_OCT` either way
+    #[inline]
+    #[must_use]
+    pub fn cutoff_ratio(&self) -> f32 {
+        (self.amount(LfoTarget::Cutoff) * LFO_CUTOFF_RANGE_OCT).exp2()
+    }
+
+    /// Multiplier for an oscillator frequency, up to `LFO_PITCH_RANGE_ST`
+    /// either way
+    #[inline]
+    #[must_use]
+    pub fn pitch_ratio(&self) -> f32 {
+        (self.amount(LfoTarget::Pitch) * LFO_PITCH_RANGE_ST / 12.0).exp2()
+    }
+
+    /// Gain dipping from 1 down to `1 - depth` over a cycle
+    #[inline]
+    #[must_use]
+    pub fn amp_gain(&self) -> f32 {
+        let lfo = self.lfo.get();
+        if lfo.target == LfoTarget::Amp {
+            1.0 - lfo.depth * 0.5 * (1.0 - self.value())
+        } else {
+            1.0
+        }
+    }
+
+    /// Silent endless source that advances the LFO, added to the output mixer
+    /// so it runs at `sample_rate` whether or not any note sounds
+    #[must_use]
+    pub fn clock(&self, sample_rate: u32) -> LfoClock {
+        let lfo = self.lfo.get();
+
+        LfoClock {
+            osc: LfoOsc::new(lfo.wave, lfo.rate_hz, sample_rate),
+            modulation: self.clone(),
+            sample_rate,
+            countdown: 0,
+        }
+    }
+
+    /// Scales `input` by `amp_gain`
+    #[must_use]
+    pub fn apply_amp(&self, input: PatchSource) -> PatchSource {
+        Box::new(LfoAmpSource {
+            input,
+            modulation: self.clone(),
+        })
+    }
+}
+
+#[must_use]
+fn clamp_lfo(lfo: &Lfo) -> Lfo {
+    Lfo {
+        wave: lfo.wave.clone(),
+        rate_hz: lfo.rate_hz.max(0.0),
+        depth: lfo.depth.clamp(0.0, 1.0),
+        target: lfo.target,
+    }
+}
+
+pub struct LfoClock {
+    osc: LfoOsc,
+    modulation: Modulation,
+    sample_rate: u32,
+    countdown: usize,
+}
+
+impl Iterator for LfoClock {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.countdown == 0 {
+            self.countdown = CONTROL_SAMPLES;
+            let lfo = self.modulation.lfo();
+            self.osc.set_wave(lfo.wave);
+            self.osc.set_rate_hz(lfo.rate_hz);
+        }
+        self.countdown -= 1;
+
+        let value = self.osc.next_value();
+        self.modulation
+            .value
+            .store(value.to_bits(), Ordering::Relaxed);
+
+        Some(0.0)
+    }
+}
+
+impl Source for LfoClock {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+struct LfoAmpSource {
+    input: PatchSource,
+    modulation: Modulation,
+}
+
+impl Iterator for LfoAmpSource {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.input.next()?;
+        Some(x * self.modulation.amp_gain())
+    }
+}
+
+crate::impl_source_passthrough!(LfoAmpSource, input);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modulation(target: LfoTarget, depth: f32) -> Modulation {
+        Modulation::new(&Lfo {
+            wave: Wave::Sine,
+            rate_hz: 5.0,
+            depth,
+            target,
+        })
+    }
+
+    fn set_value(modulation: &Modulation, value: f32) {
+        modulation.value.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    #[test]
+    fn only_the_routed_target_moves() {
+        let pitch = modulation(LfoTarget::Pitch, 1.0);
+        set_value(&pitch, 1.0);
+
+        let semitone = (LFO_PITCH_RANGE_ST / 12.0).exp2();
+        assert!((pitch.pitch_ratio() - semitone).abs() < 1e-6);
+        assert_eq!(pitch.cutoff_ratio(), 1.0);
+        assert_eq!(pitch.amp_gain(), 1.0);
+
+        let cutoff = modulation(LfoTarget::Cutoff, 0.5);
+        set_value(&cutoff, -1.0);
+        let down = (-0.5 * LFO_CUTOFF_RANGE_OCT).exp2();
+        assert!((cutoff.cutoff_ratio() - down).abs() < 1e-6);
+        assert_eq!(cutoff.pitch_ratio(), 1.0);
+
+        let off = modulation(LfoTarget::Off, 1.0);
+        set_value(&off, 1.0);
+        assert_eq!(
+            (off.pitch_ratio(), off.cutoff_ratio(), off.amp_gain()),
+            (1.0, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn amp_dips_by_the_depth() {
+        let amp = modulation(LfoTarget::Amp, 0.6);
+
+        set_value(&amp, 1.0);
+        assert!((amp.amp_gain() - 1.0).abs() < 1e-6);
+        set_value(&amp, -1.0);
+        assert!((amp.amp_gain() - 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn clock_runs_the_lfo_at_its_rate() {
+        let modulation = modulation(LfoTarget::Cutoff, 1.0);
+        let rate = 1_000;
+        let mut clock = modulation.clock(rate);
+
+        let values: Vec<f32> = (0..rate * 2)
+            .map(|_| {
+                assert_eq!(clock.next(), Some(0.0), "the clock itself is silent");
+                modulation.value()
+            })
+            .collect();
+        let cycles = values
+            .windows(2)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count();
+        assert!(cycles.abs_diff(10) <= 1, "{cycles} cycles in 2 s at 5 Hz");
+    }
+}
@@ -1,19 +1,41 @@
 //! Constructs patch -> Builds voices from oscillator, ADSR, and modular effects
 
+pub mod bend;
+pub mod chain;
 pub mod effects;
+pub mod fade;
+pub mod file;
+pub mod lfo;
 pub mod oscilators;
 pub mod shared;
+pub mod slew;
+pub mod unison;
 
 use rodio::Source;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
 
+use crate::patch::bend::Bend;
+use crate::patch::chain::FxChain;
 use crate::patch::effects::adsr::{Adsr, AdsrHandle, adsr};
-use crate::patch::oscilators::basic::{OscHandle, Wave, osc_source};
+use crate::patch::lfo::Modulation;
+use crate::patch::oscilators::basic::{OscHandle, Wave};
+use crate::patch::oscilators::stack::{LayersHandle, stack_source};
+use crate::patch::shared::Shared;
+use crate::patch::unison::{Spread, spread_source};
 
 pub type Sample = f32;
 pub type PatchSource = Box<dyn Source<Item = Sample> + Send>;
 pub type Gate = Arc<AtomicBool>;
+/// Live pitch of a voice, retuned in place for legato and glide
+pub type Pitch = Shared<PitchTarget>;
+
+/// Frequency a voice is heading to and how long the slide there takes
+#[derive(Debug, Clone, Copy)]
+pub struct PitchTarget {
+    pub hz: f32,
+    pub glide_s: f32,
+}
 
 pub trait Effect: Send + Sync {
     fn name(&self) -> &'static str;
@@ -45,32 +67,67 @@ macro_rules! impl_source_passthrough {
     };
 }
 
+/// `effects` run on the mono oscillator before the envelope, `post_effects`
+/// run after it and may change the channel layout (e.g. panning to stereo).
+/// `modulation` drives the oscillator pitch and the level ahead of the
+/// envelope when the LFO is routed there, `bend` bends every voice at once
 #[derive(Clone)]
 pub struct Patch {
     osc: OscHandle,
+    layers: LayersHandle,
     adsr: AdsrHandle,
-    effects: Vec<SharedEffect>,
+    effects: FxChain,
+    post_effects: FxChain,
+    modulation: Modulation,
+    bend: Bend,
 }
 
 impl Patch {
-    #[must_use] 
-    pub fn new(osc: OscHandle, adsr: AdsrHandle, effects: Vec<SharedEffect>) -> Self {
-        Self { osc, adsr, effects }
+    #[must_use]
+    pub fn new(
+        osc: OscHandle,
+        layers: LayersHandle,
+        adsr: AdsrHandle,
+        effects: FxChain,
+        post_effects: FxChain,
+        modulation: Modulation,
+        bend: Bend,
+    ) -> Self {
+        Self {
+            osc,
+            layers,
+            adsr,
+            effects,
+            post_effects,
+            modulation,
+            bend,
+        }
     }
 
     #[inline]
-    pub fn build_voice(&self, frequency: f32, gate: Gate) -> PatchSource {
-        let mut source: PatchSource = Box::new(osc_source(frequency, self.osc.clone()));
-
-        for effect in &self.effects {
-            source = effect.apply(source);
-        }
-
-        adsr(source, self.adsr.clone(), gate)
+    pub fn build_voice(
+        &self,
+        pitch: Pitch,
+        gate: Gate,
+        spread: Spread,
+        velocity: f32,
+    ) -> PatchSource {
+        let source: PatchSource = Box::new(stack_source(
+            pitch,
+            2f32.powf(spread.cents / 1200.0),
+            self.osc.clone(),
+            self.layers.clone(),
+            self.modulation.clone(),
+            self.bend.clone(),
+        ));
+        let source = self.modulation.apply_amp(self.effects.apply(source));
+        let source = adsr(source, self.adsr.clone(), gate, velocity);
+
+        spread_source(self.post_effects.apply(source), spread)
     }
 
     #[inline]
-    #[must_use] 
+    #[must_use]
     pub fn wave(&self) -> Wave {
         self.osc.get().wave
     }
@@ -86,7 +143,7 @@ impl Patch {
     }
 
     #[inline]
-    #[must_use] 
+    #[must_use]
     pub fn adsr(&self) -> Adsr {
         self.adsr.get()
     }
@@ -96,21 +153,35 @@ impl Patch {
         self.adsr.set(adsr_value);
     }
 
+    /// Replaces the chain ahead of the envelope. Voices built from here on
+    /// run `effects`, ones already sounding keep the chain they started with
+    #[inline]
+    pub fn set_effects(&mut self, effects: FxChain) {
+        self.effects = effects;
+    }
+
+    /// Every stage after the oscillator in signal order, the envelope sits
+    /// between the two chains
+    #[inline]
+    #[must_use]
+    pub fn fx_names(&self) -> Vec<&'static str> {
+        let mut names = self.effects.names();
+        names.push("ADSR");
+        names.extend(self.post_effects.names());
+        names
+    }
+
     #[inline]
-    #[must_use] 
+    #[must_use]
     pub fn name(&self) -> String {
-        let mut out = self.wave().name().to_string();
+        let osc = self.osc.get();
+        let mut out = osc.wave.label(osc.duty);
+
+        let names = [self.effects.names(), self.post_effects.names()].concat();
 
-        if !self.effects.is_empty() {
+        if !names.is_empty() {
             out.push_str(" | ");
-            out.push_str(
-                &self
-                    .effects
-                    .iter()
-                    .map(|effect| effect.name())
-                    .collect::<Vec<_>>()
-                    .join(" -> "),
-            );
+            out.push_str(&names.join(" -> "));
         }
 
         out
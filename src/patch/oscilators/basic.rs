@@ -1,14 +1,21 @@
 //! Simple wave shapes for generator
 
-use crate::config::{AMP_DEFAULT, SAMPLE_RATE};
+use crate::config::{
+    AMP_DEFAULT, FM_INDEX, FM_MODULATOR, FM_RATIO, PULSE_DUTY, SAMPLE_RATE, SUB_LEVEL, SUB_WAVE,
+    WAVE_CROSSFADE_S,
+};
 use crate::patch::Sample;
+use crate::patch::oscilators::fm::Fm;
+use crate::patch::oscilators::wavetable::Wavetable;
 use crate::patch::shared::Shared;
 use rodio::Source;
 use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+use serde::{Deserialize, Serialize};
 use std::f32::consts::TAU;
 use std::time::Duration;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 #[repr(u32)]
 pub enum Wave {
     Sine = 0,
@@ -16,6 +23,11 @@ pub enum Wave {
     Square = 2,
     Triangle = 3,
     Noise = 4,
+    Pulse = 5,
+    PinkNoise = 6,
+    BrownNoise = 7,
+    /// Reads the oscillator's `table`
+    Wavetable = 8,
 }
 
 impl Wave {
@@ -25,9 +37,13 @@ impl Wave {
         match self {
             Self::Sine => Self::Saw,
             Self::Saw => Self::Square,
-            Self::Square => Self::Triangle,
+            Self::Square => Self::Pulse,
+            Self::Pulse => Self::Triangle,
             Self::Triangle => Self::Noise,
-            Self::Noise => Self::Sine,
+            Self::Noise => Self::PinkNoise,
+            Self::PinkNoise => Self::BrownNoise,
+            Self::BrownNoise => Self::Wavetable,
+            Self::Wavetable => Self::Sine,
         }
     }
 
@@ -40,6 +56,23 @@ impl Wave {
             Self::Square => "Square",
             Self::Triangle => "Triangle",
             Self::Noise => "Noise",
+            Self::Pulse => "Pulse",
+            Self::PinkNoise => "Pink Noise",
+            Self::BrownNoise => "Brown Noise",
+            Self::Wavetable => "Table",
+        }
+    }
+
+    /// Name as shown, a pulse with its duty like "Pulse 25%"
+    #[must_use]
+    pub fn label(&self, duty: f32) -> String {
+        match self {
+            Self::Pulse => format!(
+                "{} {:.0}%",
+                self.name(),
+                duty.clamp(DUTY_MIN, DUTY_MAX) * 100.0
+            ),
+            _ => self.name().to_string(),
         }
     }
 }
@@ -54,6 +87,10 @@ impl TryFrom<u32> for Wave {
             2 => Ok(Self::Square),
             3 => Ok(Self::Triangle),
             4 => Ok(Self::Noise),
+            5 => Ok(Self::Pulse),
+            6 => Ok(Self::PinkNoise),
+            7 => Ok(Self::BrownNoise),
+            8 => Ok(Self::Wavetable),
             _ => Err("invalid wave id"),
         }
     }
@@ -68,11 +105,66 @@ impl FromSql for Wave {
     }
 }
 
+/// Shape of the sub-oscillator, kept to the two that stay clean an octave down
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubWave {
+    Sine,
+    Square,
+}
+
+impl SubWave {
+    pub const ALL: [Self; 2] = [Self::Sine, Self::Square];
+
+    #[inline]
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sine => "Sine",
+            Self::Square => "Square",
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn wave(self) -> Wave {
+        match self {
+            Self::Sine => Wave::Sine,
+            Self::Square => Wave::Square,
+        }
+    }
+}
+
+/// Extra oscillator one octave under the played note, `level` 0 leaves it out
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Sub {
+    pub wave: SubWave,
+    pub level: f32,
+}
+
+impl Sub {
+    #[inline]
+    #[must_use]
+    pub fn new(wave: SubWave, level: f32) -> Self {
+        Self {
+            wave,
+            level: level.clamp(0.0, 1.0),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Osc {
     pub wave: Wave,
     pub amplitude: f32,
     pub sample_rate: u32,
+    pub crossfade_s: f32,
+    /// Fraction of the period spent high for `Wave::Pulse`
+    pub duty: f32,
+    pub sub: Sub,
+    /// Single cycle played by `Wave::Wavetable`
+    pub table: Wavetable,
+    pub fm: Fm,
 }
 
 impl Default for Osc {
@@ -81,17 +173,31 @@ impl Default for Osc {
             wave: Wave::Sine,
             amplitude: AMP_DEFAULT,
             sample_rate: SAMPLE_RATE,
+            crossfade_s: WAVE_CROSSFADE_S,
+            duty: PULSE_DUTY,
+            sub: Sub::new(SUB_WAVE, SUB_LEVEL),
+            table: Wavetable::default(),
+            fm: Fm::new(FM_MODULATOR, FM_RATIO, FM_INDEX),
         }
     }
 }
 
 pub type OscHandle = Shared<Osc>;
 
+// bring pink and brown noise to the RMS of uniform white noise (~0.58)
+const PINK_LEVEL: f32 = 0.33;
+const BROWN_LEVEL: f32 = 10.0;
+
+pub const DUTY_MIN: f32 = 0.05;
+pub const DUTY_MAX: f32 = 0.95;
+
 #[inline]
 #[must_use]
-pub fn make_osc(wave: Wave) -> OscHandle {
+pub fn make_osc(wave: Wave, crossfade_s: f32, duty: f32) -> OscHandle {
     Shared::new(Osc {
         wave,
+        crossfade_s: crossfade_s.max(0.0),
+        duty: duty.clamp(DUTY_MIN, DUTY_MAX),
         ..Osc::default()
     })
 }
@@ -102,31 +208,62 @@ pub fn osc_source(frequency: f32, osc: OscHandle) -> OscSource {
     OscSource::new(frequency, osc)
 }
 
+/// Renders the live wave; when it changes mid-note the previous wave fades out
+/// while the new one fades in over `crossfade_s`, sharing the same phase
 pub struct OscSource {
     osc: OscHandle,
     frequency: f32,
     phase: f32,
+    /// Added to the phase when shaping, in cycles, for phase modulation
+    phase_offset: f32,
     rng: u64,
+    wave: Wave,
+    fading_from: Option<Wave>,
+    fade: f32,
+    pink: [f32; 7],
+    brown: f32,
 }
 
 impl OscSource {
     #[must_use]
     pub fn new(frequency: f32, osc: OscHandle) -> Self {
+        let wave = osc.get().wave;
+
         Self {
             osc,
             frequency: frequency.max(0.0),
             phase: 0.0,
+            phase_offset: 0.0,
             rng: 0x1234_5678_9ABC_DEF0,
+            wave,
+            fading_from: None,
+            fade: 0.0,
+            pink: [0.0; 7],
+            brown: 0.0,
         }
     }
 
+    #[inline]
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.frequency = frequency.max(0.0);
+    }
+
+    #[inline]
+    pub fn set_phase_offset(&mut self, offset: f32) {
+        self.phase_offset = offset;
+    }
+
     #[inline]
     fn sample_rate_live(&self) -> u32 {
         self.osc.get().sample_rate.max(1)
     }
 
     fn step_phase(&mut self) -> f32 {
-        let p = self.phase;
+        let p = if self.phase_offset == 0.0 {
+            self.phase
+        } else {
+            (self.phase + self.phase_offset).rem_euclid(1.0)
+        };
         self.phase += self.frequency / self.sample_rate_live() as f32;
 
         if self.phase >= 1.0 {
@@ -149,35 +286,96 @@ impl OscSource {
 
         2.0 * f - 1.0
     }
-}
 
-impl Iterator for OscSource {
-    type Item = Sample;
+    /// Paul Kellet's filtered white noise, -3 dB/octave
+    fn next_pink(&mut self) -> f32 {
+        let white = self.next_noise();
+        let b = &mut self.pink;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let osc = self.osc.get();
-        let amp = osc.amplitude.max(0.0);
+        b[0] = 0.998_86 * b[0] + white * 0.055_517_9;
+        b[1] = 0.993_32 * b[1] + white * 0.075_075_9;
+        b[2] = 0.969_00 * b[2] + white * 0.153_852;
+        b[3] = 0.866_50 * b[3] + white * 0.310_485_6;
+        b[4] = 0.550_00 * b[4] + white * 0.532_952_2;
+        b[5] = -0.761_6 * b[5] - white * 0.016_898;
+        let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.536_2;
+        b[6] = white * 0.115_926;
+
+        (pink * PINK_LEVEL).clamp(-1.0, 1.0)
+    }
 
-        let y = match osc.wave {
-            Wave::Sine => (TAU * self.step_phase()).sin(),
+    /// Leaky integral of white noise, -6 dB/octave
+    fn next_brown(&mut self) -> f32 {
+        let white = self.next_noise();
+        self.brown = (self.brown + 0.02 * white) / 1.02;
+
+        (self.brown * BROWN_LEVEL).clamp(-1.0, 1.0)
+    }
+
+    fn shape(&mut self, wave: &Wave, p: f32, osc: &Osc) -> f32 {
+        match wave {
+            Wave::Sine => (TAU * p).sin(),
+            Wave::Pulse => {
+                if p < osc.duty.clamp(DUTY_MIN, DUTY_MAX) {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
             Wave::Square => {
-                if self.step_phase() < 0.5 {
+                if p < 0.5 {
                     1.0
                 } else {
                     -1.0
                 }
             }
             Wave::Triangle => {
-                let p = self.step_phase();
                 if p < 0.5 {
                     -1.0 + 4.0 * p
                 } else {
                     3.0 - 4.0 * p
                 }
             }
-            Wave::Saw => 2.0 * self.step_phase() - 1.0,
+            Wave::Saw => 2.0 * p - 1.0,
             Wave::Noise => self.next_noise(),
-        };
+            Wave::PinkNoise => self.next_pink(),
+            Wave::BrownNoise => self.next_brown(),
+            Wave::Wavetable => osc.table.sample(p),
+        }
+    }
+}
+
+impl Iterator for OscSource {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let osc = self.osc.get();
+        let amp = osc.amplitude.max(0.0);
+
+        let fade_samples = osc.crossfade_s.max(0.0) * osc.sample_rate.max(1) as f32;
+
+        if osc.wave != self.wave {
+            let prev = std::mem::replace(&mut self.wave, osc.wave.clone());
+
+            if fade_samples >= 1.0 {
+                self.fading_from = Some(prev);
+                self.fade = 0.0;
+            }
+        }
+
+        let p = self.step_phase();
+        let wave = self.wave.clone();
+        let mut y = self.shape(&wave, p, &osc);
+
+        if let Some(prev) = self.fading_from.clone() {
+            let old = self.shape(&prev, p, &osc);
+            y = old * (1.0 - self.fade) + y * self.fade;
+
+            self.fade += 1.0 / fade_samples.max(1.0);
+            if self.fade >= 1.0 {
+                self.fading_from = None;
+            }
+        }
 
         Some(y * amp)
     }
@@ -200,3 +398,141 @@ impl Source for OscSource {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FREQ: f32 = 440.0;
+
+    /// `Wave::Wavetable` plays a sine table, so it has one crossing a cycle
+    fn osc(wave: Wave, crossfade_s: f32) -> OscHandle {
+        Shared::new(Osc {
+            wave,
+            amplitude: 1.0,
+            crossfade_s,
+            table: Wavetable::from_harmonics("Sine", &[1.0]).unwrap(),
+            ..Osc::default()
+        })
+    }
+
+    fn render(wave: Wave, skip: usize, n: usize) -> Vec<f32> {
+        OscSource::new(FREQ, osc(wave, 0.0))
+            .skip(skip)
+            .take(n)
+            .collect()
+    }
+
+    #[test]
+    fn pulse_label_shows_the_duty() {
+        assert_eq!(Wave::Pulse.label(0.25), "Pulse 25%");
+        assert_eq!(Wave::Pulse.label(0.0), "Pulse 5%");
+        assert_eq!(Wave::Saw.label(0.25), "Saw");
+
+        let mut patch = osc(Wave::Pulse, 0.0).get();
+        patch.duty = 0.25;
+        assert_eq!(patch.wave.label(patch.duty), "Pulse 25%");
+    }
+
+    #[test]
+    fn crossfade_blends_old_and_new_over_the_fade() {
+        let fade = 100;
+        let handle = osc(Wave::Sine, fade as f32 / SAMPLE_RATE as f32);
+        let mut source = OscSource::new(FREQ, handle.clone());
+
+        let before = 37;
+        for _ in 0..before {
+            source.next();
+        }
+        handle.update(|osc| osc.wave = Wave::Square);
+        let out: Vec<f32> = source.by_ref().take(fade + 10).collect();
+
+        let sine = render(Wave::Sine, before, fade);
+        let square = render(Wave::Square, before, fade + 10);
+        for i in 0..fade {
+            let w = i as f32 / fade as f32;
+            let expected = sine[i] * (1.0 - w) + square[i] * w;
+            assert!(
+                (out[i] - expected).abs() < 1e-4,
+                "sample {i}: {} != {expected}",
+                out[i]
+            );
+        }
+        // past the fade only the new wave is left
+        for i in fade..fade + 10 {
+            assert!((out[i] - square[i]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn zero_crossfade_switches_at_once() {
+        let handle = osc(Wave::Sine, 0.0);
+        let mut source = OscSource::new(FREQ, handle.clone());
+        source.next();
+        handle.update(|osc| osc.wave = Wave::Saw);
+
+        let saw = render(Wave::Saw, 1, 4);
+        let out: Vec<f32> = source.take(4).collect();
+        assert_eq!(out, saw);
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn coloured_noise_matches_the_white_level() {
+        let seconds = SAMPLE_RATE as usize * 4;
+        let white = rms(&render(Wave::Noise, 0, seconds));
+        assert!((white - 0.577).abs() < 0.01, "uniform white noise {white}");
+
+        for wave in [Wave::PinkNoise, Wave::BrownNoise] {
+            let level = rms(&render(wave.clone(), SAMPLE_RATE as usize, seconds));
+            let db = 20.0 * (level / white).log10();
+            assert!(db.abs() < 3.0, "{} is {db:.1} dB from white", wave.name());
+        }
+    }
+
+    #[test]
+    fn periodic_waves_stay_in_range_at_their_pitch() {
+        for wave in [
+            Wave::Sine,
+            Wave::Square,
+            Wave::Pulse,
+            Wave::Triangle,
+            Wave::Saw,
+            Wave::Wavetable,
+        ] {
+            let out = render(wave.clone(), 0, SAMPLE_RATE as usize);
+            assert!(
+                out.iter().all(|s| (-1.0..=1.0).contains(s)),
+                "{} leaves -1..1",
+                wave.name()
+            );
+            assert!(out.iter().any(|s| *s > 0.9) && out.iter().any(|s| *s < -0.9));
+
+            // one upward crossing of the midpoint per cycle, a second's worth
+            let cycles = out
+                .windows(2)
+                .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+                .count();
+            assert!(
+                cycles.abs_diff(FREQ as usize) <= 1,
+                "{}: {cycles} cycles",
+                wave.name()
+            );
+        }
+    }
+
+    #[test]
+    fn noise_stays_in_range() {
+        for wave in [Wave::Noise, Wave::PinkNoise, Wave::BrownNoise] {
+            let out = render(wave.clone(), 0, SAMPLE_RATE as usize);
+            assert!(
+                out.iter().all(|s| (-1.0..=1.0).contains(s)),
+                "{}",
+                wave.name()
+            );
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Two-operator FM, a modulator oscillator bends the phase of the carrier
+
+use crate::patch::Sample;
+use crate::patch::oscilators::basic::{Osc, OscHandle, OscSource, Wave};
+use crate::patch::shared::Shared;
+use rodio::Source;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+/// `ratio` sets the modulator frequency against the carrier, `index` is the
+/// peak phase deviation in radians. Index 0 leaves the carrier untouched
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fm {
+    pub modulator: Wave,
+    pub ratio: f32,
+    pub index: f32,
+}
+
+impl Fm {
+    #[inline]
+    #[must_use]
+    pub fn new(modulator: Wave, ratio: f32, index: f32) -> Self {
+        Self {
+            modulator,
+            ratio: ratio.max(0.0),
+            index: index.max(0.0),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn enabled(&self) -> bool {
+        self.index > 0.0
+    }
+}
+
+/// Carrier from the patch oscillator, phase modulated by a full-scale
+/// modulator at `ratio` times its frequency. The modulator has its own
+/// oscillator so it never picks up the patch level
+pub struct FmSource {
+    carrier: OscSource,
+    modulator: OscSource,
+    modulator_osc: OscHandle,
+    fm: Fm,
+    frequency: f32,
+}
+
+impl FmSource {
+    #[must_use]
+    pub fn new(frequency: f32, osc: OscHandle) -> Self {
+        let main = osc.get();
+        let modulator_osc = Shared::new(Osc {
+            wave: main.fm.modulator.clone(),
+            amplitude: 1.0,
+            ..main.clone()
+        });
+
+        Self {
+            carrier: OscSource::new(frequency, osc),
+            modulator: OscSource::new(frequency * main.fm.ratio, modulator_osc.clone()),
+            modulator_osc,
+            fm: main.fm,
+            frequency,
+        }
+    }
+
+    #[inline]
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.frequency = frequency;
+        self.carrier.set_frequency(frequency);
+        self.modulator.set_frequency(frequency * self.fm.ratio);
+    }
+
+    /// Picks up new FM settings, the carrier keeps its own
+    pub fn set_fm(&mut self, fm: &Fm, main: &Osc) {
+        if !fm.enabled() {
+            self.carrier.set_phase_offset(0.0);
+        }
+
+        self.modulator_osc.set(Osc {
+            wave: fm.modulator.clone(),
+            amplitude: 1.0,
+            ..main.clone()
+        });
+        self.fm = fm.clone();
+        self.set_frequency(self.frequency);
+    }
+}
+
+impl Iterator for FmSource {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.fm.enabled() {
+            let m = self.modulator.next().unwrap_or(0.0);
+            self.carrier.set_phase_offset(self.fm.index * m / TAU);
+        }
+
+        self.carrier.next()
+    }
+}
+
+impl Source for FmSource {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.carrier.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SAMPLE_RATE;
+    use crate::patch::oscilators::basic::Sub;
+    use crate::play::spectrum::level;
+
+    fn osc(wave: Wave, fm: Fm) -> OscHandle {
+        Shared::new(Osc {
+            wave,
+            amplitude: 1.0,
+            crossfade_s: 0.0,
+            sub: Sub::new(Osc::default().sub.wave, 0.0),
+            fm,
+            ..Osc::default()
+        })
+    }
+
+    #[test]
+    fn index_zero_is_the_pure_carrier() {
+        for wave in [Wave::Sine, Wave::Saw, Wave::Square, Wave::Triangle] {
+            let fm = Fm::new(Wave::Sine, 3.0, 0.0);
+            let out: Vec<f32> = FmSource::new(220.0, osc(wave.clone(), fm.clone()))
+                .take(4096)
+                .collect();
+            let carrier: Vec<f32> = OscSource::new(220.0, osc(wave.clone(), fm))
+                .take(4096)
+                .collect();
+
+            assert_eq!(out, carrier, "{wave:?}");
+        }
+    }
+
+    #[test]
+    fn index_adds_sidebands_at_the_modulator_spacing() {
+        // a 110 Hz modulator on a 440 Hz carrier
+        let fm = Fm::new(Wave::Sine, 0.25, 1.0);
+        let out: Vec<f32> = FmSource::new(440.0, osc(Wave::Sine, fm))
+            .take(SAMPLE_RATE as usize)
+            .collect();
+
+        // index 1 leaves J0(1) on the carrier, J1(1) on the first sidebands
+        // and J2(1) on the second
+        assert!((level(&out, 440.0) - 0.765).abs() < 0.01);
+        assert!((level(&out, 330.0) - 0.440).abs() < 0.01);
+        assert!((level(&out, 550.0) - 0.440).abs() < 0.01);
+        assert!((level(&out, 660.0) - 0.115).abs() < 0.01);
+        assert!(level(&out, 495.0) < 0.01, "nothing between the sidebands");
+    }
+
+    #[test]
+    fn turning_the_index_off_restores_the_carrier() {
+        let main = osc(Wave::Sine, Fm::new(Wave::Sine, 2.0, 5.0));
+        let mut source = FmSource::new(440.0, main.clone());
+        source.by_ref().take(1000).for_each(drop);
+
+        let off = Fm::new(Wave::Sine, 2.0, 0.0);
+        source.set_fm(&off, &main.get());
+        let out: Vec<f32> = source.take(SAMPLE_RATE as usize).collect();
+
+        assert!((level(&out, 440.0) - 1.0).abs() < 0.01);
+        assert!(level(&out, 1320.0) < 0.01);
+    }
+}
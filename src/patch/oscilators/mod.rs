@@ -1 +1,4 @@
 pub mod basic;
+pub mod fm;
+pub mod stack;
+pub mod wavetable;
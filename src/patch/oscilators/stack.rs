@@ -0,0 +1,337 @@
+//! Layers extra detuned oscillators on top of the patch oscillator
+
+use crate::config::LAYERS_MAX;
+use crate::patch::bend::Bend;
+use crate::patch::lfo::Modulation;
+use crate::patch::oscilators::basic::{Osc, OscHandle, OscSource, Wave};
+use crate::patch::oscilators::fm::FmSource;
+use crate::patch::shared::Shared;
+use crate::patch::slew::Slew;
+use crate::patch::{Pitch, PitchTarget, Sample};
+
+// layer edits are picked up at control rate rather than every sample
+const CONTROL_SAMPLES: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub wave: Wave,
+    pub cents: f32,
+    pub level: f32,
+}
+
+pub type LayersHandle = Shared<Vec<Layer>>;
+
+#[inline]
+#[must_use]
+pub fn make_layers(layers: &[Layer]) -> LayersHandle {
+    Shared::new(clamp_layers(layers))
+}
+
+#[must_use]
+pub fn clamp_layers(layers: &[Layer]) -> Vec<Layer> {
+    layers
+        .iter()
+        .take(LAYERS_MAX)
+        .map(|layer| Layer {
+            wave: layer.wave.clone(),
+            cents: layer.cents.clamp(-1200.0, 1200.0),
+            level: layer.level.clamp(0.0, 1.0),
+        })
+        .collect()
+}
+
+/// `ratio` scales the shared pitch, e.g. for a detuned unison copy
+#[inline]
+#[must_use]
+pub fn stack_source(
+    pitch: Pitch,
+    ratio: f32,
+    osc: OscHandle,
+    layers: LayersHandle,
+    modulation: Modulation,
+    bend: Bend,
+) -> StackSource {
+    StackSource::new(pitch, ratio, osc, layers, modulation, bend)
+}
+
+struct LayerVoice {
+    osc: OscHandle,
+    source: OscSource,
+    /// Detune against the main oscillator
+    ratio: f32,
+    level: f32,
+}
+
+/// Sums the patch oscillator with every layer and the sub an octave below,
+/// each gets its own oscillator that follows the main one's duty and
+/// crossfade, and the mix is scaled by the total level so stacking never
+/// clips harder than one voice.
+/// Pitch targets are picked up at control rate along with the layers, the
+/// glide towards them runs per sample in octaves so every interval slides
+/// at an even musical speed. LFO vibrato and pitch bend are applied on top
+/// of the glide. The patch oscillator alone carries the FM
+pub struct StackSource {
+    main: FmSource,
+    osc: OscHandle,
+    layers: LayersHandle,
+    pitch: Pitch,
+    target: PitchTarget,
+    glide: Slew,
+    modulation: Modulation,
+    bend: Bend,
+    sample_rate: u32,
+    ratio: f32,
+    frequency: f32,
+    voices: Vec<LayerVoice>,
+    sub: LayerVoice,
+    gain: f32,
+    countdown: usize,
+}
+
+impl StackSource {
+    #[must_use]
+    pub fn new(
+        pitch: Pitch,
+        ratio: f32,
+        osc: OscHandle,
+        layers: LayersHandle,
+        modulation: Modulation,
+        bend: Bend,
+    ) -> Self {
+        let target = pitch.get();
+        let frequency = target.hz * ratio;
+        let sample_rate = osc.get().sample_rate;
+
+        let mut glide = Slew::new();
+        glide.next(target.hz.max(1.0).log2(), 0.0, sample_rate);
+
+        let sub_osc = Shared::new(osc.get());
+        let mut stack = Self {
+            main: FmSource::new(frequency, osc.clone()),
+            sub: LayerVoice {
+                source: OscSource::new(frequency * 0.5, sub_osc.clone()),
+                osc: sub_osc,
+                ratio: 0.5,
+                level: 0.0,
+            },
+            osc,
+            layers,
+            pitch,
+            target,
+            glide,
+            modulation,
+            bend,
+            sample_rate,
+            ratio,
+            frequency,
+            voices: Vec::new(),
+            gain: 1.0,
+            countdown: CONTROL_SAMPLES,
+        };
+        stack.sync_layers();
+        stack
+    }
+
+    fn sync_layers(&mut self) {
+        let layers = self.layers.get();
+        let main = self.osc.get();
+
+        self.voices.truncate(layers.len());
+
+        for (i, layer) in layers.iter().enumerate() {
+            let ratio = (layer.cents / 1200.0).exp2();
+            let frequency = self.frequency * ratio;
+
+            if let Some(voice) = self.voices.get_mut(i) {
+                voice.source.set_frequency(frequency);
+                voice.ratio = ratio;
+                voice.level = layer.level;
+            } else {
+                let osc = Shared::new(main.clone());
+                self.voices.push(LayerVoice {
+                    source: OscSource::new(frequency, osc.clone()),
+                    osc,
+                    ratio,
+                    level: layer.level,
+                });
+            }
+
+            self.voices[i].osc.set(Osc {
+                wave: layer.wave.clone(),
+                ..main.clone()
+            });
+        }
+
+        self.main.set_fm(&main.fm, &main);
+        self.sub.level = main.sub.level;
+        self.sub.osc.set(Osc {
+            wave: main.sub.wave.wave(),
+            ..main.clone()
+        });
+
+        let layers: f32 = self.voices.iter().map(|voice| voice.level).sum();
+        let total = 1.0 + layers + self.sub.level;
+        self.gain = 1.0 / total;
+        self.sample_rate = main.sample_rate;
+    }
+
+    /// Moves every oscillator one sample further along the glide
+    fn step_glide(&mut self) {
+        let octaves = self.target.hz.max(1.0).log2();
+        let frequency = self
+            .glide
+            .next(octaves, self.target.glide_s, self.sample_rate)
+            .exp2()
+            * self.ratio
+            * self.modulation.pitch_ratio()
+            * self.bend.ratio();
+
+        if frequency == self.frequency {
+            return;
+        }
+
+        self.frequency = frequency;
+        self.main.set_frequency(frequency);
+        for voice in self.voices.iter_mut().chain([&mut self.sub]) {
+            voice.source.set_frequency(frequency * voice.ratio);
+        }
+    }
+}
+
+impl Iterator for StackSource {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.countdown -= 1;
+        if self.countdown == 0 {
+            self.target = self.pitch.get();
+            self.sync_layers();
+            self.countdown = CONTROL_SAMPLES;
+        }
+        self.step_glide();
+
+        let mut y = self.main.next()?;
+
+        if self.voices.is_empty() && self.sub.level == 0.0 {
+            return Some(y);
+        }
+
+        for voice in self.voices.iter_mut().chain([&mut self.sub]) {
+            y += voice.source.next().unwrap_or(0.0) * voice.level;
+        }
+
+        Some(y * self.gain)
+    }
+}
+
+crate::impl_source_passthrough!(StackSource, main);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SAMPLE_RATE;
+    use crate::patch::lfo::{Lfo, LfoTarget};
+    use crate::patch::oscilators::basic::{Sub, SubWave};
+    use crate::play::spectrum::level;
+
+    fn stack(hz: f32, layers: &[Layer]) -> StackSource {
+        let osc = Shared::new(Osc {
+            wave: Wave::Sine,
+            amplitude: 1.0,
+            crossfade_s: 0.0,
+            ..Osc::default()
+        });
+        let modulation = Modulation::new(&Lfo {
+            wave: Wave::Sine,
+            rate_hz: 1.0,
+            depth: 0.0,
+            target: LfoTarget::Off,
+        });
+        let pitch = Shared::new(PitchTarget { hz, glide_s: 0.0 });
+
+        stack_source(
+            pitch,
+            1.0,
+            osc,
+            make_layers(layers),
+            modulation,
+            Bend::new(2.0),
+        )
+    }
+
+    #[test]
+    fn detuned_sines_beat_at_their_difference() {
+        // 440 Hz against 444 Hz beats 4 times a second
+        let cents = 1200.0 * (444.0_f32 / 440.0).log2();
+        let layer = Layer {
+            wave: Wave::Sine,
+            cents,
+            level: 1.0,
+        };
+        let seconds = 2;
+        let out: Vec<f32> = stack(440.0, &[layer])
+            .take(SAMPLE_RATE as usize * seconds)
+            .collect();
+
+        // peak of each 5 ms, a few cycles of 440 Hz and a sliver of a beat
+        let window = SAMPLE_RATE as usize / 200;
+        let envelope: Vec<f32> = out
+            .chunks(window)
+            .map(|chunk| chunk.iter().fold(0.0_f32, |peak, s| peak.max(s.abs())))
+            .collect();
+        assert!(
+            envelope.iter().all(|peak| *peak <= 1.0 + 1e-4),
+            "the sum is normalised"
+        );
+
+        let dips = envelope
+            .windows(2)
+            .filter(|pair| pair[0] >= 0.5 && pair[1] < 0.5)
+            .count();
+        assert_eq!(dips, 4 * seconds, "one dip per beat");
+    }
+
+    #[test]
+    fn bend_retunes_a_sounding_voice() {
+        let bend = Bend::new(12.0);
+        let mut source = stack(440.0, &[]);
+        source.bend = bend.clone();
+
+        let crossings = |source: &mut StackSource| {
+            let out: Vec<f32> = source.take(SAMPLE_RATE as usize).collect();
+            out.windows(2)
+                .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+                .count()
+        };
+
+        assert!(crossings(&mut source).abs_diff(440) <= 1);
+        bend.set_amount(1.0);
+        assert!(crossings(&mut source).abs_diff(880) <= 1, "an octave up");
+    }
+
+    #[test]
+    fn sub_adds_the_octave_below_and_keeps_the_level() {
+        let source = stack(440.0, &[]);
+        source
+            .osc
+            .update(|osc| osc.sub = Sub::new(SubWave::Sine, 1.0));
+
+        // past the first control update, which picks the sub up
+        let out: Vec<f32> = source
+            .skip(CONTROL_SAMPLES)
+            .take(SAMPLE_RATE as usize)
+            .collect();
+
+        assert!(
+            (level(&out, 440.0) - 0.5).abs() < 0.01,
+            "{}",
+            level(&out, 440.0)
+        );
+        assert!(
+            (level(&out, 220.0) - 0.5).abs() < 0.01,
+            "{}",
+            level(&out, 220.0)
+        );
+        assert!(out.iter().all(|s| s.abs() <= 1.0));
+    }
+}
@@ -0,0 +1,351 @@
+//! Single-cycle wavetables, read with linear interpolation at any frequency
+
+use crate::config::{WAVETABLE_MAX_FILE_BYTES, WAVETABLE_MAX_SIZE, WAVETABLE_SIZE};
+use std::f32::consts::TAU;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+/// One period of a waveform, normalised to a peak of 1. Cloning shares the
+/// samples
+#[derive(Debug, Clone)]
+pub struct Wavetable {
+    name: Arc<str>,
+    samples: Arc<[f32]>,
+}
+
+impl Wavetable {
+    /// `None` for an empty or silent table
+    #[must_use]
+    pub fn new(name: &str, samples: Vec<f32>) -> Option<Self> {
+        let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        if !peak.is_normal() {
+            return None;
+        }
+
+        Some(Self {
+            name: name.into(),
+            samples: samples.into_iter().map(|s| s / peak).collect(),
+        })
+    }
+
+    /// A sum of sines, `harmonics` holds their amplitudes from the
+    /// fundamental up. `None` if they are all zero
+    #[must_use]
+    pub fn from_harmonics(name: &str, harmonics: &[f32]) -> Option<Self> {
+        Self::new(name, additive(harmonics))
+    }
+
+    /// Reads a WAV holding one cycle and resamples it to a power-of-two
+    /// table of at most `WAVETABLE_MAX_SIZE`, named after the file. The
+    /// whole file is taken as the cycle so its sample rate does not matter,
+    /// stereo and wider are mixed down to mono
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = fs::metadata(path)?.len();
+        if bytes > WAVETABLE_MAX_FILE_BYTES {
+            return Err(invalid(format!(
+                "{bytes} bytes is too long for a single cycle (at most {WAVETABLE_MAX_FILE_BYTES})"
+            )));
+        }
+
+        let cycle = read_wav(&fs::read(path)?)?;
+        if cycle.len() < 2 {
+            return Err(invalid("needs at least two samples"));
+        }
+
+        let size = cycle.len().next_power_of_two().min(WAVETABLE_MAX_SIZE);
+        let len = cycle.len() as f32;
+        let samples = (0..size)
+            .map(|i| {
+                let pos = i as f32 / size as f32 * len;
+                let j = pos as usize;
+                let frac = pos - j as f32;
+                cycle[j] + (cycle[(j + 1) % cycle.len()] - cycle[j]) * frac
+            })
+            .collect();
+
+        let name = path
+            .file_stem()
+            .map_or_else(|| "WAV".into(), |stem| stem.to_string_lossy());
+        Self::new(&name, samples).ok_or_else(|| invalid("the file is silent"))
+    }
+
+    /// The tables that ship with the synth, the first one is the default
+    #[must_use]
+    pub fn builtins() -> &'static [Self] {
+        BUILTINS.get_or_init(render_builtins)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Value at `phase` (0..1 of the period) between the two nearest samples
+    #[inline]
+    #[must_use]
+    pub fn sample(&self, phase: f32) -> f32 {
+        let len = self.samples.len();
+        let pos = phase.rem_euclid(1.0) * len as f32;
+        let i = (pos as usize).min(len - 1);
+        let frac = pos - i as f32;
+
+        let a = self.samples[i];
+        let b = self.samples[(i + 1) % len];
+        a + (b - a) * frac
+    }
+}
+
+impl Default for Wavetable {
+    fn default() -> Self {
+        Self::builtins()[0].clone()
+    }
+}
+
+static BUILTINS: OnceLock<Vec<Wavetable>> = OnceLock::new();
+
+#[must_use]
+fn render_builtins() -> Vec<Wavetable> {
+    // harmonic amplitudes from the fundamental up
+    let tables: [(&str, &[f32]); 4] = [
+        ("Organ", &[1.0, 0.8, 0.6, 0.5, 0.0, 0.4, 0.0, 0.3]),
+        ("Hollow", &[1.0, 0.0, 0.33, 0.0, 0.2, 0.0, 0.14, 0.0, 0.11]),
+        (
+            "Bright",
+            &[1.0, 0.5, 0.33, 0.25, 0.2, 0.17, 0.14, 0.12, 0.11, 0.1],
+        ),
+        ("Vocal", &[0.3, 0.5, 1.0, 0.8, 0.3, 0.1, 0.2, 0.4, 0.2]),
+    ];
+
+    tables
+        .into_iter()
+        .filter_map(|(name, harmonics)| Wavetable::new(name, additive(harmonics)))
+        .collect()
+}
+
+/// `WAVETABLE_SIZE` samples of a sum of sines, one per harmonic
+#[must_use]
+fn additive(harmonics: &[f32]) -> Vec<f32> {
+    (0..WAVETABLE_SIZE)
+        .map(|i| {
+            let phase = i as f32 / WAVETABLE_SIZE as f32;
+            harmonics
+                .iter()
+                .enumerate()
+                .map(|(n, amp)| amp * (TAU * (n + 1) as f32 * phase).sin())
+                .sum()
+        })
+        .collect()
+}
+
+#[must_use]
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// Samples of a RIFF WAVE file averaged across channels. Takes 8 to 32-bit
+/// integer PCM and 32-bit float, plain or in the extensible format
+fn read_wav(bytes: &[u8]) -> io::Result<Vec<f32>> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(invalid("not a WAV file"));
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut rest = &bytes[12..];
+    while rest.len() >= 8 {
+        let id = &rest[0..4];
+        let len = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        let body = rest.get(8..8 + len).unwrap_or(&rest[8..]);
+
+        match id {
+            b"fmt " => format = Some(body),
+            b"data" => data = Some(body),
+            _ => {}
+        }
+        // chunks are padded to an even length
+        rest = rest.get(8 + len + len % 2..).unwrap_or_default();
+    }
+
+    let (Some(format), Some(data)) = (format, data) else {
+        return Err(invalid("missing the fmt or data chunk"));
+    };
+    if format.len() < 16 {
+        return Err(invalid("fmt chunk too short"));
+    }
+
+    let le16 = |at: usize| u16::from_le_bytes([format[at], format[at + 1]]);
+    let mut tag = le16(0);
+    let channels = usize::from(le16(2));
+    let bits = le16(14);
+    // WAVE_FORMAT_EXTENSIBLE keeps the real tag at the start of its subformat
+    if tag == 0xFFFE && format.len() >= 26 {
+        tag = le16(24);
+    }
+
+    let width = usize::from(bits / 8);
+    let decode: fn(&[u8]) -> f32 = match (tag, bits) {
+        (1, 8) => |b| (f32::from(b[0]) - 128.0) / 128.0,
+        (1, 16) => |b| f32::from(i16::from_le_bytes([b[0], b[1]])) / 32_768.0,
+        (1, 24) => |b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.0,
+        (1, 32) => |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0,
+        (3, 32) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        _ => return Err(invalid(format!("unsupported format {tag} at {bits} bits"))),
+    };
+    if channels == 0 {
+        return Err(invalid("no channels"));
+    }
+
+    Ok(data
+        .chunks_exact(width * channels)
+        .map(|frame| frame.chunks_exact(width).map(decode).sum::<f32>() / channels as f32)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_normalises_the_peak_and_refuses_silence() {
+        let table = Wavetable::new("t", vec![0.0, 0.5, -0.25, 0.0]).unwrap();
+
+        assert_eq!(table.len(), 4);
+        assert!((table.sample(0.25) - 1.0).abs() < 1e-6);
+        assert!((table.sample(0.5) + 0.5).abs() < 1e-6);
+        assert!(Wavetable::new("t", vec![0.0; 8]).is_none());
+        assert!(Wavetable::new("t", Vec::new()).is_none());
+    }
+
+    #[test]
+    fn sample_interpolates_and_wraps_around() {
+        let table = Wavetable::new("t", vec![0.0, 1.0, 0.0, -1.0]).unwrap();
+
+        assert!((table.sample(0.125) - 0.5).abs() < 1e-6);
+        // between the last sample and the first
+        assert!((table.sample(0.875) + 0.5).abs() < 1e-6);
+        assert!((table.sample(1.25) - 1.0).abs() < 1e-6);
+        assert!((table.sample(-0.75) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn from_harmonics_is_a_normalised_sum_of_sines() {
+        let sine = Wavetable::from_harmonics("sine", &[1.0]).unwrap();
+        let second = Wavetable::from_harmonics("second", &[0.0, 1.0]).unwrap();
+
+        assert_eq!(sine.len(), WAVETABLE_SIZE);
+        for i in 0..32 {
+            let phase = i as f32 / 32.0;
+            assert!((sine.sample(phase) - (TAU * phase).sin()).abs() < 1e-3);
+            assert!((second.sample(phase) - (2.0 * TAU * phase).sin()).abs() < 1e-3);
+        }
+        assert!(Wavetable::from_harmonics("none", &[0.0, 0.0]).is_none());
+    }
+
+    /// A RIFF WAVE file with one `fmt ` and one `data` chunk
+    fn wav(tag: u16, channels: u16, bits: u16, data: &[u8]) -> Vec<u8> {
+        let mut fmt = Vec::new();
+        fmt.extend(tag.to_le_bytes());
+        fmt.extend(channels.to_le_bytes());
+        fmt.extend(44_100u32.to_le_bytes());
+        fmt.extend((44_100 * u32::from(channels * bits / 8)).to_le_bytes());
+        fmt.extend((channels * bits / 8).to_le_bytes());
+        fmt.extend(bits.to_le_bytes());
+
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend((4 + 8 + fmt.len() as u32 + 8 + data.len() as u32).to_le_bytes());
+        bytes.extend(b"WAVE");
+        for (id, body) in [(b"fmt ", &fmt[..]), (b"data", data)] {
+            bytes.extend(id);
+            bytes.extend((body.len() as u32).to_le_bytes());
+            bytes.extend(body);
+        }
+        bytes
+    }
+
+    fn pcm16(samples: &[i16]) -> Vec<u8> {
+        samples.iter().flat_map(|s| s.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn read_wav_decodes_pcm_and_float() {
+        let pcm = read_wav(&wav(1, 1, 16, &pcm16(&[0, 16_384, -32_768]))).unwrap();
+        assert_eq!(pcm, [0.0, 0.5, -1.0]);
+
+        let float: Vec<u8> = [0.25f32, -0.75]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        assert_eq!(read_wav(&wav(3, 1, 32, &float)).unwrap(), [0.25, -0.75]);
+
+        assert_eq!(read_wav(&wav(1, 1, 8, &[128, 192])).unwrap(), [0.0, 0.5]);
+    }
+
+    #[test]
+    fn read_wav_mixes_stereo_down_to_mono() {
+        let stereo = pcm16(&[16_384, 0, -16_384, -16_384]);
+
+        assert_eq!(read_wav(&wav(1, 2, 16, &stereo)).unwrap(), [0.25, -0.5]);
+    }
+
+    #[test]
+    fn read_wav_refuses_what_it_cannot_read() {
+        assert!(read_wav(b"not a wav at all").is_err());
+        assert!(read_wav(&wav(1, 1, 12, &[0; 6])).is_err());
+        assert!(read_wav(&wav(1, 0, 16, &[0; 4])).is_err());
+
+        let mut no_data = wav(1, 1, 16, &[]);
+        no_data.truncate(12 + 8 + 16);
+        assert!(read_wav(&no_data).is_err());
+    }
+
+    #[test]
+    fn load_resamples_a_cycle_to_a_power_of_two() {
+        let dir = std::env::temp_dir().join(format!("mugen-wavetable-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Triangle.wav");
+        // three samples of a cycle, stretched over four
+        fs::write(&path, wav(1, 1, 16, &pcm16(&[0, 16_384, -16_384]))).unwrap();
+        let table = Wavetable::load(&path);
+        let silent = dir.join("silent.wav");
+        fs::write(&silent, wav(1, 1, 16, &pcm16(&[0, 0, 0]))).unwrap();
+        let silent = Wavetable::load(&silent);
+        let _ = fs::remove_dir_all(&dir);
+
+        let table = table.unwrap();
+        assert_eq!(table.name(), "Triangle");
+        assert_eq!(table.len(), 4);
+        for (i, want) in [0.0, 1.0, 0.0, -1.0].into_iter().enumerate() {
+            let got = table.sample(i as f32 / 4.0);
+            assert!((got - want).abs() < 1e-4, "{i}: {got}");
+        }
+        assert!(silent.is_err());
+    }
+
+    #[test]
+    fn builtins_are_named_and_peak_at_one() {
+        let builtins = Wavetable::builtins();
+
+        assert_eq!(Wavetable::default().name(), builtins[0].name());
+        for table in builtins {
+            let peak = (0..table.len())
+                .map(|i| table.sample(i as f32 / table.len() as f32).abs())
+                .fold(0.0, f32::max);
+            assert!((peak - 1.0).abs() < 1e-6, "{}: {peak}", table.name());
+        }
+    }
+}
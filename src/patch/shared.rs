@@ -16,7 +16,7 @@ impl<T> Shared<T> {
     }
 
     #[inline]
-    #[must_use] 
+    #[must_use]
     pub fn get(&self) -> T
     where
         T: Clone,
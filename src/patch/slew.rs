@@ -0,0 +1,65 @@
+//! Ramps stepped control values over time to avoid zipper noise
+
+use crate::config::{PARAM_SLEW_MAX_MS, PARAM_SLEW_S};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Slew time for live parameter changes in microseconds, shared by every voice
+static PARAM_SLEW_US: AtomicU32 = AtomicU32::new((PARAM_SLEW_S * 1e6) as u32);
+
+/// Sets how long gain, pan, cutoff and mix changes take to settle
+pub fn set_param_slew(time_s: f32) {
+    let us = (time_s.clamp(0.0, PARAM_SLEW_MAX_MS as f32 / 1000.0) * 1e6).round() as u32;
+    PARAM_SLEW_US.store(us, Ordering::Relaxed);
+}
+
+#[inline]
+#[must_use]
+pub fn param_slew() -> f32 {
+    PARAM_SLEW_US.load(Ordering::Relaxed) as f32 / 1e6
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Slew {
+    value: Option<f32>,
+    target: f32,
+    step: f32,
+}
+
+impl Slew {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves one sample towards `target`, reaching it `time_s` after it last changed
+    pub fn next(&mut self, target: f32, time_s: f32, sample_rate: u32) -> f32 {
+        let Some(value) = self.value else {
+            self.value = Some(target);
+            self.target = target;
+            return target;
+        };
+
+        if (target - self.target).abs() > f32::EPSILON {
+            let samples = (time_s.max(0.0) * sample_rate.max(1) as f32).max(1.0);
+            self.target = target;
+            self.step = (target - value) / samples;
+        }
+
+        let remaining = self.target - value;
+        let value = if remaining.abs() <= self.step.abs() {
+            self.target
+        } else {
+            value + self.step
+        };
+
+        self.value = Some(value);
+        value
+    }
+
+    /// Moves towards a live parameter's `target` over the configured
+    /// parameter slew time
+    #[inline]
+    pub fn param(&mut self, target: f32, sample_rate: u32) -> f32 {
+        self.next(target, param_slew(), sample_rate)
+    }
+}
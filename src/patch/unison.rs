@@ -0,0 +1,184 @@
+//! Spreads one note over several detuned, panned copies
+
+use crate::config::UNISON_MAX;
+use crate::patch::PatchSource;
+
+#[derive(Debug, Clone)]
+pub struct Unison {
+    pub voices: u8,
+    pub detune_cents: f32,
+}
+
+/// Where one unison copy sits relative to the played note
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spread {
+    pub cents: f32,
+    pub pan: f32,
+    pub gain: f32,
+}
+
+impl Spread {
+    pub const CENTER: Self = Self {
+        cents: 0.0,
+        pan: 0.0,
+        gain: 1.0,
+    };
+}
+
+impl Unison {
+    #[inline]
+    #[must_use]
+    pub fn new(voices: u8, detune_cents: f32) -> Self {
+        Self {
+            voices: voices.clamp(1, UNISON_MAX),
+            detune_cents: detune_cents.clamp(0.0, 100.0),
+        }
+    }
+
+    /// Copies spaced evenly from -detune to +detune and hard left to hard
+    /// right, each at 1/sqrt(n) so the summed power stays that of one voice
+    #[must_use]
+    pub fn spreads(&self) -> Vec<Spread> {
+        let n = self.voices.clamp(1, UNISON_MAX);
+        if n == 1 {
+            return vec![Spread::CENTER];
+        }
+
+        let gain = 1.0 / f32::from(n).sqrt();
+        let last = f32::from(n - 1);
+
+        (0..n)
+            .map(|i| {
+                let t = 2.0 * f32::from(i) / last - 1.0;
+                Spread {
+                    cents: t * self.detune_cents,
+                    pan: t,
+                    gain,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Scales one unison copy and leans it toward its side with a balance law,
+/// so a centered copy keeps both channels untouched and stays as loud as a
+/// single voice through the pan stage
+struct SpreadSource {
+    input: PatchSource,
+    spread: Spread,
+    channel: u16,
+}
+
+impl Iterator for SpreadSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.input.next()? * self.spread.gain;
+
+        if self.input.channels() != 2 {
+            return Some(x);
+        }
+
+        let t = self.spread.pan.clamp(-1.0, 1.0);
+        let g = if self.channel == 0 {
+            (1.0 - t).min(1.0)
+        } else {
+            (1.0 + t).min(1.0)
+        };
+        self.channel ^= 1;
+
+        Some(x * g)
+    }
+}
+
+crate::impl_source_passthrough!(SpreadSource, input);
+
+#[inline]
+pub fn spread_source(input: PatchSource, spread: Spread) -> PatchSource {
+    if spread == Spread::CENTER {
+        return input;
+    }
+
+    Box::new(SpreadSource {
+        input,
+        spread,
+        channel: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SAMPLE_RATE;
+    use crate::patch::bend::Bend;
+    use crate::patch::chain::FxChain;
+    use crate::patch::effects::adsr::{Adsr, make_adsr};
+    use crate::patch::lfo::{Lfo, LfoTarget, Modulation};
+    use crate::patch::oscilators::basic::{Osc, Wave};
+    use crate::patch::oscilators::stack::make_layers;
+    use crate::patch::shared::Shared;
+    use crate::patch::{Patch, PitchTarget};
+    use crate::play::spectrum::level;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    fn patch() -> Patch {
+        let osc = Shared::new(Osc {
+            wave: Wave::Sine,
+            amplitude: 1.0,
+            crossfade_s: 0.0,
+            ..Osc::default()
+        });
+        let modulation = Modulation::new(&Lfo {
+            wave: Wave::Sine,
+            rate_hz: 1.0,
+            depth: 0.0,
+            target: LfoTarget::Off,
+        });
+
+        Patch::new(
+            osc,
+            make_layers(&[]),
+            make_adsr(Adsr::new(0.0, 0.0, 1.0, 0.0)),
+            FxChain::default(),
+            FxChain::default(),
+            modulation,
+            Bend::new(2.0),
+        )
+    }
+
+    #[test]
+    fn three_voices_give_three_partials() {
+        let spreads = Unison::new(3, 10.0).spreads();
+        let cents: Vec<f32> = spreads.iter().map(|spread| spread.cents).collect();
+        assert_eq!(cents, [-10.0, 0.0, 10.0]);
+
+        let patch = patch();
+        let mut voices: Vec<PatchSource> = spreads
+            .iter()
+            .map(|spread| {
+                let pitch = Shared::new(PitchTarget {
+                    hz: 440.0,
+                    glide_s: 0.0,
+                });
+                patch.build_voice(pitch, Arc::new(AtomicBool::new(true)), *spread, 1.0)
+            })
+            .collect();
+
+        // a few seconds resolves partials 2.5 Hz apart
+        let len = SAMPLE_RATE as usize * 4;
+        let mix: Vec<f32> = (0..len)
+            .map(|_| voices.iter_mut().filter_map(Iterator::next).sum())
+            .collect();
+
+        let partials: Vec<f32> = cents.iter().map(|c| 440.0 * (c / 1200.0).exp2()).collect();
+        for hz in &partials {
+            let amp = level(&mix, *hz);
+            assert!((amp - 3f32.sqrt().recip()).abs() < 0.05, "{hz} Hz at {amp}");
+        }
+        for pair in partials.windows(2) {
+            let between = level(&mix, (pair[0] + pair[1]) / 2.0);
+            assert!(between < 0.1, "a gap between {pair:?} reads {between}");
+        }
+    }
+}
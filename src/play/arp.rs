@@ -0,0 +1,108 @@
+//! Arpeggiator that steps through the held notes on the shared tempo clock
+
+use crate::config::ARP_STEPS_PER_BEAT;
+use crate::play::tempo::Tempo;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArpPattern {
+    Up,
+    Down,
+    UpDown,
+    Random,
+}
+
+impl ArpPattern {
+    pub const ALL: [Self; 4] = [Self::Up, Self::Down, Self::UpDown, Self::Random];
+
+    #[inline]
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Up => "Up",
+            Self::Down => "Down",
+            Self::UpDown => "UpDown",
+            Self::Random => "Random",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Arp {
+    pub enabled: bool,
+    pub pattern: ArpPattern,
+    /// Fraction of a step each note sounds for
+    pub length: f32,
+}
+
+impl Arp {
+    #[inline]
+    #[must_use]
+    pub fn new(enabled: bool, pattern: ArpPattern, length: f32) -> Self {
+        Self {
+            enabled,
+            pattern,
+            length: length.clamp(0.05, 1.0),
+        }
+    }
+
+    /// How long each note sounds at `tempo`, there are `ARP_STEPS_PER_BEAT`
+    /// steps per beat
+    #[must_use]
+    pub fn note_length(&self, tempo: Tempo) -> Duration {
+        tempo.step(ARP_STEPS_PER_BEAT).mul_f32(self.length)
+    }
+}
+
+/// Picks which held note plays next, the notes are expected low to high
+#[derive(Debug, Clone)]
+pub struct Arpeggiator {
+    count: usize,
+    rng: u64,
+}
+
+impl Arpeggiator {
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        Self {
+            count: 0,
+            // xorshift gets stuck on zero
+            rng: seed.max(1),
+        }
+    }
+
+    /// Index into `len` held notes, `None` and a fresh start when none are held.
+    /// Up-down bounces without repeating the top and bottom notes
+    pub fn next(&mut self, pattern: ArpPattern, len: usize) -> Option<usize> {
+        if len == 0 {
+            self.count = 0;
+            return None;
+        }
+
+        let step = self.count;
+        self.count = self.count.wrapping_add(1);
+
+        let index = match pattern {
+            ArpPattern::Up => step % len,
+            ArpPattern::Down => len - 1 - step % len,
+            ArpPattern::UpDown => {
+                let period = (2 * len - 2).max(1);
+                let pos = step % period;
+                if pos < len { pos } else { period - pos }
+            }
+            ArpPattern::Random => (self.next_random() % len as u64) as usize,
+        };
+
+        Some(index)
+    }
+
+    fn next_random(&mut self) -> u64 {
+        let mut x = self.rng;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rng = x;
+
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 32
+    }
+}
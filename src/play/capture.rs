@@ -0,0 +1,862 @@
+//! Taps the master mix so the UI can draw what is actually playing, and
+//! single voices when the scope is pinned to one
+
+use crate::config::{
+    CALLBACK_GAP_US, CALLBACK_LATE, CALLBACK_TIMER_BLOCK, CAPTURE_DC_BLOCK, CAPTURE_DC_CUTOFF_HZ,
+    CAPTURE_FRAMES, LOAD_SMOOTHING, VOICE_TAP_CHANNELS,
+};
+use crate::patch::PatchSource;
+use crate::play::recorder::RecordTap;
+use rodio::Source;
+use std::f32::consts::TAU;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// One ring per channel of the most recent output frames. Samples are stored
+/// as `f32` bits in atomics so the audio thread writes without ever locking;
+/// a reader racing the writer can at worst see one frame from the next lap
+struct Ring {
+    channels: Vec<Box<[AtomicU32]>>,
+    /// Index of the next frame to be written
+    pos: AtomicUsize,
+    /// Frames written since the ring started
+    written: AtomicU64,
+    /// `written` when the newest note started, `u64::MAX` before any
+    onset: AtomicU64,
+    sample_rate: u32,
+}
+
+impl Ring {
+    fn new(channels: u16, sample_rate: u32, frames: usize) -> Self {
+        Self {
+            channels: (0..channels.max(1))
+                .map(|_| (0..frames).map(|_| AtomicU32::new(0)).collect())
+                .collect(),
+            pos: AtomicUsize::new(0),
+            written: AtomicU64::new(0),
+            onset: AtomicU64::new(u64::MAX),
+            sample_rate,
+        }
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.channels.first().map_or(0, |ch| ch.len())
+    }
+}
+
+/// How hard the output callback is working, written by the tap and read by
+/// the UI without either side locking
+#[derive(Clone, Default)]
+pub struct AudioLoad {
+    /// Time spent filling a callback against the audio it produced, as `f32` bits
+    load: Arc<AtomicU32>,
+    underruns: Arc<AtomicUsize>,
+    callbacks: Arc<AtomicUsize>,
+}
+
+impl AudioLoad {
+    /// Smoothed 0..1 while keeping up, above 1 the output cannot
+    #[must_use]
+    pub fn load(&self) -> f32 {
+        f32::from_bits(self.load.load(Ordering::Relaxed))
+    }
+
+    /// Callbacks that arrived late enough for the device to run dry
+    #[must_use]
+    pub fn underruns(&self) -> usize {
+        self.underruns.load(Ordering::Relaxed)
+    }
+
+    /// Callbacks filled since the stream opened, each one a capture update
+    #[must_use]
+    pub fn callbacks(&self) -> usize {
+        self.callbacks.load(Ordering::Relaxed)
+    }
+}
+
+/// Shared between the tap and the UI, the mutex only guards swapping the ring
+/// when the layout changes, never individual samples
+#[derive(Clone)]
+pub struct Capture {
+    ring: Arc<Mutex<Arc<Ring>>>,
+    dc_block: Arc<AtomicBool>,
+    /// Raised when a note starts, the writer stamps the frame it writes next
+    onset: Arc<AtomicBool>,
+    audio_load: AudioLoad,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Capture {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            ring: Arc::new(Mutex::new(Arc::new(Ring::new(1, 0, 0)))),
+            dc_block: Arc::new(AtomicBool::new(CAPTURE_DC_BLOCK)),
+            onset: Arc::new(AtomicBool::new(false)),
+            audio_load: AudioLoad::default(),
+        }
+    }
+
+    /// Only the tap on the synth output fills this in, an input capture
+    /// stays at zero
+    #[must_use]
+    pub fn audio_load(&self) -> &AudioLoad {
+        &self.audio_load
+    }
+
+    /// Whether the captured samples have their DC offset taken out. Only
+    /// the copy kept for display is filtered, the output and recordings
+    /// never are
+    #[must_use]
+    pub fn dc_block(&self) -> bool {
+        self.dc_block.load(Ordering::Relaxed)
+    }
+
+    pub fn set_dc_block(&self, on: bool) {
+        self.dc_block.store(on, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn ring(&self) -> Arc<Ring> {
+        Arc::clone(&self.ring.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Starts a fresh ring for the output layout and hands it to the writer
+    fn configure(&self, channels: u16, sample_rate: u32) -> Arc<Ring> {
+        let ring = Arc::new(Ring::new(channels, sample_rate, CAPTURE_FRAMES));
+        *self.ring.lock().unwrap_or_else(PoisonError::into_inner) = Arc::clone(&ring);
+        ring
+    }
+
+    #[must_use]
+    pub fn channels(&self) -> u16 {
+        u16::try_from(self.ring().channels.len()).unwrap_or(u16::MAX)
+    }
+
+    /// 0 until the output stream is open
+    #[must_use]
+    pub fn sample_rate(&self) -> u32 {
+        self.ring().sample_rate
+    }
+
+    /// Frames of history kept per channel, `frames` never returns more.
+    /// 0 until the output stream is open
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.ring().capacity()
+    }
+
+    /// Frames written since the newest note started, counting its first.
+    /// `None` before any note, or once it is older than the ring reaches
+    #[must_use]
+    pub fn onset_age(&self) -> Option<usize> {
+        let ring = self.ring();
+        let onset = ring.onset.load(Ordering::Relaxed);
+        if onset == u64::MAX {
+            return None;
+        }
+
+        let age = ring.written.load(Ordering::Acquire).checked_sub(onset)?;
+        usize::try_from(age)
+            .ok()
+            .filter(|&age| age <= ring.capacity())
+    }
+
+    /// The last `frames` frames split per channel, oldest sample first
+    #[must_use]
+    pub fn frames(&self, frames: usize) -> Vec<Vec<f32>> {
+        let ring = self.ring();
+        let cap = ring.capacity();
+        let frames = frames.min(cap);
+
+        if frames == 0 {
+            return vec![Vec::new(); ring.channels.len()];
+        }
+
+        let start = (ring.pos.load(Ordering::Acquire) + cap - frames) % cap;
+
+        ring.channels
+            .iter()
+            .map(|ch| {
+                (0..frames)
+                    .map(|i| f32::from_bits(ch[(start + i) % cap].load(Ordering::Relaxed)))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// One-pole high-pass, strips a constant offset and leaves the audible band
+#[derive(Clone, Copy, Default)]
+struct DcBlocker {
+    x1: f32,
+    y1: f32,
+}
+
+impl DcBlocker {
+    #[inline]
+    fn process(&mut self, x: f32, pole: f32) -> f32 {
+        let y = x - self.x1 + pole * self.y1;
+        self.x1 = x;
+        self.y1 = y;
+        y
+    }
+}
+
+#[inline]
+fn dc_pole(sample_rate: u32) -> f32 {
+    (-TAU * CAPTURE_DC_CUTOFF_HZ / sample_rate.max(1) as f32).exp()
+}
+
+/// Writes interleaved samples into a capture one frame at a time. The ring
+/// follows the layout the samples arrive in
+pub(crate) struct CaptureWriter {
+    capture: Capture,
+    ring: Arc<Ring>,
+    /// One per channel, they run even while blocking is off so turning it
+    /// back on does not start with a step
+    dc: Vec<DcBlocker>,
+    dc_pole: f32,
+    /// Channel the next sample belongs to, 0 on a frame boundary
+    channel: usize,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl CaptureWriter {
+    #[must_use]
+    pub(crate) fn new(capture: Capture, channels: u16, sample_rate: u32) -> Self {
+        let channels = channels.max(1);
+        let ring = capture.configure(channels, sample_rate);
+
+        Self {
+            capture,
+            ring,
+            dc: vec![DcBlocker::default(); usize::from(channels)],
+            dc_pole: dc_pole(sample_rate),
+            channel: 0,
+            channels,
+            sample_rate,
+        }
+    }
+
+    /// Writes into `capture` without starting it over when its layout
+    /// already matches, so another writer can carry on where one left off
+    #[must_use]
+    pub(crate) fn join(capture: Capture, channels: u16, sample_rate: u32) -> Self {
+        let channels = channels.max(1);
+        let current = capture.ring();
+        let ring = if current.channels.len() == usize::from(channels)
+            && current.sample_rate == sample_rate
+            && current.capacity() > 0
+        {
+            current
+        } else {
+            capture.configure(channels, sample_rate)
+        };
+
+        Self {
+            capture,
+            ring,
+            dc: vec![DcBlocker::default(); usize::from(channels)],
+            dc_pole: dc_pole(sample_rate),
+            channel: 0,
+            channels,
+            sample_rate,
+        }
+    }
+
+    /// Ignored off a frame boundary, switching mid-frame would shift every
+    /// later sample onto the wrong channel
+    pub(crate) fn follow_layout(&mut self, channels: u16, sample_rate: u32) {
+        let layout = (channels.max(1), sample_rate);
+
+        if self.channel == 0 && layout != (self.channels, self.sample_rate) {
+            (self.channels, self.sample_rate) = layout;
+            self.ring = self.capture.configure(self.channels, self.sample_rate);
+            self.dc = vec![DcBlocker::default(); usize::from(self.channels)];
+            self.dc_pole = dc_pole(self.sample_rate);
+        }
+    }
+
+    /// Channel the next sample is for
+    #[inline]
+    pub(crate) fn channel(&self) -> usize {
+        self.channel
+    }
+
+    #[inline]
+    pub(crate) fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    #[inline]
+    pub(crate) fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub(crate) fn push(&mut self, x: f32) {
+        if self.channel == 0 && self.capture.onset.swap(false, Ordering::Relaxed) {
+            self.ring
+                .onset
+                .store(self.ring.written.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+
+        let blocked = self.dc[self.channel].process(x, self.dc_pole);
+        let shown = if self.capture.dc_block() { blocked } else { x };
+
+        let cap = self.ring.capacity();
+        let pos = self.ring.pos.load(Ordering::Relaxed);
+        if cap > 0 {
+            self.ring.channels[self.channel][pos].store(shown.to_bits(), Ordering::Relaxed);
+        }
+
+        self.channel += 1;
+        if self.channel == usize::from(self.channels) {
+            self.channel = 0;
+            if cap > 0 {
+                self.ring.written.fetch_add(1, Ordering::Relaxed);
+                // publishes the whole frame to readers
+                self.ring.pos.store((pos + 1) % cap, Ordering::Release);
+            }
+        }
+    }
+}
+
+/// The device pulls samples in bursts, one per callback. A pause between
+/// blocks of samples marks where one callback ended and the next began,
+/// which is enough to time each against the audio it produced. The clock
+/// is read once a block, not per sample
+struct CallbackTimer {
+    audio_load: AudioLoad,
+    /// First block of the callback being filled
+    started: Option<Instant>,
+    last: Instant,
+    samples: usize,
+    /// Samples pulled since the clock was last read
+    block: usize,
+}
+
+impl CallbackTimer {
+    fn new(audio_load: AudioLoad) -> Self {
+        Self {
+            audio_load,
+            started: None,
+            last: Instant::now(),
+            samples: 0,
+            block: 0,
+        }
+    }
+
+    /// Counts a sample, reading `now` every `CALLBACK_TIMER_BLOCK` samples
+    /// and closing the callback before the block when the pause says a new
+    /// one started. A block takes less than its own length to make unless
+    /// the output is already falling behind, so only time past that counts
+    fn sample(&mut self, now: impl FnOnce() -> Instant, samples_per_s: u32) {
+        self.block += 1;
+        if self.block < CALLBACK_TIMER_BLOCK {
+            return;
+        }
+
+        let now = now();
+        let gap = now.duration_since(self.last);
+        let block_s = self.block as f32 / samples_per_s.max(1) as f32;
+        let pause = Duration::from_micros(CALLBACK_GAP_US) + Duration::from_secs_f32(block_s);
+
+        match self.started {
+            None => self.started = Some(now),
+            Some(started) if gap >= pause => {
+                self.finish(started, now, samples_per_s);
+                self.started = Some(now);
+                self.samples = 0;
+            }
+            Some(_) => {}
+        }
+
+        self.last = now;
+        self.samples += self.block;
+        self.block = 0;
+    }
+
+    fn finish(&self, started: Instant, next: Instant, samples_per_s: u32) {
+        self.audio_load.callbacks.fetch_add(1, Ordering::Relaxed);
+        if samples_per_s == 0 {
+            return;
+        }
+
+        let audio_s = self.samples as f32 / samples_per_s as f32;
+        let busy_s = self.last.duration_since(started).as_secs_f32();
+        let period_s = next.duration_since(started).as_secs_f32();
+
+        let load = self.audio_load.load();
+        let load = load + (busy_s / audio_s - load) * LOAD_SMOOTHING;
+        self.audio_load
+            .load
+            .store(load.to_bits(), Ordering::Relaxed);
+
+        if period_s > audio_s * CALLBACK_LATE {
+            self.audio_load.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Sits between the voice mixer and the device, writing every sample into
+/// the capture and any running recording as it passes. The capture layout
+/// always follows what the source itself reports
+pub struct TapSource {
+    input: PatchSource,
+    record: RecordTap,
+    writer: CaptureWriter,
+    timer: CallbackTimer,
+}
+
+impl TapSource {
+    #[must_use]
+    pub fn new(input: PatchSource, capture: Capture, record: RecordTap) -> Self {
+        let timer = CallbackTimer::new(capture.audio_load().clone());
+        let writer = CaptureWriter::new(capture, input.channels(), input.sample_rate());
+
+        Self {
+            input,
+            record,
+            writer,
+            timer,
+        }
+    }
+}
+
+impl Iterator for TapSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.writer.channel() == 0 {
+            self.writer
+                .follow_layout(self.input.channels(), self.input.sample_rate());
+        }
+
+        let x = self.input.next()?;
+        self.timer.sample(
+            Instant::now,
+            u32::from(self.writer.channels()) * self.writer.sample_rate(),
+        );
+        let channels = usize::from(self.writer.channels());
+        self.record.push(x, self.writer.channel(), channels);
+        self.writer.push(x);
+
+        Some(x)
+    }
+}
+
+crate::impl_source_passthrough!(TapSource, input);
+
+/// Feeds one voice into a capture of its own while it is pinned, so the
+/// scope can follow a single note while others play. Shared by the voice's
+/// unison copies, which sum into it
+#[derive(Clone)]
+pub struct VoiceTap {
+    capture: Capture,
+    /// Onset flag of the mix the voice plays into, raised on its first sample
+    onset: Arc<AtomicBool>,
+    pinned: Arc<AtomicBool>,
+    /// The other copies' samples since the lead copy last wrote each
+    /// channel, as bits
+    others: Arc<[AtomicU32]>,
+    /// Only touched on the control side, see `Handoff`
+    handoff: Arc<Mutex<Option<Handoff>>>,
+}
+
+/// Joining the capture locks it and may allocate a new ring, so the writer
+/// is made on the control side when the voice is pinned and handed to the
+/// lead copy, which only ever polls for it
+struct Handoff {
+    writer: SyncSender<CaptureWriter>,
+    channels: u16,
+    sample_rate: u32,
+    sent: bool,
+}
+
+impl VoiceTap {
+    #[must_use]
+    pub fn new(capture: Capture, output: Capture) -> Self {
+        Self {
+            capture,
+            onset: output.onset,
+            pinned: Arc::new(AtomicBool::new(false)),
+            others: (0..VOICE_TAP_CHANNELS).map(|_| AtomicU32::new(0)).collect(),
+            handoff: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Sends the lead copy its writer once the voice is pinned
+    fn offer_writer(&self) {
+        let mut handoff = self.handoff.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(handoff) = handoff.as_mut().filter(|handoff| !handoff.sent) else {
+            return;
+        };
+
+        let writer =
+            CaptureWriter::join(self.capture.clone(), handoff.channels, handoff.sample_rate);
+        handoff.sent = handoff.writer.try_send(writer).is_ok();
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn pinned(&self) -> bool {
+        self.pinned.load(Ordering::Relaxed)
+    }
+
+    /// Takes effect from the voice's next frame
+    pub fn set_pinned(&self, pinned: bool) {
+        self.pinned.store(pinned, Ordering::Relaxed);
+        if pinned {
+            self.offer_writer();
+        }
+    }
+
+    /// Wraps one unison copy of the voice. Only the `lead` copy writes the
+    /// capture, the rest add into what it writes next
+    #[must_use]
+    pub fn apply(&self, input: PatchSource, lead: bool) -> PatchSource {
+        let writers = lead.then(|| {
+            let (writer, writers) = sync_channel(1);
+            *self.handoff.lock().unwrap_or_else(PoisonError::into_inner) = Some(Handoff {
+                writer,
+                channels: input.channels().max(1),
+                sample_rate: input.sample_rate(),
+                sent: false,
+            });
+            writers
+        });
+
+        let source = Box::new(VoiceTapSource {
+            input,
+            tap: self.clone(),
+            lead,
+            writers,
+            writer: None,
+            active: false,
+            started: false,
+            channel: 0,
+        });
+        if lead && self.pinned() {
+            self.offer_writer();
+        }
+
+        source
+    }
+}
+
+struct VoiceTapSource {
+    input: PatchSource,
+    tap: VoiceTap,
+    lead: bool,
+    /// Where the lead copy's writer arrives from the control side
+    writers: Option<Receiver<CaptureWriter>>,
+    writer: Option<CaptureWriter>,
+    /// Whether the pin was on at the start of this frame, a frame is tapped
+    /// whole or not at all
+    active: bool,
+    /// Whether the voice has marked its onset in the mix yet
+    started: bool,
+    channel: u16,
+}
+
+impl Iterator for VoiceTapSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.input.next()?;
+        let channels = self.input.channels().max(1);
+
+        // the mix pulls this sample for the frame it is about to write
+        if self.lead && !self.started {
+            self.started = true;
+            self.tap.onset.store(true, Ordering::Relaxed);
+        }
+
+        if self.channel == 0 {
+            self.active = self.tap.pinned();
+        }
+
+        if self.active {
+            let others = self.tap.others.get(usize::from(self.channel));
+            if self.lead {
+                let others =
+                    others.map_or(0.0, |acc| f32::from_bits(acc.swap(0, Ordering::Relaxed)));
+                if self.writer.is_none() {
+                    self.writer = self.writers.as_ref().and_then(|rx| rx.try_recv().ok());
+                }
+                if let Some(writer) = &mut self.writer {
+                    writer.push(x + others);
+                }
+            } else if let Some(acc) = others {
+                let sum = f32::from_bits(acc.load(Ordering::Relaxed)) + x;
+                acc.store(sum.to_bits(), Ordering::Relaxed);
+            }
+        }
+
+        self.channel = (self.channel + 1) % channels;
+        Some(x)
+    }
+}
+
+crate::impl_source_passthrough!(VoiceTapSource, input);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    const RATE: u32 = 48_000;
+
+    fn tone(step: f32) -> Vec<f32> {
+        (0..CAPTURE_FRAMES)
+            .map(|i| (i as f32 * step).sin() * 0.5)
+            .collect()
+    }
+
+    /// Pulls one sample from every source in turn the way the mixer does,
+    /// returning the mix
+    fn mix(sources: &mut [PatchSource]) -> Vec<f32> {
+        let mut out = Vec::new();
+        loop {
+            let frame: Vec<f32> = sources.iter_mut().filter_map(|s| s.next()).collect();
+            if frame.is_empty() {
+                return out;
+            }
+            out.push(frame.iter().sum());
+        }
+    }
+
+    fn voice_capture() -> Capture {
+        let capture = Capture::new();
+        capture.set_dc_block(false);
+        capture
+    }
+
+    #[test]
+    fn pinned_voice_is_captured_alone() {
+        let capture = voice_capture();
+        let (a, b) = (tone(0.01), tone(0.07));
+        let taps = [
+            VoiceTap::new(capture.clone(), Capture::new()),
+            VoiceTap::new(capture.clone(), Capture::new()),
+        ];
+        taps[1].set_pinned(true);
+
+        let mut sources: Vec<PatchSource> = [&a, &b]
+            .into_iter()
+            .zip(&taps)
+            .map(|(samples, tap)| {
+                tap.apply(Box::new(SamplesBuffer::new(1, RATE, samples.clone())), true)
+            })
+            .collect();
+        let mixed = mix(&mut sources);
+
+        let captured = capture.frames(CAPTURE_FRAMES).remove(0);
+        assert_eq!(captured, b);
+        assert_ne!(captured, mixed);
+    }
+
+    #[test]
+    fn pinning_a_sounding_voice_hands_it_a_writer() {
+        let capture = voice_capture();
+        let tap = VoiceTap::new(capture.clone(), Capture::new());
+        let samples = tone(0.01);
+        let mut voice = tap.apply(Box::new(SamplesBuffer::new(1, RATE, samples.clone())), true);
+
+        voice.by_ref().take(100).for_each(drop);
+        assert_eq!(capture.capacity(), 0, "nothing joined before the pin");
+
+        tap.set_pinned(true);
+        assert_eq!(capture.capacity(), CAPTURE_FRAMES, "joined on this side");
+        voice.for_each(drop);
+
+        let rest = samples.len() - 100;
+        assert_eq!(capture.frames(rest).remove(0), samples[100..]);
+    }
+
+    #[test]
+    fn unpinned_voices_leave_the_capture_alone() {
+        let capture = voice_capture();
+        let tap = VoiceTap::new(capture.clone(), Capture::new());
+        let mut sources = vec![tap.apply(Box::new(SamplesBuffer::new(1, RATE, tone(0.01))), true)];
+        mix(&mut sources);
+
+        assert_eq!(capture.capacity(), 0);
+    }
+
+    #[test]
+    fn unison_copies_sum_into_the_pinned_voice() {
+        let capture = voice_capture();
+        let tap = VoiceTap::new(capture.clone(), Capture::new());
+        tap.set_pinned(true);
+
+        let mut sources = vec![
+            tap.apply(Box::new(SamplesBuffer::new(1, RATE, vec![0.25; 64])), true),
+            tap.apply(Box::new(SamplesBuffer::new(1, RATE, vec![0.5; 64])), false),
+        ];
+        mix(&mut sources);
+
+        // the first frame has only the lead, the others land a sample later
+        let captured = capture.frames(63).remove(0);
+        assert!(
+            captured.iter().all(|&s| (s - 0.75).abs() < 1e-6),
+            "{captured:?}"
+        );
+    }
+
+    #[test]
+    fn stereo_unison_copies_sum_on_their_own_channel() {
+        let capture = voice_capture();
+        let tap = VoiceTap::new(capture.clone(), Capture::new());
+        tap.set_pinned(true);
+
+        let frames = |l: f32, r: f32| [l, r].repeat(64);
+        let mut sources = vec![
+            tap.apply(
+                Box::new(SamplesBuffer::new(2, RATE, frames(0.25, -0.25))),
+                true,
+            ),
+            tap.apply(
+                Box::new(SamplesBuffer::new(2, RATE, frames(0.5, -0.125))),
+                false,
+            ),
+        ];
+        mix(&mut sources);
+
+        // as in mono, the first frame has only the lead
+        let captured = capture.frames(63);
+        assert!(
+            captured[0].iter().all(|&s| (s - 0.75).abs() < 1e-6),
+            "left {:?}",
+            captured[0]
+        );
+        assert!(
+            captured[1].iter().all(|&s| (s + 0.375).abs() < 1e-6),
+            "right {:?}",
+            captured[1]
+        );
+    }
+
+    #[test]
+    fn callbacks_are_timed_reading_the_clock_once_a_block() {
+        const PER_CALLBACK: usize = 480;
+        let load = AudioLoad::default();
+        let mut timer = CallbackTimer::new(load.clone());
+        let origin = Instant::now();
+        let mut reads = 0;
+
+        // 10 ms callbacks filled in 2 ms, one of them 25 ms late
+        let mut at = Duration::ZERO;
+        for callback in 0..20 {
+            at += Duration::from_millis(if callback == 15 { 35 } else { 10 });
+            for i in 0..PER_CALLBACK {
+                let now =
+                    origin + at + Duration::from_micros(2000 * i as u64 / PER_CALLBACK as u64);
+                timer.sample(
+                    || {
+                        reads += 1;
+                        now
+                    },
+                    RATE,
+                );
+            }
+        }
+
+        assert_eq!(reads, 20 * PER_CALLBACK / CALLBACK_TIMER_BLOCK);
+        assert_eq!(load.callbacks(), 19);
+        assert_eq!(load.underruns(), 1);
+        assert!((0.1..0.3).contains(&load.load()), "load {}", load.load());
+    }
+
+    #[test]
+    fn reads_whole_frames_split_per_channel() {
+        let capture = voice_capture();
+        let mut writer = CaptureWriter::new(capture.clone(), 2, RATE);
+        for i in 0..10 {
+            writer.push(i as f32);
+            writer.push(-(i as f32));
+        }
+        // half a frame is not published until its right sample lands
+        writer.push(100.0);
+
+        assert_eq!(
+            capture.frames(4),
+            [vec![6.0, 7.0, 8.0, 9.0], vec![-6.0, -7.0, -8.0, -9.0]]
+        );
+        writer.push(-100.0);
+        assert_eq!(capture.frames(1), [vec![100.0], vec![-100.0]]);
+    }
+
+    #[test]
+    fn wraps_around_keeping_the_newest_frames() {
+        let capture = voice_capture();
+        let mut writer = CaptureWriter::new(capture.clone(), 1, RATE);
+        let extra = 10;
+        for i in 0..CAPTURE_FRAMES + extra {
+            writer.push(i as f32);
+        }
+
+        let expected: Vec<f32> = (extra..CAPTURE_FRAMES + extra).map(|i| i as f32).collect();
+        assert_eq!(capture.frames(CAPTURE_FRAMES).remove(0), expected);
+        // asking for more than the ring holds gets what it holds
+        assert_eq!(capture.frames(CAPTURE_FRAMES * 2).remove(0), expected);
+        assert_eq!(
+            capture.frames(3).remove(0),
+            expected[CAPTURE_FRAMES - 3..].to_vec()
+        );
+    }
+
+    /// Half a unit of DC under a 480 Hz sine, captured for two seconds
+    fn offset_sine(dc_block: bool) -> Vec<f32> {
+        let capture = Capture::new();
+        capture.set_dc_block(dc_block);
+        let mut writer = CaptureWriter::new(capture.clone(), 1, RATE);
+        for i in 0..RATE * 2 {
+            writer.push(0.5 + 0.3 * (TAU * 480.0 * i as f32 / RATE as f32).sin());
+        }
+
+        capture.frames(CAPTURE_FRAMES).remove(0)
+    }
+
+    fn mean(samples: &[f32]) -> f32 {
+        samples.iter().sum::<f32>() / samples.len() as f32
+    }
+
+    fn peak_to_peak(samples: &[f32]) -> f32 {
+        let (lo, hi) = samples
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(lo, hi), s| (lo.min(*s), hi.max(*s)));
+        hi - lo
+    }
+
+    #[test]
+    fn dc_block_centres_the_capture_and_keeps_the_sine() {
+        let captured = offset_sine(true);
+
+        assert!(mean(&captured).abs() < 0.01, "mean {}", mean(&captured));
+        let swing = peak_to_peak(&captured);
+        assert!((swing - 0.6).abs() < 0.02, "swing {swing}");
+    }
+
+    #[test]
+    fn without_dc_block_the_offset_stays() {
+        let captured = offset_sine(false);
+
+        assert!(
+            (mean(&captured) - 0.5).abs() < 0.01,
+            "mean {}",
+            mean(&captured)
+        );
+        assert!((peak_to_peak(&captured) - 0.6).abs() < 0.01);
+    }
+}
@@ -0,0 +1,77 @@
+//! Chord mode, one key plays a chord built on its note
+
+use crate::config::CHORD_CUSTOM;
+use crate::play::key::Key;
+use crate::play::scale::Quantizer;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chord {
+    Off,
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Sus4,
+    Power,
+    /// Intervals from `CHORD_CUSTOM`
+    Custom,
+}
+
+impl Chord {
+    pub const ALL: [Self; 8] = [
+        Self::Off,
+        Self::Major,
+        Self::Minor,
+        Self::Diminished,
+        Self::Augmented,
+        Self::Sus4,
+        Self::Power,
+        Self::Custom,
+    ];
+
+    #[inline]
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Major => "Major",
+            Self::Minor => "Minor",
+            Self::Diminished => "Dim",
+            Self::Augmented => "Aug",
+            Self::Sus4 => "Sus4",
+            Self::Power => "Power",
+            Self::Custom => "Custom",
+        }
+    }
+
+    /// Semitones above the played note, the note itself included
+    #[must_use]
+    pub fn intervals(self) -> &'static [i32] {
+        match self {
+            Self::Off => &[0],
+            Self::Major => &[0, 4, 7],
+            Self::Minor => &[0, 3, 7],
+            Self::Diminished => &[0, 3, 6],
+            Self::Augmented => &[0, 4, 8],
+            Self::Sus4 => &[0, 5, 7],
+            Self::Power => &[0, 7, 12],
+            Self::Custom => CHORD_CUSTOM,
+        }
+    }
+
+    /// Every chord tone on `root`, each snapped into the quantizer's scale
+    /// so a chord stays diatonic. Tones the scale folds together play once
+    #[must_use]
+    pub fn tones(self, root: Key, quantizer: Quantizer) -> Vec<Key> {
+        let mut tones: Vec<Key> = Vec::new();
+
+        for &interval in self.intervals() {
+            let tone = quantizer.quantize(root.transpose(interval));
+            if !tones.contains(&tone) {
+                tones.push(tone);
+            }
+        }
+
+        tones
+    }
+}
@@ -0,0 +1,340 @@
+//! Input engines, terminal key tracking with release timeouts, startup and
+//! shortcut key masking, and debouncing of polled keys
+
+use device_query::Keycode;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEngine {
+    Global,
+    Terminal,
+}
+
+impl InputEngine {
+    #[inline]
+    #[must_use]
+    pub fn toggle(self) -> Self {
+        match self {
+            Self::Global => Self::Terminal,
+            Self::Terminal => Self::Global,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Global => "Global",
+            Self::Terminal => "Terminal",
+        }
+    }
+}
+
+/// Terminals only report presses and repeats, so a key counts as held until no
+/// repeat arrives before its deadline. The first press waits `hold` to cover the
+/// OS repeat delay, every repeat after that only extends it by `release`.
+#[derive(Debug, Clone)]
+pub struct TerminalKeys {
+    hold: Duration,
+    release: Duration,
+    deadlines: HashMap<Keycode, Instant>,
+}
+
+impl TerminalKeys {
+    #[must_use]
+    pub fn new(hold: Duration, release: Duration) -> Self {
+        Self {
+            hold,
+            release,
+            deadlines: HashMap::new(),
+        }
+    }
+
+    /// Registers a press or repeat, returns true when the key was not held yet
+    pub fn press(&mut self, keycode: Keycode, now: Instant) -> bool {
+        if let Some(deadline) = self.deadlines.get_mut(&keycode) {
+            *deadline = now + self.release;
+            false
+        } else {
+            self.deadlines.insert(keycode, now + self.hold);
+            true
+        }
+    }
+
+    /// Drops every key whose deadline has passed, returns true if any was released
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let before = self.deadlines.len();
+        self.deadlines.retain(|_, deadline| *deadline > now);
+        self.deadlines.len() != before
+    }
+
+    #[must_use]
+    pub fn held(&self) -> HashSet<Keycode> {
+        self.deadlines.keys().copied().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.deadlines.clear();
+    }
+}
+
+/// Keys already down when the engine starts (held through launch or stuck
+/// after a crash) are masked until a poll shows them released
+#[derive(Debug, Clone)]
+pub struct StartupKeys {
+    ignored: Option<HashSet<Keycode>>,
+}
+
+impl StartupKeys {
+    /// Disabled, every key passes straight through
+    #[must_use]
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            ignored: if enabled { None } else { Some(HashSet::new()) },
+        }
+    }
+
+    /// Drops masked keys from `keys`, the first call masks everything it sees
+    pub fn filter(&mut self, keys: HashSet<Keycode>) -> HashSet<Keycode> {
+        let ignored = self.ignored.get_or_insert_with(|| keys.clone());
+        ignored.retain(|k| keys.contains(k));
+
+        keys.into_iter().filter(|k| !ignored.contains(k)).collect()
+    }
+}
+
+/// Keys pressed while Control is down are UI shortcuts like Ctrl+S, not
+/// notes. Each stays masked until it is released, even once Control is up
+#[derive(Debug, Clone, Default)]
+pub struct ControlKeys {
+    last: HashSet<Keycode>,
+    masked: HashSet<Keycode>,
+}
+
+impl ControlKeys {
+    /// Drops keys that went down with Control held, and Control itself
+    pub fn filter(&mut self, keys: HashSet<Keycode>) -> HashSet<Keycode> {
+        let control = keys.contains(&Keycode::LControl) || keys.contains(&Keycode::RControl);
+
+        self.masked.retain(|k| keys.contains(k));
+        if control {
+            self.masked.extend(keys.difference(&self.last));
+        }
+        self.last.clone_from(&keys);
+
+        keys.into_iter()
+            .filter(|k| !self.masked.contains(k))
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.last.clear();
+        self.masked.clear();
+    }
+}
+
+/// Some platforms report a held key as released for a poll or two, which
+/// would stop and restart its note. A key seen down stays held until it has
+/// been missing for the whole `window`
+#[derive(Debug, Clone)]
+pub struct KeyDebounce {
+    window: Duration,
+    last_seen: HashMap<Keycode, Instant>,
+}
+
+impl KeyDebounce {
+    #[must_use]
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last_seen: HashMap::new(),
+        }
+    }
+
+    /// `keys` plus every key that dropped out less than `window` before `now`
+    pub fn filter(&mut self, keys: HashSet<Keycode>, now: Instant) -> HashSet<Keycode> {
+        let window = self.window;
+        self.last_seen
+            .retain(|key, seen| keys.contains(key) || now.duration_since(*seen) < window);
+        for key in keys {
+            self.last_seen.insert(key, now);
+        }
+
+        self.last_seen.keys().copied().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.last_seen.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{KEY_DEBOUNCE_MS, TICK};
+
+    const HOLD: Duration = Duration::from_millis(500);
+    const RELEASE: Duration = Duration::from_millis(120);
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn first_press_holds_through_the_repeat_delay() {
+        let start = Instant::now();
+        let mut keys = TerminalKeys::new(HOLD, RELEASE);
+
+        assert!(keys.press(Keycode::A, start));
+        assert!(!keys.expire(start + ms(400)));
+        assert!(keys.held().contains(&Keycode::A));
+    }
+
+    #[test]
+    fn repeats_keep_the_note_alive() {
+        let start = Instant::now();
+        let mut keys = TerminalKeys::new(HOLD, RELEASE);
+        keys.press(Keycode::A, start);
+
+        // a repeat every 100 ms stays inside the 120 ms release window
+        for i in 1..=20 {
+            let now = start + ms(400 + 100 * i);
+            assert!(!keys.press(Keycode::A, now));
+            assert!(!keys.expire(now + ms(100)));
+        }
+        assert!(keys.held().contains(&Keycode::A));
+    }
+
+    #[test]
+    fn a_gap_past_the_timeout_releases() {
+        let start = Instant::now();
+        let mut keys = TerminalKeys::new(HOLD, RELEASE);
+        keys.press(Keycode::A, start);
+        keys.press(Keycode::A, start + ms(450));
+
+        assert!(!keys.expire(start + ms(560)));
+        assert!(keys.expire(start + ms(571)));
+        assert!(keys.held().is_empty());
+
+        // pressed again it starts a fresh note
+        assert!(keys.press(Keycode::A, start + ms(600)));
+    }
+
+    #[test]
+    fn keys_expire_independently() {
+        let start = Instant::now();
+        let mut keys = TerminalKeys::new(HOLD, RELEASE);
+        keys.press(Keycode::A, start);
+        keys.press(Keycode::S, start + ms(300));
+
+        assert!(keys.expire(start + ms(600)));
+        assert_eq!(keys.held(), HashSet::from([Keycode::S]));
+    }
+
+    /// Feeds polls `TICK` ms apart through `debounce` the way the poll loop
+    /// does, counting how often `A` starts a note and how often it stops
+    fn notes(window: u64, polls: &[bool]) -> (usize, usize) {
+        let start = Instant::now();
+        let mut debounce = KeyDebounce::new(ms(window));
+        let mut last = HashSet::new();
+        let (mut starts, mut stops) = (0, 0);
+
+        for (i, &down) in polls.iter().enumerate() {
+            let keys = if down {
+                HashSet::from([Keycode::A])
+            } else {
+                HashSet::new()
+            };
+            let now = debounce.filter(keys, start + ms(TICK * i as u64));
+
+            starts += now.difference(&last).count();
+            stops += last.difference(&now).count();
+            last = now;
+        }
+
+        (starts, stops)
+    }
+
+    #[test]
+    fn a_flickering_hold_plays_one_note() {
+        // held for a second, dropping out of a poll or two now and then
+        let mut polls = vec![true; 100];
+        for i in [10, 11, 35, 60, 61, 80] {
+            polls[i] = false;
+        }
+        polls.extend([false; 10]);
+
+        assert_eq!(notes(KEY_DEBOUNCE_MS, &polls), (1, 1));
+        // without the window every dropout retriggers
+        assert_eq!(notes(0, &polls), (5, 5));
+    }
+
+    #[test]
+    fn a_release_past_the_window_stops_the_note() {
+        let mut polls = vec![true; 10];
+        polls.extend([false; 5]);
+        polls.extend([true; 10]);
+
+        assert_eq!(notes(KEY_DEBOUNCE_MS, &polls), (2, 1));
+    }
+
+    #[test]
+    fn shortcuts_do_not_play_notes() {
+        let mut control = ControlKeys::default();
+        let keys = |keys: &[Keycode]| keys.iter().copied().collect::<HashSet<_>>();
+
+        assert!(control.filter(keys(&[Keycode::LControl])).is_empty());
+        assert!(
+            control
+                .filter(keys(&[Keycode::LControl, Keycode::S]))
+                .is_empty()
+        );
+        // letting go of Control first does not start the note
+        assert!(control.filter(keys(&[Keycode::S])).is_empty());
+
+        // pressed again on its own it plays
+        control.filter(keys(&[]));
+        assert_eq!(control.filter(keys(&[Keycode::S])), keys(&[Keycode::S]));
+    }
+
+    #[test]
+    fn held_notes_survive_a_shortcut() {
+        let mut control = ControlKeys::default();
+        let keys = |keys: &[Keycode]| keys.iter().copied().collect::<HashSet<_>>();
+
+        control.filter(keys(&[Keycode::A]));
+        assert_eq!(
+            control.filter(keys(&[Keycode::A, Keycode::RControl, Keycode::O])),
+            keys(&[Keycode::A])
+        );
+        assert_eq!(control.filter(keys(&[Keycode::A])), keys(&[Keycode::A]));
+    }
+
+    #[test]
+    fn keys_held_at_start_wait_for_a_re_press() {
+        let mut startup = StartupKeys::new(true);
+
+        assert!(startup.filter(HashSet::from([Keycode::A])).is_empty());
+        // a key pressed later plays, the one held through launch does not
+        assert_eq!(
+            startup.filter(HashSet::from([Keycode::A, Keycode::S])),
+            HashSet::from([Keycode::S])
+        );
+
+        // once released it plays like any other
+        assert!(startup.filter(HashSet::new()).is_empty());
+        assert_eq!(
+            startup.filter(HashSet::from([Keycode::A])),
+            HashSet::from([Keycode::A])
+        );
+    }
+
+    #[test]
+    fn disabled_startup_mask_plays_held_keys() {
+        let mut startup = StartupKeys::new(false);
+        let held = HashSet::from([Keycode::A, Keycode::S]);
+
+        assert_eq!(startup.filter(held.clone()), held);
+    }
+}
@@ -2,7 +2,10 @@
 
 use crate::config::{A4_SEMITONES, BASE_FREQ, KEYBOARD_BASE_OCTAVE, SEMITONES_PER_OCTAVE};
 use device_query::Keycode;
+use std::collections::HashMap;
 use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
@@ -38,19 +41,19 @@ impl Note {
     ];
 
     #[inline]
-    #[must_use] 
+    #[must_use]
     pub const fn semitone(self) -> i32 {
         self as i32
     }
 
     #[inline]
-    #[must_use] 
+    #[must_use]
     pub const fn from_semitone(semitone: i32) -> Self {
         Self::ALL[semitone.rem_euclid(SEMITONES_PER_OCTAVE) as usize]
     }
 
     #[inline]
-    #[must_use] 
+    #[must_use]
     pub const fn name(self) -> &'static str {
         match self {
             Note::C => "C",
@@ -67,6 +70,46 @@ impl Note {
             Note::B => "B",
         }
     }
+
+    /// The sharps and flats, the short keys on a piano
+    #[inline]
+    #[must_use]
+    pub const fn is_black(self) -> bool {
+        matches!(self, Note::Db | Note::Eb | Note::Gb | Note::Ab | Note::Bb)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyRow {
+    Home,
+    Top,
+}
+
+impl KeyRow {
+    #[must_use]
+    pub fn from_keycode(keycode: Keycode) -> Option<Self> {
+        match keycode {
+            Keycode::A
+            | Keycode::S
+            | Keycode::D
+            | Keycode::F
+            | Keycode::G
+            | Keycode::H
+            | Keycode::J
+            | Keycode::K
+            | Keycode::L
+            | Keycode::Semicolon
+            | Keycode::Apostrophe => Some(Self::Home),
+            Keycode::W
+            | Keycode::E
+            | Keycode::T
+            | Keycode::Y
+            | Keycode::U
+            | Keycode::O
+            | Keycode::P => Some(Self::Top),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -77,22 +120,45 @@ pub struct Key {
 
 impl Key {
     #[inline]
-    #[must_use] 
+    #[must_use]
     pub const fn new(note: Note, octave: i32) -> Self {
         Self { note, octave }
     }
 
     #[inline]
-    #[must_use] 
+    #[must_use]
     pub const fn absolute_semitone(self) -> i32 {
         self.octave * SEMITONES_PER_OCTAVE + self.note.semitone()
     }
 
+    /// Equal temperament against the standard 440 Hz A4
     #[inline]
-    #[must_use] 
+    #[must_use]
     pub fn frequency(self) -> f32 {
+        self.frequency_at(BASE_FREQ)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn frequency_at(self, a4_hz: f32) -> f32 {
         let diff = self.absolute_semitone() - A4_SEMITONES;
-        BASE_FREQ * 2.0f32.powf(diff as f32 / 12.0)
+        a4_hz * 2.0f32.powf(diff as f32 / SEMITONES_PER_OCTAVE as f32)
+    }
+
+    /// How far `hz` sits from this key in equal temperament at 440 Hz, in
+    /// cents
+    #[inline]
+    #[must_use]
+    pub fn cents_off(self, hz: f32) -> f32 {
+        1200.0 * (hz / self.frequency()).log2()
+    }
+
+    /// Closest equal-tempered key to `hz`
+    #[inline]
+    #[must_use]
+    pub fn nearest(hz: f32, a4_hz: f32) -> Self {
+        let diff = (SEMITONES_PER_OCTAVE as f32 * (hz / a4_hz).log2()).round() as i32;
+        Self::new(Note::C, 0).transpose(A4_SEMITONES + diff)
     }
 
     #[inline]
@@ -104,36 +170,277 @@ impl Key {
         Self { note, octave }
     }
 
+    /// Looks the key up in the active keymap, relative to `KEYBOARD_BASE_OCTAVE`
     #[must_use]
     pub fn from_keycode(keycode: Keycode) -> Option<Self> {
-        let base = KEYBOARD_BASE_OCTAVE;
-
-        Some(match keycode {
-            Keycode::A => Self::new(Note::C, base),
-            Keycode::W => Self::new(Note::Db, base),
-            Keycode::S => Self::new(Note::D, base),
-            Keycode::E => Self::new(Note::Eb, base),
-            Keycode::D => Self::new(Note::E, base),
-            Keycode::F => Self::new(Note::F, base),
-            Keycode::T => Self::new(Note::Gb, base),
-            Keycode::G => Self::new(Note::G, base),
-            Keycode::Y => Self::new(Note::Ab, base),
-            Keycode::H => Self::new(Note::A, base),
-            Keycode::U => Self::new(Note::Bb, base),
-            Keycode::J => Self::new(Note::B, base),
-            Keycode::K => Self::new(Note::C, base + 1),
-            Keycode::O => Self::new(Note::Db, base + 1),
-            Keycode::L => Self::new(Note::D, base + 1),
-            Keycode::P => Self::new(Note::Eb, base + 1),
-            Keycode::Semicolon => Self::new(Note::E, base + 1),
-            Keycode::Apostrophe => Self::new(Note::F, base + 1),
-            _ => return None,
-        })
+        let semitones = keymap().get(&keycode)?;
+        Some(Self::new(Note::C, KEYBOARD_BASE_OCTAVE).transpose(*semitones))
     }
 }
 
+/// Built-in layout, semitones above C of the base octave
+pub const DEFAULT_KEYMAP: [(Keycode, i32); 18] = [
+    (Keycode::A, 0),
+    (Keycode::W, 1),
+    (Keycode::S, 2),
+    (Keycode::E, 3),
+    (Keycode::D, 4),
+    (Keycode::F, 5),
+    (Keycode::T, 6),
+    (Keycode::G, 7),
+    (Keycode::Y, 8),
+    (Keycode::H, 9),
+    (Keycode::U, 10),
+    (Keycode::J, 11),
+    (Keycode::K, 12),
+    (Keycode::O, 13),
+    (Keycode::L, 14),
+    (Keycode::P, 15),
+    (Keycode::Semicolon, 16),
+    (Keycode::Apostrophe, 17),
+];
+
+pub type Keymap = HashMap<Keycode, i32>;
+
+static KEYMAP: OnceLock<Keymap> = OnceLock::new();
+
+#[inline]
+fn keymap() -> &'static Keymap {
+    KEYMAP.get_or_init(|| DEFAULT_KEYMAP.into_iter().collect())
+}
+
+/// Replaces the built-in layout, only takes effect before the first lookup.
+/// Returns false if a keymap was already in use
+pub fn set_keymap(map: Keymap) -> bool {
+    KEYMAP.set(map).is_ok()
+}
+
+/// Every computer key in the active keymap with the key it plays before any
+/// octave shift, lowest first
+#[must_use]
+pub fn mapped_keys() -> Vec<(Keycode, Key)> {
+    let mut keys: Vec<(Keycode, Key)> = keymap()
+        .iter()
+        .map(|(&code, &semitones)| {
+            let key = Key::new(Note::C, KEYBOARD_BASE_OCTAVE).transpose(semitones);
+            (code, key)
+        })
+        .collect();
+    keys.sort_by_key(|(_, key)| key.absolute_semitone());
+    keys
+}
+
+/// The character a mapped key types, the inverse of `keycode_from_char`
+#[must_use]
+pub fn keycode_char(keycode: Keycode) -> Option<char> {
+    ('a'..='z')
+        .chain([';', '\''])
+        .find(|&c| keycode_from_char(c) == Some(keycode))
+}
+
+#[must_use]
+pub fn keycode_from_char(c: char) -> Option<Keycode> {
+    Some(match c.to_ascii_lowercase() {
+        'a' => Keycode::A,
+        'b' => Keycode::B,
+        'c' => Keycode::C,
+        'd' => Keycode::D,
+        'e' => Keycode::E,
+        'f' => Keycode::F,
+        'g' => Keycode::G,
+        'h' => Keycode::H,
+        'i' => Keycode::I,
+        'j' => Keycode::J,
+        'k' => Keycode::K,
+        'l' => Keycode::L,
+        'm' => Keycode::M,
+        'n' => Keycode::N,
+        'o' => Keycode::O,
+        'p' => Keycode::P,
+        'q' => Keycode::Q,
+        'r' => Keycode::R,
+        's' => Keycode::S,
+        't' => Keycode::T,
+        'u' => Keycode::U,
+        'v' => Keycode::V,
+        'w' => Keycode::W,
+        'x' => Keycode::X,
+        'y' => Keycode::Y,
+        'z' => Keycode::Z,
+        ';' => Keycode::Semicolon,
+        '\'' => Keycode::Apostrophe,
+        _ => return None,
+    })
+}
+
 impl fmt::Display for Key {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}{}", self.note.name(), self.octave)
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseKeyError {
+    Empty,
+    /// Not a letter A-G with an optional `b` or `#`
+    UnknownNote(String),
+    /// Missing or non-numeric octave after the note
+    BadOctave(String),
+}
+
+impl fmt::Display for ParseKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty note"),
+            Self::UnknownNote(s) => write!(f, "unknown note {s:?}, expected e.g. C, Db or F#"),
+            Self::BadOctave(s) => write!(f, "bad octave in {s:?}, expected e.g. A4 or C-1"),
+        }
+    }
+}
+
+impl std::error::Error for ParseKeyError {}
+
+/// Splits "Db4" into the note's semitone above C (-1..=12 with accidentals)
+/// and the rest of the string
+fn split_note(s: &str) -> Result<(i32, &str), ParseKeyError> {
+    let letter = s.chars().next().ok_or(ParseKeyError::Empty)?;
+    let natural = match letter.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return Err(ParseKeyError::UnknownNote(s.to_string())),
+    };
+
+    let rest = &s[1..];
+    Ok(match rest.chars().next() {
+        Some('b') => (natural - 1, &rest[1..]),
+        Some('#') => (natural + 1, &rest[1..]),
+        _ => (natural, rest),
+    })
+}
+
+impl FromStr for Note {
+    type Err = ParseKeyError;
+
+    /// Note name alone, sharps map onto their flat spelling ("F#" is `Gb`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (semitone, rest) = split_note(s.trim())?;
+        if !rest.is_empty() {
+            return Err(ParseKeyError::UnknownNote(s.to_string()));
+        }
+
+        Ok(Self::from_semitone(semitone))
+    }
+}
+
+impl FromStr for Key {
+    type Err = ParseKeyError;
+
+    /// Note name followed by a signed octave, the inverse of `Display`.
+    /// Accidentals may cross the octave, "Cb4" is `B3`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (semitone, octave) = split_note(s)?;
+        let octave = octave
+            .parse::<i32>()
+            .map_err(|_| ParseKeyError::BadOctave(s.to_string()))?;
+
+        Ok(Self::new(Note::C, octave).transpose(semitone))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_round_trip_through_their_names() {
+        for octave in -2..=9 {
+            for note in Note::ALL {
+                let key = Key::new(note, octave);
+                assert_eq!(key.to_string().parse::<Key>(), Ok(key), "{key}");
+            }
+        }
+    }
+
+    #[test]
+    fn sharps_and_flats_parse() {
+        assert_eq!("F#".parse::<Note>(), Ok(Note::Gb));
+        assert_eq!("Db".parse::<Note>(), Ok(Note::Db));
+        assert_eq!("C-1".parse::<Key>(), Ok(Key::new(Note::C, -1)));
+        assert_eq!("Gb3".parse::<Key>(), Ok(Key::new(Note::Gb, 3)));
+        // accidentals may cross into the next octave
+        assert_eq!("Cb4".parse::<Key>(), Ok(Key::new(Note::B, 3)));
+        assert_eq!("B#3".parse::<Key>(), Ok(Key::new(Note::C, 4)));
+    }
+
+    #[test]
+    fn bad_names_are_errors() {
+        assert_eq!("".parse::<Key>(), Err(ParseKeyError::Empty));
+        assert!(matches!(
+            "H4".parse::<Key>(),
+            Err(ParseKeyError::UnknownNote(_))
+        ));
+        assert!(matches!(
+            "A".parse::<Key>(),
+            Err(ParseKeyError::BadOctave(_))
+        ));
+        assert!(matches!(
+            "Ax".parse::<Key>(),
+            Err(ParseKeyError::BadOctave(_))
+        ));
+        assert!(matches!(
+            "C4".parse::<Note>(),
+            Err(ParseKeyError::UnknownNote(_))
+        ));
+    }
+
+    #[test]
+    fn frequencies_of_known_keys() {
+        let close = |key: Key, hz: f32| {
+            let got = key.frequency();
+            assert!((got - hz).abs() < hz * 1e-5, "{key}: {got} vs {hz}");
+        };
+
+        assert_eq!(Key::new(Note::A, 4).frequency(), 440.0);
+        close(Key::new(Note::C, 0), 16.351_598);
+        close(Key::new(Note::C, 4), 261.625_57);
+        close(Key::new(Note::E, 2), 82.406_89);
+        close(Key::new(Note::A, 0), 27.5);
+        close(Key::new(Note::C, 8), 4186.009);
+    }
+
+    #[test]
+    fn absolute_semitones_count_up_from_c0() {
+        assert_eq!(Key::new(Note::C, 0).absolute_semitone(), 0);
+        assert_eq!(Key::new(Note::A, 4).absolute_semitone(), A4_SEMITONES);
+        assert_eq!(Key::new(Note::B, -1).absolute_semitone(), -1);
+        assert_eq!(Key::new(Note::Db, -2).absolute_semitone(), -23);
+    }
+
+    #[test]
+    fn transpose_crosses_octaves_both_ways() {
+        assert_eq!(Key::new(Note::B, 3).transpose(1), Key::new(Note::C, 4));
+        assert_eq!(Key::new(Note::C, 4).transpose(-1), Key::new(Note::B, 3));
+        assert_eq!(Key::new(Note::C, 0).transpose(-13), Key::new(Note::B, -2));
+        assert_eq!(Key::new(Note::A, 4).transpose(24), Key::new(Note::A, 6));
+        assert_eq!(Key::new(Note::Eb, 1).transpose(-27), Key::new(Note::C, -1));
+    }
+
+    #[test]
+    fn transpose_and_from_semitone_agree() {
+        for (i, note) in Note::ALL.into_iter().enumerate() {
+            assert_eq!(note.semitone(), i as i32);
+            assert_eq!(Note::from_semitone(note.semitone()), note);
+        }
+        for semitones in -36..=36 {
+            let key = Key::new(Note::C, 0).transpose(semitones);
+            assert_eq!(key.note, Note::from_semitone(semitones), "{semitones}");
+            assert_eq!(key.absolute_semitone(), semitones);
+        }
+    }
+}
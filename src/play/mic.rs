@@ -0,0 +1,125 @@
+//! Captures an input device, a microphone or line in, so the scope can show
+//! sound from outside the synth
+
+use crate::error::{MugenError, Result};
+use crate::play::capture::{Capture, CaptureWriter};
+use rodio::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use rodio::cpal::{self, FromSample, SampleFormat, SizedSample};
+use std::sync::mpsc;
+
+/// An open input stream writing into its own capture, in whatever layout
+/// the device records. The stream lives on its own thread, as a cpal stream
+/// cannot be moved between threads, and closes when this is dropped
+pub struct MicCapture {
+    capture: Capture,
+    name: String,
+    _stop: mpsc::Sender<()>,
+}
+
+impl MicCapture {
+    /// Opens the input device named `device`, or the default one
+    pub fn open(device: Option<&str>) -> Result<Self> {
+        let capture = Capture::new();
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+
+        let device = device.map(str::to_string);
+        let writer_capture = capture.clone();
+        std::thread::spawn(move || {
+            let (stream, name) = match open_stream(device.as_deref(), writer_capture) {
+                Ok(opened) => opened,
+                Err(err) => {
+                    let _ = ready_tx.send(Err(err));
+                    return;
+                }
+            };
+
+            let _ = ready_tx.send(Ok(name));
+            // returns once the sender is dropped
+            let _ = stop_rx.recv();
+            drop(stream);
+        });
+
+        let name = ready_rx
+            .recv()
+            .map_err(|_| MugenError::audio_device("input thread stopped"))??;
+
+        Ok(Self {
+            capture,
+            name,
+            _stop: stop_tx,
+        })
+    }
+
+    #[must_use]
+    pub fn capture(&self) -> &Capture {
+        &self.capture
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+fn open_stream(device: Option<&str>, capture: Capture) -> Result<(cpal::Stream, String)> {
+    let host = cpal::default_host();
+    let device = match device {
+        None => host
+            .default_input_device()
+            .ok_or_else(|| MugenError::audio_device("no input device"))?,
+        Some(name) => host
+            .input_devices()
+            .map_err(MugenError::audio_device)?
+            .find(|d| d.name().is_ok_and(|n| n == name))
+            .ok_or_else(|| MugenError::AudioDevice(format!("no input device named {name}")))?,
+    };
+    let name = device.name().unwrap_or_else(|_| "input".to_string());
+
+    let supported = device
+        .default_input_config()
+        .map_err(MugenError::audio_device)?;
+    let config = supported.config();
+
+    let stream = match supported.sample_format() {
+        SampleFormat::F32 => build::<f32>(&device, &config, capture),
+        SampleFormat::I16 => build::<i16>(&device, &config, capture),
+        SampleFormat::U16 => build::<u16>(&device, &config, capture),
+        SampleFormat::I32 => build::<i32>(&device, &config, capture),
+        SampleFormat::U8 => build::<u8>(&device, &config, capture),
+        format => {
+            return Err(MugenError::AudioDevice(format!(
+                "unsupported input format {format}"
+            )));
+        }
+    }?;
+
+    stream.play().map_err(MugenError::audio_device)?;
+    Ok((stream, name))
+}
+
+fn build<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    capture: Capture,
+) -> Result<cpal::Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let mut writer = CaptureWriter::new(capture, config.channels, config.sample_rate.0);
+
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _: &cpal::InputCallbackInfo| {
+                for &s in data {
+                    writer.push(s.to_sample::<f32>());
+                }
+            },
+            // a device that goes away just leaves the last frames on screen
+            |_| {},
+            None,
+        )
+        .map_err(MugenError::audio_device)
+}
@@ -0,0 +1,120 @@
+//! MIDI keyboard input, note events are handed to a callback on midir's thread
+
+use crate::error::{MugenError, Result};
+use midir::{Ignore, MidiInput, MidiInputConnection};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiEvent {
+    NoteOn {
+        note: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        note: u8,
+    },
+    Sustain {
+        down: bool,
+    },
+    /// 14-bit wheel position, `BEND_CENTER` is at rest
+    PitchBend {
+        value: u16,
+    },
+}
+
+/// Control change number of the sustain pedal
+const CC_SUSTAIN: u8 = 64;
+pub const BEND_CENTER: u16 = 0x2000;
+
+/// Wheel position as a bend in -1..1
+#[inline]
+#[must_use]
+pub fn bend_amount(value: u16) -> f32 {
+    let offset = f32::from(value) - f32::from(BEND_CENTER);
+    (offset / f32::from(BEND_CENTER - 1)).clamp(-1.0, 1.0)
+}
+
+impl MidiEvent {
+    /// Note-on with velocity 0 is a note-off by convention and the sustain
+    /// pedal counts as down from half way. Pitch bend carries its low seven
+    /// bits first, everything else is ignored
+    #[must_use]
+    pub fn parse(message: &[u8]) -> Option<Self> {
+        let [status, data, value, ..] = *message else {
+            return None;
+        };
+
+        match status & 0xF0 {
+            0x90 if value > 0 => Some(Self::NoteOn {
+                note: data,
+                velocity: value,
+            }),
+            0x80 | 0x90 => Some(Self::NoteOff { note: data }),
+            0xB0 if data == CC_SUSTAIN => Some(Self::Sustain { down: value >= 64 }),
+            0xE0 => Some(Self::PitchBend {
+                value: u16::from(value & 0x7F) << 7 | u16::from(data & 0x7F),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Keeps the port open, input stops when this is dropped
+pub struct MidiConnection {
+    pub port: String,
+    _conn: MidiInputConnection<()>,
+}
+
+/// Opens the first input port whose name contains `port`, or the first port
+/// at all. `Ok(None)` when there are no MIDI inputs, asking for a port that
+/// does not exist is an error
+pub fn connect<F>(port: Option<&str>, mut on_event: F) -> Result<Option<MidiConnection>>
+where
+    F: FnMut(MidiEvent) + Send + 'static,
+{
+    let mut input = match MidiInput::new("mugen") {
+        Ok(input) => input,
+        // no MIDI subsystem is the same as no devices, unless one was asked for
+        Err(err) if port.is_some() => return Err(MugenError::audio_device(err)),
+        Err(_) => return Ok(None),
+    };
+    input.ignore(Ignore::All);
+
+    let ports = input.ports();
+    let named = ports
+        .iter()
+        .filter_map(|p| Some((p, input.port_name(p).ok()?)));
+
+    let found = match port {
+        Some(wanted) => {
+            let wanted = wanted.to_lowercase();
+            let found = named
+                .into_iter()
+                .find(|(_, name)| name.to_lowercase().contains(&wanted));
+            Some(found.ok_or_else(|| {
+                MugenError::AudioDevice(format!("no MIDI input matching {wanted:?}"))
+            })?)
+        }
+        None => named.into_iter().next(),
+    };
+    let Some((found, name)) = found else {
+        return Ok(None);
+    };
+
+    let conn = input
+        .connect(
+            found,
+            "mugen-in",
+            move |_, message, ()| {
+                if let Some(event) = MidiEvent::parse(message) {
+                    on_event(event);
+                }
+            },
+            (),
+        )
+        .map_err(MugenError::audio_device)?;
+
+    Ok(Some(MidiConnection {
+        port: name,
+        _conn: conn,
+    }))
+}
@@ -2,6 +2,19 @@
 
 mod player;
 
+pub mod arp;
+pub mod capture;
+pub mod chord;
+pub mod input;
 pub mod key;
+pub mod mic;
+pub mod midi;
+pub mod recorder;
+pub mod scale;
+pub mod sequencer;
+pub mod spectrum;
+pub mod tempo;
+pub mod tuning;
+pub mod velocity;
 
-pub use player::{ActiveVoice, Player};
+pub use player::{ActiveVoice, Player, StreamOptions, VoiceKey, VoiceMode};
@@ -1,76 +1,672 @@
 //! Playback engine responsible for active sinks, note lifecycle, and stream control
 
-use crate::patch::Gate;
+use crate::config::{MAX_VOICES, RECORD_DIR, RECORD_MAX_S};
+use crate::error::{MugenError, Result};
+use crate::patch::chain::FxChain;
+use crate::patch::fade::Cut;
+use crate::patch::{Gate, PatchSource, Pitch, PitchTarget};
+use crate::play::capture::{Capture, TapSource, VoiceTap};
+use crate::play::recorder::{Recorder, Recording};
 use device_query::Keycode;
-use rodio::Sink;
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use rodio::cpal::{self, BufferSize};
+use rodio::mixer::{self, Mixer};
+use rodio::source::Zero;
 use rodio::stream::{OutputStream, OutputStreamBuilder};
+use rodio::{Sink, Source};
 use std::collections::HashMap;
-use std::error::Error;
+use std::io;
+use std::path::Path;
 use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
 
-pub type ActiveVoice = (Sink, Gate);
+/// One sounding note, a sink per unison copy all sharing the gate
+pub struct ActiveVoice {
+    pub sinks: Vec<Sink>,
+    pub gate: Gate,
+    pub pitch: Pitch,
+    /// Fades the sinks out when the voice is stopped outright
+    pub cut: Cut,
+    /// Writes the voice to the voice scope while it is pinned
+    pub tap: VoiceTap,
+    /// When the note it plays began, a legato retune starts a new one
+    pub started: Instant,
+    /// Insertion order, the lowest is the oldest voice
+    seq: u64,
+}
+
+impl ActiveVoice {
+    #[inline]
+    fn released(&self) -> bool {
+        !self.gate.load(Ordering::Relaxed)
+    }
+
+    /// Fades out over `VOICE_CUT_FADE_MS` rather than cutting mid-cycle.
+    /// The sinks play on detached until the fade ends the source, a paused
+    /// one would never get there and is silent anyway, so it stops at once
+    fn stop(self) {
+        self.gate.store(false, Ordering::Relaxed);
+        self.tap.set_pinned(false);
+        self.cut.store(true, Ordering::Relaxed);
+        for sink in self.sinks {
+            if sink.is_paused() {
+                sink.stop();
+            } else {
+                sink.detach();
+            }
+        }
+    }
+}
+
+/// What started a voice, so the matching release stops it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VoiceKey {
+    Key(Keycode),
+    /// MIDI note number
+    Midi(u8),
+    /// The single voice shared by every key in mono and legato modes
+    Mono,
+    /// The note the arpeggiator is playing
+    Arp,
+    /// The note the step sequencer is playing
+    Seq,
+}
+
+impl From<Keycode> for VoiceKey {
+    fn from(keycode: Keycode) -> Self {
+        Self::Key(keycode)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceMode {
+    Poly,
+    /// One voice, every new key restarts the envelope
+    Mono,
+    /// One voice, overlapping keys only change its pitch
+    Legato,
+}
+
+impl VoiceMode {
+    #[inline]
+    #[must_use]
+    pub fn next(self) -> Self {
+        match self {
+            Self::Poly => Self::Mono,
+            Self::Mono => Self::Legato,
+            Self::Legato => Self::Poly,
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Poly => "Poly",
+            Self::Mono => "Mono",
+            Self::Legato => "Legato",
+        }
+    }
+}
+
+/// Output stream overrides, anything left `None` uses the device default
+#[derive(Debug, Clone, Default)]
+pub struct StreamOptions {
+    pub device: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+    pub buffer_size: Option<u32>,
+    /// Sum the mix to one channel whatever the device has
+    pub mono: bool,
+    /// How far recordings start back to line up with what was heard,
+    /// defaults to a fixed `buffer_size`
+    pub record_latency_ms: Option<u32>,
+    /// Longest recording in seconds, 0 for no limit, defaults to `RECORD_MAX_S`
+    pub record_max_s: Option<u32>,
+}
+
+impl StreamOptions {
+    /// A channel count the device turns down falls back to its default
+    /// rather than failing, handing back a warning to show
+    fn open(&self) -> Result<(OutputStream, Option<String>)> {
+        match (self.channels, self.open_with(self.channels)) {
+            (Some(channels), Err(err)) => {
+                let warning =
+                    format!("{channels} output channels unavailable ({err}), using the default");
+                Ok((self.open_with(None)?, Some(warning)))
+            }
+            (_, res) => Ok((res?, None)),
+        }
+    }
+
+    fn open_with(&self, channels: Option<u16>) -> Result<OutputStream> {
+        let mut builder = match &self.device {
+            None if self.sample_rate.is_none()
+                && channels.is_none()
+                && self.buffer_size.is_none() =>
+            {
+                return OutputStreamBuilder::open_default_stream()
+                    .map_err(MugenError::audio_device);
+            }
+            None => OutputStreamBuilder::from_default_device().map_err(MugenError::audio_device)?,
+            Some(name) => {
+                let device = cpal::default_host()
+                    .output_devices()
+                    .map_err(MugenError::audio_device)?
+                    .find(|d| d.name().is_ok_and(|n| n == *name))
+                    .ok_or_else(|| {
+                        MugenError::AudioDevice(format!("no output device named {name}"))
+                    })?;
+                OutputStreamBuilder::from_device(device).map_err(MugenError::audio_device)?
+            }
+        };
+
+        if let Some(sample_rate) = self.sample_rate {
+            builder = builder.with_sample_rate(sample_rate);
+        }
+        if let Some(channels) = channels {
+            builder = builder.with_channels(channels);
+        }
+        if let Some(frames) = self.buffer_size {
+            builder = builder.with_buffer_size(BufferSize::Fixed(frames));
+        }
+
+        builder.open_stream().map_err(MugenError::audio_device)
+    }
+}
+
+/// Averages each frame down to one channel, the device stream spreads it
+/// back over its own channels
+struct Downmix {
+    input: PatchSource,
+}
+
+impl Iterator for Downmix {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let channels = self.input.channels().max(1);
+        let mut sum = 0.0;
+        for _ in 0..channels {
+            sum += self.input.next()?;
+        }
+
+        Some(sum / f32::from(channels))
+    }
+}
+
+impl Source for Downmix {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
 
+/// Voices play into `mixer`, whose output runs through the master bus and is
+/// tapped for the capture and the recorder on its way to the device stream.
+/// `channels` is the layout after any downmix, what the capture and
+/// recordings see. A pinned voice is also tapped on its own into
+/// `voice_capture`
 pub struct Player {
-    pub stream: OutputStream,
-    voices: HashMap<Keycode, Vec<ActiveVoice>>,
+    /// Kept open as long as the player, `None` for one that plays nowhere
+    _stream: Option<OutputStream>,
+    pub mixer: Mixer,
+    pub recorder: Recorder,
+    channels: u16,
+    sample_rate: u32,
+    voices: HashMap<VoiceKey, Vec<ActiveVoice>>,
+    max_voices: usize,
+    next_seq: u64,
+    capture: Capture,
+    voice_capture: Capture,
+    /// Voice the scope is pinned to, by insertion order
+    pinned: Option<u64>,
+    /// Something about the stream the user should hear of, see `take_warning`
+    warning: Option<String>,
 }
 
 impl Player {
-    pub fn new() -> Result<Self, Box<dyn Error + Send + Sync>> {
-        let mut stream = OutputStreamBuilder::open_default_stream()?;
+    pub fn new(
+        capture: &Capture,
+        voice_capture: &Capture,
+        master: &FxChain,
+        options: &StreamOptions,
+    ) -> Result<Self> {
+        let (mut stream, warning) = options.open()?;
         stream.log_on_drop(false);
 
+        let sample_rate = stream.config().sample_rate();
+        // voices can be stereo once panned, left to the device a mono
+        // output would keep only their left side
+        let downmix = options.mono || stream.config().channel_count() == 1;
+        let (mix_channels, channels) = if downmix {
+            (2, 1)
+        } else {
+            let channels = stream.config().channel_count();
+            (channels, channels)
+        };
+
+        let (mixer, source) = mixer::mixer(mix_channels, sample_rate);
+        // an empty mixer ends, the silent source keeps it alive between notes
+        mixer.add(Zero::new(mix_channels, sample_rate));
+
+        // the master bus hears every voice, tails and all
+        let source = master.apply(Box::new(source));
+        let source: PatchSource = if downmix {
+            Box::new(Downmix { input: source })
+        } else {
+            source
+        };
+
+        let latency_frames = match (options.record_latency_ms, stream.config().buffer_size()) {
+            (Some(ms), _) => u64::from(ms) * u64::from(sample_rate) / 1000,
+            (None, BufferSize::Fixed(frames)) => u64::from(*frames),
+            (None, BufferSize::Default) => 0,
+        };
+        let mut recorder = Recorder::new(usize::try_from(latency_frames).unwrap_or(0));
+        let record_max_s = options.record_max_s.unwrap_or(RECORD_MAX_S);
+        recorder.set_max_length(
+            (record_max_s > 0).then(|| Duration::from_secs(u64::from(record_max_s))),
+        );
+        stream.mixer().add(TapSource::new(
+            source,
+            capture.clone(),
+            recorder.tap(channels),
+        ));
+
         Ok(Self {
-            stream,
+            _stream: Some(stream),
+            mixer,
+            recorder,
+            channels,
+            sample_rate,
             voices: HashMap::new(),
+            max_voices: MAX_VOICES,
+            next_seq: 0,
+            capture: capture.clone(),
+            voice_capture: voice_capture.clone(),
+            pinned: None,
+            warning,
         })
     }
 
-    pub fn add_voice(&mut self, keycode: Keycode, sink: Sink, gate: Gate) {
-        self.voices.entry(keycode).or_default().push((sink, gate));
+    /// A player with no device behind it, its voices are never pulled
+    #[cfg(test)]
+    fn detached(capture: &Capture, voice_capture: &Capture) -> Self {
+        let (mixer, _) = mixer::mixer(2, 48_000);
+
+        Self {
+            _stream: None,
+            mixer,
+            recorder: Recorder::new(0),
+            channels: 2,
+            sample_rate: 48_000,
+            voices: HashMap::new(),
+            max_voices: MAX_VOICES,
+            next_seq: 0,
+            capture: capture.clone(),
+            voice_capture: voice_capture.clone(),
+            pinned: None,
+            warning: None,
+        }
+    }
+
+    /// The warning from opening the stream, once. Printing it from here
+    /// would land under the UI's alternate screen
+    pub fn take_warning(&mut self) -> Option<String> {
+        self.warning.take()
+    }
+
+    /// Starts recording the output, or stops the running recording and
+    /// leaves its file to be finalized. Returns the recording in progress
+    /// afterwards
+    pub fn toggle_recording(&mut self) -> io::Result<Option<Recording>> {
+        if self.recorder.recording().is_some() {
+            self.recorder.stop();
+            Ok(None)
+        } else {
+            self.recorder
+                .start(Path::new(RECORD_DIR), self.channels, self.sample_rate)
+                .map(Some)
+        }
+    }
+
+    #[must_use]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    #[must_use]
+    pub fn max_voices(&self) -> usize {
+        self.max_voices
+    }
+
+    /// Voices over the new limit are stolen on the next note
+    pub fn set_max_voices(&mut self, max_voices: usize) {
+        self.max_voices = max_voices.max(1);
     }
 
-    pub fn stop_note(&mut self, keycode: Keycode) {
-        if let Some(voices) = self.voices.get_mut(&keycode) {
-            for (_, gate) in voices {
-                gate.store(false, Ordering::Relaxed);
+    /// Notes sounding, including ones still in their release
+    #[must_use]
+    pub fn voice_count(&self) -> usize {
+        self.voices.values().map(Vec::len).sum()
+    }
+
+    /// Steals voices until there is room for one more, then adds it. A key
+    /// or MIDI note pressed again while its last note is still releasing
+    /// fades that tail out, rather than sounding twice
+    pub fn add_voice(
+        &mut self,
+        key: VoiceKey,
+        sinks: Vec<Sink>,
+        gate: Gate,
+        pitch: Pitch,
+        cut: Cut,
+        tap: VoiceTap,
+    ) {
+        self.clear_finished();
+        if matches!(key, VoiceKey::Key(_) | VoiceKey::Midi(_)) {
+            self.cut_released(key);
+        }
+        while self.voice_count() >= self.max_voices && self.steal_voice() {}
+
+        self.voices.entry(key).or_default().push(ActiveVoice {
+            sinks,
+            gate,
+            pitch,
+            cut,
+            tap,
+            started: Instant::now(),
+            seq: self.next_seq,
+        });
+        self.next_seq += 1;
+    }
+
+    /// Fades out the voices under `key` that are already releasing
+    fn cut_released(&mut self, key: VoiceKey) {
+        let Some(voices) = self.voices.get_mut(&key) else {
+            return;
+        };
+
+        let (released, held) = voices.drain(..).partition(ActiveVoice::released);
+        *voices = held;
+        if voices.is_empty() {
+            self.voices.remove(&key);
+        }
+        for voice in released {
+            voice.stop();
+        }
+    }
+
+    /// Stops the oldest released voice, or the oldest held one if nothing
+    /// is releasing. False when there was nothing to steal
+    fn steal_voice(&mut self) -> bool {
+        let victim = self
+            .voices
+            .iter()
+            .flat_map(|(key, voices)| voices.iter().map(move |v| (*key, v)))
+            .min_by_key(|(_, v)| (!v.released(), v.seq))
+            .map(|(key, v)| (key, v.seq));
+
+        let Some((key, seq)) = victim else {
+            return false;
+        };
+
+        if let Some(voices) = self.voices.get_mut(&key) {
+            if let Some(i) = voices.iter().position(|v| v.seq == seq) {
+                voices.remove(i).stop();
+            }
+            if voices.is_empty() {
+                self.voices.remove(&key);
             }
         }
+
+        true
     }
 
-    pub fn kill_all(&mut self) {
-        for (_, mut voices) in self.voices.drain() {
-            for (sink, gate) in voices.drain(..) {
-                gate.store(false, Ordering::Relaxed);
-                sink.stop();
+    /// Tap for a new voice's sources, handed back with it to `add_voice`
+    #[must_use]
+    pub fn voice_tap(&self) -> VoiceTap {
+        VoiceTap::new(self.voice_capture.clone(), self.capture.clone())
+    }
+
+    /// Pins the scope to the next sounding voice in the order they started,
+    /// back to the whole mix after the newest
+    pub fn pin_next_voice(&mut self) {
+        let mut voices: Vec<&ActiveVoice> = self.voices.values().flatten().collect();
+        voices.sort_by_key(|voice| voice.seq);
+
+        let next = match self.pinned {
+            Some(seq) => voices.iter().find(|voice| voice.seq > seq),
+            None => voices.first(),
+        };
+        self.pinned = next.map(|voice| voice.seq);
+
+        for voice in voices {
+            voice.tap.set_pinned(Some(voice.seq) == self.pinned);
+        }
+    }
+
+    /// Target frequency of the voice the scope is pinned to, `None` while it
+    /// shows the mix or once that voice has ended
+    #[must_use]
+    pub fn pinned_pitch(&self) -> Option<f32> {
+        let seq = self.pinned?;
+        self.voices
+            .values()
+            .flatten()
+            .find(|voice| voice.seq == seq)
+            .map(|voice| voice.pitch.get().hz)
+    }
+
+    /// Drops the gate of every voice that started more than `max` ago
+    pub fn release_expired(&mut self, max: Duration, now: Instant) {
+        for voice in self.voices.values().flatten() {
+            if now.duration_since(voice.started) >= max {
+                voice.gate.store(false, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Moves the held voices under `key` to a new frequency without
+    /// restarting them, releasing ones keep their pitch. The new note gets
+    /// the whole `release_expired` limit
+    pub fn retune(&mut self, key: VoiceKey, target: PitchTarget) {
+        if let Some(voices) = self.voices.get_mut(&key) {
+            let now = Instant::now();
+            for voice in voices.iter_mut().filter(|voice| !voice.released()) {
+                voice.pitch.set(target);
+                voice.started = now;
             }
         }
     }
 
+    /// Target frequency of the newest voice under `key`
+    #[must_use]
+    pub fn last_pitch(&self, key: VoiceKey) -> Option<f32> {
+        let voices = self.voices.get(&key)?;
+        let newest = voices.iter().max_by_key(|voice| voice.seq)?;
+
+        Some(newest.pitch.get().hz)
+    }
+
+    /// Target frequencies of every voice that has not been released
+    #[must_use]
+    pub fn held_pitches(&self) -> Vec<f32> {
+        self.voices
+            .values()
+            .flatten()
+            .filter(|voice| !voice.released())
+            .map(|voice| voice.pitch.get().hz)
+            .collect()
+    }
+
+    pub fn stop_note(&mut self, key: VoiceKey) {
+        if let Some(voices) = self.voices.get(&key) {
+            for voice in voices {
+                voice.gate.store(false, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn kill_all(&mut self) {
+        for voice in self.voices.drain().flat_map(|(_, voices)| voices) {
+            voice.stop();
+        }
+    }
+
     pub fn clear_finished(&mut self) {
         self.voices.retain(|_, voices| {
-            voices.retain(|(sink, _)| !sink.empty());
+            voices.retain(|voice| !voice.sinks.iter().all(Sink::empty));
             !voices.is_empty()
         });
     }
 
+    fn sinks(&self) -> impl Iterator<Item = &Sink> {
+        self.voices
+            .values()
+            .flatten()
+            .flat_map(|voice| &voice.sinks)
+    }
+
     pub fn set_volume(&mut self, volume: f32) {
-        for voices in self.voices.values_mut() {
-            for (sink, _) in voices {
-                sink.set_volume(volume);
-            }
+        for sink in self.sinks() {
+            sink.set_volume(volume);
         }
     }
 
     pub fn set_muted(&mut self, muted: bool) {
-        for voices in self.voices.values_mut() {
-            for (sink, _) in voices {
-                if muted {
-                    sink.pause();
-                } else {
-                    sink.play();
-                }
+        for sink in self.sinks() {
+            if muted {
+                sink.pause();
+            } else {
+                sink.play();
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    fn player() -> Player {
+        Player::detached(&Capture::new(), &Capture::new())
+    }
+
+    /// Starts a voice under `key` that never ends, returning its gate
+    fn start(player: &mut Player, key: VoiceKey, hz: f32) -> Gate {
+        start_cut(player, key, hz).0
+    }
+
+    /// `start`, with the voice's cut flag too
+    fn start_cut(player: &mut Player, key: VoiceKey, hz: f32) -> (Gate, Cut) {
+        let gate: Gate = Arc::new(AtomicBool::new(true));
+        let cut: Cut = Arc::new(AtomicBool::new(false));
+        let sink = Sink::connect_new(&player.mixer);
+        sink.append(Zero::new(1, 48_000));
+
+        player.add_voice(
+            key,
+            vec![sink],
+            gate.clone(),
+            Pitch::new(PitchTarget { hz, glide_s: 0.0 }),
+            cut.clone(),
+            player.voice_tap(),
+        );
+        (gate, cut)
+    }
+
+    #[test]
+    fn notes_past_the_limit_are_released() {
+        let mut player = player();
+        let long = start(&mut player, VoiceKey::Midi(60), 261.6);
+        let short = start(&mut player, VoiceKey::Midi(64), 329.6);
+        let now = Instant::now();
+        let max = Duration::from_millis(500);
+
+        player.release_expired(max, now);
+        assert!(long.load(Ordering::Relaxed) && short.load(Ordering::Relaxed));
+
+        player.voices.get_mut(&VoiceKey::Midi(60)).unwrap()[0].started = now - max;
+        player.release_expired(max, now);
+        assert!(!long.load(Ordering::Relaxed));
+        assert!(short.load(Ordering::Relaxed));
+
+        // released voices ring out rather than being dropped
+        assert_eq!(player.voice_count(), 2);
+    }
+
+    #[test]
+    fn a_legato_note_gets_the_whole_limit() {
+        let mut player = player();
+        let gate = start(&mut player, VoiceKey::Mono, 261.6);
+        let max = Duration::from_millis(500);
+        player.voices.get_mut(&VoiceKey::Mono).unwrap()[0].started = Instant::now() - max;
+
+        player.retune(
+            VoiceKey::Mono,
+            PitchTarget {
+                hz: 329.6,
+                glide_s: 0.0,
+            },
+        );
+        player.release_expired(max, Instant::now());
+        assert!(gate.load(Ordering::Relaxed), "cut on the line's age");
+
+        player.release_expired(max, Instant::now() + max);
+        assert!(!gate.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn retrigger_fades_out_the_release_tail() {
+        let mut player = player();
+        let key = VoiceKey::Key(Keycode::A);
+        let (_, first) = start_cut(&mut player, key, 440.0);
+        player.stop_note(key);
+
+        let (gate, second) = start_cut(&mut player, key, 440.0);
+        assert!(first.load(Ordering::Relaxed));
+        assert!(!second.load(Ordering::Relaxed) && gate.load(Ordering::Relaxed));
+        assert_eq!(player.voice_count(), 1);
+
+        // the arpeggiator's notes are left to ring over each other
+        start(&mut player, VoiceKey::Arp, 440.0);
+        player.stop_note(VoiceKey::Arp);
+        start(&mut player, VoiceKey::Arp, 440.0);
+        assert_eq!(player.voice_count(), 3);
+    }
+
+    #[test]
+    fn stealing_takes_the_oldest_and_prefers_released_voices() {
+        let mut player = player();
+        player.set_max_voices(2);
+
+        let (_, a) = start_cut(&mut player, VoiceKey::Midi(60), 261.6);
+        let (_, b) = start_cut(&mut player, VoiceKey::Midi(62), 293.7);
+        let (_, c) = start_cut(&mut player, VoiceKey::Midi(64), 329.6);
+        assert!(a.load(Ordering::Relaxed), "the oldest goes first");
+        assert!(!b.load(Ordering::Relaxed) && !c.load(Ordering::Relaxed));
+        assert_eq!(player.voice_count(), 2);
+
+        // a releasing voice goes before an older held one
+        player.stop_note(VoiceKey::Midi(64));
+        let (_, d) = start_cut(&mut player, VoiceKey::Midi(65), 349.2);
+        assert!(c.load(Ordering::Relaxed));
+        assert!(!b.load(Ordering::Relaxed) && !d.load(Ordering::Relaxed));
+        assert_eq!(player.voice_count(), 2);
+    }
+}
@@ -0,0 +1,681 @@
+//! Records the master output to a 16-bit WAV file from a background thread
+
+use crate::config::{RECORD_CHUNK_FRAMES, RECORD_SPARE_CHUNKS};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone)]
+pub struct Recording {
+    pub path: PathBuf,
+    pub started: Instant,
+    /// Length the recording stops itself at
+    pub limit: Option<Duration>,
+}
+
+/// A session's channels as the tap holds them: filled chunks go out to the
+/// writer, emptied ones come back to be filled again
+struct Link {
+    session: u64,
+    chunks: Sender<Vec<f32>>,
+    spare: Receiver<Vec<f32>>,
+}
+
+/// Hand-off between the control side and the tap: `active` says whether to
+/// record, `pending` carries a new session's link until the tap claims it
+#[derive(Default)]
+struct Shared {
+    active: AtomicBool,
+    /// Counts sessions started, a tap still on an older one lets it go
+    session: AtomicU64,
+    pending: Mutex<Option<Link>>,
+}
+
+/// Control side, owned by the player. The tap sits ahead of the device
+/// buffer, so what it passes is heard `latency_frames` later; a recording
+/// starts that far back, with what was playing when it was asked for
+pub struct Recorder {
+    shared: Arc<Shared>,
+    session: Option<(Recording, JoinHandle<io::Result<()>>)>,
+    /// Stopped sessions whose writer has yet to finalize the file
+    finishing: Vec<(Recording, JoinHandle<io::Result<()>>)>,
+    latency_frames: usize,
+    max_length: Option<Duration>,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Recorder {
+    #[must_use]
+    pub fn new(latency_frames: usize) -> Self {
+        Self {
+            shared: Arc::new(Shared::default()),
+            session: None,
+            finishing: Vec::new(),
+            latency_frames,
+            max_length: None,
+        }
+    }
+
+    /// Recordings started from here on stop themselves after `max_length`
+    pub fn set_max_length(&mut self, max_length: Option<Duration>) {
+        self.max_length = max_length;
+    }
+
+    /// Writer half that goes into an output tap of `channels`
+    #[must_use]
+    pub fn tap(&self, channels: u16) -> RecordTap {
+        RecordTap {
+            shared: Arc::clone(&self.shared),
+            link: None,
+            chunk: Vec::new(),
+            preroll: vec![0.0; self.latency_frames * usize::from(channels.max(1))],
+            preroll_pos: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn recording(&self) -> Option<&Recording> {
+        self.session.as_ref().map(|(recording, _)| recording)
+    }
+
+    /// Opens a new file under `dir`, normally `RECORD_DIR`. Any running
+    /// session is stopped first
+    pub fn start(&mut self, dir: &Path, channels: u16, sample_rate: u32) -> io::Result<Recording> {
+        self.stop();
+
+        fs::create_dir_all(dir)?;
+        let (path, file) = create_stamped(dir, "wav")?;
+        let wav = WavWriter::new(file, channels, sample_rate)?;
+        let (tx, rx): (Sender<Vec<f32>>, Receiver<Vec<f32>>) = mpsc::channel();
+        let (spare_tx, spare_rx) = mpsc::channel();
+        // room for the preroll and a whole chunk, filled in on the audio thread
+        let capacity = (self.latency_frames + RECORD_CHUNK_FRAMES) * usize::from(channels.max(1));
+        for _ in 0..RECORD_SPARE_CHUNKS {
+            let _ = spare_tx.send(Vec::with_capacity(capacity));
+        }
+        let max_samples = max_samples(self.max_length, channels, sample_rate);
+
+        let shared = Arc::clone(&self.shared);
+        let writer =
+            thread::spawn(move || write_session(wav, &rx, &spare_tx, max_samples, &shared));
+
+        let session = self.shared.session.fetch_add(1, Ordering::AcqRel) + 1;
+        *self
+            .shared
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Link {
+            session,
+            chunks: tx,
+            spare: spare_rx,
+        });
+        self.shared.active.store(true, Ordering::Release);
+
+        let recording = Recording {
+            path,
+            started: Instant::now(),
+            limit: self.max_length,
+        };
+        self.session = Some((recording.clone(), writer));
+
+        Ok(recording)
+    }
+
+    /// A stopped recording once its file is finalized, including one that
+    /// stopped itself at its limit. Checked by the engine every tick, so
+    /// nothing on the engine's side ever waits for the writer
+    pub fn poll(&mut self) -> Option<io::Result<Recording>> {
+        if self
+            .session
+            .as_ref()
+            .is_some_and(|(_, writer)| writer.is_finished())
+        {
+            self.stop();
+        }
+
+        let done = self
+            .finishing
+            .iter()
+            .position(|(_, writer)| writer.is_finished())?;
+        let (recording, writer) = self.finishing.swap_remove(done);
+        let res = match writer.join() {
+            Ok(res) => res.map(|()| recording),
+            Err(_) => Err(io::Error::other("recording writer panicked")),
+        };
+
+        Some(res)
+    }
+
+    /// Lets the tap flush what it holds and leaves the writer to finalize
+    /// the header, which `poll` reports. The tap lets go on its next frame,
+    /// so a stalled output keeps the file unfinished until it runs again.
+    /// Returns the stopped recording, if one was running
+    pub fn stop(&mut self) -> Option<Recording> {
+        let (recording, writer) = self.session.take()?;
+
+        self.shared.active.store(false, Ordering::Release);
+        // a link the tap never claimed would keep the writer waiting
+        self.shared
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+
+        self.finishing.push((recording.clone(), writer));
+        Some(recording)
+    }
+
+    /// Whether a stopped recording is still being finalized
+    #[must_use]
+    pub fn finishing(&self) -> bool {
+        !self.finishing.is_empty()
+    }
+
+    /// Stops waiting on recordings still being finalized, the writers finish
+    /// them whenever the output runs again. An error naming the first, if
+    /// there were any
+    pub fn abandon(&mut self) -> Option<io::Error> {
+        let (recording, _) = self.finishing.drain(..).next()?;
+        Some(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!(
+                "{} is still being written, the output stopped",
+                recording.path.display()
+            ),
+        ))
+    }
+}
+
+/// Samples a session may write: `max_length` of them, and never more than
+/// a WAV's 32-bit sizes can count, so even an unlimited session stops short
+/// of 4 GiB. Always whole frames
+fn max_samples(max_length: Option<Duration>, channels: u16, sample_rate: u32) -> u64 {
+    let channels = u64::from(channels.max(1));
+    let wav_max = u64::from(WAV_MAX_DATA_BYTES / 2) / channels * channels;
+
+    max_length.map_or(wav_max, |max| {
+        let frames = (max.as_secs_f64() * f64::from(sample_rate)).round() as u64;
+        (frames * channels).min(wav_max)
+    })
+}
+
+/// Writes chunks until the tap lets go, or until `max_samples` are in the
+/// file. Then the tap is told to stop and anything past the limit dropped,
+/// so the file ends on exactly that sample. Written chunks go back to the
+/// tap through `spare`
+fn write_session(
+    mut wav: WavWriter,
+    rx: &Receiver<Vec<f32>>,
+    spare: &Sender<Vec<f32>>,
+    max_samples: u64,
+    shared: &Shared,
+) -> io::Result<()> {
+    let mut written: u64 = 0;
+
+    for mut chunk in rx {
+        let room = usize::try_from(max_samples - written)
+            .unwrap_or(usize::MAX)
+            .min(chunk.len());
+        wav.write(&chunk[..room])?;
+        written += room as u64;
+        chunk.clear();
+        let _ = spare.send(chunk);
+
+        if written >= max_samples {
+            shared.active.store(false, Ordering::Release);
+            break;
+        }
+    }
+
+    wav.finish()
+}
+
+/// Audio-thread half. Sessions start and stop only on frame boundaries and
+/// samples leave in chunks, so the file never gets a partial frame. Chunks
+/// are buffers the writer hands back, so nothing is allocated here while it
+/// keeps up
+pub struct RecordTap {
+    shared: Arc<Shared>,
+    link: Option<Link>,
+    chunk: Vec<f32>,
+    /// The output latency's worth of frames passed while idle, oldest at
+    /// `preroll_pos`. Silence until the stream has played that long
+    preroll: Vec<f32>,
+    preroll_pos: usize,
+}
+
+impl RecordTap {
+    /// `channel` is the sample's position in its frame of `channels`
+    pub fn push(&mut self, x: f32, channel: usize, channels: usize) {
+        if self.link.is_none() && !self.claim(channel) {
+            if !self.preroll.is_empty() {
+                self.preroll[self.preroll_pos] = x;
+                self.preroll_pos = (self.preroll_pos + 1) % self.preroll.len();
+            }
+            return;
+        }
+
+        let Some(link) = &self.link else {
+            return;
+        };
+
+        self.chunk.push(x);
+        if channel + 1 != channels {
+            return;
+        }
+
+        let stopping = !self.shared.active.load(Ordering::Acquire)
+            || self.shared.session.load(Ordering::Acquire) != link.session;
+        if stopping || self.chunk.len() >= RECORD_CHUNK_FRAMES * channels {
+            let next = if stopping {
+                Vec::new()
+            } else {
+                // a fresh buffer only if the writer has fallen behind
+                link.spare
+                    .try_recv()
+                    .unwrap_or_else(|_| Vec::with_capacity(RECORD_CHUNK_FRAMES * channels))
+            };
+            let chunk = std::mem::replace(&mut self.chunk, next);
+            // the writer hangs up once the file is at its limit
+            if link.chunks.send(chunk).is_err() {
+                self.link = None;
+                return;
+            }
+        }
+
+        if stopping {
+            self.link = None;
+        }
+    }
+}
+
+impl RecordTap {
+    /// Takes a new session's link on a frame boundary and starts its first
+    /// chunk with the preroll
+    fn claim(&mut self, channel: usize) -> bool {
+        if channel != 0 || !self.shared.active.load(Ordering::Acquire) {
+            return false;
+        }
+
+        // the control side only holds this lock briefly, try again next frame
+        let Ok(mut pending) = self.shared.pending.try_lock() else {
+            return false;
+        };
+        let Some(link) = pending.take() else {
+            return false;
+        };
+
+        // the session starts with buffers big enough for the preroll
+        if let Ok(buffer) = link.spare.try_recv() {
+            self.chunk = buffer;
+        }
+        self.chunk.clear();
+        self.link = Some(link);
+        let (newer, older) = self.preroll.split_at(self.preroll_pos);
+        self.chunk.extend_from_slice(older);
+        self.chunk.extend_from_slice(newer);
+        self.preroll.fill(0.0);
+        self.preroll_pos = 0;
+
+        true
+    }
+}
+
+impl Drop for RecordTap {
+    fn drop(&mut self) {
+        if let Some(link) = self.link.take() {
+            let _ = link.chunks.send(std::mem::take(&mut self.chunk));
+        }
+    }
+}
+
+/// Creates a new `mugen-<unix time>.<ext>` under `dir`, counting up a
+/// suffix while the name is taken, so a second file in the same second
+/// never truncates the first
+pub(crate) fn create_stamped(dir: &Path, ext: &str) -> io::Result<(PathBuf, File)> {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+
+    for n in 1.. {
+        let name = if n == 1 {
+            format!("mugen-{stamp}.{ext}")
+        } else {
+            format!("mugen-{stamp}-{n}.{ext}")
+        };
+        let path = dir.join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    unreachable!("ran out of suffixes")
+}
+
+/// Largest data chunk whose RIFF size, 36 bytes more, still fits in 32 bits
+const WAV_MAX_DATA_BYTES: u32 = u32::MAX - 36;
+
+/// PCM WAV with the RIFF and data sizes patched in on `finish`. Writing
+/// more than `WAV_MAX_DATA_BYTES` is the caller's to prevent
+struct WavWriter {
+    out: BufWriter<File>,
+    data_bytes: u32,
+}
+
+impl WavWriter {
+    fn new(file: File, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        let mut out = BufWriter::new(file);
+        let block_align = channels * 2;
+
+        out.write_all(b"RIFF")?;
+        out.write_all(&0u32.to_le_bytes())?;
+        out.write_all(b"WAVEfmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?;
+        out.write_all(&channels.to_le_bytes())?;
+        out.write_all(&sample_rate.to_le_bytes())?;
+        out.write_all(&(sample_rate * u32::from(block_align)).to_le_bytes())?;
+        out.write_all(&block_align.to_le_bytes())?;
+        out.write_all(&16u16.to_le_bytes())?;
+        out.write_all(b"data")?;
+        out.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self { out, data_bytes: 0 })
+    }
+
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        for s in samples {
+            let s = (s.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+            self.out.write_all(&s.to_le_bytes())?;
+        }
+        self.data_bytes = self.data_bytes.saturating_add((samples.len() * 2) as u32);
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out.write_all(&(36 + self.data_bytes).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(40))?;
+        self.out.write_all(&self.data_bytes.to_le_bytes())?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of its own under the system temp dir for a test's files
+    fn scratch_dir(test: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("mugen-recorder-{}-{test}", std::process::id()))
+    }
+
+    /// Starts a session by hand with `spare` buffers for the tap, returning
+    /// where its chunks arrive
+    fn listen(recorder: &Recorder, spare: Vec<Vec<f32>>) -> Receiver<Vec<f32>> {
+        let (tx, rx) = mpsc::channel();
+        let (spare_tx, spare_rx) = mpsc::channel();
+        for buffer in spare {
+            spare_tx.send(buffer).unwrap();
+        }
+
+        let session = recorder.shared.session.fetch_add(1, Ordering::AcqRel) + 1;
+        *recorder.shared.pending.lock().unwrap() = Some(Link {
+            session,
+            chunks: tx,
+            spare: spare_rx,
+        });
+        recorder.shared.active.store(true, Ordering::Release);
+        rx
+    }
+
+    /// Runs `idle` silent frames, then records `frames` through a tap with
+    /// `latency` frames of alignment and returns what reached the writer
+    fn record(latency: usize, channels: u16, idle: usize, frames: &[Vec<f32>]) -> Vec<f32> {
+        let recorder = Recorder::new(latency);
+        let mut tap = recorder.tap(channels);
+        let channels = usize::from(channels);
+
+        for _ in 0..idle {
+            for ch in 0..channels {
+                tap.push(0.0, ch, channels);
+            }
+        }
+
+        let rx = listen(&recorder, Vec::new());
+        for frame in frames {
+            for (ch, &x) in frame.iter().enumerate() {
+                tap.push(x, ch, channels);
+            }
+        }
+        recorder.shared.active.store(false, Ordering::Release);
+        drop(tap);
+
+        rx.into_iter().flatten().collect()
+    }
+
+    fn onset(at: usize, len: usize) -> Vec<Vec<f32>> {
+        (0..len)
+            .map(|i| vec![if i == at { 1.0 } else { 0.0 }])
+            .collect()
+    }
+
+    #[test]
+    fn latency_shifts_the_onset_later() {
+        let plain = record(0, 1, 10, &onset(4, 20));
+        let aligned = record(3, 1, 10, &onset(4, 20));
+
+        assert_eq!(plain.iter().position(|&x| x == 1.0), Some(4));
+        assert_eq!(aligned.iter().position(|&x| x == 1.0), Some(7));
+        assert_eq!(aligned.len(), plain.len() + 3);
+    }
+
+    #[test]
+    fn preroll_keeps_what_played_before_the_start() {
+        let recorder = Recorder::new(2);
+        let mut tap = recorder.tap(1);
+        for x in [0.1, 0.2, 0.3, 0.4, 0.5] {
+            tap.push(x, 0, 1);
+        }
+
+        let rx = listen(&recorder, Vec::new());
+        tap.push(0.6, 0, 1);
+        drop(tap);
+
+        let out: Vec<f32> = rx.into_iter().flatten().collect();
+        assert_eq!(out, [0.4, 0.5, 0.6]);
+    }
+
+    #[test]
+    fn stereo_preroll_stays_frame_aligned() {
+        let frames: Vec<Vec<f32>> = (0..6).map(|i| vec![i as f32, -(i as f32)]).collect();
+        let out = record(2, 2, 3, &frames);
+
+        assert_eq!(&out[..4], [0.0, 0.0, 0.0, 0.0]);
+        for frame in out[4..].chunks(2) {
+            assert_eq!(frame[0], -frame[1]);
+        }
+        assert_eq!(out.len(), (2 + 6) * 2);
+    }
+
+    #[test]
+    fn limit_finalizes_the_file_at_exactly_its_length() {
+        const RATE: u32 = 48_000;
+        let dir = scratch_dir("limit");
+        let mut recorder = Recorder::new(0);
+        recorder.set_max_length(Some(Duration::from_millis(10)));
+        let mut tap = recorder.tap(2);
+        let recording = recorder.start(&dir, 2, RATE).unwrap();
+
+        for _ in 0..RECORD_CHUNK_FRAMES * 2 {
+            tap.push(0.5, 0, 2);
+            tap.push(-0.5, 1, 2);
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let done = loop {
+            if let Some(done) = recorder.poll() {
+                break done.unwrap();
+            }
+            assert!(Instant::now() < deadline, "recording never stopped");
+            thread::sleep(Duration::from_millis(1));
+        };
+        assert_eq!(done.path, recording.path);
+        assert!(recorder.recording().is_none());
+
+        tap.push(0.5, 0, 2);
+        tap.push(-0.5, 1, 2);
+        assert!(tap.link.is_none(), "the tap kept recording");
+
+        let bytes = fs::read(&done.path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        // 10 ms of 16-bit stereo
+        let data = (RATE as usize / 100) * 2 * 2;
+        let le = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+        assert_eq!(le(40), data);
+        assert_eq!(le(4), 36 + data);
+        assert_eq!(bytes.len(), 44 + data);
+    }
+
+    #[test]
+    fn chunks_fill_the_buffers_handed_back() {
+        let recorder = Recorder::new(0);
+        let mut tap = recorder.tap(1);
+        let spare: Vec<Vec<f32>> = (0..3)
+            .map(|_| Vec::with_capacity(RECORD_CHUNK_FRAMES))
+            .collect();
+        let ptrs: Vec<*const f32> = spare.iter().map(|buffer| buffer.as_ptr()).collect();
+        let rx = listen(&recorder, spare);
+
+        for _ in 0..RECORD_CHUNK_FRAMES * 3 {
+            tap.push(0.5, 0, 1);
+        }
+
+        let chunks: Vec<Vec<f32>> = rx.try_iter().collect();
+        assert_eq!(chunks.len(), 3);
+        for (chunk, ptr) in chunks.iter().zip(&ptrs) {
+            assert_eq!(chunk.len(), RECORD_CHUNK_FRAMES);
+            assert_eq!(chunk.as_ptr(), *ptr, "a new buffer was allocated");
+        }
+    }
+
+    #[test]
+    fn stop_never_waits_on_a_stalled_output() {
+        let dir = scratch_dir("stalled");
+        let mut recorder = Recorder::new(0);
+        let mut tap = recorder.tap(1);
+        let recording = recorder.start(&dir, 1, 48_000).unwrap();
+        // the tap claims the session, then the output stops calling it
+        tap.push(0.5, 0, 1);
+
+        let started = Instant::now();
+        assert_eq!(
+            recorder.stop().map(|r| r.path),
+            Some(recording.path.clone())
+        );
+        assert!(started.elapsed() < Duration::from_millis(50));
+        assert!(recorder.recording().is_none());
+        thread::sleep(Duration::from_millis(10));
+        assert!(recorder.poll().is_none(), "finished without the tap");
+        assert!(recorder.finishing());
+
+        // once it runs again the tap lets go and the file is finished
+        tap.push(0.5, 0, 1);
+        assert!(tap.link.is_none());
+        drop(tap);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let done = loop {
+            if let Some(done) = recorder.poll() {
+                break done.unwrap();
+            }
+            assert!(Instant::now() < deadline, "the file was never finished");
+            thread::sleep(Duration::from_millis(1));
+        };
+        assert_eq!(done.path, recording.path);
+        assert!(!recorder.finishing());
+        assert_eq!(fs::metadata(&done.path).unwrap().len(), 44 + 4);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn takes_in_the_same_second_get_their_own_files() {
+        let dir = scratch_dir("takes");
+        fs::create_dir_all(&dir).unwrap();
+
+        let (first, mut file) = create_stamped(&dir, "wav").unwrap();
+        file.write_all(b"first take").unwrap();
+        let (second, _) = create_stamped(&dir, "wav").unwrap();
+        let first_bytes = fs::read(&first).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(first_bytes, b"first take");
+    }
+
+    #[test]
+    fn an_unlimited_session_stops_before_the_wav_sizes_overflow() {
+        for channels in [1, 2, 3, 8] {
+            let max = max_samples(None, channels, 48_000);
+            let bytes = max * 2;
+
+            assert!(bytes <= u64::from(WAV_MAX_DATA_BYTES));
+            assert!(36 + bytes <= u64::from(u32::MAX));
+            assert_eq!(max % u64::from(channels), 0, "ends on a partial frame");
+        }
+
+        let day = Duration::from_secs(24 * 3600);
+        assert_eq!(
+            max_samples(Some(day), 2, 48_000),
+            max_samples(None, 2, 48_000)
+        );
+        assert_eq!(
+            max_samples(Some(Duration::from_millis(10)), 2, 48_000),
+            480 * 2
+        );
+    }
+
+    #[test]
+    fn wav_header_counts_the_data_written() {
+        let dir = scratch_dir("header");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("take.wav");
+
+        let mut wav = WavWriter::new(File::create(&path).unwrap(), 2, 44_100).unwrap();
+        wav.write(&[0.0, 0.5, -1.0, 2.0]).unwrap();
+        wav.write(&[0.25, -0.25]).unwrap();
+        wav.finish().unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let le16 = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+        let le32 = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(le32(4) as usize, bytes.len() - 8);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!((le16(20), le16(22)), (1, 2), "PCM in stereo");
+        assert_eq!(le32(24), 44_100);
+        assert_eq!(le32(28), 44_100 * 4, "bytes per second");
+        assert_eq!((le16(32), le16(34)), (4, 16), "frame size and bits");
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(le32(40), 12);
+
+        let samples: Vec<i16> = bytes[44..]
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        assert_eq!(samples, [0, 16_383, -32_767, 32_767, 8_191, -8_191]);
+    }
+}
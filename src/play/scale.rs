@@ -0,0 +1,152 @@
+//! Scale quantizer that snaps played notes into a chosen scale
+
+use crate::config::SEMITONES_PER_OCTAVE;
+use crate::play::key::{Key, Note};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scale {
+    Chromatic,
+    Major,
+    Minor,
+    HarmonicMinor,
+    Dorian,
+    MajorPentatonic,
+    MinorPentatonic,
+    Blues,
+}
+
+impl Scale {
+    pub const ALL: [Self; 8] = [
+        Self::Chromatic,
+        Self::Major,
+        Self::Minor,
+        Self::HarmonicMinor,
+        Self::Dorian,
+        Self::MajorPentatonic,
+        Self::MinorPentatonic,
+        Self::Blues,
+    ];
+
+    #[inline]
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Chromatic => "Chromatic",
+            Self::Major => "Major",
+            Self::Minor => "Minor",
+            Self::HarmonicMinor => "Harm Minor",
+            Self::Dorian => "Dorian",
+            Self::MajorPentatonic => "Maj Penta",
+            Self::MinorPentatonic => "Min Penta",
+            Self::Blues => "Blues",
+        }
+    }
+
+    /// Semitones above the root, ascending and starting at 0
+    #[must_use]
+    pub fn intervals(self) -> &'static [i32] {
+        match self {
+            Self::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            Self::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Self::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            Self::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11],
+            Self::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            Self::MajorPentatonic => &[0, 2, 4, 7, 9],
+            Self::MinorPentatonic => &[0, 3, 5, 7, 10],
+            Self::Blues => &[0, 3, 5, 6, 7, 10],
+        }
+    }
+}
+
+/// `Scale::Chromatic` leaves every note alone
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quantizer {
+    pub scale: Scale,
+    pub root: Note,
+}
+
+impl Quantizer {
+    #[inline]
+    #[must_use]
+    pub const fn new(scale: Scale, root: Note) -> Self {
+        Self { scale, root }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn enabled(self) -> bool {
+        self.scale != Scale::Chromatic
+    }
+
+    /// Nearest in-scale key. A note halfway between two scale notes goes
+    /// down, towards the root below it
+    #[must_use]
+    pub fn quantize(self, key: Key) -> Key {
+        let offset = (key.note.semitone() - self.root.semitone()).rem_euclid(SEMITONES_PER_OCTAVE);
+
+        // the root an octave up catches notes just under it
+        let mut best = 0;
+        for &degree in self.scale.intervals().iter().chain([&SEMITONES_PER_OCTAVE]) {
+            if (offset - degree).abs() < (offset - best).abs() {
+                best = degree;
+            }
+        }
+
+        key.transpose(best - offset)
+    }
+
+    /// Like "C Major"
+    #[must_use]
+    pub fn label(self) -> String {
+        format!("{} {}", self.root.name(), self.scale.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quantize(scale: Scale, root: Note, key: Key) -> Key {
+        Quantizer::new(scale, root).quantize(key)
+    }
+
+    #[test]
+    fn in_scale_notes_stay_put() {
+        for scale in Scale::ALL {
+            for root in Note::ALL {
+                for &degree in scale.intervals() {
+                    let key = Key::new(root, 3).transpose(degree);
+                    assert_eq!(quantize(scale, root, key), key, "{scale:?} on {root:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn off_scale_notes_snap_to_the_nearest() {
+        // Db is between C and D in C major, a tie goes down
+        assert_eq!(
+            quantize(Scale::Major, Note::C, Key::new(Note::Db, 4)),
+            Key::new(Note::C, 4)
+        );
+        // B in C major pentatonic is nearer the C above, across the octave
+        assert_eq!(
+            quantize(Scale::MajorPentatonic, Note::C, Key::new(Note::B, 4)),
+            Key::new(Note::C, 5)
+        );
+        // E in A minor pentatonic stays, F lands on E
+        assert_eq!(
+            quantize(Scale::MinorPentatonic, Note::A, Key::new(Note::F, 2)),
+            Key::new(Note::E, 2)
+        );
+    }
+
+    #[test]
+    fn chromatic_is_off() {
+        assert!(!Quantizer::new(Scale::Chromatic, Note::C).enabled());
+        for semitones in 0..24 {
+            let key = Key::new(Note::C, 3).transpose(semitones);
+            assert_eq!(quantize(Scale::Chromatic, Note::Eb, key), key);
+        }
+    }
+}
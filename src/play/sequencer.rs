@@ -0,0 +1,92 @@
+//! Step sequencer, a loop of steps that each play a note or rest, saved to
+//! `~/.config/mugen/sequence.toml` so the pattern survives a restart
+
+use crate::config::{SEQ_STEPS, SEQUENCE_PATH};
+use crate::play::key::Key;
+use crate::play::tempo::Tempo;
+use crate::settings::config_path;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sequence {
+    /// `None` is a rest
+    pub steps: Vec<Option<Key>>,
+    pub playing: bool,
+}
+
+/// On disk a rest is written as "-". The shared tempo is kept alongside
+#[derive(Debug, Serialize, Deserialize)]
+struct SequenceFile {
+    bpm: f32,
+    steps: Vec<String>,
+}
+
+const REST: &str = "-";
+
+impl Sequence {
+    /// Pads or cuts `steps` to `SEQ_STEPS`
+    #[must_use]
+    pub fn new(mut steps: Vec<Option<Key>>, playing: bool) -> Self {
+        steps.resize(SEQ_STEPS, None);
+
+        Self { steps, playing }
+    }
+
+    #[must_use]
+    pub fn empty() -> Self {
+        Self::new(Vec::new(), false)
+    }
+
+    pub fn clear(&mut self) {
+        self.steps.fill(None);
+    }
+
+    /// The saved pattern and tempo, `None` when there is none. Steps that
+    /// fail to parse become rests
+    #[must_use]
+    pub fn load() -> Option<(Self, Tempo)> {
+        let path = config_path(SEQUENCE_PATH)?;
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+            Err(err) => {
+                eprintln!("ignoring {}: {err}", path.display());
+                return None;
+            }
+        };
+
+        let file = match toml::from_str::<SequenceFile>(&text) {
+            Ok(file) => file,
+            Err(err) => {
+                eprintln!("ignoring {}: {err}", path.display());
+                return None;
+            }
+        };
+
+        let steps = file.steps.iter().map(|step| step.parse().ok()).collect();
+        Some((Self::new(steps, false), Tempo::new(file.bpm)))
+    }
+
+    /// Writes the steps and tempo, whether it is playing is not kept
+    pub fn save(&self, tempo: Tempo) -> io::Result<()> {
+        let path = config_path(SEQUENCE_PATH)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+
+        let file = SequenceFile {
+            bpm: tempo.bpm,
+            steps: self
+                .steps
+                .iter()
+                .map(|step| step.map_or_else(|| REST.to_string(), |key| key.to_string()))
+                .collect(),
+        };
+        let text = toml::to_string(&file).map_err(io::Error::other)?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)
+    }
+}
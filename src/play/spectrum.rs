@@ -0,0 +1,244 @@
+//! Magnitude spectrum of captured audio for the analyzer view
+
+use crate::config::{RESYNTH_MAX_HARMONIC, RESYNTH_MIN_GAP_BINS, RESYNTH_RANGE_DB};
+use fundsp::fft::real_fft;
+use std::f32::consts::TAU;
+
+/// Quietest level reported, also what silence maps to
+pub const FLOOR_DB: f32 = -100.0;
+
+/// Equal-loudness curve laid over the spectrum, IEC 61672
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Weighting {
+    Flat,
+    /// Follows hearing at moderate levels, cuts lows and the very top
+    A,
+    /// Nearly flat, rolls off only the extremes
+    C,
+}
+
+impl Weighting {
+    #[must_use]
+    pub fn next(self) -> Self {
+        match self {
+            Self::Flat => Self::A,
+            Self::A => Self::C,
+            Self::C => Self::Flat,
+        }
+    }
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Flat => "flat",
+            Self::A => "A-weighted",
+            Self::C => "C-weighted",
+        }
+    }
+
+    /// Gain in dB at `hz`, 0 at 1 kHz
+    #[must_use]
+    pub fn gain_db(self, hz: f32) -> f32 {
+        let f2 = f64::from(hz).powi(2);
+        let pole = |hz: f64| f2 + hz * hz;
+
+        let (response, offset) = match self {
+            Self::Flat => return 0.0,
+            Self::A => (
+                12_194f64.powi(2) * f2 * f2
+                    / (pole(20.6) * (pole(107.7) * pole(737.9)).sqrt() * pole(12_194.0)),
+                2.0,
+            ),
+            Self::C => (12_194f64.powi(2) * f2 / (pole(20.6) * pole(12_194.0)), 0.06),
+        };
+        (20.0 * response.max(1e-12).log10() + offset) as f32
+    }
+
+    /// Adds the curve to magnitudes in dB spaced `bin_hz` apart
+    pub fn apply(self, mags: &mut [f32], bin_hz: f32) {
+        if self == Self::Flat {
+            return;
+        }
+        for (k, db) in mags.iter_mut().enumerate() {
+            *db = (*db + self.gain_db(k as f32 * bin_hz)).max(FLOOR_DB);
+        }
+    }
+}
+
+/// Hann-windowed magnitudes in dBFS for bins `0..=n/2`, where `n` is the
+/// input length rounded up to a power of two and the tail is zero-padded
+#[must_use]
+pub fn magnitudes_db(samples: &[f32]) -> Vec<f32> {
+    let n = samples.len().next_power_of_two().clamp(2, 32_768);
+    let used = samples.len().min(n);
+
+    let mut data = vec![0.0; n];
+    for (i, (d, s)) in data.iter_mut().zip(samples).enumerate() {
+        let w = 0.5 - 0.5 * (TAU * i as f32 / used.max(2) as f32).cos();
+        *d = s * w;
+    }
+
+    let bins = real_fft(&mut data);
+    // the Hann window halves a full-scale sine, so it peaks at used / 4
+    let scale = 4.0 / used.max(1) as f32;
+
+    let mut out: Vec<f32> = bins.iter().map(|c| to_db(c.norm() * scale)).collect();
+
+    // real_fft packs the Nyquist bin into the imaginary part of DC
+    let nyquist = bins[0].im.abs() * scale;
+    out[0] = to_db(bins[0].re.abs() * scale);
+    out.push(to_db(nyquist));
+
+    out
+}
+
+/// Harmonic amplitudes, fundamental first, that approximate the `count`
+/// strongest peaks of a spectrum in dB, relative to the loudest. The lowest
+/// of those peaks is taken as the fundamental and each one lands on the
+/// nearest harmonic of it, peaks more than `RESYNTH_RANGE_DB` down are left
+/// out. Empty for a silent spectrum
+#[must_use]
+pub fn partials(mags_db: &[f32], count: usize) -> Vec<f32> {
+    let loudest = mags_db.iter().skip(1).fold(FLOOR_DB, |a, &b| a.max(b));
+    if loudest <= FLOOR_DB {
+        return Vec::new();
+    }
+
+    let mut peaks: Vec<usize> = (1..mags_db.len().saturating_sub(1))
+        .filter(|&k| mags_db[k] >= mags_db[k - 1] && mags_db[k] > mags_db[k + 1])
+        .filter(|&k| mags_db[k] > loudest - RESYNTH_RANGE_DB)
+        .collect();
+    peaks.sort_by(|&a, &b| mags_db[b].total_cmp(&mags_db[a]));
+
+    // the window's side lobes show up as small peaks beside a big one
+    let mut kept: Vec<usize> = Vec::with_capacity(count);
+    for k in peaks {
+        if kept.len() == count {
+            break;
+        }
+        if kept.iter().all(|&p| p.abs_diff(k) > RESYNTH_MIN_GAP_BINS) {
+            kept.push(k);
+        }
+    }
+    let peaks = kept;
+
+    let Some(&fundamental) = peaks.iter().min() else {
+        return Vec::new();
+    };
+
+    let mut harmonics = Vec::new();
+    for k in peaks {
+        let n = (k as f32 / fundamental as f32).round() as usize;
+        if n > RESYNTH_MAX_HARMONIC {
+            continue;
+        }
+        if harmonics.len() < n {
+            harmonics.resize(n, 0.0);
+        }
+        let amp = 10f32.powf((mags_db[k] - loudest) / 20.0);
+        harmonics[n - 1] = f32::max(harmonics[n - 1], amp);
+    }
+
+    harmonics
+}
+
+#[inline]
+fn to_db(amplitude: f32) -> f32 {
+    (20.0 * amplitude.max(1e-9).log10()).max(FLOOR_DB)
+}
+
+/// Level of the `hz` component of `samples` at `SAMPLE_RATE`, from one bin
+/// of a Hann-windowed DFT, so a full scale sine reads 1 whether or not it
+/// lands on an FFT bin
+#[cfg(test)]
+pub(crate) fn level(samples: &[f32], hz: f32) -> f32 {
+    let n = samples.len() as f32;
+    let (re, im) = samples
+        .iter()
+        .enumerate()
+        .fold((0.0, 0.0), |(re, im), (i, s)| {
+            let t = i as f32;
+            let w = 0.5 - 0.5 * (TAU * t / n).cos();
+            let phase = TAU * hz * t / crate::config::SAMPLE_RATE as f32;
+            (re + s * w * phase.cos(), im + s * w * phase.sin())
+        });
+    4.0 * re.hypot(im) / n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_weighting_is_flat_at_1k_and_cuts_the_lows() {
+        assert!(Weighting::A.gain_db(1000.0).abs() < 0.01);
+        assert!(Weighting::C.gain_db(1000.0).abs() < 0.01);
+
+        // IEC 61672 table values, rounded and at nominal frequencies
+        for (hz, db) in [
+            (31.5, -39.4),
+            (63.0, -26.2),
+            (125.0, -16.1),
+            (250.0, -8.6),
+            (500.0, -3.2),
+            (4000.0, 1.0),
+        ] {
+            let gain = Weighting::A.gain_db(hz);
+            assert!((gain - db).abs() < 0.2, "{hz} Hz: {gain}");
+        }
+        assert!((Weighting::C.gain_db(31.5) + 3.0).abs() < 0.1);
+        assert_eq!(Weighting::Flat.gain_db(31.5), 0.0);
+    }
+
+    #[test]
+    fn partials_follow_the_peaks() {
+        const RATE: f32 = 48_000.0;
+        const N: usize = 4096;
+
+        // a fundamental between bins and its third harmonic a quarter as loud
+        let hz = RATE / N as f32 * 40.3;
+        let samples: Vec<f32> = (0..N)
+            .map(|i| {
+                let t = i as f32 / RATE;
+                0.8 * (TAU * hz * t).sin() + 0.2 * (TAU * 3.0 * hz * t).sin()
+            })
+            .collect();
+
+        let harmonics = partials(&magnitudes_db(&samples), 8);
+        assert_eq!(harmonics.len(), 3, "{harmonics:?}");
+        assert!((harmonics[0] - 1.0).abs() < 1e-6);
+        assert!(harmonics[1] < 0.01, "{harmonics:?}");
+        assert!((harmonics[2] - 0.25).abs() < 0.05, "{harmonics:?}");
+
+        assert!(partials(&[FLOOR_DB; 64], 8).is_empty());
+    }
+
+    #[test]
+    fn weighting_applies_per_bin() {
+        let mut mags = vec![-20.0; 4];
+        Weighting::A.apply(&mut mags, 1000.0);
+
+        // DC has no level left at all
+        assert_eq!(mags[0], FLOOR_DB);
+        assert!((mags[1] + 20.0).abs() < 0.01);
+        assert!(mags[2] > -20.0);
+    }
+
+    #[test]
+    fn odd_lengths_are_zero_padded_and_read_full_scale() {
+        const RATE: f32 = 48_000.0;
+
+        // 3000 samples pad out to 4096, a sine on bin 100 of that
+        let hz = RATE / 4096.0 * 100.0;
+        let samples: Vec<f32> = (0..3000)
+            .map(|i| (TAU * hz * i as f32 / RATE).sin())
+            .collect();
+        let mags = magnitudes_db(&samples);
+
+        assert_eq!(mags.len(), 2049);
+        let peak = (0..mags.len()).max_by(|&a, &b| mags[a].total_cmp(&mags[b]));
+        assert_eq!(peak, Some(100));
+        assert!(mags[100].abs() < 0.1, "{}", mags[100]);
+        assert!(mags[1000] < -60.0, "far from the sine {}", mags[1000]);
+    }
+}
@@ -0,0 +1,112 @@
+//! Shared tempo and the one clock the arp and sequencer both follow, so
+//! changing the tempo moves them together and they never drift apart
+
+use crate::config::{TAP_TEMPO_RESET_MS, TAP_TEMPO_TAPS, TEMPO_BPM_MAX, TEMPO_BPM_MIN, TEMPO_PPQ};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::time::{MissedTickBehavior, interval_at};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tempo {
+    pub bpm: f32,
+}
+
+impl Tempo {
+    #[inline]
+    #[must_use]
+    pub fn new(bpm: f32) -> Self {
+        Self {
+            bpm: bpm.clamp(TEMPO_BPM_MIN, TEMPO_BPM_MAX),
+        }
+    }
+
+    #[must_use]
+    pub fn beat(self) -> Duration {
+        Duration::from_secs_f32(60.0 / self.bpm.max(1.0))
+    }
+
+    /// Time from one clock tick to the next, there are `TEMPO_PPQ` per beat
+    #[must_use]
+    pub fn tick(self) -> Duration {
+        self.beat() / TEMPO_PPQ
+    }
+
+    /// Length of one of `per_beat` equal steps in a beat
+    #[must_use]
+    pub fn step(self, per_beat: u32) -> Duration {
+        self.beat() / per_beat.max(1)
+    }
+}
+
+/// Whether clock tick `tick` starts one of `per_beat` equal steps in a beat
+#[inline]
+#[must_use]
+pub fn on_step(tick: u64, per_beat: u32) -> bool {
+    let ticks = u64::from((TEMPO_PPQ / per_beat.max(1)).max(1));
+    tick.is_multiple_of(ticks)
+}
+
+/// Tempo from the spacing of the last few taps, a long pause starts over
+#[derive(Debug, Default)]
+pub struct TapTempo {
+    taps: VecDeque<Instant>,
+}
+
+impl TapTempo {
+    /// Adds a tap, `None` until there are two to measure between
+    pub fn tap(&mut self, now: Instant) -> Option<Tempo> {
+        let reset = Duration::from_millis(TAP_TEMPO_RESET_MS);
+        if self.taps.back().is_some_and(|last| now - *last > reset) {
+            self.taps.clear();
+        }
+
+        self.taps.push_back(now);
+        while self.taps.len() > TAP_TEMPO_TAPS {
+            self.taps.pop_front();
+        }
+
+        let first = *self.taps.front()?;
+        let gaps = self.taps.len() - 1;
+        if gaps == 0 {
+            return None;
+        }
+
+        let beat = (now - first).as_secs_f32() / gaps as f32;
+        Some(Tempo::new(60.0 / beat.max(f32::EPSILON)))
+    }
+}
+
+/// Calls `on_tick` with the transport position, counted in ticks from the
+/// start, until it returns false. A new tempo takes over from the next tick
+/// without resetting the position
+pub async fn clock<F>(mut tempo: watch::Receiver<Tempo>, mut on_tick: F)
+where
+    F: FnMut(u64) -> bool,
+{
+    let mut position: u64 = 0;
+
+    loop {
+        let period = tempo.borrow_and_update().tick();
+        let mut ticker = interval_at(tokio::time::Instant::now() + period, period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    if !on_tick(position) {
+                        return;
+                    }
+                    position = position.wrapping_add(1);
+                }
+
+                changed = tempo.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
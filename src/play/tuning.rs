@@ -0,0 +1,390 @@
+//! Tuning systems, equal temperament, pure intervals counted up from a root
+//! or a custom scale read from a Scala `.scl` file or a list of cents
+
+use crate::config::{SEMITONES_PER_OCTAVE, TUNING_CUSTOM_ROOT_OCTAVE, TUNING_MAX_NOTES};
+use crate::play::key::{Key, Note};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tuning {
+    Equal,
+    /// 5-limit just intonation, pure 3:2 fifths and 5:4 major thirds
+    Just,
+    /// Every interval built from stacked 3:2 fifths
+    Pythagorean,
+    /// Cents of each degree above the root, ascending, the last is the period
+    /// the scale repeats at. Each key steps up one degree, however many
+    /// notes the scale has
+    Custom(Vec<f64>),
+}
+
+const JUST: [f64; 12] = [
+    1.0,
+    16.0 / 15.0,
+    9.0 / 8.0,
+    6.0 / 5.0,
+    5.0 / 4.0,
+    4.0 / 3.0,
+    45.0 / 32.0,
+    3.0 / 2.0,
+    8.0 / 5.0,
+    5.0 / 3.0,
+    9.0 / 5.0,
+    15.0 / 8.0,
+];
+
+const PYTHAGOREAN: [f64; 12] = [
+    1.0,
+    256.0 / 243.0,
+    9.0 / 8.0,
+    32.0 / 27.0,
+    81.0 / 64.0,
+    4.0 / 3.0,
+    729.0 / 512.0,
+    3.0 / 2.0,
+    128.0 / 81.0,
+    27.0 / 16.0,
+    16.0 / 9.0,
+    243.0 / 128.0,
+];
+
+impl Tuning {
+    pub const ALL: [Self; 3] = [Self::Equal, Self::Just, Self::Pythagorean];
+
+    #[inline]
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Equal => "Equal",
+            Self::Just => "Just",
+            Self::Pythagorean => "Pythagorean",
+            Self::Custom(_) => "Custom",
+        }
+    }
+
+    /// Name, with the number of notes for a custom scale
+    #[must_use]
+    pub fn label(&self) -> String {
+        match self {
+            Self::Custom(cents) => format!("Custom {}", cents.len()),
+            _ => self.name().to_string(),
+        }
+    }
+
+    /// Reads a Scala `.scl` file, any other file is taken as a list of cents
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let scl = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("scl"));
+
+        if scl {
+            Self::from_scl(&text)
+        } else {
+            Self::from_cents(&text)
+        }
+    }
+
+    /// Degrees in cents separated by spaces, commas or lines, the root is
+    /// left out and the last one is the period. `!` starts a comment
+    pub fn from_cents(text: &str) -> io::Result<Self> {
+        let mut cents = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('!').next().unwrap_or_default();
+            for token in line.split(|c: char| c == ',' || c.is_whitespace()) {
+                if token.is_empty() {
+                    continue;
+                }
+                let value = token.parse::<f64>().map_err(|_| {
+                    invalid(format!("line {}: expected cents, got {token:?}", i + 1))
+                })?;
+                cents.push(value);
+            }
+        }
+
+        custom(cents)
+    }
+
+    /// Scala scale file: a description line, the number of notes, then one
+    /// pitch per line as cents (with a `.`) or a ratio like `3/2`. Lines
+    /// starting with `!` are comments
+    pub fn from_scl(text: &str) -> io::Result<Self> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.starts_with('!'));
+
+        // the description may be blank, but it has to be there
+        lines
+            .next()
+            .ok_or_else(|| invalid("missing the description line"))?;
+
+        let (i, count) = lines
+            .next()
+            .ok_or_else(|| invalid("missing the number of notes"))?;
+        let count = count.trim().parse::<usize>().map_err(|_| {
+            invalid(format!(
+                "line {}: expected the number of notes, got {:?}",
+                i + 1,
+                count.trim()
+            ))
+        })?;
+
+        let mut cents = Vec::with_capacity(count.min(TUNING_MAX_NOTES));
+        for _ in 0..count {
+            let (i, line) = lines
+                .next()
+                .ok_or_else(|| invalid(format!("expected {count} notes, found {}", cents.len())))?;
+            // anything after the pitch is a label
+            let token = line.split_whitespace().next().unwrap_or_default();
+            let value = scl_pitch(token).ok_or_else(|| {
+                invalid(format!(
+                    "line {}: expected cents or a ratio, got {token:?}",
+                    i + 1
+                ))
+            })?;
+            cents.push(value);
+        }
+
+        custom(cents)
+    }
+
+    /// Frequency ratio of an interval `semitones` above the root, octaves
+    /// included
+    #[must_use]
+    pub fn interval_ratio(&self, semitones: i32) -> f64 {
+        let octaves = semitones.div_euclid(SEMITONES_PER_OCTAVE);
+        let degree = semitones.rem_euclid(SEMITONES_PER_OCTAVE) as usize;
+
+        let ratio = match self {
+            Self::Equal => (degree as f64 / f64::from(SEMITONES_PER_OCTAVE)).exp2(),
+            Self::Just => JUST[degree],
+            Self::Pythagorean => PYTHAGOREAN[degree],
+            Self::Custom(cents) => return custom_ratio(cents, semitones),
+        };
+        ratio * f64::from(octaves).exp2()
+    }
+
+    /// Multiplier from `key`'s equal-tempered pitch to its pitch in this
+    /// tuning, counting intervals up from the nearest `root` below it. The
+    /// root keeps its equal-tempered pitch. A custom scale counts keys from
+    /// the root in `TUNING_CUSTOM_ROOT_OCTAVE` instead, since its period
+    /// need not be an octave
+    #[must_use]
+    pub fn ratio(&self, key: Key, root: Note) -> f32 {
+        let steps = match self {
+            Self::Custom(_) => {
+                key.absolute_semitone()
+                    - Key::new(root, TUNING_CUSTOM_ROOT_OCTAVE).absolute_semitone()
+            }
+            _ => (key.note.semitone() - root.semitone()).rem_euclid(SEMITONES_PER_OCTAVE),
+        };
+        (self.interval_ratio(steps) / Self::Equal.interval_ratio(steps)) as f32
+    }
+
+    /// Pitch of `key` with A4 at `a4_hz`
+    #[inline]
+    #[must_use]
+    pub fn frequency(&self, key: Key, root: Note, a4_hz: f32) -> f32 {
+        key.frequency_at(a4_hz) * self.ratio(key, root)
+    }
+}
+
+/// Ratio `steps` degrees above the root, whole periods included
+fn custom_ratio(cents: &[f64], steps: i32) -> f64 {
+    let Some(&period) = cents.last() else {
+        return 1.0;
+    };
+
+    let len = i32::try_from(cents.len()).unwrap_or(i32::MAX);
+    let periods = steps.div_euclid(len);
+    let degree = steps.rem_euclid(len) as usize;
+    let above = if degree == 0 { 0.0 } else { cents[degree - 1] };
+
+    ((above + period * f64::from(periods)) / 1200.0).exp2()
+}
+
+/// Checks the degrees climb from the root and there are not too many
+fn custom(cents: Vec<f64>) -> io::Result<Tuning> {
+    if cents.is_empty() {
+        return Err(invalid("no notes"));
+    }
+    if cents.len() > TUNING_MAX_NOTES {
+        return Err(invalid(format!(
+            "{} notes is too many (at most {TUNING_MAX_NOTES})",
+            cents.len()
+        )));
+    }
+
+    let mut below = 0.0;
+    for (i, &value) in cents.iter().enumerate() {
+        if !value.is_finite() || value <= below {
+            return Err(invalid(format!(
+                "note {} at {value} cents does not rise above {below}",
+                i + 1
+            )));
+        }
+        below = value;
+    }
+
+    Ok(Tuning::Custom(cents))
+}
+
+/// Cents if there is a `.`, otherwise a positive ratio like `3/2` or `2`
+fn scl_pitch(token: &str) -> Option<f64> {
+    if token.contains('.') {
+        return token.parse().ok();
+    }
+
+    let (num, den) = token.split_once('/').unwrap_or((token, "1"));
+    let num = num.parse::<u64>().ok()?;
+    let den = den.parse::<u64>().ok()?;
+    if num == 0 || den == 0 {
+        return None;
+    }
+
+    Some(1200.0 * (num as f64 / den as f64).log2())
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cents(ratio: f32) -> f32 {
+        1200.0 * ratio.log2()
+    }
+
+    fn off(tuning: &Tuning, note: Note, root: Note) -> f32 {
+        cents(tuning.ratio(Key::new(note, 4), root))
+    }
+
+    #[test]
+    fn equal_is_untouched() {
+        for note in [Note::C, Note::E, Note::G, Note::B] {
+            assert_eq!(Tuning::Equal.ratio(Key::new(note, 4), Note::D), 1.0);
+        }
+    }
+
+    #[test]
+    fn just_intervals_are_pure() {
+        let just = Tuning::Just;
+
+        assert!(off(&just, Note::C, Note::C).abs() < 1e-3, "the root stays");
+        assert!((off(&just, Note::E, Note::C) + 13.686).abs() < 1e-2);
+        assert!((off(&just, Note::G, Note::C) - 1.955).abs() < 1e-2);
+        assert!((off(&just, Note::A, Note::C) + 15.641).abs() < 1e-2);
+        // counted up from the root, wherever it is
+        assert!((off(&just, Note::Gb, Note::D) + 13.686).abs() < 1e-2);
+        assert!((off(&just, Note::C, Note::A) - 15.641).abs() < 1e-2);
+    }
+
+    #[test]
+    fn pythagorean_stacks_fifths() {
+        let pythagorean = Tuning::Pythagorean;
+
+        assert!((off(&pythagorean, Note::E, Note::C) - 7.820).abs() < 1e-2);
+        assert!((off(&pythagorean, Note::G, Note::C) - 1.955).abs() < 1e-2);
+        assert!((off(&pythagorean, Note::Db, Note::C) + 9.775).abs() < 1e-2);
+    }
+
+    #[test]
+    fn intervals_repeat_every_octave() {
+        assert!((Tuning::Just.interval_ratio(12) - 2.0).abs() < 1e-12);
+        assert!((Tuning::Just.interval_ratio(19) - 3.0).abs() < 1e-12);
+        assert!((Tuning::Pythagorean.interval_ratio(-5) - 0.75).abs() < 1e-12);
+    }
+
+    #[test]
+    fn from_scl_reads_cents_and_ratios() {
+        let scl = "! fifth.scl\n\
+                   !\n\
+                   A fifth and an octave\n\
+                   3\n\
+                   ! the degrees\n\
+                   386.314 major third\n\
+                   3/2\n\
+                   2\n";
+        let Tuning::Custom(cents) = Tuning::from_scl(scl).unwrap() else {
+            panic!("a custom scale");
+        };
+
+        assert_eq!(cents.len(), 3);
+        assert!((cents[0] - 386.314).abs() < 1e-9);
+        assert!((cents[1] - 701.955).abs() < 1e-3);
+        assert!((cents[2] - 1200.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn from_scl_refuses_broken_files() {
+        assert!(Tuning::from_scl("").is_err());
+        assert!(Tuning::from_scl("no count\n").is_err());
+        assert!(Tuning::from_scl("short\n3\n100.0\n200.0\n").is_err());
+        assert!(Tuning::from_scl("bad ratio\n1\n3/0\n").is_err());
+        assert!(Tuning::from_scl("falling\n2\n700.0\n500.0\n").is_err());
+    }
+
+    #[test]
+    fn from_cents_splits_on_commas_spaces_and_lines() {
+        let tuning = Tuning::from_cents("100, 200 300\n! the period\n1200\n").unwrap();
+
+        assert_eq!(tuning, Tuning::Custom(vec![100.0, 200.0, 300.0, 1200.0]));
+        assert_eq!(tuning.label(), "Custom 4");
+        assert!(Tuning::from_cents("").is_err());
+        assert!(Tuning::from_cents("100 abc").is_err());
+        assert!(Tuning::from_cents("100 100").is_err(), "must rise");
+    }
+
+    #[test]
+    fn custom_ratio_steps_degrees_and_periods() {
+        // three equal steps to a 3:1 tritave, like Bohlen-Pierce
+        let tritave = 1200.0 * 3.0_f64.log2();
+        let cents = [tritave / 3.0, tritave * 2.0 / 3.0, tritave];
+
+        assert!((custom_ratio(&cents, 0) - 1.0).abs() < 1e-12);
+        assert!((custom_ratio(&cents, 3) - 3.0).abs() < 1e-12);
+        assert!((custom_ratio(&cents, 6) - 9.0).abs() < 1e-9);
+        assert!((custom_ratio(&cents, 1) - 3.0_f64.cbrt()).abs() < 1e-12);
+        assert!((custom_ratio(&cents, -3) - 1.0 / 3.0).abs() < 1e-12);
+        assert_eq!(custom_ratio(&[], 5), 1.0);
+    }
+
+    #[test]
+    fn custom_scale_counts_keys_from_the_root_octave() {
+        // five equal steps to the octave
+        let tuning = Tuning::Custom(vec![240.0, 480.0, 720.0, 960.0, 1200.0]);
+        let root = Key::new(Note::C, TUNING_CUSTOM_ROOT_OCTAVE);
+
+        assert!((tuning.ratio(root, Note::C) - 1.0).abs() < 1e-6);
+        // the next key is the next degree, 240 cents up rather than 100
+        let db = Key::new(Note::Db, TUNING_CUSTOM_ROOT_OCTAVE);
+        assert!((cents(tuning.ratio(db, Note::C)) - 140.0).abs() < 1e-2);
+        // five keys up is a whole octave
+        let f = Key::new(Note::F, TUNING_CUSTOM_ROOT_OCTAVE);
+        assert!((cents(tuning.ratio(f, Note::C)) - 700.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn nineteen_equal_steps_loaded_from_cents() {
+        let step = 1200.0 / 19.0;
+        let text: Vec<String> = (1..=19)
+            .map(|n| format!("{:.3}", f64::from(n) * step))
+            .collect();
+        let tuning = Tuning::from_cents(&text.join("\n")).unwrap();
+        let root = Key::new(Note::C, TUNING_CUSTOM_ROOT_OCTAVE);
+        let hz = |steps: i32| f64::from(tuning.frequency(root.transpose(steps), Note::C, 440.0));
+
+        for n in 0..19 {
+            let rise = 1200.0 * (hz(n + 1) / hz(n)).log2();
+            assert!((rise - step).abs() < 0.01, "key {n} steps {rise} cents");
+        }
+        // the last degree is written as exactly 1200.000
+        assert!((hz(19) / hz(0) - 2.0).abs() < 1e-6, "{}", hz(19) / hz(0));
+    }
+}
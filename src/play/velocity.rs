@@ -0,0 +1,99 @@
+//! Note velocity, how hard a note is played sets how loud it peaks
+
+use crate::config::VELOCITY_CURVE_EXPONENT;
+
+/// How a 0..127 velocity maps to level: `Exponential` keeps soft notes
+/// quieter so there is more room between a light and a hard touch
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VelocityCurve {
+    Linear,
+    Exponential,
+}
+
+impl VelocityCurve {
+    pub const ALL: [Self; 2] = [Self::Linear, Self::Exponential];
+
+    #[inline]
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Linear => "Linear",
+            Self::Exponential => "Exp",
+        }
+    }
+
+    /// Envelope peak for `velocity`, 0..1
+    #[inline]
+    #[must_use]
+    pub fn gain(self, velocity: u8) -> f32 {
+        let level = f32::from(velocity.min(127)) / 127.0;
+        match self {
+            Self::Linear => level,
+            Self::Exponential => level.powf(VELOCITY_CURVE_EXPONENT),
+        }
+    }
+}
+
+/// MIDI notes bring their own velocity, computer keys play at `keys`, or at
+/// `accent` while shift is held
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Velocity {
+    pub curve: VelocityCurve,
+    pub keys: u8,
+    pub accent: u8,
+}
+
+impl Velocity {
+    #[inline]
+    #[must_use]
+    pub fn new(curve: VelocityCurve, keys: u8, accent: u8) -> Self {
+        Self {
+            curve,
+            keys: keys.clamp(1, 127),
+            accent: accent.clamp(1, 127),
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn gain(self, velocity: u8) -> f32 {
+        self.curve.gain(velocity)
+    }
+
+    /// Envelope peak for a computer key
+    #[inline]
+    #[must_use]
+    pub fn key_gain(self, accent: bool) -> f32 {
+        self.gain(if accent { self.accent } else { self.keys })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves_map_the_midi_range() {
+        for curve in VelocityCurve::ALL {
+            assert_eq!(curve.gain(0), 0.0);
+            assert_eq!(curve.gain(127), 1.0);
+            assert_eq!(curve.gain(200), 1.0, "clamped to 127");
+        }
+
+        let half = 64.0 / 127.0;
+        assert!((VelocityCurve::Linear.gain(64) - half).abs() < 1e-6);
+        let soft = half.powf(VELOCITY_CURVE_EXPONENT);
+        assert!((VelocityCurve::Exponential.gain(64) - soft).abs() < 1e-6);
+        assert!(VelocityCurve::Exponential.gain(64) < VelocityCurve::Linear.gain(64));
+    }
+
+    #[test]
+    fn accent_plays_computer_keys_harder() {
+        let velocity = Velocity::new(VelocityCurve::Linear, 80, 127);
+
+        assert!((velocity.key_gain(false) - 80.0 / 127.0).abs() < 1e-6);
+        assert_eq!(velocity.key_gain(true), 1.0);
+        // a key velocity of 0 would be silent, so it is kept at 1
+        assert_eq!(Velocity::new(VelocityCurve::Linear, 0, 0).keys, 1);
+    }
+}
@@ -0,0 +1,103 @@
+//! Patches the UI can cycle through: a few factory ones built into the
+//! binary plus every `.patch` file under `PATCH_DIR`
+
+use crate::config::PATCH_EXT;
+use crate::patch::file::{PatchFile, patch_dir};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const FACTORY: [(&str, &str); 3] = [
+    ("Dub Echo", include_str!("factory/dub_echo.patch")),
+    ("Glass Bell", include_str!("factory/glass_bell.patch")),
+    ("Warm Pad", include_str!("factory/warm_pad.patch")),
+];
+
+#[derive(Debug, Clone)]
+pub struct LibraryEntry {
+    /// Factory name or the file stem
+    pub name: String,
+    /// `None` for a factory patch
+    pub path: Option<PathBuf>,
+    pub patch: PatchFile,
+}
+
+#[derive(Debug, Default)]
+pub struct PatchLibrary {
+    /// Sorted by name, ignoring case
+    pub entries: Vec<LibraryEntry>,
+    /// Files that could not be read or parsed, with the reason
+    pub skipped: Vec<(PathBuf, io::Error)>,
+}
+
+impl PatchLibrary {
+    /// Reads the whole library again, so files saved since the last scan
+    /// show up. Without a patch directory only the factory patches are left
+    #[must_use]
+    pub fn scan() -> Self {
+        let mut library = Self::default();
+
+        for (name, text) in FACTORY {
+            match PatchFile::parse(text) {
+                Ok(patch) => library.entries.push(LibraryEntry {
+                    name: name.to_string(),
+                    path: None,
+                    patch,
+                }),
+                Err(err) => library.skipped.push((PathBuf::from(name), err)),
+            }
+        }
+
+        if let Some(dir) = patch_dir() {
+            match fs::read_dir(&dir) {
+                Ok(entries) => {
+                    let paths = entries
+                        .filter_map(Result::ok)
+                        .map(|entry| entry.path())
+                        .filter(|path| path.extension().is_some_and(|ext| ext == PATCH_EXT));
+
+                    for path in paths {
+                        match PatchFile::load(&path) {
+                            Ok(patch) => library.entries.push(LibraryEntry {
+                                name: path
+                                    .file_stem()
+                                    .map_or_else(String::new, |s| s.to_string_lossy().into()),
+                                path: Some(path),
+                                patch,
+                            }),
+                            Err(err) => library.skipped.push((path, err)),
+                        }
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => library.skipped.push((dir, err)),
+            }
+        }
+
+        library
+            .entries
+            .sort_by_cached_key(|entry| entry.name.to_lowercase());
+        library.skipped.sort_by(|a, b| a.0.cmp(&b.0));
+        library
+    }
+
+    /// The entry `step` places on from the one named `from`, wrapping at
+    /// both ends. From a name not in the library, forward starts at the
+    /// first entry and back at the last
+    #[must_use]
+    pub fn step(&self, from: Option<&str>, step: isize) -> Option<&LibraryEntry> {
+        let len = self.entries.len() as isize;
+        if len == 0 {
+            return None;
+        }
+
+        let current = from.and_then(|name| self.entries.iter().position(|e| e.name == name));
+        let i = match current {
+            Some(i) => (i as isize + step).rem_euclid(len),
+            None if step < 0 => (len + step).rem_euclid(len),
+            None => (step - 1).rem_euclid(len),
+        };
+
+        self.entries.get(i as usize)
+    }
+}
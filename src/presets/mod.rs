@@ -1,8 +1,14 @@
-use std::error::Error;
+pub mod library;
 
 use rusqlite::Connection;
+use std::path::Path;
 
+use crate::audio::Snapshot;
+use crate::config::PATCH_EXT;
+use crate::error::{MugenError, Result};
+use crate::patch::file::PatchFile;
 use crate::patch::oscilators::basic::Wave;
+use library::PatchLibrary;
 
 #[derive(Debug, Clone)]
 pub struct Preset {
@@ -21,7 +27,7 @@ pub struct Preset {
     pub cutoff: f32,
 }
 
-pub async fn import_db() -> Result<Vec<Preset>, Box<dyn Error + Send + Sync>> {
+pub async fn import_db() -> Result<Vec<Preset>> {
     let conn = Connection::open("./bin/db.sqlite")?;
 
     let mut stmt = conn.prepare(
@@ -48,7 +54,52 @@ pub async fn import_db() -> Result<Vec<Preset>, Box<dyn Error + Send + Sync>> {
                 cutoff: row.get(12)?,
             })
         })?
-        .collect::<Result<Vec<Preset>, _>>()?;
+        .collect::<rusqlite::Result<Vec<Preset>>>()?;
 
     Ok(presets)
 }
+
+/// Looks a preset up by name, ignoring case
+pub async fn find_preset(name: &str) -> Result<Option<Preset>> {
+    let presets = import_db().await?;
+
+    Ok(presets
+        .into_iter()
+        .find(|preset| preset.name.eq_ignore_ascii_case(name)))
+}
+
+/// Snapshot the engine starts from for `--patch` or the `patch` setting.
+/// A path to a patch file loads the whole file, effect chain included,
+/// otherwise the name is looked up in the patch library and then the
+/// preset database, ignoring case
+pub async fn start_patch(patch: &str) -> Result<Snapshot> {
+    let path = Path::new(patch);
+    if path.is_file() || path.extension().is_some_and(|ext| ext == PATCH_EXT) {
+        let file = PatchFile::load(path).map_err(|source| MugenError::PatchIo {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut snapshot = Snapshot::default();
+        file.apply(&mut snapshot);
+        return Ok(snapshot);
+    }
+
+    let library = PatchLibrary::scan();
+    if let Some(entry) = library
+        .entries
+        .into_iter()
+        .find(|entry| entry.name.eq_ignore_ascii_case(patch))
+    {
+        let mut snapshot = Snapshot::default();
+        entry.patch.apply(&mut snapshot);
+        return Ok(snapshot);
+    }
+
+    match find_preset(patch).await {
+        Ok(Some(preset)) => Ok(Snapshot::from_preset(preset)),
+        Ok(None) => Err(MugenError::Config(format!("unknown patch: {patch}"))),
+        Err(err) => Err(MugenError::Config(format!(
+            "failed to load patch {patch}: {err}"
+        ))),
+    }
+}
@@ -0,0 +1,370 @@
+//! User settings read from `~/.config/mugen/config.toml` at startup
+//!
+//! ```toml
+//! volume = 0.8
+//! crossfade = 0.05
+//! slew = 0.02
+//! # seconds, 0 records without a limit
+//! record_max = 600
+//! # milliseconds before a held note is released, 0 holds it as long as
+//! # the key, the same unit as --max-note
+//! max_note_ms = 8000
+//! wave = "saw"
+//! theme = "high contrast"
+//! intro = "daily"
+//! # false plays keys already held at launch
+//! ignore_held = true
+//! # a patch or preset name, or a patch file, --patch overrides it
+//! patch = "Warm Pad"
+//!
+//! [adsr]
+//! attack = 0.01
+//! release = 0.4
+//!
+//! # semitones above C of the base octave
+//! [keys]
+//! a = 0
+//! w = 1
+//! ```
+
+use crate::audio::Snapshot;
+use crate::config::{
+    INTRO_STAMP, MAX_NOTE_MAX_MS, PARAM_SLEW_MAX_MS, SETTINGS_PATH, WAVE_CROSSFADE_MAX_S,
+};
+use crate::patch::oscilators::basic::Wave;
+use crate::play::key::{Keymap, keycode_from_char};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SettingsFile {
+    volume: Option<f32>,
+    crossfade: Option<f32>,
+    slew: Option<f32>,
+    record_max: Option<u32>,
+    max_note_ms: Option<u64>,
+    wave: Option<String>,
+    theme: Option<String>,
+    intro: Option<String>,
+    ignore_held: Option<bool>,
+    patch: Option<String>,
+    adsr: AdsrFile,
+    keys: BTreeMap<String, i32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AdsrFile {
+    attack: Option<f32>,
+    decay: Option<f32>,
+    sustain: Option<f32>,
+    release: Option<f32>,
+}
+
+/// Everything left `None` keeps the built-in default
+#[derive(Debug, Default)]
+pub struct Settings {
+    pub volume: Option<f32>,
+    /// Seconds a sounding note blends from the old wave to the new one
+    pub crossfade_s: Option<f32>,
+    /// Seconds a live gain, pan, cutoff or mix change takes to settle
+    pub slew_s: Option<f32>,
+    /// Seconds a recording runs before it stops itself, 0 for no limit
+    pub record_max_s: Option<u32>,
+    /// Milliseconds a note plays before it is released, 0 for no limit
+    pub max_note_ms: Option<u64>,
+    pub wave: Option<Wave>,
+    pub theme: Option<Theme>,
+    pub intro: Option<IntroMode>,
+    /// Whether keys held at launch wait for a re-press
+    pub ignore_held: Option<bool>,
+    /// Preset name or patch file to start from
+    pub patch: Option<String>,
+    pub attack_s: Option<f32>,
+    pub decay_s: Option<f32>,
+    pub sustain: Option<f32>,
+    pub release_s: Option<f32>,
+    pub keymap: Option<Keymap>,
+}
+
+impl Settings {
+    /// Reads the settings file, a missing file is not an error. A malformed
+    /// one is reported and ignored, as are single entries that fail to parse
+    #[must_use]
+    pub fn load() -> Self {
+        let Some(path) = config_path(SETTINGS_PATH) else {
+            return Self::default();
+        };
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Self::default(),
+            Err(err) => {
+                eprintln!("ignoring {}: {err}", path.display());
+                return Self::default();
+            }
+        };
+
+        match toml::from_str::<SettingsFile>(&text) {
+            Ok(file) => Self::from_file(file),
+            Err(err) => {
+                eprintln!("ignoring {}: {err}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    fn from_file(file: SettingsFile) -> Self {
+        let wave = file.wave.and_then(|name| {
+            let wave = parse_wave(&name);
+            if wave.is_none() {
+                eprintln!("settings: unknown wave {name:?}");
+            }
+            wave
+        });
+        let theme = file.theme.and_then(|name| {
+            let theme = Theme::parse(&name);
+            if theme.is_none() {
+                eprintln!("settings: unknown theme {name:?}");
+            }
+            theme
+        });
+        let intro = file.intro.and_then(|name| {
+            let intro = IntroMode::parse(&name);
+            if intro.is_none() {
+                eprintln!("settings: unknown intro {name:?}, expected always, daily or never");
+            }
+            intro
+        });
+
+        let mut keymap = Keymap::new();
+        let mut bad_keys = Vec::new();
+        for (name, semitones) in file.keys {
+            let mut chars = name.chars();
+            match (chars.next().and_then(keycode_from_char), chars.next()) {
+                (Some(keycode), None) => {
+                    keymap.insert(keycode, semitones);
+                }
+                _ => bad_keys.push(name),
+            }
+        }
+        if !bad_keys.is_empty() {
+            eprintln!("settings: skipped keys {}", bad_keys.join(", "));
+        }
+
+        Self {
+            volume: file.volume.map(|v| v.clamp(0.0, 1.0)),
+            crossfade_s: file.crossfade.map(|s| s.clamp(0.0, WAVE_CROSSFADE_MAX_S)),
+            slew_s: file
+                .slew
+                .map(|s| s.clamp(0.0, PARAM_SLEW_MAX_MS as f32 / 1000.0)),
+            record_max_s: file.record_max,
+            max_note_ms: file.max_note_ms.map(|ms| ms.min(MAX_NOTE_MAX_MS)),
+            wave,
+            theme,
+            intro,
+            ignore_held: file.ignore_held,
+            patch: file.patch,
+            attack_s: file.adsr.attack.map(|s| s.max(0.0)),
+            decay_s: file.adsr.decay.map(|s| s.max(0.0)),
+            sustain: file.adsr.sustain.map(|s| s.clamp(0.0, 1.0)),
+            release_s: file.adsr.release.map(|s| s.max(0.0)),
+            keymap: (!keymap.is_empty()).then_some(keymap),
+        }
+    }
+
+    /// Volume and crossfade always apply, the sound defaults only when no
+    /// preset was chosen so a preset still plays the way it was saved
+    pub fn apply(&self, snapshot: &mut Snapshot, from_preset: bool) {
+        if let Some(volume) = self.volume {
+            snapshot.volume = volume;
+        }
+        if let Some(crossfade_s) = self.crossfade_s {
+            snapshot.crossfade_s = crossfade_s;
+        }
+        if from_preset {
+            return;
+        }
+
+        if let Some(wave) = &self.wave {
+            snapshot.wave = wave.clone();
+            snapshot.patch_name = wave.label(snapshot.pulse_duty);
+        }
+
+        let adsr = &mut snapshot.adsr;
+        adsr.attack_s = self.attack_s.unwrap_or(adsr.attack_s);
+        adsr.decay_s = self.decay_s.unwrap_or(adsr.decay_s);
+        adsr.sustain = self.sustain.unwrap_or(adsr.sustain);
+        adsr.release_s = self.release_s.unwrap_or(adsr.release_s);
+    }
+}
+
+/// Colour schemes, Ctrl+T cycles through them. `HighContrast` sticks to the
+/// basic ANSI colours so it reads on any terminal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Theme {
+    Dragon,
+    Solarized,
+    Mono,
+    HighContrast,
+}
+
+impl Theme {
+    pub const ALL: [Self; 4] = [
+        Self::Dragon,
+        Self::Solarized,
+        Self::Mono,
+        Self::HighContrast,
+    ];
+
+    #[inline]
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Dragon => "Dragon",
+            Self::Solarized => "Solarized",
+            Self::Mono => "Mono",
+            Self::HighContrast => "High Contrast",
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|&t| t == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    /// Matches `name` ignoring case, spaces, dashes and underscores
+    #[must_use]
+    pub fn parse(name: &str) -> Option<Self> {
+        let squash = |s: &str| {
+            s.chars()
+                .filter(|c| !matches!(c, ' ' | '-' | '_'))
+                .collect::<String>()
+                .to_lowercase()
+        };
+        let wanted = squash(name);
+
+        Self::ALL
+            .into_iter()
+            .find(|theme| squash(theme.name()) == wanted)
+    }
+}
+
+/// When the intro plays at launch
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntroMode {
+    Always,
+    /// Only on the first launch of the day
+    Daily,
+    Never,
+}
+
+impl IntroMode {
+    #[must_use]
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "always" => Some(Self::Always),
+            "daily" => Some(Self::Daily),
+            "never" => Some(Self::Never),
+            _ => None,
+        }
+    }
+}
+
+/// Where daily mode notes the last day the intro played, beside the
+/// settings file
+#[must_use]
+pub fn intro_stamp() -> Option<PathBuf> {
+    config_path(INTRO_STAMP)
+}
+
+/// `relative` under `$XDG_CONFIG_HOME` if set, otherwise `$HOME/.config`
+pub(crate) fn config_path(relative: &str) -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(base.join(relative))
+}
+
+/// Matches `Wave::name` ignoring case and spaces, so "pinknoise" works too
+fn parse_wave(name: &str) -> Option<Wave> {
+    let wanted = name.replace(' ', "").to_lowercase();
+    let mut wave = Wave::Sine;
+
+    loop {
+        if wave.name().replace(' ', "").to_lowercase() == wanted {
+            return Some(wave);
+        }
+        wave = wave.toggle();
+        if wave == Wave::Sine {
+            return None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use device_query::Keycode;
+
+    fn settings(text: &str) -> Settings {
+        Settings::from_file(toml::from_str(text).unwrap())
+    }
+
+    #[test]
+    fn values_are_clamped_to_their_ranges() {
+        let high = settings(
+            "volume = 1.5\ncrossfade = 60.0\nslew = 5.0\nmax_note_ms = 99999999\n\
+             [adsr]\nattack = -1.0\nsustain = 2.0",
+        );
+        assert_eq!(high.volume, Some(1.0));
+        assert_eq!(high.crossfade_s, Some(WAVE_CROSSFADE_MAX_S));
+        assert_eq!(high.slew_s, Some(PARAM_SLEW_MAX_MS as f32 / 1000.0));
+        assert_eq!(high.max_note_ms, Some(MAX_NOTE_MAX_MS));
+        assert_eq!(high.attack_s, Some(0.0));
+        assert_eq!(high.sustain, Some(1.0));
+
+        let low = settings("volume = -0.5\ncrossfade = -1.0\nslew = -1.0\nmax_note_ms = 0");
+        assert_eq!(low.volume, Some(0.0));
+        assert_eq!(low.crossfade_s, Some(0.0));
+        assert_eq!(low.slew_s, Some(0.0));
+        assert_eq!(low.max_note_ms, Some(0));
+
+        let fine = settings("volume = 0.8\nmax_note_ms = 8000");
+        assert_eq!(fine.volume, Some(0.8));
+        assert_eq!(fine.max_note_ms, Some(8000));
+    }
+
+    #[test]
+    fn names_parse_loosely_and_unknown_ones_keep_the_default() {
+        let known =
+            settings("wave = \"Pink Noise\"\ntheme = \"high-contrast\"\nintro = \" Never\"");
+        assert_eq!(known.wave, Some(Wave::PinkNoise));
+        assert_eq!(known.theme, Some(Theme::HighContrast));
+        assert_eq!(known.intro, Some(IntroMode::Never));
+
+        let unknown = settings("wave = \"kazoo\"\ntheme = \"neon\"\nintro = \"weekly\"");
+        assert_eq!(unknown.wave, None);
+        assert_eq!(unknown.theme, None);
+        assert_eq!(unknown.intro, None);
+    }
+
+    #[test]
+    fn bad_keys_are_skipped_and_the_rest_kept() {
+        let keys = settings("[keys]\na = 0\nw = 1\nab = 2\n\"\" = 3\n\"~\" = 4");
+        let keymap = keys.keymap.unwrap();
+
+        assert_eq!(keymap.len(), 2);
+        assert_eq!(keymap.get(&Keycode::A), Some(&0));
+        assert_eq!(keymap.get(&Keycode::W), Some(&1));
+
+        assert!(settings("[keys]\nab = 2").keymap.is_none());
+    }
+}
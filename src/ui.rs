@@ -1,18 +1,22 @@
 use std::{
-    collections::HashSet,
-    error::Error,
+    cell::Cell,
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
     io::{self, stdout},
+    path::Path,
     sync::{
         Arc,
         atomic::{AtomicBool, Ordering},
     },
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use crossterm::{
+    clipboard::CopyToClipboard,
     event::{
-        self, DisableFocusChange, EnableFocusChange, Event, KeyCode, KeyEvent, KeyEventKind,
-        KeyModifiers,
+        self, DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture,
+        Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+        MouseEventKind,
     },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
@@ -22,21 +26,73 @@ use device_query::Keycode;
 use ratatui::{
     Terminal,
     backend::CrosstermBackend,
-    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
     prelude::Stylize,
-    style::Style,
+    style::{Color, Style},
+    symbols::Marker,
     text::{Line, Span},
-    widgets::{Block, Borders, Clear, Paragraph, Row, Table, Wrap},
+    widgets::{
+        Axis, Block, Borders, Chart, Clear, Dataset, GraphType, Paragraph, Row, Table, Wrap,
+    },
 };
 use tokio::sync::{mpsc, watch};
-use tokio::time::sleep;
+use tokio::time::sleep_until;
 
 use crate::audio::{Client, Snapshot};
-use crate::patch::effects::adsr::Adsr;
+use crate::config::{
+    A4_MAX_HZ, A4_MIN_HZ, ADSR_GRAPH_HOLD_S, ADSR_GRAPH_MIN_HEIGHT, ADSR_GRAPH_SIDE_MIN_WIDTH,
+    ADSR_GRAPH_STAGE_POINTS, ADSR_PARAMS_WIDTH, AUDIO_LOAD_WARN, BASE_FREQ, BEATS_PER_BAR,
+    BEND_RANGE_MAX_ST, FM_INDEX_MAX, FM_RATIO_MAX, FRAME_TIME_WINDOW, GLIDE_MAX_S,
+    INTRO_DURATION_MS, KEYBOARD_BASE_OCTAVE, LAYER_DETUNE_CENTS, LAYERS_MAX, LFO_MOD_RATE_MAX_HZ,
+    OCTAVE_SHIFT_MAX, OCTAVE_SHIFT_MIN, PULSE_DUTY, RECORD_WARN_S, RESYNTH_PARTIALS,
+    SCOPE_AFTERGLOW, SCOPE_AFTERGLOW_DEPTH, SCOPE_AFTERGLOW_DEPTH_MAX, SCOPE_AUTOSCALE,
+    SCOPE_AUTOSCALE_FILL, SCOPE_AUTOSCALE_FLOOR, SCOPE_AUTOSCALE_TAU_S, SCOPE_CONTRAST,
+    SCOPE_CONTRAST_STEPS, SCOPE_FRAMES, SCOPE_GRID, SCOPE_GRID_DIVISIONS, SCOPE_GRID_LINES,
+    SCOPE_MONO, SCOPE_NOTES, SCOPE_SCALE_MAX, SCOPE_SCALE_MIN, SCOPE_SHADE_STEPS, SCOPE_SHADES,
+    SCOPE_TRIGGER_LEVEL, SEMITONES_PER_OCTAVE, SPECTROGRAM_ROW_MS, SPECTRUM_FRAMES,
+    SPECTRUM_WEIGHTING, TRANSPOSE_MAX, UI_FPS_MAX, UI_FPS_STEPS, UNISON_MAX, VELOCITY_STEP,
+    VOLUME_STEP, WAVE_CROSSFADE_MAX_S, WAVE_CROSSFADE_STEP_S,
+};
+use crate::error::{MugenError, Result};
+use crate::patch::bend::{BendReturn, bend_ratio};
+use crate::patch::chain::Fx;
+use crate::patch::effects::adsr::{Adsr, EnvelopeCurve, EnvelopeMode};
+use crate::patch::effects::am::Am;
+use crate::patch::effects::bitcrush::Bitcrush;
+use crate::patch::effects::delay::Delay;
+use crate::patch::effects::gain::Gain;
+use crate::patch::effects::highpass::HighPass;
 use crate::patch::effects::lfo_amp::LfoAmp;
 use crate::patch::effects::lowpass::LowPass;
-use crate::patch::oscilators::basic::Wave;
+use crate::patch::effects::noise_gate::NoiseGate;
+use crate::patch::effects::pan::Pan;
+use crate::patch::effects::reverb::Reverb;
+use crate::patch::effects::ringmod::RingMod;
+use crate::patch::effects::sample_hold::{HoldTarget, SampleHold};
+use crate::patch::file::{PatchFile, latest_patch};
+use crate::patch::lfo::{Lfo, LfoTarget};
+use crate::patch::oscilators::basic::{DUTY_MAX, DUTY_MIN, Sub, SubWave, Wave};
+use crate::patch::oscilators::fm::Fm;
+use crate::patch::oscilators::stack::Layer;
+use crate::patch::oscilators::wavetable::Wavetable;
+use crate::patch::unison::Unison;
+use crate::play::VoiceMode;
+use crate::play::arp::{Arp, ArpPattern};
+use crate::play::capture::{AudioLoad, Capture};
+use crate::play::chord::Chord;
+use crate::play::input::InputEngine;
+use crate::play::key::{Key, Note, keycode_char, keycode_from_char, mapped_keys};
+use crate::play::mic::MicCapture;
+use crate::play::recorder::Recording;
+use crate::play::scale::{Quantizer, Scale};
+use crate::play::sequencer::Sequence;
+use crate::play::spectrum::{FLOOR_DB, Weighting, magnitudes_db, partials};
+use crate::play::tempo::{TapTempo, Tempo};
+use crate::play::tuning::Tuning;
+use crate::play::velocity::{Velocity, VelocityCurve};
+use crate::presets::library::PatchLibrary;
 use crate::presets::{Preset, import_db};
+use crate::settings::{IntroMode, Theme, intro_stamp};
 
 const INTRO_MIN_W: u16 = 136;
 const INTRO_MIN_H: u16 = 25;
@@ -44,6 +100,14 @@ const UI_MIN_W: u16 = 136;
 const UI_MIN_H: u16 = 33;
 const KEYBOARD_MIN_W: u16 = 18;
 const KEYBOARD_MIN_H: u16 = 6;
+const PIANO_H: u16 = 3;
+const PIANO_OCTAVES: i32 = 3;
+const FX_PANE_W: u16 = 44;
+const FX_LIST_W: u16 = 14;
+const SPARKLINE_W: usize = 32;
+const SPARKLINE_SILENCE: f32 = 1e-4; // peak below which the scope counts as silent
+const SPARKLINE: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const RESYNTH_TABLE: &str = "Frozen"; // wavetable Ctrl+X fills from the spectrum
 
 const PRESET_CATEGORIES: [(u32, &str); 9] = [
     (0, "Bass"),
@@ -57,27 +121,257 @@ const PRESET_CATEGORIES: [(u32, &str); 9] = [
     (8, "Effects"),
 ];
 
-#[allow(dead_code)]
-mod kdr {
+/// Colours of the current theme, read on every draw so a switch repaints the
+/// whole screen on the next frame
+mod colors {
+    use super::Theme;
     use ratatui::style::Color;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Palette {
+        bg: Color,
+        border: Color,
+        fg: Color,
+        muted: Color,
+        accent: Color,
+        highlight: Color,
+    }
+
+    // same order as `Theme::ALL`
+    static PALETTES: [Palette; 4] = [
+        Palette {
+            bg: Color::Rgb(24, 22, 22),
+            border: Color::Rgb(98, 94, 90),
+            fg: Color::Rgb(197, 201, 197),
+            muted: Color::Rgb(158, 155, 147),
+            accent: Color::Rgb(182, 146, 123),
+            highlight: Color::Rgb(196, 178, 138),
+        },
+        Palette {
+            bg: Color::Rgb(0, 43, 54),
+            border: Color::Rgb(88, 110, 117),
+            fg: Color::Rgb(147, 161, 161),
+            muted: Color::Rgb(101, 123, 131),
+            accent: Color::Rgb(203, 75, 22),
+            highlight: Color::Rgb(181, 137, 0),
+        },
+        Palette {
+            bg: Color::Rgb(18, 18, 18),
+            border: Color::Rgb(96, 96, 96),
+            fg: Color::Rgb(208, 208, 208),
+            muted: Color::Rgb(136, 136, 136),
+            accent: Color::Rgb(255, 255, 255),
+            highlight: Color::Rgb(232, 232, 232),
+        },
+        Palette {
+            bg: Color::Black,
+            border: Color::White,
+            fg: Color::White,
+            muted: Color::Gray,
+            accent: Color::LightYellow,
+            highlight: Color::LightCyan,
+        },
+    ];
+
+    static THEME: AtomicUsize = AtomicUsize::new(0);
+
+    pub fn set_theme(theme: Theme) {
+        let i = Theme::ALL.iter().position(|&t| t == theme).unwrap_or(0);
+        THEME.store(i, Ordering::Relaxed);
+    }
+
+    fn palette() -> &'static Palette {
+        &PALETTES[THEME.load(Ordering::Relaxed)]
+    }
+
+    pub fn bg() -> Color {
+        palette().bg
+    }
+
+    pub fn border() -> Color {
+        palette().border
+    }
+
+    pub fn fg() -> Color {
+        palette().fg
+    }
+
+    pub fn muted() -> Color {
+        palette().muted
+    }
+
+    pub fn accent() -> Color {
+        palette().accent
+    }
+
+    pub fn highlight() -> Color {
+        palette().highlight
+    }
+
+    /// `from` moved `t` of the way to `to`, `None` unless both are RGB
+    fn blend(from: Color, to: Color, t: f32) -> Option<Color> {
+        match (from, to) {
+            (Color::Rgb(r1, g1, b1), Color::Rgb(r2, g2, b2)) => {
+                let mix =
+                    |a: u8, b: u8| (f32::from(a) + (f32::from(b) - f32::from(a)) * t).round() as u8;
+                Some(Color::Rgb(mix(r1, r2), mix(g1, g2), mix(b1, b2)))
+            }
+            _ => None,
+        }
+    }
+
+    /// `color` blended towards the background, `level` 1 is full colour.
+    /// Named colours cannot be blended, those themes get `muted` instead
+    pub fn faded(color: Color, level: f32) -> Color {
+        blend(bg(), color, level).unwrap_or_else(muted)
+    }
 
-    pub const BG0: Color = Color::Rgb(24, 22, 22);
-    pub const BORDER: Color = Color::Rgb(98, 94, 90);
+    /// Trace colour for channel `i`, the first is `accent` so a mono trace
+    /// looks as it always has
+    pub fn channel(i: usize) -> Color {
+        let p = palette();
+        [p.accent, p.highlight, p.fg, p.muted][i % 4]
+    }
+
+    /// Magnitude colour for `level` 0..1, from the background through
+    /// `accent` up to `highlight`. Named colours step between those instead
+    pub fn heat(level: f32) -> Color {
+        let level = level.clamp(0.0, 1.0);
+        let blended = if level < 0.5 {
+            blend(bg(), accent(), level * 2.0)
+        } else {
+            blend(accent(), highlight(), level * 2.0 - 1.0)
+        };
+
+        blended.unwrap_or_else(|| match level {
+            l if l < 0.25 => bg(),
+            l if l < 0.5 => muted(),
+            l if l < 0.75 => accent(),
+            _ => highlight(),
+        })
+    }
+}
 
-    pub const FG: Color = Color::Rgb(197, 201, 197);
-    pub const MUTED: Color = Color::Rgb(158, 155, 147);
+/// Leaves raw mode, mouse capture and the alternate screen, safe to call
+/// more than once
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let mut stdout = io::stdout();
+    let _ = execute!(
+        stdout,
+        DisableMouseCapture,
+        DisableFocusChange,
+        LeaveAlternateScreen
+    );
+}
 
-    pub const ORANGE: Color = Color::Rgb(182, 146, 123);
-    pub const YELLOW: Color = Color::Rgb(196, 178, 138);
+/// Calls `restore` when the UI returns or unwinds. A panic on another
+/// thread never reaches this guard, so it also restores from the hook
+struct TuiGuard {
+    restore: fn(),
 }
 
-struct TuiGuard;
+impl TuiGuard {
+    #[must_use]
+    fn new(restore: fn()) -> Self {
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore();
+            hook(info);
+        }));
+
+        Self { restore }
+    }
+}
 
 impl Drop for TuiGuard {
     fn drop(&mut self) {
-        let _ = disable_raw_mode();
-        let mut stdout = io::stdout();
-        let _ = execute!(stdout, DisableFocusChange, LeaveAlternateScreen);
+        (self.restore)();
+    }
+}
+
+/// Frames actually drawn per second, counted over windows of about a second
+/// so the number holds still long enough to read. Alongside it the gaps
+/// between the last `FRAME_TIME_WINDOW` frames, and how often the output
+/// callback refreshed the capture over the same window, so a stutter can be
+/// put down to the terminal or to the audio
+struct FpsCounter {
+    since: Instant,
+    frames: u32,
+    /// Oldest first
+    frame_times: VecDeque<Duration>,
+    last: Instant,
+    audio_load: AudioLoad,
+    /// Callback count when the window opened
+    callbacks: usize,
+    audio_hz: Option<f32>,
+}
+
+/// Gaps between drawn frames over the rolling window
+#[derive(Clone, Copy, Debug)]
+struct FrameTimes {
+    min: Duration,
+    avg: Duration,
+    max: Duration,
+}
+
+impl FpsCounter {
+    #[must_use]
+    fn new(now: Instant, audio_load: AudioLoad) -> Self {
+        Self {
+            since: now,
+            frames: 0,
+            frame_times: VecDeque::with_capacity(FRAME_TIME_WINDOW + 1),
+            last: now,
+            callbacks: audio_load.callbacks(),
+            audio_load,
+            audio_hz: None,
+        }
+    }
+
+    /// Counts a drawn frame, the rate comes out each time a window closes
+    fn frame(&mut self, now: Instant) -> Option<f32> {
+        self.frames += 1;
+        self.frame_times.push_back(now.duration_since(self.last));
+        if self.frame_times.len() > FRAME_TIME_WINDOW {
+            self.frame_times.pop_front();
+        }
+        self.last = now;
+
+        let elapsed = now.duration_since(self.since);
+        if elapsed < Duration::from_secs(1) {
+            return None;
+        }
+
+        let callbacks = self.audio_load.callbacks();
+        self.audio_hz =
+            Some(callbacks.saturating_sub(self.callbacks) as f32 / elapsed.as_secs_f32());
+        self.callbacks = callbacks;
+
+        let fps = self.frames as f32 / elapsed.as_secs_f32();
+        self.since = now;
+        self.frames = 0;
+        Some(fps)
+    }
+
+    /// `None` until a frame has been drawn after the first
+    #[must_use]
+    fn frame_times(&self) -> Option<FrameTimes> {
+        let min = self.frame_times.iter().min()?;
+        let max = self.frame_times.iter().max()?;
+        let total: Duration = self.frame_times.iter().sum();
+
+        Some(FrameTimes {
+            min: *min,
+            avg: total / u32::try_from(self.frame_times.len()).unwrap_or(u32::MAX),
+            max: *max,
+        })
+    }
+
+    /// Capture updates per second over the last closed window
+    #[must_use]
+    fn audio_hz(&self) -> Option<f32> {
+        self.audio_hz
     }
 }
 
@@ -86,6 +380,8 @@ enum Pane {
     Waveforms,
     Adsr,
     Mod,
+    /// The chain ahead of the envelope, reordered and edited slot by slot
+    Effects,
     Keyboard,
 }
 
@@ -95,24 +391,252 @@ impl Pane {
         match self {
             Self::Waveforms => Self::Adsr,
             Self::Adsr => Self::Mod,
-            Self::Mod => Self::Keyboard,
+            Self::Mod => Self::Effects,
+            Self::Effects => Self::Keyboard,
             Self::Keyboard => Self::Waveforms,
         }
     }
 }
 
+/// What the scope panel draws from the captured output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ScopeMode {
+    Wave,
+    Spectrum,
+    /// Spectra over time, newest at the top
+    Spectrogram,
+    /// Left against right, needs a stereo stream
+    Xy,
+}
+
+impl ScopeMode {
+    #[must_use]
+    fn next(self) -> Self {
+        match self {
+            Self::Wave => Self::Spectrum,
+            Self::Spectrum => Self::Spectrogram,
+            Self::Spectrogram => Self::Xy,
+            Self::Xy => Self::Wave,
+        }
+    }
+
+    #[must_use]
+    fn name(self) -> &'static str {
+        match self {
+            Self::Wave => "scope",
+            Self::Spectrum => "spectrum",
+            Self::Spectrogram => "spectrogram",
+            Self::Xy => "x/y",
+        }
+    }
+}
+
+/// How scope intensities, afterglow age and spectrogram level, turn into
+/// colours. A level goes through the contrast curve, then snaps to one of
+/// `shades` steps so a terminal with few colours still shows clear bands.
+/// `mono` draws in black and white only, for palettes where blends vanish
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ScopeShading {
+    shades: usize,
+    /// Exponent of the curve, above 1 sinks the quiet end towards the background
+    contrast: f32,
+    mono: bool,
+}
+
+impl ScopeShading {
+    #[must_use]
+    fn shades(self) -> usize {
+        if self.mono { 2 } else { self.shades.max(2) }
+    }
+
+    /// Shade 0..shades that `level` 0..1 lands on, 0 is the background
+    #[must_use]
+    fn step(self, level: f32) -> usize {
+        let curved = level.clamp(0.0, 1.0).powf(self.contrast.max(0.01));
+        (curved * (self.shades() - 1) as f32).round() as usize
+    }
+
+    /// `level` snapped to its shade, 0..1
+    #[must_use]
+    fn level(self, level: f32) -> f32 {
+        self.step(level) as f32 / (self.shades() - 1) as f32
+    }
+
+    /// Spectrogram colour for `level`
+    #[must_use]
+    fn heat(self, level: f32) -> Color {
+        if self.mono {
+            return Self::mono_color(self.step(level));
+        }
+        colors::heat(self.level(level))
+    }
+
+    /// `color` at `level`, for afterglow trails and the live trace
+    #[must_use]
+    fn trace(self, color: Color, level: f32) -> Color {
+        if self.mono {
+            return Self::mono_color(self.step(level));
+        }
+        colors::faded(color, self.level(level))
+    }
+
+    #[must_use]
+    fn mono_color(step: usize) -> Color {
+        if step == 0 {
+            Color::Black
+        } else {
+            Color::White
+        }
+    }
+}
+
+/// Edge the scope trace waits for so a steady tone stands still
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Trigger {
+    Off,
+    Rising,
+    Falling,
+    /// Starts where the newest note did
+    NoteOn,
+}
+
+impl Trigger {
+    #[must_use]
+    fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Rising,
+            Self::Rising => Self::Falling,
+            Self::Falling => Self::NoteOn,
+            Self::NoteOn => Self::Off,
+        }
+    }
+
+    /// First sample where the signal crosses `level` on this edge, searched
+    /// only where a full `window` still fits after it
+    #[must_use]
+    fn find(self, samples: &[f32], level: f32, window: usize) -> Option<usize> {
+        let last = samples.len().checked_sub(window)?;
+
+        (1..=last).find(|&i| {
+            let (prev, cur) = (samples[i - 1], samples[i]);
+            match self {
+                Self::Off | Self::NoteOn => false,
+                Self::Rising => prev < level && cur >= level,
+                Self::Falling => prev > level && cur <= level,
+            }
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum ModTab {
     Lfo,
+    LfoRoute,
+    Am,
+    RingMod,
+    Bitcrush,
+    SampleHold,
+    HighPass,
     LowPass,
+    Delay,
+    Reverb,
+    NoiseGate,
+    Pan,
+    Layers,
+    Sub,
+    Fm,
+    Unison,
+    Glide,
+    Bend,
+    Scale,
+    Chord,
+    Velocity,
+    Arp,
+    Seq,
 }
 
 impl ModTab {
+    const ALL: [Self; 23] = [
+        Self::Lfo,
+        Self::LfoRoute,
+        Self::Am,
+        Self::RingMod,
+        Self::Bitcrush,
+        Self::SampleHold,
+        Self::HighPass,
+        Self::LowPass,
+        Self::Delay,
+        Self::Reverb,
+        Self::NoiseGate,
+        Self::Pan,
+        Self::Layers,
+        Self::Sub,
+        Self::Fm,
+        Self::Unison,
+        Self::Glide,
+        Self::Bend,
+        Self::Scale,
+        Self::Chord,
+        Self::Velocity,
+        Self::Arp,
+        Self::Seq,
+    ];
+
     #[must_use]
     fn next(self) -> Self {
         match self {
-            Self::Lfo => Self::LowPass,
-            Self::LowPass => Self::Lfo,
+            Self::Lfo => Self::LfoRoute,
+            Self::LfoRoute => Self::Am,
+            Self::Am => Self::RingMod,
+            Self::RingMod => Self::Bitcrush,
+            Self::Bitcrush => Self::SampleHold,
+            Self::SampleHold => Self::HighPass,
+            Self::HighPass => Self::LowPass,
+            Self::LowPass => Self::Delay,
+            Self::Delay => Self::Reverb,
+            Self::Reverb => Self::NoiseGate,
+            Self::NoiseGate => Self::Pan,
+            Self::Pan => Self::Layers,
+            Self::Layers => Self::Sub,
+            Self::Sub => Self::Fm,
+            Self::Fm => Self::Unison,
+            Self::Unison => Self::Glide,
+            Self::Glide => Self::Bend,
+            Self::Bend => Self::Scale,
+            Self::Scale => Self::Chord,
+            Self::Chord => Self::Velocity,
+            Self::Velocity => Self::Arp,
+            Self::Arp => Self::Seq,
+            Self::Seq => Self::Lfo,
+        }
+    }
+
+    #[must_use]
+    fn name(self) -> &'static str {
+        match self {
+            Self::Lfo => "lfo",
+            Self::LfoRoute => "route",
+            Self::Am => "am",
+            Self::RingMod => "ring",
+            Self::Bitcrush => "crush",
+            Self::SampleHold => "s&h",
+            Self::HighPass => "highpass",
+            Self::LowPass => "lowpass",
+            Self::Delay => "delay",
+            Self::Reverb => "reverb",
+            Self::NoiseGate => "gate",
+            Self::Pan => "pan",
+            Self::Layers => "layers",
+            Self::Sub => "sub",
+            Self::Fm => "fm",
+            Self::Unison => "unison",
+            Self::Glide => "glide",
+            Self::Bend => "bend",
+            Self::Scale => "scale",
+            Self::Chord => "chord",
+            Self::Velocity => "velocity",
+            Self::Arp => "arp",
+            Self::Seq => "seq",
         }
     }
 }
@@ -123,10 +647,19 @@ enum AdsrParam {
     Decay,
     Sustain,
     Release,
+    Mode,
+    Curve,
 }
 
 impl AdsrParam {
-    const ALL: [Self; 4] = [Self::Attack, Self::Decay, Self::Sustain, Self::Release];
+    const ALL: [Self; 6] = [
+        Self::Attack,
+        Self::Decay,
+        Self::Sustain,
+        Self::Release,
+        Self::Mode,
+        Self::Curve,
+    ];
 
     #[must_use]
     fn label_and_hint(self) -> (&'static str, &'static str) {
@@ -135,6 +668,8 @@ impl AdsrParam {
             Self::Decay => ("Decay", "(s)"),
             Self::Sustain => ("Sustain", "(0..1)"),
             Self::Release => ("Release", "(s)"),
+            Self::Mode => ("Mode", ""),
+            Self::Curve => ("Curve", ""),
         }
     }
 }
@@ -160,526 +695,3132 @@ impl LfoParam {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum LowPassParam {
-    CutoffHz,
+enum LfoRouteParam {
+    Target,
+    Kind,
+    RateHz,
+    Depth,
 }
 
-impl LowPassParam {
-    const ALL: [Self; 1] = [Self::CutoffHz];
+impl LfoRouteParam {
+    const ALL: [Self; 4] = [Self::Target, Self::Kind, Self::RateHz, Self::Depth];
 
     #[must_use]
     fn label_and_hint(self) -> (&'static str, &'static str) {
         match self {
-            Self::CutoffHz => ("Cutoff", "(Hz)"),
+            Self::Target => ("Target", ""),
+            Self::Kind => ("Wave", ""),
+            Self::RateHz => ("Rate", "(Hz)"),
+            Self::Depth => ("Depth", "(0..1)"),
         }
     }
 }
 
-struct UiState {
-    pane: Pane,
-
-    presets: Vec<Preset>,
-    preset_category_idx: usize,
-    preset_row_idx: usize,
-    show_presets: bool,
-
-    waves: [Wave; 5],
-    wave_idx: usize,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BendParam {
+    Range,
+    Return,
+}
 
-    adsr_param_idx: usize,
-    adsr: Adsr,
+impl BendParam {
+    const ALL: [Self; 2] = [Self::Range, Self::Return];
 
-    mod_tab: ModTab,
+    #[must_use]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Range => ("Range", "(st)"),
+            Self::Return => ("Return", ""),
+        }
+    }
+}
 
-    lfo_param_idx: usize,
-    lfo: LfoAmp,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ScaleParam {
+    Scale,
+    Root,
+    Tuning,
+}
 
-    lowpass_param_idx: usize,
-    lowpass: LowPass,
+impl ScaleParam {
+    const ALL: [Self; 3] = [Self::Scale, Self::Root, Self::Tuning];
 
-    patch_name: String,
-    wave: Wave,
-    muted: bool,
-    volume: f32,
-    held_keys: HashSet<Keycode>,
-    octave: i32,
+    #[must_use]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Scale => ("Scale", ""),
+            Self::Root => ("Root", ""),
+            Self::Tuning => ("Tuning", "(from root)"),
+        }
+    }
 }
 
-impl UiState {
-    #[must_use]
-    fn new(snapshot: Snapshot, presets: Vec<Preset>) -> Self {
-        let waves = [
-            Wave::Sine,
-            Wave::Saw,
-            Wave::Square,
-            Wave::Triangle,
-            Wave::Noise,
-        ];
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SubParam {
+    Wave,
+    Level,
+}
 
-        let wave_idx = waves.iter().position(|w| *w == snapshot.wave).unwrap_or(0);
+impl SubParam {
+    const ALL: [Self; 2] = [Self::Wave, Self::Level];
 
-        Self {
-            pane: Pane::Waveforms,
+    #[must_use]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Wave => ("Wave", ""),
+            Self::Level => ("Level", "(0..1)"),
+        }
+    }
+}
 
-            presets,
-            preset_category_idx: 0,
-            preset_row_idx: 0,
-            show_presets: false,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FmParam {
+    Modulator,
+    Ratio,
+    Index,
+}
 
-            waves,
-            wave_idx,
+impl FmParam {
+    const ALL: [Self; 3] = [Self::Modulator, Self::Ratio, Self::Index];
 
-            adsr_param_idx: 0,
-            adsr: snapshot.adsr,
+    #[must_use]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Modulator => ("Mod", ""),
+            Self::Ratio => ("Ratio", "(x)"),
+            Self::Index => ("Index", "(rad)"),
+        }
+    }
+}
 
-            mod_tab: ModTab::Lfo,
-            lfo_param_idx: 0,
-            lfo: snapshot.lfo_amp,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum VelocityParam {
+    Curve,
+    Keys,
+    Accent,
+}
 
-            lowpass_param_idx: 0,
-            lowpass: snapshot.lowpass,
+impl VelocityParam {
+    const ALL: [Self; 3] = [Self::Curve, Self::Keys, Self::Accent];
 
-            patch_name: snapshot.patch_name,
-            wave: snapshot.wave,
-            muted: snapshot.muted,
-            volume: snapshot.volume,
-            held_keys: HashSet::new(),
-            octave: snapshot.octave,
+    #[must_use]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Curve => ("Curve", ""),
+            Self::Keys => ("Keys", "(1..127)"),
+            Self::Accent => ("Shift", "(1..127)"),
         }
     }
+}
 
-    #[must_use]
-    fn presets_in_selected_category(&self) -> Vec<&Preset> {
-        let category_id = PRESET_CATEGORIES[self.preset_category_idx].0;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AmParam {
+    CarrierHz,
+    Depth,
+}
 
-        self.presets
-            .iter()
-            .filter(|preset| preset.category_id == category_id)
-            .collect()
-    }
+impl AmParam {
+    const ALL: [Self; 2] = [Self::CarrierHz, Self::Depth];
 
     #[must_use]
-    fn selected_preset(&self) -> Option<&Preset> {
-        let presets = self.presets_in_selected_category();
-        presets.get(self.preset_row_idx).copied()
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::CarrierHz => ("Carrier", "(Hz)"),
+            Self::Depth => ("Depth", "(0..1)"),
+        }
     }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RingModParam {
+    FreqHz,
+    Mix,
+}
+
+impl RingModParam {
+    const ALL: [Self; 2] = [Self::FreqHz, Self::Mix];
 
     #[must_use]
-    fn selected_wave(&self) -> Wave {
-        self.waves[self.wave_idx].clone()
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::FreqHz => ("Freq", "(Hz)"),
+            Self::Mix => ("Mix", "(0..1)"),
+        }
     }
+}
 
-    #[must_use]
-    fn selected_adsr_param(&self) -> AdsrParam {
-        AdsrParam::ALL[self.adsr_param_idx]
-    }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BitcrushParam {
+    Bits,
+    Downsample,
+}
+
+impl BitcrushParam {
+    const ALL: [Self; 2] = [Self::Bits, Self::Downsample];
 
     #[must_use]
-    fn selected_lfo_param(&self) -> LfoParam {
-        LfoParam::ALL[self.lfo_param_idx]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Bits => ("Bits", "(1..16)"),
+            Self::Downsample => ("Downsample", "(x)"),
+        }
     }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SampleHoldParam {
+    RateHz,
+    Depth,
+    Target,
+}
+
+impl SampleHoldParam {
+    const ALL: [Self; 3] = [Self::RateHz, Self::Depth, Self::Target];
 
     #[must_use]
-    fn selected_lowpass_param(&self) -> LowPassParam {
-        LowPassParam::ALL[self.lowpass_param_idx]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::RateHz => ("Rate", "(Hz)"),
+            Self::Depth => ("Depth", "(0..1)"),
+            Self::Target => ("Target", ""),
+        }
     }
+}
 
-    fn sync_from_snapshot(&mut self, snapshot: Snapshot) {
-        self.patch_name = snapshot.patch_name;
-        self.wave = snapshot.wave;
-        self.muted = snapshot.muted;
-        self.volume = snapshot.volume;
-        self.adsr = snapshot.adsr;
-        self.lfo = snapshot.lfo_amp;
-        self.lowpass = snapshot.lowpass;
-        self.octave = snapshot.octave;
-        self.sync_wave_idx();
-    }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HighPassParam {
+    CutoffHz,
+    Q,
+}
 
-    fn sync_wave_idx(&mut self) {
-        if let Some(i) = self.waves.iter().position(|wave| *wave == self.wave) {
-            self.wave_idx = i;
+impl HighPassParam {
+    const ALL: [Self; 2] = [Self::CutoffHz, Self::Q];
+
+    #[must_use]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::CutoffHz => ("Cutoff", "(Hz)"),
+            Self::Q => ("Q", ""),
         }
     }
 }
 
-#[allow(clippy::missing_errors_doc)]
-pub async fn run_ui(
-    client: Client,
-    shutdown_tx: watch::Sender<bool>,
-    focused: Arc<AtomicBool>,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let mut stdout = stdout();
-
-    enable_raw_mode()?;
-    execute!(stdout, EnterAlternateScreen, EnableFocusChange)?;
-    let _guard = TuiGuard;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LowPassParam {
+    CutoffHz,
+}
 
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-    terminal.clear()?;
+impl LowPassParam {
+    const ALL: [Self; 1] = [Self::CutoffHz];
 
-    let initial = client.subscribe().borrow().clone();
-    let presets = match import_db().await {
-        Ok(presets) => presets,
-        Err(err) => {
-            eprintln!("failed to load presets: {err}");
-            Vec::new()
+    #[must_use]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::CutoffHz => ("Cutoff", "(Hz)"),
         }
-    };
-    let mut ui = UiState::new(initial, presets);
+    }
+}
 
-    let (key_tx, mut key_rx) = mpsc::unbounded_channel::<KeyEvent>();
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DelayParam {
+    TimeMs,
+    Feedback,
+    Mix,
+}
 
-    let stop = Arc::new(AtomicBool::new(false));
-    spawn_input_thread(key_tx, Arc::clone(&stop), Arc::clone(&focused));
+impl DelayParam {
+    const ALL: [Self; 3] = [Self::TimeMs, Self::Feedback, Self::Mix];
 
-    let mut snapshot_rx = client.subscribe();
-    let mut held_keys_rx = client.subscribe_held_keys();
+    #[must_use]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::TimeMs => ("Time", "(ms)"),
+            Self::Feedback => ("Feedback", "(0..0.95)"),
+            Self::Mix => ("Mix", "(0..1)"),
+        }
+    }
+}
 
-    let intro_start = Instant::now();
-    let mut show_intro = true;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ReverbParam {
+    Room,
+    Damping,
+    Mix,
+}
 
-    loop {
-        if show_intro && intro_start.elapsed() >= Duration::from_secs(1) {
-            show_intro = false;
-        }
+impl ReverbParam {
+    const ALL: [Self; 3] = [Self::Room, Self::Damping, Self::Mix];
 
-        draw_frame(&mut terminal, &ui, show_intro)?;
+    #[must_use]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Room => ("Room", "(0..1)"),
+            Self::Damping => ("Damping", "(0..1)"),
+            Self::Mix => ("Mix", "(0..1)"),
+        }
+    }
+}
 
-        tokio::select! {
-            _ = snapshot_rx.changed() => {
-                let snapshot = snapshot_rx.borrow().clone();
-                ui.sync_from_snapshot(snapshot);
-            }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum NoiseGateParam {
+    Threshold,
+    Attack,
+    Release,
+}
 
-            _ = held_keys_rx.changed() => {
-                ui.held_keys.clone_from(&held_keys_rx.borrow());
-            }
+impl NoiseGateParam {
+    const ALL: [Self; 3] = [Self::Threshold, Self::Attack, Self::Release];
 
-            key = key_rx.recv() => {
-                let Some(key) = key else { break; };
+    #[must_use]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Threshold => ("Threshold", "(dBFS)"),
+            Self::Attack => ("Attack", "(ms)"),
+            Self::Release => ("Release", "(ms)"),
+        }
+    }
+}
 
-                if should_quit(&key) {
-                    let _ = shutdown_tx.send(true);
-                    break;
-                }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PanParam {
+    Position,
+}
 
-                if show_intro {
-                    continue;
-                }
+impl PanParam {
+    const ALL: [Self; 1] = [Self::Position];
 
-                if ui.show_presets {
-                    handle_presets_popup(&mut ui, &client, &key);
-                    continue;
-                }
+    #[must_use]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Position => ("Position", "(L..R)"),
+        }
+    }
+}
 
-                match key.code {
-                    KeyCode::Char(' ') => {
-                        ui.show_presets = true;
-                        ui.preset_row_idx = 0;
-                        continue;
-                    }
-                    KeyCode::Tab => {
-                        ui.pane = ui.pane.next();
-                        continue;
-                    }
-                    _ => {}
-                }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LayerParam {
+    Layer,
+    Wave,
+    Detune,
+    Level,
+}
 
-                match ui.pane {
-                    Pane::Waveforms => handle_waveforms(&mut ui, &client, &key),
-                    Pane::Adsr => handle_adsr(&mut ui, &client, &key),
-                    Pane::Mod => handle_mod(&mut ui, &client, &key),
-                    Pane::Keyboard => handle_keyboard(&mut ui, &client, &key),
-                }
-            }
+impl LayerParam {
+    const ALL: [Self; 4] = [Self::Layer, Self::Wave, Self::Detune, Self::Level];
 
-            () = sleep(Duration::from_millis(16)) => {}
+    #[must_use]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Layer => ("Layer", ""),
+            Self::Wave => ("Wave", ""),
+            Self::Detune => ("Detune", "(cents)"),
+            Self::Level => ("Level", "(0..1)"),
         }
     }
+}
 
-    stop.store(true, Ordering::Relaxed);
-    terminal.show_cursor()?;
-    Ok(())
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum UnisonParam {
+    Voices,
+    Detune,
 }
 
-fn spawn_input_thread(
-    key_tx: mpsc::UnboundedSender<KeyEvent>,
-    stop: Arc<AtomicBool>,
-    focused: Arc<AtomicBool>,
-) {
-    std::thread::spawn(move || {
-        while !stop.load(Ordering::Relaxed) {
-            if event::poll(Duration::from_millis(50)).ok() != Some(true) {
-                continue;
-            }
+impl UnisonParam {
+    const ALL: [Self; 2] = [Self::Voices, Self::Detune];
 
-            match event::read() {
-                Ok(Event::Key(key))
-                    if matches!(key.kind, KeyEventKind::Press | KeyEventKind::Repeat) =>
-                {
-                    let _ = key_tx.send(key);
-                }
-                Ok(Event::FocusLost) => {
-                    focused.store(false, Ordering::Relaxed);
-                }
-                Ok(Event::FocusGained) => {
-                    focused.store(true, Ordering::Relaxed);
-                }
-                _ => {}
-            }
+    #[must_use]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Voices => ("Voices", ""),
+            Self::Detune => ("Detune", "(cents)"),
         }
-    });
+    }
 }
 
-fn draw_frame(
-    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
-    ui: &UiState,
-    show_intro: bool,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    if show_intro {
-        terminal.draw(draw_intro)?;
-    } else {
-        terminal.draw(|f| draw_ui(f, ui))?;
-    }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ArpParam {
+    On,
+    Bpm,
+    Pattern,
+    Length,
+}
 
-    Ok(())
+impl ArpParam {
+    const ALL: [Self; 4] = [Self::On, Self::Bpm, Self::Pattern, Self::Length];
+
+    #[must_use]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::On => ("On", ""),
+            Self::Bpm => ("Tempo", "(bpm, ` tap)"),
+            Self::Pattern => ("Pattern", ""),
+            Self::Length => ("Length", "(of step)"),
+        }
+    }
 }
 
-#[must_use]
-fn should_quit(key: &KeyEvent) -> bool {
-    (key.modifiers.contains(KeyModifiers::CONTROL) && matches!(key.code, KeyCode::Char('c')))
-        || matches!(key.code, KeyCode::Char('q'))
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SeqParam {
+    Play,
+    Bpm,
+    Step,
+    Note,
+    Clear,
 }
 
-fn apply_selected_preset(ui: &mut UiState, client: &Client) {
-    let Some(preset) = ui.selected_preset().cloned() else {
-        return;
-    };
+impl SeqParam {
+    const ALL: [Self; 5] = [Self::Play, Self::Bpm, Self::Step, Self::Note, Self::Clear];
 
-    ui.patch_name = preset.name.clone();
-    ui.wave = preset.wave.clone();
-    ui.sync_wave_idx();
+    #[must_use]
+    fn label_and_hint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Play => ("Play", ""),
+            Self::Bpm => ("Tempo", "(bpm, ` tap)"),
+            Self::Step => ("Step", ""),
+            Self::Note => ("Note", "(Del/Bksp rest)"),
+            Self::Clear => ("Clear", "(←/→)"),
+        }
+    }
+}
 
-    ui.adsr.attack_s = preset.attack;
-    ui.adsr.decay_s = preset.decay;
-    ui.adsr.sustain = preset.sustain;
-    ui.adsr.release_s = preset.release;
+struct UiState {
+    pane: Pane,
 
-    ui.lfo.wave = preset.lfo_wave.clone();
-    ui.lfo.rate_hz = preset.lfo_rate;
-    ui.lfo.depth = preset.lfo_depth;
+    presets: Vec<Preset>,
+    preset_category_idx: usize,
+    preset_row_idx: usize,
+    show_presets: bool,
 
-    ui.lowpass.cutoff_hz = preset.cutoff;
-    ui.octave = preset.octave_shift;
+    waves: [Wave; 9],
+    wave_idx: usize,
+    split_wave_idx: usize,
+    edit_split: bool,
 
-    client.set_wave(preset.wave);
-    client.set_adsr(ui.adsr.clone());
-    client.set_lfo_amp(ui.lfo.clone());
-    client.set_lowpass(ui.lowpass.clone());
-    client.set_octave(ui.octave);
-}
+    adsr_param_idx: usize,
+    adsr: Adsr,
 
-fn handle_presets_popup(ui: &mut UiState, client: &Client, key: &KeyEvent) {
-    match key.code {
+    mod_tab: ModTab,
+
+    lfo_param_idx: usize,
+    lfo: LfoAmp,
+
+    lfo_route_param_idx: usize,
+    lfo_route: Lfo,
+
+    am_param_idx: usize,
+    am: Am,
+
+    ringmod_param_idx: usize,
+    ringmod: RingMod,
+
+    bitcrush_param_idx: usize,
+    bitcrush: Bitcrush,
+
+    sample_hold_param_idx: usize,
+    sample_hold: SampleHold,
+
+    highpass_param_idx: usize,
+    highpass: HighPass,
+
+    lowpass_param_idx: usize,
+    lowpass: LowPass,
+
+    delay_param_idx: usize,
+    delay: Delay,
+
+    reverb_param_idx: usize,
+    reverb: Reverb,
+
+    noise_gate_param_idx: usize,
+    noise_gate: NoiseGate,
+
+    pan_param_idx: usize,
+    pan: Pan,
+
+    gain: Gain,
+    /// Chain ahead of the envelope in signal order, as the effects pane edits it
+    effects: Vec<Fx>,
+    fx_slot: usize,
+    /// Into the effects missing from the chain, what Insert adds next
+    fx_palette_idx: usize,
+
+    layer_param_idx: usize,
+    layer_idx: usize,
+    layers: Vec<Layer>,
+
+    unison_param_idx: usize,
+    unison: Unison,
+
+    tempo: Tempo,
+    /// Beats since the engine's clock started
+    beat: u64,
+    tap_tempo: TapTempo,
+
+    arp_param_idx: usize,
+    arp: Arp,
+
+    seq_param_idx: usize,
+    sequence: Sequence,
+    /// Step being edited
+    seq_cursor: usize,
+    seq_step: Option<usize>,
+
+    patch_name: String,
+    /// Preset or patch file loaded last, where Ctrl+N/P carry on from
+    preset_name: Option<String>,
+    wave: Wave,
+    pulse_duty: f32,
+    /// Blend time when the wave changes under a sounding note
+    crossfade_s: f32,
+    /// Tables the `Table` wave can cycle through
+    wavetables: Vec<Wavetable>,
+    wavetable: Wavetable,
+    sub_param_idx: usize,
+    sub: Sub,
+    fm_param_idx: usize,
+    fm: Fm,
+    split: bool,
+    split_wave: Wave,
+    muted: bool,
+    volume: f32,
+    held_keys: HashSet<Keycode>,
+    octave: i32,
+    transpose: i32,
+    a4_hz: f32,
+    max_voices: usize,
+    voice_mode: VoiceMode,
+    glide_s: f32,
+    /// Current bend in -1..1 of `bend_range_st`
+    bend: f32,
+    bend_param_idx: usize,
+    bend_range_st: f32,
+    bend_return: BendReturn,
+    scale_param_idx: usize,
+    quantizer: Quantizer,
+    tuning: Tuning,
+    /// Scale from `--tuning`, offered after the built-in tunings
+    custom_tuning: Option<Tuning>,
+    chord: Chord,
+    velocity_param_idx: usize,
+    velocity: Velocity,
+    sustain: bool,
+    active_voices: usize,
+    active_keys: Vec<Key>,
+    last_note: Option<Key>,
+    fx_chain: Vec<&'static str>,
+    input: InputEngine,
+    recording: Option<Recording>,
+    /// Engine errors already shown in the status line
+    errors_seen: u64,
+    status: String,
+    /// Target frame rate, `measured_fps` is what the terminal keeps up with
+    fps: u32,
+    measured_fps: Option<f32>,
+    frame_times: Option<FrameTimes>,
+    /// Output callbacks per second, each one a fresh capture
+    audio_hz: Option<f32>,
+    theme: Theme,
+
+    scope_mode: ScopeMode,
+    trigger: Trigger,
+    /// Vertical zoom of the scope trace, set by hand or by `scope_autoscale`
+    scope_scale: f32,
+    scope_autoscale: bool,
+    scope_grid: bool,
+    /// Lists each sounding note's pitch over the scope
+    scope_notes: bool,
+    /// Loudness curve over the spectrum and spectrogram
+    weighting: Weighting,
+    /// Spectrum held by Ctrl+X, in dB before weighting
+    frozen_spectrum: Option<Vec<f32>>,
+    /// Earlier frames of the wave scope, oldest first, newest last
+    scope_trails: VecDeque<ScopeTrace>,
+    scope_afterglow: bool,
+    scope_afterglow_depth: usize,
+    scope_shading: ScopeShading,
+    /// Frames the scope window is panned back from the newest, by dragging
+    scope_offset: usize,
+    /// Column the current drag was last seen at
+    scope_drag: Option<u16>,
+    /// Where the scope was last drawn, for hit testing the mouse
+    scope_area: Cell<Rect>,
+    /// Newest first, a level 0..1 per column
+    spectrogram: VecDeque<Vec<f32>>,
+    spectrogram_at: Instant,
+    /// What the scope shows, the synth output or `mic` while it is open
+    capture: Capture,
+    /// The whole synth mix
+    output: Capture,
+    /// The voice the scope is pinned to, shown instead of `output` while
+    /// `scope_voice` is set
+    voice_capture: Capture,
+    /// Pitch of the pinned voice
+    scope_voice: Option<f32>,
+    mic: Option<MicCapture>,
+    /// From `--input-device`, `None` is the system default
+    input_device: Option<String>,
+    /// Output callback load, kept apart from `capture` since that may be the mic
+    audio_load: AudioLoad,
+}
+
+impl UiState {
+    #[must_use]
+    fn new(
+        snapshot: Snapshot,
+        presets: Vec<Preset>,
+        capture: Capture,
+        voice_capture: Capture,
+        fps: u32,
+        theme: Theme,
+    ) -> Self {
+        let waves = [
+            Wave::Sine,
+            Wave::Saw,
+            Wave::Square,
+            Wave::Pulse,
+            Wave::Triangle,
+            Wave::Noise,
+            Wave::PinkNoise,
+            Wave::BrownNoise,
+            Wave::Wavetable,
+        ];
+
+        let wave_idx = waves.iter().position(|w| *w == snapshot.wave).unwrap_or(0);
+
+        // a table loaded with --wavetable joins the built-in ones
+        let mut wavetables = Wavetable::builtins().to_vec();
+        if !wavetables
+            .iter()
+            .any(|t| t.name() == snapshot.wavetable.name())
+        {
+            wavetables.push(snapshot.wavetable.clone());
+        }
+        let split_wave_idx = waves
+            .iter()
+            .position(|w| *w == snapshot.split_wave)
+            .unwrap_or(0);
+
+        Self {
+            pane: Pane::Waveforms,
+
+            presets,
+            preset_category_idx: 0,
+            preset_row_idx: 0,
+            show_presets: false,
+
+            waves,
+            wave_idx,
+            split_wave_idx,
+            edit_split: false,
+
+            adsr_param_idx: 0,
+            adsr: snapshot.adsr,
+
+            mod_tab: ModTab::Lfo,
+            lfo_param_idx: 0,
+            lfo: snapshot.lfo_amp,
+
+            lfo_route_param_idx: 0,
+            lfo_route: snapshot.lfo,
+
+            am_param_idx: 0,
+            am: snapshot.am,
+
+            ringmod_param_idx: 0,
+            ringmod: snapshot.ringmod,
+
+            bitcrush_param_idx: 0,
+            bitcrush: snapshot.bitcrush,
+
+            sample_hold_param_idx: 0,
+            sample_hold: snapshot.sample_hold,
+
+            highpass_param_idx: 0,
+            highpass: snapshot.highpass,
+
+            lowpass_param_idx: 0,
+            lowpass: snapshot.lowpass,
+
+            delay_param_idx: 0,
+            delay: snapshot.delay,
+
+            reverb_param_idx: 0,
+            reverb: snapshot.reverb,
+
+            noise_gate_param_idx: 0,
+            noise_gate: snapshot.noise_gate,
+
+            pan_param_idx: 0,
+            pan: snapshot.pan,
+
+            gain: snapshot.gain,
+            effects: snapshot.effects,
+            fx_slot: 0,
+            fx_palette_idx: 0,
+
+            layer_param_idx: 0,
+            layer_idx: 0,
+            layers: snapshot.layers,
+
+            unison_param_idx: 0,
+            unison: snapshot.unison,
+
+            tempo: snapshot.tempo,
+            beat: snapshot.beat,
+            tap_tempo: TapTempo::default(),
+
+            arp_param_idx: 0,
+            arp: snapshot.arp,
+
+            seq_param_idx: 0,
+            sequence: snapshot.sequence,
+            seq_cursor: 0,
+            seq_step: snapshot.seq_step,
+
+            patch_name: snapshot.patch_name,
+            preset_name: None,
+            wave: snapshot.wave,
+            pulse_duty: snapshot.pulse_duty,
+            crossfade_s: snapshot.crossfade_s,
+            wavetables,
+            wavetable: snapshot.wavetable,
+            sub_param_idx: 0,
+            sub: snapshot.sub,
+            fm_param_idx: 0,
+            fm: snapshot.fm,
+            split: snapshot.split,
+            split_wave: snapshot.split_wave,
+            muted: snapshot.muted,
+            volume: snapshot.volume,
+            held_keys: HashSet::new(),
+            octave: snapshot.octave,
+            transpose: snapshot.transpose,
+            a4_hz: snapshot.a4_hz,
+            max_voices: snapshot.max_voices,
+            voice_mode: snapshot.voice_mode,
+            glide_s: snapshot.glide_s,
+            bend: snapshot.bend,
+            bend_param_idx: 0,
+            bend_range_st: snapshot.bend_range_st,
+            bend_return: snapshot.bend_return,
+            scale_param_idx: 0,
+            quantizer: snapshot.quantizer,
+            custom_tuning: matches!(snapshot.tuning, Tuning::Custom(_))
+                .then(|| snapshot.tuning.clone()),
+            tuning: snapshot.tuning,
+            chord: snapshot.chord,
+            velocity_param_idx: 0,
+            velocity: snapshot.velocity,
+            sustain: snapshot.sustain,
+            active_voices: snapshot.active_voices,
+            active_keys: snapshot.active_keys,
+            last_note: snapshot.last_note,
+            fx_chain: snapshot.fx_chain,
+            input: snapshot.input,
+            recording: snapshot.recording,
+            errors_seen: snapshot.error.as_ref().map_or(0, |(count, _)| *count),
+            // an error from before the UI started, like a stream fallback
+            status: snapshot
+                .error
+                .as_ref()
+                .map(|(_, err)| err.clone())
+                .unwrap_or_default(),
+            fps: fps.clamp(1, UI_FPS_MAX),
+            measured_fps: None,
+            frame_times: None,
+            audio_hz: None,
+            theme,
+
+            scope_mode: ScopeMode::Wave,
+            trigger: Trigger::Off,
+            scope_scale: 1.0,
+            scope_autoscale: SCOPE_AUTOSCALE,
+            scope_grid: SCOPE_GRID,
+            scope_notes: SCOPE_NOTES,
+            weighting: SPECTRUM_WEIGHTING,
+            frozen_spectrum: None,
+            scope_trails: VecDeque::with_capacity(SCOPE_AFTERGLOW_DEPTH_MAX + 1),
+            scope_afterglow: SCOPE_AFTERGLOW,
+            scope_afterglow_depth: SCOPE_AFTERGLOW_DEPTH,
+            scope_shading: ScopeShading {
+                shades: SCOPE_SHADES,
+                contrast: SCOPE_CONTRAST,
+                mono: SCOPE_MONO,
+            },
+            scope_offset: 0,
+            scope_drag: None,
+            scope_area: Cell::new(Rect::default()),
+            spectrogram: VecDeque::new(),
+            spectrogram_at: Instant::now(),
+            audio_load: capture.audio_load().clone(),
+            output: capture.clone(),
+            capture,
+            voice_capture,
+            scope_voice: None,
+            mic: None,
+            input_device: None,
+        }
+    }
+
+    /// The synth side of the scope, the pinned voice or else the whole mix
+    fn synth_capture(&self) -> Capture {
+        if self.scope_voice.is_some() {
+            self.voice_capture.clone()
+        } else {
+            self.output.clone()
+        }
+    }
+
+    /// Follows the engine pinning the scope to a voice or letting go of it,
+    /// the mic keeps the scope while it is open
+    fn sync_scope_voice(&mut self, scope_voice: Option<f32>) {
+        let switched = scope_voice.is_some() != self.scope_voice.is_some();
+        self.scope_voice = scope_voice;
+        if !switched {
+            return;
+        }
+
+        self.status = match scope_voice {
+            Some(hz) => format!("scope pinned to the {hz:.1} Hz voice"),
+            None => "scope showing the mix".to_string(),
+        };
+        if self.mic.is_none() {
+            let dc_block = self.capture.dc_block();
+            self.capture = self.synth_capture();
+            self.capture.set_dc_block(dc_block);
+            self.scope_trails.clear();
+            self.scope_offset = 0;
+        }
+    }
+
+    #[must_use]
+    fn presets_in_selected_category(&self) -> Vec<&Preset> {
+        let category_id = PRESET_CATEGORIES[self.preset_category_idx].0;
+
+        self.presets
+            .iter()
+            .filter(|preset| preset.category_id == category_id)
+            .collect()
+    }
+
+    #[must_use]
+    fn selected_preset(&self) -> Option<&Preset> {
+        let presets = self.presets_in_selected_category();
+        presets.get(self.preset_row_idx).copied()
+    }
+
+    #[must_use]
+    fn selected_wave(&self) -> Wave {
+        self.waves[self.wave_idx].clone()
+    }
+
+    #[must_use]
+    fn selected_split_wave(&self) -> Wave {
+        self.waves[self.split_wave_idx].clone()
+    }
+
+    #[must_use]
+    fn selected_adsr_param(&self) -> AdsrParam {
+        AdsrParam::ALL[self.adsr_param_idx]
+    }
+
+    #[must_use]
+    fn selected_lfo_param(&self) -> LfoParam {
+        LfoParam::ALL[self.lfo_param_idx]
+    }
+
+    #[must_use]
+    fn selected_lfo_route_param(&self) -> LfoRouteParam {
+        LfoRouteParam::ALL[self.lfo_route_param_idx]
+    }
+
+    #[must_use]
+    fn selected_bend_param(&self) -> BendParam {
+        BendParam::ALL[self.bend_param_idx]
+    }
+
+    #[must_use]
+    fn selected_scale_param(&self) -> ScaleParam {
+        ScaleParam::ALL[self.scale_param_idx]
+    }
+
+    #[must_use]
+    fn selected_sub_param(&self) -> SubParam {
+        SubParam::ALL[self.sub_param_idx]
+    }
+
+    #[must_use]
+    fn selected_fm_param(&self) -> FmParam {
+        FmParam::ALL[self.fm_param_idx]
+    }
+
+    #[must_use]
+    fn selected_velocity_param(&self) -> VelocityParam {
+        VelocityParam::ALL[self.velocity_param_idx]
+    }
+
+    #[must_use]
+    fn selected_am_param(&self) -> AmParam {
+        AmParam::ALL[self.am_param_idx]
+    }
+
+    #[must_use]
+    fn selected_ringmod_param(&self) -> RingModParam {
+        RingModParam::ALL[self.ringmod_param_idx]
+    }
+
+    #[must_use]
+    fn selected_bitcrush_param(&self) -> BitcrushParam {
+        BitcrushParam::ALL[self.bitcrush_param_idx]
+    }
+
+    #[must_use]
+    fn selected_sample_hold_param(&self) -> SampleHoldParam {
+        SampleHoldParam::ALL[self.sample_hold_param_idx]
+    }
+
+    #[must_use]
+    fn selected_highpass_param(&self) -> HighPassParam {
+        HighPassParam::ALL[self.highpass_param_idx]
+    }
+
+    #[must_use]
+    fn selected_lowpass_param(&self) -> LowPassParam {
+        LowPassParam::ALL[self.lowpass_param_idx]
+    }
+
+    #[must_use]
+    fn selected_delay_param(&self) -> DelayParam {
+        DelayParam::ALL[self.delay_param_idx]
+    }
+
+    #[must_use]
+    fn selected_reverb_param(&self) -> ReverbParam {
+        ReverbParam::ALL[self.reverb_param_idx]
+    }
+
+    #[must_use]
+    fn selected_noise_gate_param(&self) -> NoiseGateParam {
+        NoiseGateParam::ALL[self.noise_gate_param_idx]
+    }
+
+    #[must_use]
+    fn selected_pan_param(&self) -> PanParam {
+        PanParam::ALL[self.pan_param_idx]
+    }
+
+    #[must_use]
+    fn selected_layer_param(&self) -> LayerParam {
+        LayerParam::ALL[self.layer_param_idx]
+    }
+
+    #[must_use]
+    fn selected_unison_param(&self) -> UnisonParam {
+        UnisonParam::ALL[self.unison_param_idx]
+    }
+
+    #[must_use]
+    fn selected_arp_param(&self) -> ArpParam {
+        ArpParam::ALL[self.arp_param_idx]
+    }
+
+    #[must_use]
+    fn selected_seq_param(&self) -> SeqParam {
+        SeqParam::ALL[self.seq_param_idx]
+    }
+
+    #[must_use]
+    fn selected_fx(&self) -> Option<Fx> {
+        self.effects.get(self.fx_slot).copied()
+    }
+
+    /// Effects not in the chain yet, in their default order
+    #[must_use]
+    fn fx_palette(&self) -> Vec<Fx> {
+        Fx::ALL
+            .into_iter()
+            .filter(|fx| !self.effects.contains(fx))
+            .collect()
+    }
+
+    #[must_use]
+    fn fx_pick(&self) -> Option<Fx> {
+        let palette = self.fx_palette();
+        palette
+            .get(self.fx_palette_idx.min(palette.len().saturating_sub(1)))
+            .copied()
+    }
+
+    fn sync_from_snapshot(&mut self, snapshot: Snapshot) {
+        self.patch_name = snapshot.patch_name;
+        self.wave = snapshot.wave;
+        self.pulse_duty = snapshot.pulse_duty;
+        self.crossfade_s = snapshot.crossfade_s;
+        self.wavetable = snapshot.wavetable;
+        self.sub = snapshot.sub;
+        self.fm = snapshot.fm;
+        self.split = snapshot.split;
+        self.split_wave = snapshot.split_wave;
+        self.edit_split = snapshot.edit_split;
+        self.muted = snapshot.muted;
+        self.volume = snapshot.volume;
+        self.adsr = snapshot.adsr;
+        self.lfo = snapshot.lfo_amp;
+        self.lfo_route = snapshot.lfo;
+        self.am = snapshot.am;
+        self.ringmod = snapshot.ringmod;
+        self.bitcrush = snapshot.bitcrush;
+        self.sample_hold = snapshot.sample_hold;
+        self.highpass = snapshot.highpass;
+        self.lowpass = snapshot.lowpass;
+        self.delay = snapshot.delay;
+        self.reverb = snapshot.reverb;
+        self.noise_gate = snapshot.noise_gate;
+        self.pan = snapshot.pan;
+        self.gain = snapshot.gain;
+        self.effects = snapshot.effects;
+        self.fx_slot = self.fx_slot.min(self.effects.len().saturating_sub(1));
+        self.layers = snapshot.layers;
+        self.layer_idx = self.layer_idx.min(self.layers.len().saturating_sub(1));
+        self.unison = snapshot.unison;
+        self.octave = snapshot.octave;
+        self.transpose = snapshot.transpose;
+        self.a4_hz = snapshot.a4_hz;
+        self.max_voices = snapshot.max_voices;
+        self.voice_mode = snapshot.voice_mode;
+        self.glide_s = snapshot.glide_s;
+        self.bend = snapshot.bend;
+        self.bend_range_st = snapshot.bend_range_st;
+        self.bend_return = snapshot.bend_return;
+        self.quantizer = snapshot.quantizer;
+        self.tuning = snapshot.tuning;
+        self.chord = snapshot.chord;
+        self.velocity = snapshot.velocity;
+        self.sustain = snapshot.sustain;
+        self.tempo = snapshot.tempo;
+        self.beat = snapshot.beat;
+        self.seq_step = snapshot.seq_step;
+        self.active_voices = snapshot.active_voices;
+        self.sync_scope_voice(snapshot.scope_voice);
+        self.active_keys = snapshot.active_keys;
+        self.last_note = snapshot.last_note;
+        self.fx_chain = snapshot.fx_chain;
+        self.input = snapshot.input;
+
+        if let (Some(done), None) = (&self.recording, &snapshot.recording) {
+            self.status = match done.limit {
+                Some(limit) if done.started.elapsed() >= limit => format!(
+                    "recording reached its {} limit, saved {}",
+                    clock(limit.as_secs()),
+                    done.path.display()
+                ),
+                _ => format!("saved {}", done.path.display()),
+            };
+        }
+        self.recording = snapshot.recording;
+        if let Some((count, err)) = snapshot.error
+            && count != self.errors_seen
+        {
+            self.errors_seen = count;
+            self.status = err;
+        }
+        self.sync_wave_idx();
+    }
+
+    /// Moves the auto zoom one frame towards filling `SCOPE_AUTOSCALE_FILL`
+    /// of the height. A louder peak zooms out at once so nothing clips, a
+    /// quieter one zooms in over `SCOPE_AUTOSCALE_TAU_S`. Silence keeps the
+    /// zoom it had
+    fn update_scope_scale(&mut self) {
+        if !self.scope_autoscale {
+            return;
+        }
+
+        let window = scope_window(&self.capture);
+        let peak = self
+            .capture
+            .frames(window + self.scope_offset)
+            .iter()
+            .flat_map(|ch| ch.iter().take(window))
+            .fold(0.0f32, |peak, s| peak.max(s.abs()));
+        if peak < SCOPE_AUTOSCALE_FLOOR {
+            return;
+        }
+
+        let target = (SCOPE_AUTOSCALE_FILL / peak).clamp(SCOPE_SCALE_MIN, SCOPE_SCALE_MAX);
+        if target < self.scope_scale {
+            self.scope_scale = target;
+        } else {
+            let alpha = 1.0 - (-1.0 / (self.fps as f32 * SCOPE_AUTOSCALE_TAU_S)).exp();
+            self.scope_scale += (target - self.scope_scale) * alpha;
+        }
+    }
+
+    /// Scrolls a new row into the spectrogram every `SPECTROGRAM_ROW_MS`,
+    /// sized to the panel as it was last drawn. A resize starts it over
+    fn update_spectrogram(&mut self, now: Instant) {
+        if self.scope_mode != ScopeMode::Spectrogram {
+            self.spectrogram.clear();
+            return;
+        }
+
+        let area = self.scope_area.get();
+        let cols = usize::from(area.width.saturating_sub(2));
+        // two rows per cell, less the border and the label line
+        let rows = usize::from(area.height.saturating_sub(3)) * 2;
+        let sample_rate = self.capture.sample_rate();
+        if cols == 0 || rows == 0 || sample_rate == 0 {
+            return;
+        }
+
+        if let Some(row) = self.spectrogram.front()
+            && row.len() != cols
+        {
+            self.spectrogram.clear();
+        }
+        if !self.spectrogram.is_empty()
+            && now < self.spectrogram_at + Duration::from_millis(SPECTROGRAM_ROW_MS)
+        {
+            return;
+        }
+
+        self.spectrogram_at = now;
+        self.spectrogram.push_front(spectrogram_row(
+            &self.capture,
+            sample_rate,
+            cols,
+            self.weighting,
+        ));
+        self.spectrogram.truncate(rows);
+    }
+
+    /// Captures this frame's trace for the wave scope and keeps the last
+    /// `scope_afterglow_depth` before it when afterglow is on
+    fn update_scope_trails(&mut self) {
+        if !matches!(self.scope_mode, ScopeMode::Wave | ScopeMode::Xy) {
+            self.scope_trails.clear();
+            return;
+        }
+
+        let keep = if self.scope_afterglow {
+            self.scope_afterglow_depth + 1
+        } else {
+            1
+        };
+        while self.scope_trails.len() >= keep {
+            self.scope_trails.pop_front();
+        }
+        self.scope_trails
+            .push_back(scope_trace(&self.capture, self.trigger, self.scope_offset));
+    }
+
+    fn sync_wave_idx(&mut self) {
+        if let Some(i) = self.waves.iter().position(|wave| *wave == self.wave) {
+            self.wave_idx = i;
+        }
+        if let Some(i) = self.waves.iter().position(|wave| *wave == self.split_wave) {
+            self.split_wave_idx = i;
+        }
+    }
+}
+
+#[allow(clippy::missing_errors_doc)]
+pub async fn run_ui(
+    client: Client,
+    shutdown_tx: watch::Sender<bool>,
+    focused: Arc<AtomicBool>,
+    fps: u32,
+    theme: Theme,
+    intro_mode: IntroMode,
+    input_device: Option<String>,
+) -> Result<()> {
+    let mut stdout = stdout();
+    colors::set_theme(theme);
+
+    enable_raw_mode().map_err(MugenError::Terminal)?;
+    let _guard = TuiGuard::new(restore_terminal);
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableFocusChange,
+        EnableMouseCapture
+    )
+    .map_err(MugenError::Terminal)?;
+
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend).map_err(MugenError::Terminal)?;
+    terminal.clear().map_err(MugenError::Terminal)?;
+
+    let initial = client.subscribe().borrow().clone();
+    // stderr is hidden behind the alternate screen, so say it in the status
+    let (presets, presets_err) = match import_db().await {
+        Ok(presets) => (presets, None),
+        Err(err) => (Vec::new(), Some(err)),
+    };
+    let mut ui = UiState::new(
+        initial,
+        presets,
+        client.capture().clone(),
+        client.voice_capture().clone(),
+        fps,
+        theme,
+    );
+    ui.input_device = input_device;
+    if let Some(err) = presets_err {
+        ui.status = format!("failed to load presets: {err}");
+    }
+
+    let (key_tx, mut key_rx) = mpsc::unbounded_channel::<KeyEvent>();
+    let (mouse_tx, mut mouse_rx) = mpsc::unbounded_channel::<MouseEvent>();
+
+    let stop = Arc::new(AtomicBool::new(false));
+    spawn_input_thread(key_tx, mouse_tx, Arc::clone(&stop), Arc::clone(&focused));
+
+    let mut snapshot_rx = client.subscribe();
+    let mut held_keys_rx = client.subscribe_held_keys();
+    let mut shutdown_rx = shutdown_tx.subscribe();
+
+    let mut intro = Intro::new(
+        intro_mode,
+        intro_stamp().as_deref(),
+        Instant::now(),
+        today(),
+    );
+    let mut next_frame = Instant::now();
+    let mut fps_counter = FpsCounter::new(next_frame, ui.audio_load.clone());
+
+    loop {
+        let show_intro = intro.visible(Instant::now());
+
+        // events only change state, drawing keeps to the frame clock so a
+        // burst of keys or snapshots cannot push the rate past the target
+        let now = Instant::now();
+        if now >= next_frame {
+            ui.update_scope_scale();
+            ui.update_scope_trails();
+            ui.update_spectrogram(now);
+            draw_frame(&mut terminal, &ui, show_intro)?;
+            if let Some(fps) = fps_counter.frame(now) {
+                ui.measured_fps = Some(fps);
+                ui.audio_hz = fps_counter.audio_hz();
+            }
+            ui.frame_times = fps_counter.frame_times();
+            // a slow frame pushes the next one back rather than bunching up
+            next_frame = (next_frame + Duration::from_secs(1) / ui.fps).max(now);
+        }
+
+        tokio::select! {
+            // the audio side gave up, e.g. no output device
+            _ = shutdown_rx.wait_for(|&down| down) => break,
+
+            _ = snapshot_rx.changed() => {
+                let snapshot = snapshot_rx.borrow().clone();
+                ui.sync_from_snapshot(snapshot);
+            }
+
+            _ = held_keys_rx.changed() => {
+                ui.held_keys.clone_from(&held_keys_rx.borrow());
+            }
+
+            Some(mouse) = mouse_rx.recv() => {
+                if !show_intro && !ui.show_presets {
+                    handle_scope_mouse(&mut ui, mouse);
+                }
+            }
+
+            key = key_rx.recv() => {
+                let Some(key) = key else { break; };
+
+                if should_quit(&key) {
+                    let _ = shutdown_tx.send(true);
+                    break;
+                }
+
+                if show_intro {
+                    intro.dismiss();
+                    continue;
+                }
+
+                // End is free in every pane, Backspace clears a sequencer step
+                if key.code == KeyCode::End {
+                    all_notes_off(&mut ui, &client);
+                    continue;
+                }
+
+                if ui.show_presets {
+                    handle_presets_popup(&mut ui, &client, &key);
+                    continue;
+                }
+
+                if key.modifiers.contains(KeyModifiers::CONTROL) {
+                    match key.code {
+                        KeyCode::Char('s') => {
+                            save_patch(&mut ui, &snapshot_rx.borrow());
+                            continue;
+                        }
+                        KeyCode::Char('o') => {
+                            open_patch(&mut ui, &client);
+                            continue;
+                        }
+                        KeyCode::Char('n') => {
+                            cycle_patch(&mut ui, &client, 1);
+                            continue;
+                        }
+                        KeyCode::Char('p') => {
+                            cycle_patch(&mut ui, &client, -1);
+                            continue;
+                        }
+                        KeyCode::Char('f') => {
+                            cycle_fps(&mut ui);
+                            continue;
+                        }
+                        KeyCode::Char('d') => {
+                            let on = !ui.capture.dc_block();
+                            ui.capture.set_dc_block(on);
+                            ui.status = format!("scope DC blocker {}", if on { "on" } else { "off" });
+                            continue;
+                        }
+                        KeyCode::Char('a') => {
+                            ui.scope_autoscale = !ui.scope_autoscale;
+                            ui.status = format!(
+                                "scope auto-scale {}",
+                                if ui.scope_autoscale { "on" } else { "off" }
+                            );
+                            continue;
+                        }
+                        KeyCode::Char('r') => {
+                            ui.scope_grid = !ui.scope_grid;
+                            ui.status =
+                                format!("scope grid {}", if ui.scope_grid { "on" } else { "off" });
+                            continue;
+                        }
+                        KeyCode::Char('g') => {
+                            ui.scope_afterglow = !ui.scope_afterglow;
+                            ui.status = format!(
+                                "scope afterglow {}",
+                                if ui.scope_afterglow { "on" } else { "off" }
+                            );
+                            continue;
+                        }
+                        KeyCode::Char('e') => {
+                            cycle_scope_shades(&mut ui);
+                            continue;
+                        }
+                        KeyCode::Char('k') => {
+                            cycle_scope_contrast(&mut ui);
+                            continue;
+                        }
+                        KeyCode::Char('v') => {
+                            client.pin_next_voice();
+                            continue;
+                        }
+                        KeyCode::Char('y') => {
+                            ui.scope_notes = !ui.scope_notes;
+                            ui.status = format!(
+                                "scope note pitches {}",
+                                if ui.scope_notes { "on" } else { "off" }
+                            );
+                            continue;
+                        }
+                        KeyCode::Char('x') => {
+                            freeze_spectrum(&mut ui, &client);
+                            continue;
+                        }
+                        KeyCode::Char('w') => {
+                            ui.weighting = ui.weighting.next();
+                            ui.spectrogram.clear();
+                            ui.status = format!("spectrum {}", ui.weighting.name());
+                            continue;
+                        }
+                        KeyCode::Char('b') => {
+                            let shading = &mut ui.scope_shading;
+                            shading.mono = !shading.mono;
+                            ui.status = format!(
+                                "scope black and white {}",
+                                if shading.mono { "on" } else { "off" }
+                            );
+                            continue;
+                        }
+                        KeyCode::Left => {
+                            step_afterglow(&mut ui, -1);
+                            continue;
+                        }
+                        KeyCode::Right => {
+                            step_afterglow(&mut ui, 1);
+                            continue;
+                        }
+                        KeyCode::Up => {
+                            zoom_scope(&mut ui, 2.0);
+                            continue;
+                        }
+                        KeyCode::Down => {
+                            zoom_scope(&mut ui, 0.5);
+                            continue;
+                        }
+                        KeyCode::Char('l') => {
+                            toggle_scope_source(&mut ui);
+                            continue;
+                        }
+                        KeyCode::Char('t') => {
+                            ui.theme = ui.theme.next();
+                            colors::set_theme(ui.theme);
+                            ui.status = format!("theme {}", ui.theme.name());
+                            continue;
+                        }
+                        // an unbound chord does nothing, rather than
+                        // playing or acting as the bare key
+                        _ => continue,
+                    }
+                }
+
+                if ui.input == InputEngine::Terminal
+                    && let Some(keycode) = terminal_note(&key)
+                {
+                    client.press_key(keycode, accented(&key));
+                    continue;
+                }
+
+                match key.code {
+                    KeyCode::Char(' ') => {
+                        ui.show_presets = true;
+                        ui.preset_row_idx = 0;
+                        continue;
+                    }
+                    KeyCode::Tab => {
+                        ui.pane = ui.pane.next();
+                        continue;
+                    }
+                    KeyCode::Char('i') => {
+                        ui.input = ui.input.toggle();
+                        client.set_input_engine(ui.input);
+                        continue;
+                    }
+                    KeyCode::Char('c') => {
+                        copy_wave_sparkline(&mut ui);
+                        continue;
+                    }
+                    KeyCode::Char('v') => {
+                        ui.scope_mode = ui.scope_mode.next();
+                        continue;
+                    }
+                    KeyCode::Char('x') => {
+                        ui.trigger = ui.trigger.next();
+                        continue;
+                    }
+                    KeyCode::Char('r') => {
+                        client.toggle_recording();
+                        continue;
+                    }
+                    KeyCode::Char('m') => {
+                        ui.voice_mode = ui.voice_mode.next();
+                        client.set_voice_mode(ui.voice_mode);
+                        continue;
+                    }
+                    KeyCode::Char('\\') => {
+                        ui.sequence.playing = !ui.sequence.playing;
+                        send_sequence(&mut ui, &client);
+                        continue;
+                    }
+                    KeyCode::Char('/') => {
+                        ui.arp.enabled = !ui.arp.enabled;
+                        client.set_arp(ui.arp.clone());
+                        continue;
+                    }
+                    KeyCode::Char('`') => {
+                        tap_tempo(&mut ui, &client);
+                        continue;
+                    }
+                    KeyCode::Char('9') => {
+                        nudge_volume(&mut ui, &client, -VOLUME_STEP);
+                        continue;
+                    }
+                    KeyCode::Char('0') => {
+                        nudge_volume(&mut ui, &client, VOLUME_STEP);
+                        continue;
+                    }
+                    KeyCode::Char('M') => {
+                        ui.muted = !ui.muted;
+                        client.set_muted(ui.muted);
+                        continue;
+                    }
+                    KeyCode::Char('n') => {
+                        ui.sustain = !ui.sustain;
+                        client.set_sustain(ui.sustain);
+                        continue;
+                    }
+                    KeyCode::Char('[') => {
+                        shift_octave(&mut ui, &client, -1);
+                        continue;
+                    }
+                    KeyCode::Char(']') => {
+                        shift_octave(&mut ui, &client, 1);
+                        continue;
+                    }
+                    KeyCode::Char('{') => {
+                        client.bend_key(-1.0);
+                        continue;
+                    }
+                    KeyCode::Char('}') => {
+                        client.bend_key(1.0);
+                        continue;
+                    }
+                    KeyCode::Char(',') => {
+                        nudge_transpose(&mut ui, &client, -1);
+                        continue;
+                    }
+                    KeyCode::Char('.') => {
+                        nudge_transpose(&mut ui, &client, 1);
+                        continue;
+                    }
+                    KeyCode::Char('z') => {
+                        ui.split = !ui.split;
+                        ui.edit_split &= ui.split;
+                        client.set_split(ui.split);
+                        continue;
+                    }
+                    _ => {}
+                }
+
+                match ui.pane {
+                    Pane::Waveforms => handle_waveforms(&mut ui, &client, &key),
+                    Pane::Adsr => handle_adsr(&mut ui, &client, &key),
+                    Pane::Mod => handle_mod(&mut ui, &client, &key),
+                    Pane::Effects => handle_effects(&mut ui, &client, &key),
+                    Pane::Keyboard => handle_keyboard(&mut ui, &client, &key),
+                }
+            }
+
+            () = sleep_until(next_frame.into()) => {}
+        }
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    terminal.show_cursor().map_err(MugenError::Terminal)?;
+    Ok(())
+}
+
+fn spawn_input_thread(
+    key_tx: mpsc::UnboundedSender<KeyEvent>,
+    mouse_tx: mpsc::UnboundedSender<MouseEvent>,
+    stop: Arc<AtomicBool>,
+    focused: Arc<AtomicBool>,
+) {
+    std::thread::spawn(move || {
+        while !stop.load(Ordering::Relaxed) {
+            if event::poll(Duration::from_millis(50)).ok() != Some(true) {
+                continue;
+            }
+
+            match event::read() {
+                Ok(Event::Key(key))
+                    if matches!(key.kind, KeyEventKind::Press | KeyEventKind::Repeat) =>
+                {
+                    let _ = key_tx.send(key);
+                }
+                Ok(Event::Mouse(mouse)) => {
+                    let _ = mouse_tx.send(mouse);
+                }
+                Ok(Event::FocusLost) => {
+                    focused.store(false, Ordering::Relaxed);
+                }
+                Ok(Event::FocusGained) => {
+                    focused.store(true, Ordering::Relaxed);
+                }
+                _ => {}
+            }
+        }
+    });
+}
+
+fn draw_frame(
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    ui: &UiState,
+    show_intro: bool,
+) -> Result<()> {
+    if show_intro {
+        terminal.draw(draw_intro).map_err(MugenError::Terminal)?;
+    } else {
+        terminal
+            .draw(|f| draw_ui(f, ui))
+            .map_err(MugenError::Terminal)?;
+    }
+
+    Ok(())
+}
+
+/// The intro plays for `INTRO_DURATION_MS` from launch, any key cuts it short
+struct Intro {
+    until: Option<Instant>,
+}
+
+impl Intro {
+    /// Daily mode only plays on the first launch of day `today`, counted in
+    /// days since the epoch, and notes it in `stamp`. Without a stamp file
+    /// it plays every time
+    fn new(mode: IntroMode, stamp: Option<&Path>, now: Instant, today: u64) -> Self {
+        let show = match mode {
+            IntroMode::Always => true,
+            IntroMode::Never => false,
+            IntroMode::Daily => stamp.is_none_or(|stamp| {
+                let last = fs::read_to_string(stamp)
+                    .ok()
+                    .and_then(|s| s.trim().parse::<u64>().ok());
+                if last == Some(today) {
+                    return false;
+                }
+                if let Some(dir) = stamp.parent() {
+                    let _ = fs::create_dir_all(dir);
+                }
+                let _ = fs::write(stamp, today.to_string());
+                true
+            }),
+        };
+
+        Self {
+            until: show.then(|| now + Duration::from_millis(INTRO_DURATION_MS)),
+        }
+    }
+
+    fn visible(&mut self, now: Instant) -> bool {
+        if self.until.is_some_and(|until| now >= until) {
+            self.until = None;
+        }
+        self.until.is_some()
+    }
+
+    fn dismiss(&mut self) {
+        self.until = None;
+    }
+}
+
+/// Days since the epoch
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() / 86_400)
+}
+
+/// `secs` as minutes and seconds
+#[must_use]
+fn clock(secs: u64) -> String {
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+#[must_use]
+fn should_quit(key: &KeyEvent) -> bool {
+    (key.modifiers.contains(KeyModifiers::CONTROL) && matches!(key.code, KeyCode::Char('c')))
+        || matches!(key.code, KeyCode::Char('q'))
+}
+
+#[must_use]
+fn sparkline(samples: &[f32]) -> String {
+    let top = SPARKLINE.len() - 1;
+
+    samples
+        .iter()
+        .map(|s| {
+            let level = (s.clamp(-1.0, 1.0) + 1.0) * 0.5 * top as f32;
+            SPARKLINE[(level.round() as usize).min(top)]
+        })
+        .collect()
+}
+
+/// `width` points spread evenly over `samples`, scaled so the loudest one
+/// reaches full height. `None` for silence, which would draw a flat line
+#[must_use]
+fn sparkline_points(samples: &[f32], width: usize) -> Option<Vec<f32>> {
+    let peak = samples.iter().fold(0.0_f32, |peak, s| peak.max(s.abs()));
+    if peak < SPARKLINE_SILENCE || width == 0 {
+        return None;
+    }
+
+    let step = samples.len() as f32 / width as f32;
+    Some(
+        (0..width)
+            .map(|i| samples[(i as f32 * step) as usize] / peak)
+            .collect(),
+    )
+}
+
+/// Holds the spectrum as it is now and plays its strongest peaks back as a
+/// wavetable, `RESYNTH_PARTIALS` of them. Pressed again it lets go
+fn freeze_spectrum(ui: &mut UiState, client: &Client) {
+    if ui.frozen_spectrum.take().is_some() {
+        ui.status = "spectrum live".to_string();
+        return;
+    }
+
+    let mags = magnitudes_db(&mono_frames(&ui.capture, SPECTRUM_FRAMES));
+    let harmonics = partials(&mags, RESYNTH_PARTIALS);
+    let Some(table) = Wavetable::from_harmonics(RESYNTH_TABLE, &harmonics) else {
+        ui.status = "nothing to freeze, the spectrum is silent".to_string();
+        return;
+    };
+
+    match ui.wavetables.iter_mut().find(|t| t.name() == RESYNTH_TABLE) {
+        Some(old) => *old = table.clone(),
+        None => ui.wavetables.push(table.clone()),
+    }
+    client.set_wavetable(table);
+    client.set_wave(Wave::Wavetable);
+
+    let partials = harmonics.iter().filter(|&&amp| amp > 0.0).count();
+    ui.status = format!(
+        "froze the spectrum, {partials} partials up to harmonic {} in wavetable {RESYNTH_TABLE}",
+        harmonics.len()
+    );
+    ui.frozen_spectrum = Some(mags);
+}
+
+/// Copies the window the scope shows, from the capture, as a sparkline
+fn copy_wave_sparkline(ui: &mut UiState) {
+    let trace = scope_trace(&ui.capture, ui.trigger, ui.scope_offset);
+    let samples: Vec<f32> = trace
+        .first()
+        .map(|ch| ch.iter().map(|&(_, s)| s as f32).collect())
+        .unwrap_or_default();
+    let Some(points) = sparkline_points(&samples, SPARKLINE_W) else {
+        ui.status = "nothing to copy, the scope is silent".to_string();
+        return;
+    };
+    let line = sparkline(&points);
+
+    ui.status = match execute!(stdout(), CopyToClipboard::to_clipboard_from(line.as_str())) {
+        Ok(()) => format!("copied scope {line}"),
+        Err(err) => format!("clipboard unavailable ({err}): {line}"),
+    };
+}
+
+#[must_use]
+fn terminal_note(key: &KeyEvent) -> Option<Keycode> {
+    let KeyCode::Char(c) = key.code else {
+        return None;
+    };
+
+    let keycode = keycode_from_char(c)?;
+    (keycode == Keycode::B || Key::from_keycode(keycode).is_some()).then_some(keycode)
+}
+
+/// Shift held, some terminals only say so through the letter's case
+#[must_use]
+fn accented(key: &KeyEvent) -> bool {
+    key.modifiers.contains(KeyModifiers::SHIFT)
+        || matches!(key.code, KeyCode::Char(c) if c.is_ascii_uppercase())
+}
+
+fn apply_selected_preset(ui: &mut UiState, client: &Client) {
+    let Some(preset) = ui.selected_preset().cloned() else {
+        return;
+    };
+
+    ui.patch_name = preset.name.clone();
+    ui.preset_name = Some(preset.name.clone());
+    ui.wave = preset.wave.clone();
+    ui.sync_wave_idx();
+
+    ui.adsr.attack_s = preset.attack;
+    ui.adsr.decay_s = preset.decay;
+    ui.adsr.sustain = preset.sustain;
+    ui.adsr.release_s = preset.release;
+
+    ui.lfo.wave = preset.lfo_wave.clone();
+    ui.lfo.rate_hz = preset.lfo_rate;
+    ui.lfo.depth = preset.lfo_depth;
+
+    ui.lowpass.cutoff_hz = preset.cutoff;
+    ui.octave = preset.octave_shift;
+
+    client.set_wave(preset.wave);
+    client.set_adsr(ui.adsr.clone());
+    client.set_lfo_amp(ui.lfo.clone());
+    client.set_lowpass(ui.lowpass.clone());
+    client.set_octave(ui.octave);
+}
+
+/// Next of `UI_FPS_STEPS` above the current rate, back to the lowest after
+/// the top. The new rate applies from the next frame
+fn cycle_fps(ui: &mut UiState) {
+    ui.fps = UI_FPS_STEPS
+        .into_iter()
+        .find(|&fps| fps > ui.fps)
+        .unwrap_or(UI_FPS_STEPS[0]);
+    ui.measured_fps = None;
+    ui.status = format!("UI at {} fps", ui.fps);
+}
+
+/// Zooming by hand takes over from auto-scale
+fn zoom_scope(ui: &mut UiState, factor: f32) {
+    ui.scope_autoscale = false;
+    ui.scope_scale = (ui.scope_scale * factor).clamp(SCOPE_SCALE_MIN, SCOPE_SCALE_MAX);
+    ui.status = format!("scope zoom ×{}", ui.scope_scale);
+}
+
+/// Points the scope at the input device or back at the synth, which plays
+/// on either way. The device is only open while it is being shown
+fn toggle_scope_source(ui: &mut UiState) {
+    if ui.mic.take().is_some() {
+        ui.capture = ui.synth_capture();
+        ui.status = "scope showing synth".to_string();
+    } else {
+        match MicCapture::open(ui.input_device.as_deref()) {
+            Ok(mic) => {
+                mic.capture().set_dc_block(ui.capture.dc_block());
+                ui.capture = mic.capture().clone();
+                ui.status = format!("scope showing {}", mic.name());
+                ui.mic = Some(mic);
+            }
+            Err(err) => {
+                ui.status = format!("input: {err}");
+                return;
+            }
+        }
+    }
+
+    ui.scope_trails.clear();
+    ui.scope_offset = 0;
+}
+
+/// Over the scope the wheel zooms like Ctrl+Up/Down and a left drag pans
+/// back through the capture history, dragging right shows older audio. A
+/// right click snaps back to the newest audio
+fn handle_scope_mouse(ui: &mut UiState, mouse: MouseEvent) {
+    let area = ui.scope_area.get();
+    let over = area.contains((mouse.column, mouse.row).into());
+
+    match mouse.kind {
+        MouseEventKind::ScrollUp if over => zoom_scope(ui, 2.0),
+        MouseEventKind::ScrollDown if over => zoom_scope(ui, 0.5),
+        MouseEventKind::Down(MouseButton::Left) if over => ui.scope_drag = Some(mouse.column),
+        MouseEventKind::Drag(MouseButton::Left) => {
+            let Some(from) = ui.scope_drag else {
+                return;
+            };
+            ui.scope_drag = Some(mouse.column);
+
+            let window = scope_window(&ui.capture);
+            let frames_per_col = window / usize::from(area.width.max(1));
+            let cols = mouse.column as isize - from as isize;
+            let max = ui.capture.capacity().saturating_sub(window * 2);
+            ui.scope_offset = ui
+                .scope_offset
+                .saturating_add_signed(cols * frames_per_col as isize)
+                .min(max);
+        }
+        MouseEventKind::Up(MouseButton::Left) => ui.scope_drag = None,
+        MouseEventKind::Down(MouseButton::Right) if over => {
+            ui.scope_offset = 0;
+            ui.status = "scope back to live".to_string();
+        }
+        _ => {}
+    }
+}
+
+/// Changing the depth turns afterglow on, so the change can be seen
+fn step_afterglow(ui: &mut UiState, step: isize) {
+    ui.scope_afterglow = true;
+    ui.scope_afterglow_depth = ui
+        .scope_afterglow_depth
+        .saturating_add_signed(step)
+        .clamp(1, SCOPE_AFTERGLOW_DEPTH_MAX);
+    ui.status = format!("scope afterglow {} frames", ui.scope_afterglow_depth);
+}
+
+fn cycle_scope_shades(ui: &mut UiState) {
+    let shading = &mut ui.scope_shading;
+    shading.shades = SCOPE_SHADE_STEPS
+        .into_iter()
+        .find(|&shades| shades > shading.shades)
+        .unwrap_or(SCOPE_SHADE_STEPS[0]);
+    ui.status = format!("scope {} shades", shading.shades);
+}
+
+fn cycle_scope_contrast(ui: &mut UiState) {
+    let shading = &mut ui.scope_shading;
+    shading.contrast = SCOPE_CONTRAST_STEPS
+        .into_iter()
+        .find(|&contrast| contrast > shading.contrast)
+        .unwrap_or(SCOPE_CONTRAST_STEPS[0]);
+    ui.status = format!("scope contrast {:.2}", shading.contrast);
+}
+
+/// Panic button, the engine cuts every voice and the sequencer stops
+fn all_notes_off(ui: &mut UiState, client: &Client) {
+    client.all_notes_off();
+    ui.sequence.playing = false;
+    ui.status = "all notes off".to_string();
+}
+
+/// Writes the sound the engine is playing now to a new file
+fn save_patch(ui: &mut UiState, snapshot: &Snapshot) {
+    ui.status = match PatchFile::from_snapshot(snapshot).save() {
+        Ok(path) => {
+            ui.preset_name = path.file_stem().map(|s| s.to_string_lossy().into());
+            format!("saved patch {}", path.display())
+        }
+        Err(err) => format!("saving patch failed: {err}"),
+    };
+}
+
+/// Loads the patch saved last, a file that fails to read only leaves a
+/// message and the sound as it was
+fn open_patch(ui: &mut UiState, client: &Client) {
+    let path = match latest_patch() {
+        Ok(Some(path)) => path,
+        Ok(None) => {
+            ui.status = "no saved patches".to_string();
+            return;
+        }
+        Err(err) => {
+            ui.status = format!("listing patches failed: {err}");
+            return;
+        }
+    };
+
+    ui.status = match PatchFile::load(&path) {
+        Ok(patch) => {
+            apply_patch_file(client, patch);
+            ui.preset_name = path.file_stem().map(|s| s.to_string_lossy().into());
+            format!("loaded patch {}", path.display())
+        }
+        Err(err) => format!("bad patch {}: {err}", path.display()),
+    };
+}
+
+/// Moves `dir` through the factory patches and saved files. The folder is
+/// read again each time, files that fail to parse are passed over and named
+/// in the status line
+fn cycle_patch(ui: &mut UiState, client: &Client, dir: isize) {
+    let library = PatchLibrary::scan();
+
+    let Some(entry) = library.step(ui.preset_name.as_deref(), dir) else {
+        ui.status = "no patches to load".to_string();
+        return;
+    };
+
+    let position = library
+        .entries
+        .iter()
+        .position(|e| e.name == entry.name)
+        .map_or(0, |i| i + 1);
+    let mut status = format!("{} ({position}/{})", entry.name, library.entries.len());
+    if !library.skipped.is_empty() {
+        let skipped: Vec<String> = library
+            .skipped
+            .iter()
+            .map(|(path, err)| {
+                let name = path.file_name().map_or(path.as_os_str(), |name| name);
+                format!("{} ({err})", name.to_string_lossy())
+            })
+            .collect();
+        status.push_str(&format!(", skipped {}", skipped.join("; ")));
+    }
+
+    apply_patch_file(client, entry.patch.clone());
+    ui.preset_name = Some(entry.name.clone());
+    ui.status = status;
+}
+
+/// The UI picks the new values up from the next snapshot
+fn apply_patch_file(client: &Client, patch: PatchFile) {
+    client.set_volume(patch.volume);
+    client.set_wave(patch.wave);
+    client.set_pulse_duty(patch.pulse_duty);
+    client.set_sub(patch.sub);
+    client.set_fm(patch.fm);
+    client.set_adsr(patch.adsr);
+    client.set_gain(patch.gain);
+    client.set_lfo_amp(patch.lfo_amp);
+    client.set_lfo(patch.lfo);
+    client.set_am(patch.am);
+    client.set_ringmod(patch.ringmod);
+    client.set_bitcrush(patch.bitcrush);
+    client.set_sample_hold(patch.sample_hold);
+    client.set_highpass(patch.highpass);
+    client.set_lowpass(patch.lowpass);
+    client.set_delay(patch.delay);
+    client.set_reverb(patch.reverb);
+    client.set_noise_gate(patch.noise_gate);
+    client.set_pan(patch.pan);
+    client.set_fx_chain(patch.effects);
+}
+
+fn handle_presets_popup(ui: &mut UiState, client: &Client, key: &KeyEvent) {
+    match key.code {
         KeyCode::Char(' ') | KeyCode::Esc => {
             ui.show_presets = false;
         }
 
-        KeyCode::Tab | KeyCode::Right => {
-            ui.preset_category_idx = (ui.preset_category_idx + 1) % PRESET_CATEGORIES.len();
-            let len = ui.presets_in_selected_category().len();
-            if len == 0 {
-                ui.preset_row_idx = 0;
-            } else if ui.preset_row_idx >= len {
-                ui.preset_row_idx = len - 1;
-            }
+        KeyCode::Tab | KeyCode::Right => {
+            ui.preset_category_idx = (ui.preset_category_idx + 1) % PRESET_CATEGORIES.len();
+            let len = ui.presets_in_selected_category().len();
+            if len == 0 {
+                ui.preset_row_idx = 0;
+            } else if ui.preset_row_idx >= len {
+                ui.preset_row_idx = len - 1;
+            }
+        }
+
+        KeyCode::Left => {
+            ui.preset_category_idx = if ui.preset_category_idx == 0 {
+                PRESET_CATEGORIES.len() - 1
+            } else {
+                ui.preset_category_idx - 1
+            };
+            let len = ui.presets_in_selected_category().len();
+            if len == 0 {
+                ui.preset_row_idx = 0;
+            } else if ui.preset_row_idx >= len {
+                ui.preset_row_idx = len - 1;
+            }
+        }
+
+        KeyCode::Up if ui.preset_row_idx > 0 => {
+            ui.preset_row_idx -= 1;
+        }
+
+        KeyCode::Down => {
+            let len = ui.presets_in_selected_category().len();
+            if ui.preset_row_idx + 1 < len {
+                ui.preset_row_idx += 1;
+            }
+        }
+
+        KeyCode::Enter => {
+            apply_selected_preset(ui, client);
+            ui.show_presets = false;
+        }
+
+        _ => {}
+    }
+}
+
+fn handle_waveforms(ui: &mut UiState, client: &Client, key: &KeyEvent) {
+    match key.code {
+        KeyCode::Enter if ui.split => {
+            ui.edit_split = !ui.edit_split;
+            client.set_edit_split(ui.edit_split);
+            return;
+        }
+        KeyCode::Char('+') => {
+            add_layer(ui, client);
+            return;
+        }
+        KeyCode::Char('-') => {
+            remove_layer(ui, client);
+            return;
+        }
+        KeyCode::Char('<') => {
+            nudge_crossfade(ui, client, -WAVE_CROSSFADE_STEP_S);
+            return;
+        }
+        KeyCode::Char('>') => {
+            nudge_crossfade(ui, client, WAVE_CROSSFADE_STEP_S);
+            return;
+        }
+        _ => {}
+    }
+
+    let len = ui.waves.len();
+    let idx = if ui.edit_split {
+        &mut ui.split_wave_idx
+    } else {
+        &mut ui.wave_idx
+    };
+    let prev = *idx;
+
+    let pulse = ui.waves[*idx] == Wave::Pulse;
+    let table = ui.waves[*idx] == Wave::Wavetable;
+
+    match key.code {
+        KeyCode::Up if *idx > 0 => *idx -= 1,
+        KeyCode::Down if *idx + 1 < len => *idx += 1,
+        KeyCode::Left if pulse => {
+            ui.pulse_duty = (ui.pulse_duty - 0.05).clamp(DUTY_MIN, DUTY_MAX);
+            client.set_pulse_duty(ui.pulse_duty);
+        }
+        KeyCode::Right if pulse => {
+            ui.pulse_duty = (ui.pulse_duty + 0.05).clamp(DUTY_MIN, DUTY_MAX);
+            client.set_pulse_duty(ui.pulse_duty);
+        }
+        KeyCode::Left if table => {
+            ui.wavetable = next_wavetable(&ui.wavetables, &ui.wavetable, -1);
+            client.set_wavetable(ui.wavetable.clone());
+        }
+        KeyCode::Right if table => {
+            ui.wavetable = next_wavetable(&ui.wavetables, &ui.wavetable, 1);
+            client.set_wavetable(ui.wavetable.clone());
+        }
+        _ => {}
+    }
+
+    if *idx != prev {
+        if ui.edit_split {
+            client.set_split_wave(ui.selected_split_wave());
+        } else {
+            client.set_wave(ui.selected_wave());
+        }
+    }
+}
+
+fn nudge_crossfade(ui: &mut UiState, client: &Client, delta: f32) {
+    ui.crossfade_s = (ui.crossfade_s + delta).clamp(0.0, WAVE_CROSSFADE_MAX_S);
+    client.set_crossfade(ui.crossfade_s);
+    ui.status = format!("wave crossfade {:.0} ms", ui.crossfade_s * 1000.0);
+}
+
+fn handle_adsr(ui: &mut UiState, client: &Client, key: &KeyEvent) {
+    match key.code {
+        KeyCode::Up if ui.adsr_param_idx > 0 => ui.adsr_param_idx -= 1,
+        KeyCode::Down if ui.adsr_param_idx + 1 < AdsrParam::ALL.len() => ui.adsr_param_idx += 1,
+        KeyCode::Left => {
+            tweak_adsr(ui, -1);
+            client.set_adsr(ui.adsr.clone());
+        }
+        KeyCode::Right => {
+            tweak_adsr(ui, 1);
+            client.set_adsr(ui.adsr.clone());
+        }
+        _ => {}
+    }
+}
+
+fn handle_mod(ui: &mut UiState, client: &Client, key: &KeyEvent) {
+    match key.code {
+        KeyCode::Up => match ui.mod_tab {
+            ModTab::Lfo if ui.lfo_param_idx > 0 => ui.lfo_param_idx -= 1,
+            ModTab::LfoRoute if ui.lfo_route_param_idx > 0 => ui.lfo_route_param_idx -= 1,
+            ModTab::Am if ui.am_param_idx > 0 => ui.am_param_idx -= 1,
+            ModTab::RingMod if ui.ringmod_param_idx > 0 => ui.ringmod_param_idx -= 1,
+            ModTab::Bitcrush if ui.bitcrush_param_idx > 0 => ui.bitcrush_param_idx -= 1,
+            ModTab::SampleHold if ui.sample_hold_param_idx > 0 => ui.sample_hold_param_idx -= 1,
+            ModTab::HighPass if ui.highpass_param_idx > 0 => ui.highpass_param_idx -= 1,
+            ModTab::LowPass if ui.lowpass_param_idx > 0 => ui.lowpass_param_idx -= 1,
+            ModTab::Delay if ui.delay_param_idx > 0 => ui.delay_param_idx -= 1,
+            ModTab::Reverb if ui.reverb_param_idx > 0 => ui.reverb_param_idx -= 1,
+            ModTab::NoiseGate if ui.noise_gate_param_idx > 0 => ui.noise_gate_param_idx -= 1,
+            ModTab::Pan if ui.pan_param_idx > 0 => ui.pan_param_idx -= 1,
+            ModTab::Layers if ui.layer_param_idx > 0 => ui.layer_param_idx -= 1,
+            ModTab::Sub if ui.sub_param_idx > 0 => ui.sub_param_idx -= 1,
+            ModTab::Fm if ui.fm_param_idx > 0 => ui.fm_param_idx -= 1,
+            ModTab::Unison if ui.unison_param_idx > 0 => ui.unison_param_idx -= 1,
+            ModTab::Bend if ui.bend_param_idx > 0 => ui.bend_param_idx -= 1,
+            ModTab::Scale if ui.scale_param_idx > 0 => ui.scale_param_idx -= 1,
+            ModTab::Velocity if ui.velocity_param_idx > 0 => ui.velocity_param_idx -= 1,
+            ModTab::Arp if ui.arp_param_idx > 0 => ui.arp_param_idx -= 1,
+            ModTab::Seq if ui.seq_param_idx > 0 => ui.seq_param_idx -= 1,
+            _ => {}
+        },
+
+        KeyCode::Down => match ui.mod_tab {
+            ModTab::Lfo if ui.lfo_param_idx + 1 < LfoParam::ALL.len() => ui.lfo_param_idx += 1,
+            ModTab::LfoRoute if ui.lfo_route_param_idx + 1 < LfoRouteParam::ALL.len() => {
+                ui.lfo_route_param_idx += 1;
+            }
+            ModTab::Am if ui.am_param_idx + 1 < AmParam::ALL.len() => ui.am_param_idx += 1,
+            ModTab::RingMod if ui.ringmod_param_idx + 1 < RingModParam::ALL.len() => {
+                ui.ringmod_param_idx += 1;
+            }
+            ModTab::Bitcrush if ui.bitcrush_param_idx + 1 < BitcrushParam::ALL.len() => {
+                ui.bitcrush_param_idx += 1;
+            }
+            ModTab::SampleHold if ui.sample_hold_param_idx + 1 < SampleHoldParam::ALL.len() => {
+                ui.sample_hold_param_idx += 1;
+            }
+            ModTab::HighPass if ui.highpass_param_idx + 1 < HighPassParam::ALL.len() => {
+                ui.highpass_param_idx += 1;
+            }
+            ModTab::LowPass if ui.lowpass_param_idx + 1 < LowPassParam::ALL.len() => {
+                ui.lowpass_param_idx += 1;
+            }
+            ModTab::Delay if ui.delay_param_idx + 1 < DelayParam::ALL.len() => {
+                ui.delay_param_idx += 1;
+            }
+            ModTab::Reverb if ui.reverb_param_idx + 1 < ReverbParam::ALL.len() => {
+                ui.reverb_param_idx += 1;
+            }
+            ModTab::NoiseGate if ui.noise_gate_param_idx + 1 < NoiseGateParam::ALL.len() => {
+                ui.noise_gate_param_idx += 1;
+            }
+            ModTab::Pan if ui.pan_param_idx + 1 < PanParam::ALL.len() => ui.pan_param_idx += 1,
+            ModTab::Layers if ui.layer_param_idx + 1 < LayerParam::ALL.len() => {
+                ui.layer_param_idx += 1;
+            }
+            ModTab::Sub if ui.sub_param_idx + 1 < SubParam::ALL.len() => ui.sub_param_idx += 1,
+            ModTab::Fm if ui.fm_param_idx + 1 < FmParam::ALL.len() => ui.fm_param_idx += 1,
+            ModTab::Unison if ui.unison_param_idx + 1 < UnisonParam::ALL.len() => {
+                ui.unison_param_idx += 1;
+            }
+            ModTab::Bend if ui.bend_param_idx + 1 < BendParam::ALL.len() => ui.bend_param_idx += 1,
+            ModTab::Scale if ui.scale_param_idx + 1 < ScaleParam::ALL.len() => {
+                ui.scale_param_idx += 1;
+            }
+            ModTab::Velocity if ui.velocity_param_idx + 1 < VelocityParam::ALL.len() => {
+                ui.velocity_param_idx += 1;
+            }
+            ModTab::Arp if ui.arp_param_idx + 1 < ArpParam::ALL.len() => ui.arp_param_idx += 1,
+            ModTab::Seq if ui.seq_param_idx + 1 < SeqParam::ALL.len() => ui.seq_param_idx += 1,
+            _ => {}
+        },
+
+        KeyCode::Left => match ui.mod_tab {
+            ModTab::Lfo => {
+                tweak_lfo(ui, -1);
+                client.set_lfo_amp(ui.lfo.clone());
+            }
+            ModTab::LfoRoute => {
+                tweak_lfo_route(ui, -1);
+                client.set_lfo(ui.lfo_route.clone());
+            }
+            ModTab::Am => {
+                tweak_am(ui, -1);
+                client.set_am(ui.am.clone());
+            }
+            ModTab::RingMod => {
+                tweak_ringmod(ui, -1);
+                client.set_ringmod(ui.ringmod.clone());
+            }
+            ModTab::Bitcrush => {
+                tweak_bitcrush(ui, -1);
+                client.set_bitcrush(ui.bitcrush.clone());
+            }
+            ModTab::SampleHold => {
+                tweak_sample_hold(ui, -1);
+                client.set_sample_hold(ui.sample_hold.clone());
+            }
+            ModTab::HighPass => {
+                tweak_highpass(ui, -1);
+                client.set_highpass(ui.highpass.clone());
+            }
+            ModTab::LowPass => {
+                tweak_lowpass(ui, -1);
+                client.set_lowpass(ui.lowpass.clone());
+            }
+            ModTab::Delay => {
+                tweak_delay(ui, -1);
+                client.set_delay(ui.delay.clone());
+            }
+            ModTab::Reverb => {
+                tweak_reverb(ui, -1);
+                client.set_reverb(ui.reverb.clone());
+            }
+            ModTab::NoiseGate => {
+                tweak_noise_gate(ui, -1);
+                client.set_noise_gate(ui.noise_gate.clone());
+            }
+            ModTab::Pan => {
+                tweak_pan(ui, -1);
+                client.set_pan(ui.pan.clone());
+            }
+            ModTab::Layers => {
+                tweak_layer(ui, -1);
+                client.set_layers(ui.layers.clone());
+            }
+            ModTab::Sub => {
+                tweak_sub(ui, -1);
+                client.set_sub(ui.sub);
+            }
+            ModTab::Fm => {
+                tweak_fm(ui, -1);
+                client.set_fm(ui.fm.clone());
+            }
+            ModTab::Unison => {
+                tweak_unison(ui, -1);
+                client.set_unison(ui.unison.clone());
+            }
+            ModTab::Glide => {
+                tweak_glide(ui, -1);
+                client.set_glide(ui.glide_s);
+            }
+            ModTab::Bend => {
+                tweak_bend(ui, -1);
+                client.set_bend_range(ui.bend_range_st);
+                client.set_bend_return(ui.bend_return);
+            }
+            ModTab::Scale => {
+                tweak_scale(ui, -1);
+                client.set_quantizer(ui.quantizer);
+                client.set_tuning_system(ui.tuning.clone());
+            }
+            ModTab::Chord => {
+                tweak_chord(ui, -1);
+                client.set_chord(ui.chord);
+            }
+            ModTab::Velocity => {
+                tweak_velocity(ui, -1);
+                client.set_velocity(ui.velocity);
+            }
+            ModTab::Arp => {
+                tweak_arp(ui, -1);
+                client.set_arp(ui.arp.clone());
+                if ui.selected_arp_param() == ArpParam::Bpm {
+                    send_tempo(ui, client);
+                }
+            }
+            ModTab::Seq => {
+                tweak_seq(ui, -1);
+                send_sequence(ui, client);
+            }
+        },
+
+        KeyCode::Right => match ui.mod_tab {
+            ModTab::Lfo => {
+                tweak_lfo(ui, 1);
+                client.set_lfo_amp(ui.lfo.clone());
+            }
+            ModTab::LfoRoute => {
+                tweak_lfo_route(ui, 1);
+                client.set_lfo(ui.lfo_route.clone());
+            }
+            ModTab::Am => {
+                tweak_am(ui, 1);
+                client.set_am(ui.am.clone());
+            }
+            ModTab::RingMod => {
+                tweak_ringmod(ui, 1);
+                client.set_ringmod(ui.ringmod.clone());
+            }
+            ModTab::Bitcrush => {
+                tweak_bitcrush(ui, 1);
+                client.set_bitcrush(ui.bitcrush.clone());
+            }
+            ModTab::SampleHold => {
+                tweak_sample_hold(ui, 1);
+                client.set_sample_hold(ui.sample_hold.clone());
+            }
+            ModTab::HighPass => {
+                tweak_highpass(ui, 1);
+                client.set_highpass(ui.highpass.clone());
+            }
+            ModTab::LowPass => {
+                tweak_lowpass(ui, 1);
+                client.set_lowpass(ui.lowpass.clone());
+            }
+            ModTab::Delay => {
+                tweak_delay(ui, 1);
+                client.set_delay(ui.delay.clone());
+            }
+            ModTab::Reverb => {
+                tweak_reverb(ui, 1);
+                client.set_reverb(ui.reverb.clone());
+            }
+            ModTab::NoiseGate => {
+                tweak_noise_gate(ui, 1);
+                client.set_noise_gate(ui.noise_gate.clone());
+            }
+            ModTab::Pan => {
+                tweak_pan(ui, 1);
+                client.set_pan(ui.pan.clone());
+            }
+            ModTab::Layers => {
+                tweak_layer(ui, 1);
+                client.set_layers(ui.layers.clone());
+            }
+            ModTab::Sub => {
+                tweak_sub(ui, 1);
+                client.set_sub(ui.sub);
+            }
+            ModTab::Fm => {
+                tweak_fm(ui, 1);
+                client.set_fm(ui.fm.clone());
+            }
+            ModTab::Unison => {
+                tweak_unison(ui, 1);
+                client.set_unison(ui.unison.clone());
+            }
+            ModTab::Glide => {
+                tweak_glide(ui, 1);
+                client.set_glide(ui.glide_s);
+            }
+            ModTab::Bend => {
+                tweak_bend(ui, 1);
+                client.set_bend_range(ui.bend_range_st);
+                client.set_bend_return(ui.bend_return);
+            }
+            ModTab::Scale => {
+                tweak_scale(ui, 1);
+                client.set_quantizer(ui.quantizer);
+                client.set_tuning_system(ui.tuning.clone());
+            }
+            ModTab::Chord => {
+                tweak_chord(ui, 1);
+                client.set_chord(ui.chord);
+            }
+            ModTab::Velocity => {
+                tweak_velocity(ui, 1);
+                client.set_velocity(ui.velocity);
+            }
+            ModTab::Arp => {
+                tweak_arp(ui, 1);
+                client.set_arp(ui.arp.clone());
+                if ui.selected_arp_param() == ArpParam::Bpm {
+                    send_tempo(ui, client);
+                }
+            }
+            ModTab::Seq => {
+                tweak_seq(ui, 1);
+                send_sequence(ui, client);
+            }
+        },
+
+        KeyCode::Enter => {
+            ui.mod_tab = ui.mod_tab.next();
+        }
+
+        KeyCode::Delete | KeyCode::Backspace if ui.mod_tab == ModTab::Seq => {
+            ui.sequence.steps[ui.seq_cursor] = None;
+            send_sequence(ui, client);
+        }
+
+        _ => {}
+    }
+}
+
+fn handle_effects(ui: &mut UiState, client: &Client, key: &KeyEvent) {
+    let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+
+    match key.code {
+        KeyCode::Up if shift => move_fx(ui, client, -1),
+        KeyCode::Down if shift => move_fx(ui, client, 1),
+        KeyCode::Up if ui.fx_slot > 0 => ui.fx_slot -= 1,
+        KeyCode::Down if ui.fx_slot + 1 < ui.effects.len() => ui.fx_slot += 1,
+        KeyCode::Left => {
+            if let Some(fx) = ui.selected_fx() {
+                tweak_fx(ui, client, fx, -1);
+            }
+        }
+        KeyCode::Right => {
+            if let Some(fx) = ui.selected_fx() {
+                tweak_fx(ui, client, fx, 1);
+            }
+        }
+        KeyCode::Enter => {
+            if let Some(fx) = ui.selected_fx() {
+                next_fx_param(ui, fx);
+            }
+        }
+        KeyCode::PageUp => step_fx_pick(ui, -1),
+        KeyCode::PageDown => step_fx_pick(ui, 1),
+        KeyCode::Insert => insert_fx(ui, client),
+        KeyCode::Delete => remove_fx(ui, client),
+        _ => {}
+    }
+}
+
+/// The mod tab editing the same settings, gain has none
+#[must_use]
+fn fx_tab(fx: Fx) -> Option<ModTab> {
+    match fx {
+        Fx::SampleHold => Some(ModTab::SampleHold),
+        Fx::Gain => None,
+        Fx::LfoAmp => Some(ModTab::Lfo),
+        Fx::Am => Some(ModTab::Am),
+        Fx::RingMod => Some(ModTab::RingMod),
+        Fx::Bitcrush => Some(ModTab::Bitcrush),
+        Fx::HighPass => Some(ModTab::HighPass),
+        Fx::LowPass => Some(ModTab::LowPass),
+    }
+}
+
+/// Shares the parameter the mod tab has selected, so both panes agree
+fn next_fx_param(ui: &mut UiState, fx: Fx) {
+    let (idx, len) = match fx {
+        Fx::Gain => return,
+        Fx::SampleHold => (&mut ui.sample_hold_param_idx, SampleHoldParam::ALL.len()),
+        Fx::LfoAmp => (&mut ui.lfo_param_idx, LfoParam::ALL.len()),
+        Fx::Am => (&mut ui.am_param_idx, AmParam::ALL.len()),
+        Fx::RingMod => (&mut ui.ringmod_param_idx, RingModParam::ALL.len()),
+        Fx::Bitcrush => (&mut ui.bitcrush_param_idx, BitcrushParam::ALL.len()),
+        Fx::HighPass => (&mut ui.highpass_param_idx, HighPassParam::ALL.len()),
+        Fx::LowPass => (&mut ui.lowpass_param_idx, LowPassParam::ALL.len()),
+    };
+
+    *idx = (*idx + 1) % len;
+}
+
+fn tweak_fx(ui: &mut UiState, client: &Client, fx: Fx, dir: i32) {
+    match fx {
+        Fx::SampleHold => {
+            tweak_sample_hold(ui, dir);
+            client.set_sample_hold(ui.sample_hold.clone());
+        }
+        Fx::Gain => {
+            tweak_gain(ui, dir);
+            client.set_gain(ui.gain.clone());
+        }
+        Fx::LfoAmp => {
+            tweak_lfo(ui, dir);
+            client.set_lfo_amp(ui.lfo.clone());
+        }
+        Fx::Am => {
+            tweak_am(ui, dir);
+            client.set_am(ui.am.clone());
+        }
+        Fx::RingMod => {
+            tweak_ringmod(ui, dir);
+            client.set_ringmod(ui.ringmod.clone());
+        }
+        Fx::Bitcrush => {
+            tweak_bitcrush(ui, dir);
+            client.set_bitcrush(ui.bitcrush.clone());
+        }
+        Fx::HighPass => {
+            tweak_highpass(ui, dir);
+            client.set_highpass(ui.highpass.clone());
+        }
+        Fx::LowPass => {
+            tweak_lowpass(ui, dir);
+            client.set_lowpass(ui.lowpass.clone());
+        }
+    }
+}
+
+fn step_fx_pick(ui: &mut UiState, dir: i32) {
+    let len = usize_to_i32(ui.fx_palette().len());
+    if len == 0 {
+        return;
+    }
+
+    let idx = usize_to_i32(ui.fx_palette_idx).min(len - 1);
+    ui.fx_palette_idx = i32_to_usize((idx + dir).rem_euclid(len));
+}
+
+/// Adds the palette pick right after the selected slot and selects it
+fn insert_fx(ui: &mut UiState, client: &Client) {
+    let Some(fx) = ui.fx_pick() else {
+        ui.status = "every effect is in the chain".to_string();
+        return;
+    };
+
+    let slot = if ui.effects.is_empty() {
+        0
+    } else {
+        ui.fx_slot + 1
+    };
+    ui.effects.insert(slot, fx);
+    ui.fx_slot = slot;
+    client.set_fx_chain(ui.effects.clone());
+    ui.status = format!("added {}", fx.name());
+}
+
+fn remove_fx(ui: &mut UiState, client: &Client) {
+    if ui.fx_slot >= ui.effects.len() {
+        return;
+    }
+
+    let fx = ui.effects.remove(ui.fx_slot);
+    ui.fx_slot = ui.fx_slot.min(ui.effects.len().saturating_sub(1));
+    client.set_fx_chain(ui.effects.clone());
+    ui.status = format!("removed {}", fx.name());
+}
+
+/// Swaps the selected slot with its neighbour, the selection moves along
+fn move_fx(ui: &mut UiState, client: &Client, dir: i32) {
+    let to = usize_to_i32(ui.fx_slot) + dir;
+    if to < 0 || i32_to_usize(to) >= ui.effects.len() {
+        return;
+    }
+
+    let to = i32_to_usize(to);
+    ui.effects.swap(ui.fx_slot, to);
+    ui.fx_slot = to;
+    client.set_fx_chain(ui.effects.clone());
+}
+
+fn handle_keyboard(ui: &mut UiState, client: &Client, key: &KeyEvent) {
+    match key.code {
+        KeyCode::Right => shift_octave(ui, client, 1),
+        KeyCode::Left => shift_octave(ui, client, -1),
+        KeyCode::Up => retune(ui, client, 1.0),
+        KeyCode::Down => retune(ui, client, -1.0),
+        _ => {}
+    }
+}
+
+/// Only notes pressed afterwards use the new octave, held ones keep sounding
+fn shift_octave(ui: &mut UiState, client: &Client, dir: i32) {
+    let octave = (ui.octave + dir).clamp(OCTAVE_SHIFT_MIN, OCTAVE_SHIFT_MAX);
+
+    if octave != ui.octave {
+        ui.octave = octave;
+        client.set_octave(octave);
+    }
+}
+
+/// Moves A4 in 1 Hz steps, held notes keep the pitch they started with
+fn retune(ui: &mut UiState, client: &Client, delta: f32) {
+    ui.a4_hz = (ui.a4_hz.round() + delta).clamp(A4_MIN_HZ, A4_MAX_HZ);
+    client.set_tuning(ui.a4_hz);
+}
+
+/// Same as the octave, held notes keep the pitch they started with
+fn nudge_transpose(ui: &mut UiState, client: &Client, dir: i32) {
+    let transpose = (ui.transpose + dir).clamp(-TRANSPOSE_MAX, TRANSPOSE_MAX);
+
+    if transpose != ui.transpose {
+        ui.transpose = transpose;
+        client.set_transpose(transpose);
+    }
+}
+
+fn tweak_adsr(ui: &mut UiState, dir: i32) {
+    let step = 0.01;
+    let delta = if dir < 0 { -step } else { step };
+
+    match ui.selected_adsr_param() {
+        AdsrParam::Attack => ui.adsr.attack_s = (ui.adsr.attack_s + delta).clamp(0.0, 10.0),
+        AdsrParam::Decay => ui.adsr.decay_s = (ui.adsr.decay_s + delta).clamp(0.0, 10.0),
+        AdsrParam::Sustain => ui.adsr.sustain = (ui.adsr.sustain + delta).clamp(0.0, 1.0),
+        AdsrParam::Release => ui.adsr.release_s = (ui.adsr.release_s + delta).clamp(0.0, 10.0),
+        AdsrParam::Mode => ui.adsr.mode = next_envelope_mode(ui.adsr.mode, dir),
+        AdsrParam::Curve => ui.adsr.curve = next_envelope_curve(ui.adsr.curve, dir),
+    }
+}
+
+fn tweak_gain(ui: &mut UiState, dir: i32) {
+    let dir_f = if dir < 0 { -1.0 } else { 1.0 };
+    ui.gain.amount = (ui.gain.amount + dir_f * 0.05).clamp(0.0, 2.0);
+}
+
+fn tweak_lfo(ui: &mut UiState, dir: i32) {
+    let dir_i = if dir < 0 { -1 } else { 1 };
+    let dir_f = if dir_i < 0 { -1.0 } else { 1.0 };
+
+    match ui.selected_lfo_param() {
+        LfoParam::Kind => ui.lfo.wave = next_wave(&ui.lfo.wave, dir_i),
+        LfoParam::RateHz => {
+            ui.lfo.rate_hz = (ui.lfo.rate_hz + dir_f * 0.25).clamp(0.05, 40.0);
+        }
+        LfoParam::Depth => {
+            ui.lfo.depth = (ui.lfo.depth + dir_f * 0.02).clamp(0.0, 1.0);
+        }
+    }
+}
+
+fn tweak_lfo_route(ui: &mut UiState, dir: i32) {
+    let dir_i = if dir < 0 { -1 } else { 1 };
+    let dir_f = if dir_i < 0 { -1.0 } else { 1.0 };
+
+    match ui.selected_lfo_route_param() {
+        LfoRouteParam::Target => {
+            let all = LfoTarget::ALL;
+            let len = usize_to_i32(all.len());
+            let idx = all.iter().position(|t| *t == ui.lfo_route.target);
+            let idx = usize_to_i32(idx.unwrap_or(0));
+
+            ui.lfo_route.target = all[i32_to_usize((idx + dir_i).rem_euclid(len))];
+        }
+        LfoRouteParam::Kind => ui.lfo_route.wave = next_wave(&ui.lfo_route.wave, dir_i),
+        LfoRouteParam::RateHz => {
+            ui.lfo_route.rate_hz =
+                (ui.lfo_route.rate_hz + dir_f * 0.25).clamp(0.05, LFO_MOD_RATE_MAX_HZ);
+        }
+        LfoRouteParam::Depth => {
+            ui.lfo_route.depth = (ui.lfo_route.depth + dir_f * 0.02).clamp(0.0, 1.0);
+        }
+    }
+}
+
+fn tweak_am(ui: &mut UiState, dir: i32) {
+    let dir_f = if dir < 0 { -1.0 } else { 1.0 };
+
+    match ui.selected_am_param() {
+        AmParam::CarrierHz => {
+            let carrier = ui.am.carrier_hz;
+
+            let step = if carrier < 20.0 {
+                0.5
+            } else if carrier < 200.0 {
+                5.0
+            } else if carrier < 1000.0 {
+                25.0
+            } else {
+                100.0
+            };
+
+            ui.am.carrier_hz = (carrier + dir_f * step).clamp(0.5, 5000.0);
         }
+        AmParam::Depth => {
+            ui.am.depth = (ui.am.depth + dir_f * 0.02).clamp(0.0, 1.0);
+        }
+    }
+}
 
-        KeyCode::Left => {
-            ui.preset_category_idx = if ui.preset_category_idx == 0 {
-                PRESET_CATEGORIES.len() - 1
+fn tweak_ringmod(ui: &mut UiState, dir: i32) {
+    let dir_f = if dir < 0 { -1.0 } else { 1.0 };
+
+    match ui.selected_ringmod_param() {
+        RingModParam::FreqHz => {
+            let freq = ui.ringmod.freq_hz;
+
+            let step = if freq < 100.0 {
+                5.0
+            } else if freq < 1000.0 {
+                25.0
             } else {
-                ui.preset_category_idx - 1
+                100.0
             };
-            let len = ui.presets_in_selected_category().len();
-            if len == 0 {
-                ui.preset_row_idx = 0;
-            } else if ui.preset_row_idx >= len {
-                ui.preset_row_idx = len - 1;
-            }
+
+            ui.ringmod.freq_hz = (freq + dir_f * step).clamp(5.0, 5000.0);
         }
+        RingModParam::Mix => {
+            ui.ringmod.mix = (ui.ringmod.mix + dir_f * 0.05).clamp(0.0, 1.0);
+        }
+    }
+}
 
-        KeyCode::Up => {
-            if ui.preset_row_idx > 0 {
-                ui.preset_row_idx -= 1;
-            }
+fn tweak_bitcrush(ui: &mut UiState, dir: i32) {
+    match ui.selected_bitcrush_param() {
+        BitcrushParam::Bits => {
+            ui.bitcrush.bits = if dir < 0 {
+                ui.bitcrush.bits.saturating_sub(1).max(1)
+            } else {
+                (ui.bitcrush.bits + 1).min(16)
+            };
+        }
+        BitcrushParam::Downsample => {
+            ui.bitcrush.downsample = if dir < 0 {
+                ui.bitcrush.downsample.saturating_sub(1).max(1)
+            } else {
+                (ui.bitcrush.downsample + 1).min(64)
+            };
         }
+    }
+}
 
-        KeyCode::Down => {
-            let len = ui.presets_in_selected_category().len();
-            if ui.preset_row_idx + 1 < len {
-                ui.preset_row_idx += 1;
-            }
+fn tweak_sample_hold(ui: &mut UiState, dir: i32) {
+    let dir_i = if dir < 0 { -1 } else { 1 };
+    let dir_f = if dir_i < 0 { -1.0 } else { 1.0 };
+
+    match ui.selected_sample_hold_param() {
+        SampleHoldParam::RateHz => {
+            ui.sample_hold.rate_hz = (ui.sample_hold.rate_hz + dir_f * 0.25).clamp(0.25, 40.0);
+        }
+        SampleHoldParam::Depth => {
+            ui.sample_hold.depth = (ui.sample_hold.depth + dir_f * 0.02).clamp(0.0, 1.0);
         }
+        SampleHoldParam::Target => {
+            let all = HoldTarget::ALL;
+            let len = usize_to_i32(all.len());
+            let idx = all.iter().position(|t| *t == ui.sample_hold.target);
+            let idx = usize_to_i32(idx.unwrap_or(0));
 
-        KeyCode::Enter => {
-            apply_selected_preset(ui, client);
-            ui.show_presets = false;
+            ui.sample_hold.target = all[i32_to_usize((idx + dir_i).rem_euclid(len))];
         }
+    }
+}
 
-        _ => {}
+fn tweak_highpass(ui: &mut UiState, dir: i32) {
+    let dir_f = if dir < 0 { -1.0 } else { 1.0 };
+
+    match ui.selected_highpass_param() {
+        HighPassParam::CutoffHz => {
+            let cutoff = ui.highpass.cutoff_hz;
+
+            let step = if cutoff < 100.0 {
+                5.0
+            } else if cutoff < 1000.0 {
+                25.0
+            } else {
+                100.0
+            };
+
+            ui.highpass.cutoff_hz = (cutoff + dir_f * step).clamp(20.0, 5000.0);
+        }
+        HighPassParam::Q => {
+            ui.highpass.q = (ui.highpass.q + dir_f * 0.05).clamp(0.1, 10.0);
+        }
     }
 }
 
-fn handle_waveforms(ui: &mut UiState, client: &Client, key: &KeyEvent) {
-    let prev = ui.wave_idx;
+fn tweak_lowpass(ui: &mut UiState, dir: i32) {
+    let dir_f = if dir < 0 { -1.0 } else { 1.0 };
 
-    match key.code {
-        KeyCode::Up if ui.wave_idx > 0 => ui.wave_idx -= 1,
-        KeyCode::Down if ui.wave_idx + 1 < ui.waves.len() => ui.wave_idx += 1,
-        _ => {}
+    match ui.selected_lowpass_param() {
+        LowPassParam::CutoffHz => {
+            let cutoff = ui.lowpass.cutoff_hz;
+
+            let step = if cutoff < 100.0 {
+                5.0
+            } else if cutoff < 1000.0 {
+                25.0
+            } else if cutoff < 5000.0 {
+                100.0
+            } else {
+                250.0
+            };
+
+            ui.lowpass.cutoff_hz = (cutoff + dir_f * step).clamp(20.0, 20_000.0);
+        }
     }
+}
+
+fn tweak_delay(ui: &mut UiState, dir: i32) {
+    let dir_f = if dir < 0 { -1.0 } else { 1.0 };
 
-    if ui.wave_idx != prev {
-        client.set_wave(ui.selected_wave());
+    match ui.selected_delay_param() {
+        DelayParam::TimeMs => {
+            let step = if ui.delay.time_ms < 100.0 { 5.0 } else { 25.0 };
+            ui.delay.time_ms = (ui.delay.time_ms + dir_f * step).clamp(5.0, 2000.0);
+        }
+        DelayParam::Feedback => {
+            ui.delay.feedback = (ui.delay.feedback + dir_f * 0.05).clamp(0.0, 0.95);
+        }
+        DelayParam::Mix => {
+            ui.delay.mix = (ui.delay.mix + dir_f * 0.05).clamp(0.0, 1.0);
+        }
     }
 }
 
-fn handle_adsr(ui: &mut UiState, client: &Client, key: &KeyEvent) {
-    match key.code {
-        KeyCode::Up if ui.adsr_param_idx > 0 => ui.adsr_param_idx -= 1,
-        KeyCode::Down if ui.adsr_param_idx + 1 < AdsrParam::ALL.len() => ui.adsr_param_idx += 1,
-        KeyCode::Left => {
-            tweak_adsr(ui, -1);
-            client.set_adsr(ui.adsr.clone());
+fn tweak_reverb(ui: &mut UiState, dir: i32) {
+    let dir_f = if dir < 0 { -1.0 } else { 1.0 };
+
+    match ui.selected_reverb_param() {
+        ReverbParam::Room => {
+            ui.reverb.room = (ui.reverb.room + dir_f * 0.05).clamp(0.0, 1.0);
         }
-        KeyCode::Right => {
-            tweak_adsr(ui, 1);
-            client.set_adsr(ui.adsr.clone());
+        ReverbParam::Damping => {
+            ui.reverb.damping = (ui.reverb.damping + dir_f * 0.05).clamp(0.0, 1.0);
+        }
+        ReverbParam::Mix => {
+            ui.reverb.mix = (ui.reverb.mix + dir_f * 0.05).clamp(0.0, 1.0);
         }
-        _ => {}
     }
 }
 
-fn handle_mod(ui: &mut UiState, client: &Client, key: &KeyEvent) {
-    match key.code {
-        KeyCode::Up => match ui.mod_tab {
-            ModTab::Lfo if ui.lfo_param_idx > 0 => ui.lfo_param_idx -= 1,
-            ModTab::LowPass if ui.lowpass_param_idx > 0 => ui.lowpass_param_idx -= 1,
-            _ => {}
-        },
+fn tweak_noise_gate(ui: &mut UiState, dir: i32) {
+    let dir_f = if dir < 0 { -1.0 } else { 1.0 };
 
-        KeyCode::Down => match ui.mod_tab {
-            ModTab::Lfo if ui.lfo_param_idx + 1 < LfoParam::ALL.len() => ui.lfo_param_idx += 1,
-            ModTab::LowPass if ui.lowpass_param_idx + 1 < LowPassParam::ALL.len() => {
-                ui.lowpass_param_idx += 1;
-            }
-            _ => {}
-        },
+    match ui.selected_noise_gate_param() {
+        NoiseGateParam::Threshold => {
+            ui.noise_gate.threshold_db = (ui.noise_gate.threshold_db + dir_f).clamp(-96.0, 0.0);
+        }
+        NoiseGateParam::Attack => {
+            ui.noise_gate.attack_ms = (ui.noise_gate.attack_ms + dir_f * 0.5).clamp(0.0, 50.0);
+        }
+        NoiseGateParam::Release => {
+            let step = if ui.noise_gate.release_ms < 100.0 {
+                5.0
+            } else {
+                25.0
+            };
+            ui.noise_gate.release_ms = (ui.noise_gate.release_ms + dir_f * step).clamp(5.0, 1000.0);
+        }
+    }
+}
 
-        KeyCode::Left => match ui.mod_tab {
-            ModTab::Lfo => {
-                tweak_lfo(ui, -1);
-                client.set_lfo_amp(ui.lfo.clone());
-            }
-            ModTab::LowPass => {
-                tweak_lowpass(ui, -1);
-                client.set_lowpass(ui.lowpass.clone());
-            }
-        },
+fn tweak_pan(ui: &mut UiState, dir: i32) {
+    let dir_f = if dir < 0 { -1.0 } else { 1.0 };
 
-        KeyCode::Right => match ui.mod_tab {
-            ModTab::Lfo => {
-                tweak_lfo(ui, 1);
-                client.set_lfo_amp(ui.lfo.clone());
-            }
-            ModTab::LowPass => {
-                tweak_lowpass(ui, 1);
-                client.set_lowpass(ui.lowpass.clone());
-            }
-        },
+    match ui.selected_pan_param() {
+        PanParam::Position => {
+            ui.pan.position = (ui.pan.position + dir_f * 0.1).clamp(-1.0, 1.0);
+        }
+    }
+}
 
-        KeyCode::Enter => {
-            ui.mod_tab = ui.mod_tab.next();
+fn tweak_layer(ui: &mut UiState, dir: i32) {
+    let dir_i = if dir < 0 { -1 } else { 1 };
+    let dir_f = if dir_i < 0 { -1.0 } else { 1.0 };
+    let param = ui.selected_layer_param();
+
+    if param == LayerParam::Layer {
+        let last = ui.layers.len().saturating_sub(1);
+        ui.layer_idx = if dir_i < 0 {
+            ui.layer_idx.saturating_sub(1)
+        } else {
+            (ui.layer_idx + 1).min(last)
+        };
+        return;
+    }
+
+    let Some(layer) = ui.layers.get_mut(ui.layer_idx) else {
+        return;
+    };
+
+    match param {
+        LayerParam::Layer => {}
+        LayerParam::Wave => layer.wave = next_wave(&layer.wave, dir_i),
+        LayerParam::Detune => layer.cents = (layer.cents + dir_f).clamp(-1200.0, 1200.0),
+        LayerParam::Level => layer.level = (layer.level + dir_f * 0.05).clamp(0.0, 1.0),
+    }
+}
+
+fn tweak_unison(ui: &mut UiState, dir: i32) {
+    let dir_f = if dir < 0 { -1.0 } else { 1.0 };
+
+    match ui.selected_unison_param() {
+        UnisonParam::Voices => {
+            ui.unison.voices = if dir < 0 {
+                ui.unison.voices.saturating_sub(1).max(1)
+            } else {
+                (ui.unison.voices + 1).min(UNISON_MAX)
+            };
         }
+        UnisonParam::Detune => {
+            ui.unison.detune_cents = (ui.unison.detune_cents + dir_f).clamp(0.0, 100.0);
+        }
+    }
+}
 
-        _ => {}
+/// Keys keep the volume within 0..1, the engine applies it to sounding notes
+fn nudge_volume(ui: &mut UiState, client: &Client, delta: f32) {
+    ui.volume = (ui.volume + delta).clamp(0.0, 1.0);
+    client.set_volume(ui.volume);
+}
+
+fn tweak_glide(ui: &mut UiState, dir: i32) {
+    let dir_f = if dir < 0 { -1.0 } else { 1.0 };
+    ui.glide_s = (ui.glide_s + dir_f * 0.01).clamp(0.0, GLIDE_MAX_S);
+}
+
+fn tweak_sub(ui: &mut UiState, dir: i32) {
+    let dir_i = if dir < 0 { -1 } else { 1 };
+    let dir_f = if dir_i < 0 { -1.0 } else { 1.0 };
+
+    match ui.selected_sub_param() {
+        SubParam::Wave => {
+            let all = SubWave::ALL;
+            let len = usize_to_i32(all.len());
+            let idx = all.iter().position(|w| *w == ui.sub.wave);
+            let idx = usize_to_i32(idx.unwrap_or(0));
+
+            ui.sub.wave = all[i32_to_usize((idx + dir_i).rem_euclid(len))];
+        }
+        SubParam::Level => ui.sub.level = (ui.sub.level + dir_f * 0.05).clamp(0.0, 1.0),
+    }
+}
+
+fn tweak_fm(ui: &mut UiState, dir: i32) {
+    let dir_i = if dir < 0 { -1 } else { 1 };
+    let dir_f = if dir_i < 0 { -1.0 } else { 1.0 };
+
+    match ui.selected_fm_param() {
+        FmParam::Modulator => ui.fm.modulator = next_wave(&ui.fm.modulator, dir_i),
+        FmParam::Ratio => ui.fm.ratio = (ui.fm.ratio + dir_f * 0.25).clamp(0.25, FM_RATIO_MAX),
+        FmParam::Index => ui.fm.index = (ui.fm.index + dir_f * 0.25).clamp(0.0, FM_INDEX_MAX),
+    }
+}
+
+fn tweak_bend(ui: &mut UiState, dir: i32) {
+    let dir_i = if dir < 0 { -1 } else { 1 };
+    let dir_f = if dir_i < 0 { -1.0 } else { 1.0 };
+
+    match ui.selected_bend_param() {
+        BendParam::Range => {
+            ui.bend_range_st = (ui.bend_range_st + dir_f).clamp(0.0, BEND_RANGE_MAX_ST);
+        }
+        BendParam::Return => {
+            let all = BendReturn::ALL;
+            let len = usize_to_i32(all.len());
+            let idx = all.iter().position(|r| *r == ui.bend_return);
+            let idx = usize_to_i32(idx.unwrap_or(0));
+
+            ui.bend_return = all[i32_to_usize((idx + dir_i).rem_euclid(len))];
+        }
+    }
+}
+
+fn tweak_scale(ui: &mut UiState, dir: i32) {
+    let dir_i = if dir < 0 { -1 } else { 1 };
+
+    match ui.selected_scale_param() {
+        ScaleParam::Scale => {
+            let all = Scale::ALL;
+            let len = usize_to_i32(all.len());
+            let idx = all.iter().position(|s| *s == ui.quantizer.scale);
+            let idx = usize_to_i32(idx.unwrap_or(0));
+
+            ui.quantizer.scale = all[i32_to_usize((idx + dir_i).rem_euclid(len))];
+        }
+        ScaleParam::Root => {
+            ui.quantizer.root = Note::from_semitone(ui.quantizer.root.semitone() + dir_i);
+        }
+        ScaleParam::Tuning => {
+            let mut all = Tuning::ALL.to_vec();
+            all.extend(ui.custom_tuning.clone());
+            let len = usize_to_i32(all.len());
+            let idx = all.iter().position(|t| *t == ui.tuning);
+            let idx = usize_to_i32(idx.unwrap_or(0));
+
+            ui.tuning = all[i32_to_usize((idx + dir_i).rem_euclid(len))].clone();
+        }
+    }
+}
+
+fn tweak_chord(ui: &mut UiState, dir: i32) {
+    let dir_i = if dir < 0 { -1 } else { 1 };
+
+    let all = Chord::ALL;
+    let len = usize_to_i32(all.len());
+    let idx = all.iter().position(|c| *c == ui.chord);
+    let idx = usize_to_i32(idx.unwrap_or(0));
+
+    ui.chord = all[i32_to_usize((idx + dir_i).rem_euclid(len))];
+}
+
+fn tweak_velocity(ui: &mut UiState, dir: i32) {
+    let dir_i = if dir < 0 { -1 } else { 1 };
+    let step = |velocity: u8| {
+        let velocity = i32::from(velocity) + dir_i * i32::from(VELOCITY_STEP);
+        u8::try_from(velocity.clamp(1, 127)).unwrap_or(127)
+    };
+
+    match ui.selected_velocity_param() {
+        VelocityParam::Curve => {
+            let all = VelocityCurve::ALL;
+            let len = usize_to_i32(all.len());
+            let idx = all.iter().position(|c| *c == ui.velocity.curve);
+            let idx = usize_to_i32(idx.unwrap_or(0));
+
+            ui.velocity.curve = all[i32_to_usize((idx + dir_i).rem_euclid(len))];
+        }
+        VelocityParam::Keys => ui.velocity.keys = step(ui.velocity.keys),
+        VelocityParam::Accent => ui.velocity.accent = step(ui.velocity.accent),
+    }
+}
+
+fn tweak_arp(ui: &mut UiState, dir: i32) {
+    let dir_i = if dir < 0 { -1 } else { 1 };
+    let dir_f = if dir_i < 0 { -1.0 } else { 1.0 };
+
+    match ui.selected_arp_param() {
+        ArpParam::On => ui.arp.enabled = dir_i > 0,
+        ArpParam::Bpm => ui.tempo = Tempo::new(ui.tempo.bpm + dir_f),
+        ArpParam::Pattern => {
+            let all = ArpPattern::ALL;
+            let len = usize_to_i32(all.len());
+            let idx = all.iter().position(|p| *p == ui.arp.pattern);
+            let idx = usize_to_i32(idx.unwrap_or(0));
+
+            ui.arp.pattern = all[i32_to_usize((idx + dir_i).rem_euclid(len))];
+        }
+        ArpParam::Length => {
+            ui.arp.length = (ui.arp.length + dir_f * 0.05).clamp(0.05, 1.0);
+        }
     }
 }
 
-fn handle_keyboard(ui: &mut UiState, client: &Client, key: &KeyEvent) {
-    match key.code {
-        KeyCode::Right if ui.octave < 4 => {
-            ui.octave += 1;
-            client.set_octave(ui.octave);
+fn tweak_seq(ui: &mut UiState, dir: i32) {
+    let dir_i = if dir < 0 { -1 } else { 1 };
+    let dir_f = if dir_i < 0 { -1.0 } else { 1.0 };
+
+    match ui.selected_seq_param() {
+        SeqParam::Play => ui.sequence.playing = dir_i > 0,
+        SeqParam::Bpm => ui.tempo = Tempo::new(ui.tempo.bpm + dir_f),
+        SeqParam::Step => {
+            let len = usize_to_i32(ui.sequence.steps.len());
+            ui.seq_cursor = i32_to_usize((usize_to_i32(ui.seq_cursor) + dir_i).rem_euclid(len));
         }
-        KeyCode::Left if ui.octave > -4 => {
-            ui.octave -= 1;
-            client.set_octave(ui.octave);
+        SeqParam::Note => {
+            let step = &mut ui.sequence.steps[ui.seq_cursor];
+            // a rest starts from the C the keyboard is on
+            let from = Key::new(Note::C, KEYBOARD_BASE_OCTAVE + ui.octave);
+            *step = Some(step.map_or(from, |key| key.transpose(dir_i)));
         }
-        _ => {}
+        SeqParam::Clear => ui.sequence.clear(),
     }
 }
 
-fn tweak_adsr(ui: &mut UiState, dir: i32) {
-    let step = 0.01;
-    let delta = if dir < 0 { -step } else { step };
+/// Hands the pattern to the engine and saves it so it is back next time
+fn send_sequence(ui: &mut UiState, client: &Client) {
+    client.set_sequence(ui.sequence.clone());
+    send_tempo(ui, client);
+}
 
-    match ui.selected_adsr_param() {
-        AdsrParam::Attack => ui.adsr.attack_s = (ui.adsr.attack_s + delta).clamp(0.0, 10.0),
-        AdsrParam::Decay => ui.adsr.decay_s = (ui.adsr.decay_s + delta).clamp(0.0, 10.0),
-        AdsrParam::Sustain => ui.adsr.sustain = (ui.adsr.sustain + delta).clamp(0.0, 1.0),
-        AdsrParam::Release => ui.adsr.release_s = (ui.adsr.release_s + delta).clamp(0.0, 10.0),
+/// Sets the tempo the arp and sequencer share, it is saved with the pattern
+fn send_tempo(ui: &mut UiState, client: &Client) {
+    client.set_tempo(ui.tempo);
+    if let Err(err) = ui.sequence.save(ui.tempo) {
+        ui.status = format!("saving sequence failed: {err}");
     }
 }
 
-fn tweak_lfo(ui: &mut UiState, dir: i32) {
-    let dir_i = if dir < 0 { -1 } else { 1 };
-    let dir_f = if dir_i < 0 { -1.0 } else { 1.0 };
+fn tap_tempo(ui: &mut UiState, client: &Client) {
+    let Some(tempo) = ui.tap_tempo.tap(Instant::now()) else {
+        ui.status = "tap again for tempo".to_string();
+        return;
+    };
 
-    match ui.selected_lfo_param() {
-        LfoParam::Kind => ui.lfo.wave = next_wave(&ui.lfo.wave, dir_i),
-        LfoParam::RateHz => {
-            ui.lfo.rate_hz = (ui.lfo.rate_hz + dir_f * 0.25).clamp(0.05, 40.0);
-        }
-        LfoParam::Depth => {
-            ui.lfo.depth = (ui.lfo.depth + dir_f * 0.02).clamp(0.0, 1.0);
-        }
+    ui.tempo = tempo;
+    send_tempo(ui, client);
+    ui.status = format!("tempo {:.0} bpm", tempo.bpm);
+}
+
+/// Stacks the highlighted wave on top of the patch oscillator, alternating the
+/// detune sign so a pair of layers spreads evenly around the root
+fn add_layer(ui: &mut UiState, client: &Client) {
+    if ui.layers.len() >= LAYERS_MAX {
+        ui.status = format!("at most {LAYERS_MAX} layers");
+        return;
     }
+
+    let wave = ui.waves[ui.wave_idx].clone();
+    let sign = if ui.layers.len().is_multiple_of(2) {
+        1.0
+    } else {
+        -1.0
+    };
+
+    ui.status = format!("added {} layer", wave.label(ui.pulse_duty));
+    ui.layers.push(Layer {
+        wave,
+        cents: sign * LAYER_DETUNE_CENTS,
+        level: 1.0,
+    });
+    ui.layer_idx = ui.layers.len() - 1;
+    client.set_layers(ui.layers.clone());
 }
 
-fn tweak_lowpass(ui: &mut UiState, dir: i32) {
-    let dir_f = if dir < 0 { -1.0 } else { 1.0 };
+fn remove_layer(ui: &mut UiState, client: &Client) {
+    let Some(layer) = ui.layers.pop() else {
+        return;
+    };
 
-    match ui.selected_lowpass_param() {
-        LowPassParam::CutoffHz => {
-            let cutoff = ui.lowpass.cutoff_hz;
+    ui.status = format!("removed {} layer", layer.wave.label(ui.pulse_duty));
+    ui.layer_idx = ui.layer_idx.min(ui.layers.len().saturating_sub(1));
+    client.set_layers(ui.layers.clone());
+}
 
-            let step = if cutoff < 100.0 {
-                5.0
-            } else if cutoff < 1000.0 {
-                25.0
-            } else if cutoff < 5000.0 {
-                100.0
-            } else {
-                250.0
-            };
+#[must_use]
+fn next_envelope_mode(mode: EnvelopeMode, dir: i32) -> EnvelopeMode {
+    let all = EnvelopeMode::ALL;
+    let len = usize_to_i32(all.len());
+    let idx = usize_to_i32(all.iter().position(|m| *m == mode).unwrap_or(0));
 
-            ui.lowpass.cutoff_hz = (cutoff + dir_f * step).clamp(20.0, 20_000.0);
-        }
-    }
+    all[i32_to_usize((idx + dir).rem_euclid(len))]
+}
+
+#[must_use]
+fn next_envelope_curve(curve: EnvelopeCurve, dir: i32) -> EnvelopeCurve {
+    let all = EnvelopeCurve::ALL;
+    let len = usize_to_i32(all.len());
+    let idx = usize_to_i32(all.iter().position(|c| *c == curve).unwrap_or(0));
+
+    all[i32_to_usize((idx + dir).rem_euclid(len))]
+}
+
+/// Tables are told apart by name
+#[must_use]
+fn next_wavetable(all: &[Wavetable], table: &Wavetable, dir: i32) -> Wavetable {
+    let len = usize_to_i32(all.len());
+    let idx = all.iter().position(|t| t.name() == table.name());
+    let idx = usize_to_i32(idx.unwrap_or(0));
+
+    all.get(i32_to_usize((idx + dir).rem_euclid(len.max(1))))
+        .cloned()
+        .unwrap_or_else(|| table.clone())
 }
 
 #[must_use]
 fn next_wave(wave: &Wave, dir: i32) -> Wave {
-    const ALL: [Wave; 5] = [
+    const ALL: [Wave; 8] = [
         Wave::Sine,
         Wave::Saw,
         Wave::Square,
+        Wave::Pulse,
         Wave::Triangle,
         Wave::Noise,
+        Wave::PinkNoise,
+        Wave::BrownNoise,
     ];
 
     let len = usize_to_i32(ALL.len());
@@ -763,7 +3904,7 @@ fn draw_intro(f: &mut ratatui::Frame) {
         )
         .alignment(Alignment::Center)
         .wrap(Wrap { trim: false })
-        .style(Style::default().fg(kdr::FG).bg(kdr::BG0)),
+        .style(Style::default().fg(colors::fg()).bg(colors::bg())),
         main_area,
     );
 
@@ -779,10 +3920,10 @@ fn draw_intro(f: &mut ratatui::Frame) {
         f.render_widget(
             Paragraph::new(vec![Line::from(Span::styled(
                 "s y n t h e s i s",
-                Style::default().fg(kdr::ORANGE).bold(),
+                Style::default().fg(colors::accent()).bold(),
             ))])
             .alignment(Alignment::Center)
-            .style(Style::default().bg(kdr::BG0)),
+            .style(Style::default().bg(colors::bg())),
             synth_area,
         );
     }
@@ -832,10 +3973,46 @@ fn draw_ui(f: &mut ratatui::Frame, ui: &UiState) {
         .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
         .split(top[1]);
 
-    draw_waveforms(f, top[0], ui);
+    let left = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(24), Constraint::Min(0)])
+        .split(top[0]);
+
+    draw_waveforms(f, left[0], ui);
+    draw_scope(f, left[1], ui);
     draw_adsr(f, right[0], ui);
     draw_mod(f, right[1], ui);
-    draw_keyboard(f, rows[1], ui);
+    let bottom = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(4), Constraint::Min(0)])
+        .split(rows[1]);
+
+    let strip = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(86), Constraint::Min(0)])
+        .split(bottom[0]);
+
+    draw_sequencer(f, strip[0], ui);
+    draw_playing(f, strip[1], ui);
+
+    let lower = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(FX_PANE_W)])
+        .split(bottom[1]);
+
+    // the piano only takes rows the computer keyboard can spare
+    if lower[0].height >= KEYBOARD_MIN_H + PIANO_H {
+        let keys = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(PIANO_H), Constraint::Min(0)])
+            .split(lower[0]);
+
+        draw_piano(f, keys[0], ui);
+        draw_keyboard(f, keys[1], ui);
+    } else {
+        draw_keyboard(f, lower[0], ui);
+    }
+    draw_effects(f, lower[1], ui);
     draw_help(f, help_area, ui);
 
     if ui.show_presets {
@@ -853,14 +4030,14 @@ fn draw_too_small(f: &mut ratatui::Frame, area: Rect, min_w: u16, min_h: u16) {
     let msg = vec![
         Line::from(Span::styled(
             "terminal too small",
-            Style::default().fg(kdr::ORANGE).bold(),
+            Style::default().fg(colors::accent()).bold(),
         )),
         Line::from(Span::styled(
             format!(
                 "need {min_w}×{min_h}  —  currently {}×{}",
                 area.width, area.height
             ),
-            Style::default().fg(kdr::MUTED),
+            Style::default().fg(colors::muted()),
         )),
     ];
 
@@ -870,7 +4047,7 @@ fn draw_too_small(f: &mut ratatui::Frame, area: Rect, min_w: u16, min_h: u16) {
     f.render_widget(
         Paragraph::new(msg)
             .alignment(Alignment::Center)
-            .style(Style::default().bg(kdr::BG0)),
+            .style(Style::default().bg(colors::bg())),
         Rect {
             x: inner.x,
             y,
@@ -882,8 +4059,8 @@ fn draw_too_small(f: &mut ratatui::Frame, area: Rect, min_w: u16, min_h: u16) {
 
 fn draw_logo(f: &mut ratatui::Frame, area: Rect) {
     let lines = vec![
-        Line::from(Span::styled("無", Style::default().fg(kdr::FG)).bold()),
-        Line::from(Span::styled("限", Style::default().fg(kdr::FG)).bold()),
+        Line::from(Span::styled("無", Style::default().fg(colors::fg())).bold()),
+        Line::from(Span::styled("限", Style::default().fg(colors::fg())).bold()),
     ];
 
     let total_h = usize_to_u16(lines.len());
@@ -893,7 +4070,7 @@ fn draw_logo(f: &mut ratatui::Frame, area: Rect) {
         Paragraph::new(lines)
             .alignment(Alignment::Center)
             .wrap(Wrap { trim: true })
-            .style(Style::default().bg(kdr::BG0)),
+            .style(Style::default().bg(colors::bg())),
         Rect {
             x: area.x,
             y,
@@ -905,11 +4082,28 @@ fn draw_logo(f: &mut ratatui::Frame, area: Rect) {
 
 fn draw_waveforms(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
     let focused = ui.pane == Pane::Waveforms;
-    let block = panel_block("waveforms", focused);
+    let title = match (ui.split, ui.edit_split) {
+        (false, _) => "waveforms",
+        (true, false) => "waveforms ─ home row",
+        (true, true) => "waveforms ─ top row",
+    };
+    let block = panel_block(title, focused)
+        .title_bottom(format!(" xfade {:.0} ms ", ui.crossfade_s * 1000.0));
+
+    let selected = if ui.edit_split {
+        ui.split_wave_idx
+    } else {
+        ui.wave_idx
+    };
 
     let mut lines = vec![Line::from("")];
     for (i, wave) in ui.waves.iter().enumerate() {
-        lines.push(simple_select_line(i == ui.wave_idx, wave.name()));
+        let label = if *wave == Wave::Wavetable {
+            format!("{} {}", wave.name(), ui.wavetable.name())
+        } else {
+            wave.label(ui.pulse_duty)
+        };
+        lines.push(simple_select_line(i == selected, &label));
     }
 
     f.render_widget(
@@ -922,9 +4116,474 @@ fn draw_waveforms(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
     );
 }
 
+fn draw_scope(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
+    ui.scope_area.set(area);
+    let title = match (ui.scope_mode, ui.trigger) {
+        (ScopeMode::Wave, Trigger::Rising) => "scope ─ trig ↑",
+        (ScopeMode::Wave, Trigger::Falling) => "scope ─ trig ↓",
+        (ScopeMode::Wave, Trigger::NoteOn) => "scope ─ trig note",
+        (ScopeMode::Spectrum, _) if ui.frozen_spectrum.is_some() => "spectrum ─ frozen",
+        (ScopeMode::Spectrum, _) if ui.weighting == Weighting::A => "spectrum ─ dB(A)",
+        (ScopeMode::Spectrum, _) if ui.weighting == Weighting::C => "spectrum ─ dB(C)",
+        (mode, _) => mode.name(),
+    };
+    // names the input device while it is the one shown
+    let source = |block: Block<'static>| match (&ui.mic, ui.scope_voice) {
+        (Some(mic), _) => block.title_bottom(format!(" {} ", mic.name())),
+        (None, Some(hz)) => block.title_bottom(format!(" voice {hz:.1} Hz ")),
+        (None, None) => block,
+    };
+    let block = source(panel_block(title, false));
+    let sample_rate = ui.capture.sample_rate();
+
+    if sample_rate == 0 {
+        f.render_widget(
+            Paragraph::new("").block(block).style(panel_style(false)),
+            area,
+        );
+        return;
+    }
+
+    match ui.scope_mode {
+        ScopeMode::Wave => draw_wave_scope(f, area, block, ui, sample_rate),
+        ScopeMode::Spectrum => draw_spectrum(f, area, block, ui, sample_rate),
+        ScopeMode::Spectrogram => draw_spectrogram(f, area, block, ui, sample_rate),
+        ScopeMode::Xy if ui.capture.channels() == 2 => draw_xy_scope(f, area, block, ui),
+        ScopeMode::Xy => {
+            let block = source(panel_block("x/y ─ mono, showing scope", false));
+            draw_wave_scope(f, area, block, ui, sample_rate);
+        }
+    }
+
+    if ui.scope_notes {
+        draw_scope_notes(f, area, ui);
+    }
+}
+
+/// One frame of the wave scope, a line of points per channel
+type ScopeTrace = Vec<Vec<(f64, f64)>>;
+
+/// Frames across the wave scope, `SCOPE_FRAMES` unless the capture keeps
+/// less history than that
+fn scope_window(capture: &Capture) -> usize {
+    SCOPE_FRAMES.min(capture.capacity())
+}
+
+/// With an edge trigger set the capture is read up to two windows deep and
+/// the trace starts at the first matching edge, the note trigger reads back
+/// to the newest note's first frame and starts there. Otherwise, or with
+/// nothing to start at, it shows the newest window. `offset` moves all of
+/// that back by as many frames, as far as the capture reaches
+fn scope_trace(capture: &Capture, trigger: Trigger, offset: usize) -> ScopeTrace {
+    let window = scope_window(capture);
+    let cap = capture.capacity();
+    let onset = capture.onset_age().filter(|_| trigger == Trigger::NoteOn);
+    let depth = match (trigger, onset) {
+        (Trigger::Off, _) | (Trigger::NoteOn, None) => window,
+        (Trigger::NoteOn, Some(age)) => age.clamp(window, cap),
+        _ => (window * 2).min(cap),
+    };
+    let offset = offset.min(cap - depth);
+
+    let mut channels = capture.frames(depth + offset);
+    for channel in &mut channels {
+        channel.truncate(channel.len().saturating_sub(offset));
+    }
+    let len = channels.first().map_or(0, Vec::len);
+
+    let start = match onset {
+        Some(age) => (depth + offset).checked_sub(age).filter(|&i| i < len),
+        None => channels
+            .first()
+            .and_then(|ch| trigger.find(ch, SCOPE_TRIGGER_LEVEL, window)),
+    }
+    .unwrap_or_else(|| len.saturating_sub(window));
+
+    channels
+        .iter()
+        .map(|channel| {
+            channel[start..]
+                .iter()
+                .take(window)
+                .enumerate()
+                .map(|(i, s)| (i as f64, f64::from(*s)))
+                .collect()
+        })
+        .collect()
+}
+
+/// The current trace over the afterglow ones, which fade the older they are
+fn draw_wave_scope(
+    f: &mut ratatui::Frame,
+    area: Rect,
+    block: Block<'static>,
+    ui: &UiState,
+    sample_rate: u32,
+) {
+    let line = |color: Color, data| {
+        Dataset::default()
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(color))
+            .data(data)
+    };
+
+    // a faded trace only needs about two points per braille dot across, far
+    // fewer than the capture holds, which keeps a deep afterglow cheap
+    let depth = ui.scope_trails.len();
+    let window = scope_window(&ui.capture);
+    let stride = (window / (usize::from(area.width) * 4).max(1)).max(1);
+    let faded: Vec<(Color, Vec<(f64, f64)>)> = ui
+        .scope_trails
+        .iter()
+        .take(depth.saturating_sub(1))
+        .enumerate()
+        .flat_map(|(age, trace)| {
+            let level = 0.6 * (age + 1) as f32 / depth as f32;
+            trace.iter().enumerate().map(move |(ch, data)| {
+                let color = ui.scope_shading.trace(colors::channel(ch), level);
+                (color, data.iter().step_by(stride).copied().collect())
+            })
+        })
+        .collect();
+
+    let range = 1.0 / f64::from(ui.scope_scale.max(SCOPE_SCALE_MIN));
+    let grid = if ui.scope_grid {
+        scope_grid(window as f64, range)
+    } else {
+        Vec::new()
+    };
+
+    // grid, then older traces, so the current one is drawn over them all
+    let mut datasets: Vec<_> = grid
+        .iter()
+        .chain(&faded)
+        .map(|(color, data)| line(*color, data))
+        .collect();
+    if let Some(current) = ui.scope_trails.back() {
+        datasets.extend(
+            current
+                .iter()
+                .enumerate()
+                .map(|(ch, data)| line(ui.scope_shading.trace(colors::channel(ch), 1.0), data)),
+        );
+    }
+
+    let frame_ms = 1000.0 / f64::from(sample_rate);
+    let pan = if ui.scope_offset > 0 {
+        format!(" −{}", ms_label(ui.scope_offset as f64 * frame_ms))
+    } else {
+        String::new()
+    };
+    let zoom = Line::from(format!(
+        " ×{:.1}{}{pan} ",
+        ui.scope_scale,
+        if ui.scope_autoscale { " auto" } else { "" }
+    ))
+    .right_aligned();
+
+    // the window in ms, fewer labels when narrow so they never run together
+    let span_ms = window as f64 * frame_ms;
+    let ticks = if area.width < 48 { 1 } else { 2 };
+    let labels: Vec<Span> = (0..=ticks)
+        .map(|i| ms_label(span_ms * f64::from(i) / f64::from(ticks)))
+        .map(|label| Span::styled(label, Style::default().fg(colors::muted())))
+        .collect();
+
+    // which colour is which channel, a mono trace needs no key
+    let channels = ui.scope_trails.back().map_or(0, Vec::len);
+    let mut legend: Vec<Span> = match channels {
+        0 | 1 => Vec::new(),
+        2 => ["L", "R"]
+            .iter()
+            .enumerate()
+            .map(|(ch, name)| Span::styled(format!(" {name}"), colors::channel(ch)))
+            .collect(),
+        _ => (0..channels)
+            .map(|ch| Span::styled(format!(" {}", ch + 1), colors::channel(ch)))
+            .collect(),
+    };
+    let block = if legend.is_empty() {
+        block
+    } else {
+        legend.push(Span::raw(" "));
+        block.title_top(Line::from(legend))
+    };
+
+    let chart = Chart::new(datasets)
+        .block(block.title_top(zoom))
+        .style(Style::default().bg(colors::bg()))
+        .x_axis(
+            Axis::default()
+                .bounds([0.0, window as f64])
+                .labels(labels)
+                .style(Style::default().fg(colors::border())),
+        )
+        .y_axis(Axis::default().bounds([-range, range]));
+
+    f.render_widget(chart, area);
+}
+
+/// Gridlines for a scope `width` frames across and `range` high either side
+/// of zero. The amplitude step is a 1, 2 or 5 that fits `SCOPE_GRID_LINES`
+/// above zero, so zooming in brings finer lines. Zero is drawn brighter
+fn scope_grid(width: f64, range: f64) -> Vec<(Color, Vec<(f64, f64)>)> {
+    let most = range / SCOPE_GRID_LINES as f64;
+    let pow = 10f64.powf(most.log10().floor());
+    let step = [5.0, 2.0, 1.0]
+        .into_iter()
+        .map(|m| m * pow)
+        .find(|&step| step <= most * (1.0 + 1e-9))
+        .unwrap_or(pow);
+
+    let steps = (range / step + 1e-9).floor() as i32;
+    let levels = (-steps..=steps).map(|k| {
+        let color = if k == 0 {
+            colors::muted()
+        } else {
+            colors::border()
+        };
+        let y = f64::from(k) * step;
+        (color, vec![(0.0, y), (width, y)])
+    });
+
+    let divisions = (1..SCOPE_GRID_DIVISIONS).map(|i| {
+        let x = width * i as f64 / SCOPE_GRID_DIVISIONS as f64;
+        (colors::border(), vec![(x, -range), (x, range)])
+    });
+
+    divisions.chain(levels).collect()
+}
+
+/// Lissajous plot, a centered mono sound is a diagonal line and wider stereo
+/// opens it into a cloud
+fn draw_xy_scope(f: &mut ratatui::Frame, area: Rect, block: Block<'static>, ui: &UiState) {
+    let channels = ui.capture.frames(scope_window(&ui.capture));
+    let (Some(left), Some(right)) = (channels.first(), channels.get(1)) else {
+        return;
+    };
+
+    let data: Vec<(f64, f64)> = left
+        .iter()
+        .zip(right)
+        .map(|(l, r)| (f64::from(*l), f64::from(*r)))
+        .collect();
+
+    let dataset = Dataset::default()
+        .marker(Marker::Braille)
+        .graph_type(GraphType::Scatter)
+        .style(Style::default().fg(colors::accent()))
+        .data(&data);
+
+    let chart = Chart::new(vec![dataset])
+        .block(block)
+        .style(Style::default().bg(colors::bg()))
+        .x_axis(Axis::default().bounds([-1.0, 1.0]))
+        .y_axis(Axis::default().bounds([-1.0, 1.0]));
+
+    f.render_widget(chart, area);
+}
+
+/// Log-frequency magnitude plot of the mono sum, from 20 Hz up to Nyquist
+fn draw_spectrum(
+    f: &mut ratatui::Frame,
+    area: Rect,
+    block: Block<'static>,
+    ui: &UiState,
+    sample_rate: u32,
+) {
+    let mut mags = match &ui.frozen_spectrum {
+        Some(mags) => mags.clone(),
+        None => magnitudes_db(&mono_frames(&ui.capture, SPECTRUM_FRAMES)),
+    };
+    let bin_hz = f64::from(sample_rate) / ((mags.len() - 1) * 2) as f64;
+    ui.weighting.apply(&mut mags, bin_hz as f32);
+    let lo = 20f64.log10();
+    let hi = (f64::from(sample_rate) / 2.0).log10();
+
+    let data: Vec<(f64, f64)> = mags
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(k, db)| ((k as f64 * bin_hz).log10(), f64::from(*db)))
+        .filter(|(x, _)| *x >= lo)
+        .collect();
+
+    let labels: Vec<Span> = (0..4)
+        .map(|i| hz_label(10f64.powf(lo + (hi - lo) * f64::from(i) / 3.0)))
+        .map(|label| Span::styled(label, Style::default().fg(colors::muted())))
+        .collect();
+
+    let dataset = Dataset::default()
+        .marker(Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(colors::accent()))
+        .data(&data);
+
+    let chart = Chart::new(vec![dataset])
+        .block(block)
+        .style(Style::default().bg(colors::bg()))
+        .x_axis(
+            Axis::default()
+                .bounds([lo, hi])
+                .labels(labels)
+                .style(Style::default().fg(colors::border())),
+        )
+        .y_axis(Axis::default().bounds([f64::from(FLOOR_DB), 0.0]));
+
+    f.render_widget(chart, area);
+}
+
+/// The last `frames` frames with the channels averaged
+fn mono_frames(capture: &Capture, frames: usize) -> Vec<f32> {
+    let channels = capture.frames(frames);
+    let len = channels.first().map_or(0, Vec::len);
+
+    (0..len)
+        .map(|i| channels.iter().map(|ch| ch[i]).sum::<f32>() / channels.len() as f32)
+        .collect()
+}
+
+/// One spectrogram row, the loudest bin under each of `cols` columns spread
+/// over a log axis from 20 Hz to Nyquist, as 0..1 above `FLOOR_DB`
+fn spectrogram_row(
+    capture: &Capture,
+    sample_rate: u32,
+    cols: usize,
+    weighting: Weighting,
+) -> Vec<f32> {
+    // only as fine as the columns can show, so a small terminal gets a
+    // shorter, cheaper transform
+    let frames = (cols * 8).next_power_of_two().clamp(512, SPECTRUM_FRAMES);
+    let mut mags = magnitudes_db(&mono_frames(capture, frames));
+    let last = mags.len() - 1;
+    let bin_hz = f64::from(sample_rate) / (last * 2) as f64;
+    weighting.apply(&mut mags, bin_hz as f32);
+    let lo = 20f64.log10();
+    let hi = (f64::from(sample_rate) / 2.0).log10();
+
+    let bin_at = |col: usize| {
+        let hz = 10f64.powf(lo + (hi - lo) * col as f64 / cols as f64);
+        ((hz / bin_hz) as usize).min(last)
+    };
+
+    (0..cols)
+        .map(|col| {
+            let (from, to) = (bin_at(col), bin_at(col + 1));
+            let db = mags[from..=to.max(from)]
+                .iter()
+                .fold(FLOOR_DB, |peak, &db| peak.max(db));
+            (db - FLOOR_DB) / -FLOOR_DB
+        })
+        .collect()
+}
+
+/// Waterfall of recent spectra, two rows per cell using the upper half
+/// block, with the same log axis and labels as the spectrum
+fn draw_spectrogram(
+    f: &mut ratatui::Frame,
+    area: Rect,
+    block: Block<'static>,
+    ui: &UiState,
+    sample_rate: u32,
+) {
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    if inner.width == 0 || inner.height < 2 {
+        return;
+    }
+
+    let buf = f.buffer_mut();
+    let level = |row: usize, col: u16| {
+        ui.spectrogram
+            .get(row)
+            .and_then(|levels| levels.get(usize::from(col)))
+            .map_or_else(colors::bg, |&level| ui.scope_shading.heat(level))
+    };
+
+    let plot_h = inner.height - 1;
+    for y in 0..plot_h {
+        let row = usize::from(y) * 2;
+        for x in 0..inner.width {
+            buf[(inner.x + x, inner.y + y)]
+                .set_char('▀')
+                .set_fg(level(row, x))
+                .set_bg(level(row + 1, x));
+        }
+    }
+
+    let label_y = inner.y + plot_h;
+    let label_style = Style::default().fg(colors::muted()).bg(colors::bg());
+    fill_rect(buf, inner, inner.x, label_y, inner.width, 1, label_style);
+
+    let lo = 20f64.log10();
+    let hi = (f64::from(sample_rate) / 2.0).log10();
+    for i in 0..4u16 {
+        let label = hz_label(10f64.powf(lo + (hi - lo) * f64::from(i) / 3.0));
+        let width = usize_to_u16(label.len());
+        // the ends sit flush with the edges, the middle two are centred
+        let x = match i {
+            0 => 0,
+            3 => inner.width.saturating_sub(width),
+            _ => (inner.width * i / 3).saturating_sub(width / 2),
+        };
+        buf.set_string(inner.x + x, label_y, &label, label_style);
+    }
+}
+
+#[must_use]
+fn hz_label(hz: f64) -> String {
+    if hz >= 10_000.0 {
+        format!("{:.0}kHz", hz / 1000.0)
+    } else if hz >= 1000.0 {
+        format!("{:.1}kHz", hz / 1000.0)
+    } else {
+        format!("{hz:.0}Hz")
+    }
+}
+
+#[must_use]
+fn ms_label(ms: f64) -> String {
+    if ms == 0.0 || ms >= 10.0 {
+        format!("{ms:.0}ms")
+    } else {
+        format!("{ms:.1}ms")
+    }
+}
+
 fn draw_adsr(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
     let focused = ui.pane == Pane::Adsr;
-    let block = panel_block("adsr", focused);
+    let block = panel_block(
+        if ui.edit_split {
+            "adsr ─ top row"
+        } else {
+            "adsr"
+        },
+        focused,
+    );
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    // a blank line above the parameters
+    let params_height = u16::try_from(AdsrParam::ALL.len() + 1).unwrap_or(u16::MAX);
+
+    // beside the parameters when there is room, under them otherwise, and
+    // left out when neither fits
+    let (params_area, graph_area) = if inner.width >= ADSR_GRAPH_SIDE_MIN_WIDTH {
+        let cols = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(ADSR_PARAMS_WIDTH), Constraint::Min(0)])
+            .split(inner);
+        (cols[0], Some(cols[1]))
+    } else if inner.height >= params_height + ADSR_GRAPH_MIN_HEIGHT {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(params_height), Constraint::Min(0)])
+            .split(inner);
+        (rows[0], Some(rows[1]))
+    } else {
+        (inner, None)
+    };
 
     let rows = AdsrParam::ALL.iter().enumerate().map(|(i, param)| {
         let value = match param {
@@ -932,10 +4591,12 @@ fn draw_adsr(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
             AdsrParam::Decay => format!("{:.3}", ui.adsr.decay_s),
             AdsrParam::Sustain => format!("{:.2}", ui.adsr.sustain),
             AdsrParam::Release => format!("{:.3}", ui.adsr.release_s),
+            AdsrParam::Mode => ui.adsr.mode.name().to_string(),
+            AdsrParam::Curve => ui.adsr.curve.name().to_string(),
         };
         let (label, hint) = param.label_and_hint();
         kv_line(
-            u16_to_usize(area.width.saturating_sub(2)),
+            u16_to_usize(params_area.width),
             i == ui.adsr_param_idx,
             label,
             hint,
@@ -948,63 +4609,137 @@ fn draw_adsr(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
 
     f.render_widget(
         Paragraph::new(lines)
-            .block(block)
             .wrap(Wrap { trim: false })
             .alignment(Alignment::Left)
             .style(panel_style(focused)),
-        area,
+        params_area,
     );
+
+    if let Some(graph_area) = graph_area {
+        draw_envelope(f, graph_area, &ui.adsr, focused);
+    }
+}
+
+/// The envelope for one note, time in seconds against level, each stage
+/// traced along the envelope's curve. The gate is held for
+/// `ADSR_GRAPH_HOLD_S` in gate mode, trigger mode goes straight from decay to
+/// release and loop mode shows one cycle. Zero-length stages just give a
+/// vertical step
+#[must_use]
+fn envelope_points(adsr: &Adsr) -> Vec<(f64, f64)> {
+    let attack = f64::from(adsr.attack_s.max(0.0));
+    let decay = f64::from(adsr.decay_s.max(0.0));
+    let sustain = f64::from(adsr.sustain.clamp(0.0, 1.0));
+    let release = f64::from(adsr.release_s.max(0.0));
+    let hold = match adsr.mode {
+        EnvelopeMode::Gate => f64::from(ADSR_GRAPH_HOLD_S),
+        EnvelopeMode::Trigger | EnvelopeMode::Loop => 0.0,
+    };
+
+    let mut points = vec![(0.0, 0.0)];
+    let mut stage = |time: f64, target: f64| {
+        let (t0, from) = *points.last().unwrap_or(&(0.0, 0.0));
+        for i in 1..=ADSR_GRAPH_STAGE_POINTS {
+            let progress = i as f32 / ADSR_GRAPH_STAGE_POINTS as f32;
+            let level = from + (target - from) * f64::from(adsr.curve.shape(progress));
+            points.push((t0 + time * f64::from(progress), level));
+        }
+    };
+    stage(attack, 1.0);
+    stage(decay, sustain);
+    stage(hold, sustain);
+    stage(release, 0.0);
+    points
+}
+
+fn draw_envelope(f: &mut ratatui::Frame, area: Rect, adsr: &Adsr, focused: bool) {
+    let data = envelope_points(adsr);
+    // an envelope with every stage at zero still needs a span to draw on
+    let end = data.last().map_or(0.0, |(t, _)| *t).max(1e-3);
+
+    let dataset = Dataset::default()
+        .marker(Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(if focused {
+            colors::accent()
+        } else {
+            colors::muted()
+        }))
+        .data(&data);
+
+    let chart = Chart::new(vec![dataset])
+        .style(Style::default().bg(colors::bg()))
+        .x_axis(Axis::default().bounds([0.0, end]))
+        .y_axis(Axis::default().bounds([0.0, 1.0]));
+
+    f.render_widget(chart, area);
 }
 
 fn draw_mod(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
     let focused = ui.pane == Pane::Mod;
 
     let border = if focused {
-        Style::default().fg(kdr::FG)
+        Style::default().fg(colors::fg())
     } else {
-        Style::default().fg(kdr::BORDER)
+        Style::default().fg(colors::border())
     };
 
     let active = if focused {
-        Style::default().fg(kdr::ORANGE).bold()
+        Style::default().fg(colors::accent()).bold()
     } else {
-        Style::default().fg(kdr::FG).bold()
+        Style::default().fg(colors::fg()).bold()
     };
 
     let inactive = if focused {
-        Style::default().fg(kdr::FG)
+        Style::default().fg(colors::fg())
     } else {
-        Style::default().fg(kdr::MUTED)
+        Style::default().fg(colors::muted())
     };
 
     let divider = if focused {
-        Style::default().fg(kdr::FG).bold()
+        Style::default().fg(colors::fg()).bold()
     } else {
-        Style::default().fg(kdr::MUTED).bold()
+        Style::default().fg(colors::muted()).bold()
     };
 
-    let title = match ui.mod_tab {
-        ModTab::Lfo => Line::from(vec![
-            Span::raw(" "),
-            Span::styled("lfo", active),
-            Span::styled(" ─ ", divider),
-            Span::styled("lowpass", inactive),
-            Span::raw(" "),
-        ]),
-        ModTab::LowPass => Line::from(vec![
-            Span::raw(" "),
-            Span::styled("lfo", inactive),
-            Span::styled(" ─ ", divider),
-            Span::styled("lowpass", active),
-            Span::raw(" "),
-        ]),
-    };
+    // names, " ─ " between them and a space on each end
+    let full_width = ModTab::ALL
+        .iter()
+        .map(|tab| tab.name().len() + 3)
+        .sum::<usize>()
+        - 1;
+
+    let mut spans = vec![Span::raw(" ")];
+    if full_width > u16_to_usize(area.width.saturating_sub(2)) {
+        // too narrow for every tab, show only the active one and its position
+        let idx = ModTab::ALL
+            .iter()
+            .position(|tab| *tab == ui.mod_tab)
+            .unwrap_or(0);
+        spans.push(Span::styled("‹ ", divider));
+        spans.push(Span::styled(ui.mod_tab.name(), active));
+        spans.push(Span::styled(" ›", divider));
+        spans.push(Span::styled(
+            format!(" {}/{}", idx + 1, ModTab::ALL.len()),
+            inactive,
+        ));
+    } else {
+        for (i, tab) in ModTab::ALL.iter().enumerate() {
+            if i > 0 {
+                spans.push(Span::styled(" ─ ", divider));
+            }
+            let style = if *tab == ui.mod_tab { active } else { inactive };
+            spans.push(Span::styled(tab.name(), style));
+        }
+    }
+    spans.push(Span::raw(" "));
+    let title = Line::from(spans);
 
     let block = Block::default()
         .borders(Borders::ALL)
         .title(title)
         .border_style(border)
-        .style(Style::default().bg(kdr::BG0));
+        .style(Style::default().bg(colors::bg()));
 
     let inner = block.inner(area);
     f.render_widget(block, area);
@@ -1014,19 +4749,120 @@ fn draw_mod(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
     }
 
     let mut lines = vec![Line::from("")];
+    lines.extend(mod_tab_lines(ui, ui.mod_tab, u16_to_usize(inner.width)));
 
-    match ui.mod_tab {
+    f.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .alignment(Alignment::Left)
+            .style(panel_style(focused)),
+        inner,
+    );
+}
+
+/// Parameter rows of `tab`, the selected one marked
+fn mod_tab_lines(ui: &UiState, tab: ModTab, width: usize) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+
+    match tab {
         ModTab::Lfo => {
             for (i, param) in LfoParam::ALL.iter().enumerate() {
                 let value = match param {
-                    LfoParam::Kind => ui.lfo.wave.name().to_string(),
-                    LfoParam::RateHz => format!("{:.2}", ui.lfo.rate_hz),
-                    LfoParam::Depth => format!("{:.2}", ui.lfo.depth),
+                    LfoParam::Kind => ui.lfo.wave.label(PULSE_DUTY),
+                    LfoParam::RateHz => format!("{:.2}", ui.lfo.rate_hz),
+                    LfoParam::Depth => format!("{:.2}", ui.lfo.depth),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(width, i == ui.lfo_param_idx, label, hint, &value));
+            }
+        }
+        ModTab::LfoRoute => {
+            for (i, param) in LfoRouteParam::ALL.iter().enumerate() {
+                let value = match param {
+                    LfoRouteParam::Target => ui.lfo_route.target.name().to_string(),
+                    LfoRouteParam::Kind => ui.lfo_route.wave.label(PULSE_DUTY),
+                    LfoRouteParam::RateHz => format!("{:.2}", ui.lfo_route.rate_hz),
+                    LfoRouteParam::Depth => format!("{:.2}", ui.lfo_route.depth),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(
+                    width,
+                    i == ui.lfo_route_param_idx,
+                    label,
+                    hint,
+                    &value,
+                ));
+            }
+        }
+        ModTab::Am => {
+            for (i, param) in AmParam::ALL.iter().enumerate() {
+                let value = match param {
+                    AmParam::CarrierHz => format!("{:.1}", ui.am.carrier_hz),
+                    AmParam::Depth => format!("{:.2}", ui.am.depth),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(width, i == ui.am_param_idx, label, hint, &value));
+            }
+        }
+        ModTab::RingMod => {
+            for (i, param) in RingModParam::ALL.iter().enumerate() {
+                let value = match param {
+                    RingModParam::FreqHz => format!("{:.1}", ui.ringmod.freq_hz),
+                    RingModParam::Mix => format!("{:.2}", ui.ringmod.mix),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(
+                    width,
+                    i == ui.ringmod_param_idx,
+                    label,
+                    hint,
+                    &value,
+                ));
+            }
+        }
+        ModTab::Bitcrush => {
+            for (i, param) in BitcrushParam::ALL.iter().enumerate() {
+                let value = match param {
+                    BitcrushParam::Bits => ui.bitcrush.bits.to_string(),
+                    BitcrushParam::Downsample => ui.bitcrush.downsample.to_string(),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(
+                    width,
+                    i == ui.bitcrush_param_idx,
+                    label,
+                    hint,
+                    &value,
+                ));
+            }
+        }
+        ModTab::SampleHold => {
+            for (i, param) in SampleHoldParam::ALL.iter().enumerate() {
+                let value = match param {
+                    SampleHoldParam::RateHz => format!("{:.2}", ui.sample_hold.rate_hz),
+                    SampleHoldParam::Depth => format!("{:.2}", ui.sample_hold.depth),
+                    SampleHoldParam::Target => ui.sample_hold.target.name().to_string(),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(
+                    width,
+                    i == ui.sample_hold_param_idx,
+                    label,
+                    hint,
+                    &value,
+                ));
+            }
+        }
+        ModTab::HighPass => {
+            for (i, param) in HighPassParam::ALL.iter().enumerate() {
+                let value = match param {
+                    HighPassParam::CutoffHz => format!("{:.0}", ui.highpass.cutoff_hz),
+                    HighPassParam::Q => format!("{:.2}", ui.highpass.q),
                 };
                 let (label, hint) = param.label_and_hint();
                 lines.push(kv_line(
-                    u16_to_usize(inner.width),
-                    i == ui.lfo_param_idx,
+                    width,
+                    i == ui.highpass_param_idx,
                     label,
                     hint,
                     &value,
@@ -1040,7 +4876,7 @@ fn draw_mod(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
                 };
                 let (label, hint) = param.label_and_hint();
                 lines.push(kv_line(
-                    u16_to_usize(inner.width),
+                    width,
                     i == ui.lowpass_param_idx,
                     label,
                     hint,
@@ -1048,15 +4884,193 @@ fn draw_mod(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
                 ));
             }
         }
+        ModTab::Layers => {
+            let layer = ui.layers.get(ui.layer_idx);
+
+            for (i, param) in LayerParam::ALL.iter().enumerate() {
+                let value = match (param, layer) {
+                    (_, None) => "-".to_string(),
+                    (LayerParam::Layer, Some(_)) => {
+                        format!("{}/{}", ui.layer_idx + 1, ui.layers.len())
+                    }
+                    (LayerParam::Wave, Some(layer)) => layer.wave.label(ui.pulse_duty),
+                    (LayerParam::Detune, Some(layer)) => format!("{:+.0}", layer.cents),
+                    (LayerParam::Level, Some(layer)) => format!("{:.2}", layer.level),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(width, i == ui.layer_param_idx, label, hint, &value));
+            }
+        }
+        ModTab::Sub => {
+            for (i, param) in SubParam::ALL.iter().enumerate() {
+                let value = match param {
+                    SubParam::Wave => ui.sub.wave.name().to_string(),
+                    SubParam::Level => format!("{:.2}", ui.sub.level),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(width, i == ui.sub_param_idx, label, hint, &value));
+            }
+        }
+        ModTab::Fm => {
+            for (i, param) in FmParam::ALL.iter().enumerate() {
+                let value = match param {
+                    FmParam::Modulator => ui.fm.modulator.name().to_string(),
+                    FmParam::Ratio => format!("{:.2}", ui.fm.ratio),
+                    FmParam::Index => format!("{:.2}", ui.fm.index),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(width, i == ui.fm_param_idx, label, hint, &value));
+            }
+        }
+        ModTab::Unison => {
+            for (i, param) in UnisonParam::ALL.iter().enumerate() {
+                let value = match param {
+                    UnisonParam::Voices => ui.unison.voices.to_string(),
+                    UnisonParam::Detune => format!("{:.0}", ui.unison.detune_cents),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(
+                    width,
+                    i == ui.unison_param_idx,
+                    label,
+                    hint,
+                    &value,
+                ));
+            }
+        }
+        ModTab::Arp => {
+            for (i, param) in ArpParam::ALL.iter().enumerate() {
+                let value = match param {
+                    ArpParam::On => if ui.arp.enabled { "on" } else { "off" }.to_string(),
+                    ArpParam::Bpm => format!("{:.0}", ui.tempo.bpm),
+                    ArpParam::Pattern => ui.arp.pattern.name().to_string(),
+                    ArpParam::Length => format!("{:.2}", ui.arp.length),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(width, i == ui.arp_param_idx, label, hint, &value));
+            }
+        }
+        ModTab::Seq => {
+            for (i, param) in SeqParam::ALL.iter().enumerate() {
+                let value = match param {
+                    SeqParam::Play => if ui.sequence.playing { "on" } else { "off" }.to_string(),
+                    SeqParam::Bpm => format!("{:.0}", ui.tempo.bpm),
+                    SeqParam::Step => format!("{}/{}", ui.seq_cursor + 1, ui.sequence.steps.len()),
+                    SeqParam::Note => ui.sequence.steps[ui.seq_cursor]
+                        .map_or_else(|| "rest".to_string(), |key| key.to_string()),
+                    SeqParam::Clear => String::new(),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(width, i == ui.seq_param_idx, label, hint, &value));
+            }
+        }
+        ModTab::Glide => {
+            lines.push(kv_line(
+                width,
+                true,
+                "Time",
+                "(s)",
+                &format!("{:.2}", ui.glide_s),
+            ));
+        }
+        ModTab::Scale => {
+            for (i, param) in ScaleParam::ALL.iter().enumerate() {
+                let value = match param {
+                    ScaleParam::Scale => ui.quantizer.scale.name(),
+                    ScaleParam::Root => ui.quantizer.root.name(),
+                    ScaleParam::Tuning => &ui.tuning.label(),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(width, i == ui.scale_param_idx, label, hint, value));
+            }
+        }
+        ModTab::Chord => {
+            lines.push(kv_line(width, true, "Type", "", ui.chord.name()));
+        }
+        ModTab::Velocity => {
+            for (i, param) in VelocityParam::ALL.iter().enumerate() {
+                let value = match param {
+                    VelocityParam::Curve => ui.velocity.curve.name().to_string(),
+                    VelocityParam::Keys => ui.velocity.keys.to_string(),
+                    VelocityParam::Accent => ui.velocity.accent.to_string(),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(
+                    width,
+                    i == ui.velocity_param_idx,
+                    label,
+                    hint,
+                    &value,
+                ));
+            }
+        }
+        ModTab::Bend => {
+            for (i, param) in BendParam::ALL.iter().enumerate() {
+                let value = match param {
+                    BendParam::Range => format!("{:.0}", ui.bend_range_st),
+                    BendParam::Return => ui.bend_return.name().to_string(),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(width, i == ui.bend_param_idx, label, hint, &value));
+            }
+        }
+        ModTab::Pan => {
+            for (i, param) in PanParam::ALL.iter().enumerate() {
+                let value = match param {
+                    PanParam::Position => format!("{:+.1}", ui.pan.position),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(width, i == ui.pan_param_idx, label, hint, &value));
+            }
+        }
+        ModTab::Delay => {
+            for (i, param) in DelayParam::ALL.iter().enumerate() {
+                let value = match param {
+                    DelayParam::TimeMs => format!("{:.0}", ui.delay.time_ms),
+                    DelayParam::Feedback => format!("{:.2}", ui.delay.feedback),
+                    DelayParam::Mix => format!("{:.2}", ui.delay.mix),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(width, i == ui.delay_param_idx, label, hint, &value));
+            }
+        }
+        ModTab::Reverb => {
+            for (i, param) in ReverbParam::ALL.iter().enumerate() {
+                let value = match param {
+                    ReverbParam::Room => format!("{:.2}", ui.reverb.room),
+                    ReverbParam::Damping => format!("{:.2}", ui.reverb.damping),
+                    ReverbParam::Mix => format!("{:.2}", ui.reverb.mix),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(
+                    width,
+                    i == ui.reverb_param_idx,
+                    label,
+                    hint,
+                    &value,
+                ));
+            }
+        }
+        ModTab::NoiseGate => {
+            for (i, param) in NoiseGateParam::ALL.iter().enumerate() {
+                let value = match param {
+                    NoiseGateParam::Threshold => format!("{:.0}", ui.noise_gate.threshold_db),
+                    NoiseGateParam::Attack => format!("{:.1}", ui.noise_gate.attack_ms),
+                    NoiseGateParam::Release => format!("{:.0}", ui.noise_gate.release_ms),
+                };
+                let (label, hint) = param.label_and_hint();
+                lines.push(kv_line(
+                    width,
+                    i == ui.noise_gate_param_idx,
+                    label,
+                    hint,
+                    &value,
+                ));
+            }
+        }
     }
 
-    f.render_widget(
-        Paragraph::new(lines)
-            .wrap(Wrap { trim: false })
-            .alignment(Alignment::Left)
-            .style(panel_style(focused)),
-        inner,
-    );
+    lines
 }
 fn draw_presets_popup(f: &mut ratatui::Frame, ui: &UiState) {
     let area = centered_rect(f.area(), 82, 72);
@@ -1066,10 +5080,10 @@ fn draw_presets_popup(f: &mut ratatui::Frame, ui: &UiState) {
         .borders(Borders::ALL)
         .title(Span::styled(
             " Presets ",
-            Style::default().fg(kdr::ORANGE).bold(),
+            Style::default().fg(colors::accent()).bold(),
         ))
-        .border_style(Style::default().fg(kdr::FG))
-        .style(Style::default().bg(kdr::BG0));
+        .border_style(Style::default().fg(colors::fg()))
+        .style(Style::default().bg(colors::bg()));
 
     let inner = outer.inner(area);
     f.render_widget(outer, area);
@@ -1098,37 +5112,34 @@ fn draw_preset_table(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
 
     let block = Block::default()
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(kdr::BORDER))
-        .style(Style::default().bg(kdr::BG0));
+        .border_style(Style::default().fg(colors::border()))
+        .style(Style::default().bg(colors::bg()));
 
     let inner = block.inner(area);
     f.render_widget(block, area);
 
     let header = Row::new(vec![
         Line::from(vec![
-            Span::styled("  ", Style::default().fg(kdr::MUTED)),
-            Span::styled(
-                category_name,
-                Style::default().fg(kdr::ORANGE).bold(),
-            ),
+            Span::styled("  ", Style::default().fg(colors::muted())),
+            Span::styled(category_name, Style::default().fg(colors::accent()).bold()),
         ]),
         Line::from(Span::styled(
             "Wave",
-            Style::default().fg(kdr::ORANGE).bold(),
+            Style::default().fg(colors::accent()).bold(),
         )),
         Line::from(Span::styled(
             "Oct",
-            Style::default().fg(kdr::ORANGE).bold(),
+            Style::default().fg(colors::accent()).bold(),
         )),
         Line::from(Span::styled(
             "Cutoff",
-            Style::default().fg(kdr::ORANGE).bold(),
+            Style::default().fg(colors::accent()).bold(),
         )),
     ]);
 
     let rows: Vec<Row> = if category_presets.is_empty() {
         vec![Row::new(vec![
-            Line::from(Span::styled("—", Style::default().fg(kdr::MUTED))),
+            Line::from(Span::styled("—", Style::default().fg(colors::muted()))),
             Line::from(""),
             Line::from(""),
             Line::from(""),
@@ -1142,32 +5153,32 @@ fn draw_preset_table(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
 
                 let preset_cell = if selected {
                     Line::from(vec![
-                        Span::styled("› ", Style::default().fg(kdr::ORANGE).bold()),
+                        Span::styled("› ", Style::default().fg(colors::accent()).bold()),
                         Span::styled(
                             preset.name.clone(),
-                            Style::default().fg(kdr::FG).bold(),
+                            Style::default().fg(colors::fg()).bold(),
                         ),
                     ])
                 } else {
                     Line::from(vec![
-                        Span::styled("  ", Style::default().fg(kdr::MUTED)),
-                        Span::styled(
-                            preset.name.clone(),
-                            Style::default().fg(kdr::FG),
-                        ),
+                        Span::styled("  ", Style::default().fg(colors::muted())),
+                        Span::styled(preset.name.clone(), Style::default().fg(colors::fg())),
                     ])
                 };
 
                 let value_style = if selected {
-                    Style::default().fg(kdr::FG).bold()
+                    Style::default().fg(colors::fg()).bold()
                 } else {
-                    Style::default().fg(kdr::FG)
+                    Style::default().fg(colors::fg())
                 };
 
                 Row::new(vec![
                     preset_cell,
-                    Line::from(Span::styled(preset.wave.name().to_string(), value_style)),
-                    Line::from(Span::styled(format!("{:+}", preset.octave_shift), value_style)),
+                    Line::from(Span::styled(preset.wave.label(PULSE_DUTY), value_style)),
+                    Line::from(Span::styled(
+                        format!("{:+}", preset.octave_shift),
+                        value_style,
+                    )),
                     Line::from(Span::styled(format!("{:.0}", preset.cutoff), value_style)),
                 ])
             })
@@ -1185,7 +5196,7 @@ fn draw_preset_table(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
     )
     .header(header)
     .column_spacing(1)
-    .style(Style::default().bg(kdr::BG0))
+    .style(Style::default().bg(colors::bg()))
     .block(Block::default());
 
     f.render_widget(table, inner);
@@ -1200,12 +5211,15 @@ fn draw_preset_tabs(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
             let tab = if selected {
                 Span::styled(
                     format!(" {name} "),
-                    Style::default().fg(kdr::BG0).bg(kdr::ORANGE).bold(),
+                    Style::default()
+                        .fg(colors::bg())
+                        .bg(colors::accent())
+                        .bold(),
                 )
             } else {
                 Span::styled(
                     format!(" {name} "),
-                    Style::default().fg(kdr::MUTED).bg(kdr::BG0),
+                    Style::default().fg(colors::muted()).bg(colors::bg()),
                 )
             };
 
@@ -1217,29 +5231,29 @@ fn draw_preset_tabs(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
     f.render_widget(
         Paragraph::new(Line::from(spans))
             .alignment(Alignment::Center)
-            .style(Style::default().bg(kdr::BG0)),
+            .style(Style::default().bg(colors::bg())),
         area,
     );
 }
 
 fn draw_preset_footer(f: &mut ratatui::Frame, area: Rect) {
     let line = Line::from(vec![
-        Span::styled("Tab", Style::default().fg(kdr::ORANGE).bold()),
-        Span::styled(" next section  ", Style::default().fg(kdr::MUTED)),
-        Span::styled("←/→", Style::default().fg(kdr::ORANGE).bold()),
-        Span::styled(" section  ", Style::default().fg(kdr::MUTED)),
-        Span::styled("↑/↓", Style::default().fg(kdr::ORANGE).bold()),
-        Span::styled(" preset  ", Style::default().fg(kdr::MUTED)),
-        Span::styled("Enter", Style::default().fg(kdr::ORANGE).bold()),
-        Span::styled(" load  ", Style::default().fg(kdr::MUTED)),
-        Span::styled("Esc", Style::default().fg(kdr::ORANGE).bold()),
-        Span::styled(" close", Style::default().fg(kdr::MUTED)),
+        Span::styled("Tab", Style::default().fg(colors::accent()).bold()),
+        Span::styled(" next section  ", Style::default().fg(colors::muted())),
+        Span::styled("←/→", Style::default().fg(colors::accent()).bold()),
+        Span::styled(" section  ", Style::default().fg(colors::muted())),
+        Span::styled("↑/↓", Style::default().fg(colors::accent()).bold()),
+        Span::styled(" preset  ", Style::default().fg(colors::muted())),
+        Span::styled("Enter", Style::default().fg(colors::accent()).bold()),
+        Span::styled(" load  ", Style::default().fg(colors::muted())),
+        Span::styled("Esc", Style::default().fg(colors::accent()).bold()),
+        Span::styled(" close", Style::default().fg(colors::muted())),
     ]);
 
     f.render_widget(
         Paragraph::new(line)
             .alignment(Alignment::Center)
-            .style(Style::default().bg(kdr::BG0)),
+            .style(Style::default().bg(colors::bg())),
         area,
     );
 }
@@ -1310,43 +5324,311 @@ const WHITE_KEYS: [WhiteKey; 11] = [
     },
 ];
 
-const BLACK_KEYS: [BlackKey; 7] = [
-    BlackKey {
-        code: Keycode::W,
-        label: "w",
-        gap_after: 0,
-    },
-    BlackKey {
-        code: Keycode::E,
-        label: "e",
-        gap_after: 1,
-    },
-    BlackKey {
-        code: Keycode::T,
-        label: "t",
-        gap_after: 3,
-    },
-    BlackKey {
-        code: Keycode::Y,
-        label: "y",
-        gap_after: 4,
-    },
-    BlackKey {
-        code: Keycode::U,
-        label: "u",
-        gap_after: 5,
-    },
-    BlackKey {
-        code: Keycode::O,
-        label: "o",
-        gap_after: 7,
-    },
-    BlackKey {
-        code: Keycode::P,
-        label: "p",
-        gap_after: 8,
-    },
-];
+const BLACK_KEYS: [BlackKey; 7] = [
+    BlackKey {
+        code: Keycode::W,
+        label: "w",
+        gap_after: 0,
+    },
+    BlackKey {
+        code: Keycode::E,
+        label: "e",
+        gap_after: 1,
+    },
+    BlackKey {
+        code: Keycode::T,
+        label: "t",
+        gap_after: 3,
+    },
+    BlackKey {
+        code: Keycode::Y,
+        label: "y",
+        gap_after: 4,
+    },
+    BlackKey {
+        code: Keycode::U,
+        label: "u",
+        gap_after: 5,
+    },
+    BlackKey {
+        code: Keycode::O,
+        label: "o",
+        gap_after: 7,
+    },
+    BlackKey {
+        code: Keycode::P,
+        label: "p",
+        gap_after: 8,
+    },
+];
+
+/// One cell per step, the playing step is lit and the one being edited is
+/// underlined while the seq tab is open
+fn draw_sequencer(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
+    let editing = ui.pane == Pane::Mod && ui.mod_tab == ModTab::Seq;
+    let block = panel_block("seq", editing);
+
+    let mut spans = Vec::with_capacity(ui.sequence.steps.len());
+    for (i, step) in ui.sequence.steps.iter().enumerate() {
+        let name = step.map_or_else(|| "·".to_string(), |key| key.to_string());
+        let mut style = if ui.seq_step == Some(i) {
+            Style::default()
+                .fg(colors::bg())
+                .bg(colors::highlight())
+                .bold()
+        } else if step.is_some() {
+            Style::default().fg(colors::fg())
+        } else {
+            Style::default().fg(colors::muted())
+        };
+        if editing && i == ui.seq_cursor {
+            style = style.underlined();
+        }
+
+        spans.push(Span::styled(format!(" {name:<4}"), style));
+        // a gap every beat keeps the grid readable
+        if (i + 1) % 4 == 0 {
+            spans.push(Span::raw(" "));
+        }
+    }
+
+    f.render_widget(
+        Paragraph::new(Line::from(spans))
+            .block(block)
+            .style(panel_style(editing)),
+        area,
+    );
+}
+
+/// The chain ahead of the envelope on the left, scrolled to keep the
+/// selected slot in view, and that effect's parameters on the right
+fn draw_effects(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
+    let focused = ui.pane == Pane::Effects;
+    let mut block = panel_block(
+        if ui.edit_split {
+            "effects ─ top row"
+        } else {
+            "effects"
+        },
+        focused,
+    );
+    if let Some(fx) = ui.fx_pick() {
+        block = block.title_bottom(
+            Line::from(Span::styled(
+                format!(" ins {} ", fx.name()),
+                Style::default().fg(colors::muted()),
+            ))
+            .right_aligned(),
+        );
+    }
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    if inner.width == 0 || inner.height == 0 {
+        return;
+    }
+
+    let cols = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(FX_LIST_W), Constraint::Min(0)])
+        .split(inner);
+
+    let rows = u16_to_usize(inner.height);
+    let first = ui.fx_slot.saturating_sub(rows.saturating_sub(1));
+    let slots: Vec<Line> = if ui.effects.is_empty() {
+        vec![Line::styled(
+            "  empty",
+            Style::default().fg(colors::muted()),
+        )]
+    } else {
+        ui.effects
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(rows)
+            .map(|(i, fx)| simple_select_line(i == ui.fx_slot, &format!("{} {}", i + 1, fx.name())))
+            .collect()
+    };
+
+    let width = u16_to_usize(cols[1].width);
+    let params = match ui.selected_fx() {
+        Some(Fx::Gain) => vec![kv_line(
+            width,
+            true,
+            "Amount",
+            "(0..2)",
+            &format!("{:.2}", ui.gain.amount),
+        )],
+        Some(fx) => fx_tab(fx).map_or_else(Vec::new, |tab| mod_tab_lines(ui, tab, width)),
+        None => Vec::new(),
+    };
+
+    f.render_widget(Paragraph::new(slots).style(panel_style(focused)), cols[0]);
+    f.render_widget(Paragraph::new(params).style(panel_style(focused)), cols[1]);
+}
+
+/// Held notes and the effect chain, cut short with a count of what did not fit
+fn draw_playing(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
+    let dim = Style::default().fg(colors::muted());
+    let strong = Style::default().fg(colors::fg()).bold();
+    let width = u16_to_usize(area.width.saturating_sub(2));
+
+    let names: Vec<String> = ui.active_keys.iter().map(Key::to_string).collect();
+    let notes: Vec<&str> = names.iter().map(String::as_str).collect();
+    let notes = if notes.is_empty() {
+        "-".to_string()
+    } else {
+        fit_list(&notes, ", ", width.saturating_sub(6))
+    };
+
+    let lines = vec![
+        Line::from(vec![
+            Span::styled("Notes ", dim),
+            Span::styled(notes, strong),
+        ]),
+        Line::from(vec![
+            Span::styled("FX    ", dim),
+            Span::styled(
+                fit_list(&ui.fx_chain, " › ", width.saturating_sub(6)),
+                strong,
+            ),
+        ]),
+    ];
+
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(
+                panel_block("playing", false)
+                    .title_top(volume_bar(ui).right_aligned())
+                    .title_bottom(note_readout(ui)),
+            )
+            .style(panel_style(false)),
+        area,
+    );
+}
+
+/// Pitch `key` sounds at in `tuning`, bent by `bend`, and its cents from
+/// the equal-tempered pitch at the same A4
+#[must_use]
+fn voice_pitch(key: Key, tuning: &Tuning, root: Note, a4_hz: f32, bend: f32) -> (f32, f32) {
+    let hz = tuning.frequency(key, root, a4_hz) * bend;
+    (hz, 1200.0 * (hz / key.frequency_at(a4_hz)).log2())
+}
+
+/// One line per sounding note in the scope's top left corner, as many as fit
+fn draw_scope_notes(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
+    let inner = area.inner(Margin::new(1, 1));
+    let bend = bend_ratio(ui.bend, ui.bend_range_st);
+    let strong = Style::default().fg(colors::fg()).bold();
+    let off = Style::default().fg(colors::highlight()).bold();
+
+    let lines: Vec<Line> = ui
+        .active_keys
+        .iter()
+        .take(usize::from(inner.height))
+        .map(|&key| {
+            let (hz, cents) = voice_pitch(key, &ui.tuning, ui.quantizer.root, ui.a4_hz, bend);
+            Line::from(vec![
+                Span::styled(format!("{:<4}", key.to_string()), strong),
+                Span::styled(format!("{hz:>8.2} Hz"), strong),
+                Span::styled(format!(" {cents:>+6.1}¢"), off),
+            ])
+        })
+        .collect();
+    if lines.is_empty() {
+        return;
+    }
+
+    let width = lines.iter().map(Line::width).max().unwrap_or(0);
+    let area = Rect {
+        width: u16::try_from(width).unwrap_or(u16::MAX).min(inner.width),
+        height: u16::try_from(lines.len()).unwrap_or(u16::MAX),
+        ..inner
+    };
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).style(panel_style(false)), area);
+}
+
+/// Newest note and the pitch it sounds at, with how far tuning and bend
+/// pull it from 440 Hz equal temperament. Fixed widths keep it from jumping
+/// about as notes change
+fn note_readout(ui: &UiState) -> Line<'static> {
+    let Some(key) = ui.last_note else {
+        return Line::default();
+    };
+
+    let hz = ui.tuning.frequency(key, ui.quantizer.root, ui.a4_hz)
+        * bend_ratio(ui.bend, ui.bend_range_st);
+    let cents = key.cents_off(hz);
+    let strong = Style::default().fg(colors::fg()).bold();
+
+    let mut spans = vec![
+        Span::styled(format!(" {:<4}", key.to_string()), strong),
+        Span::styled(format!("{hz:>8.2} Hz"), strong),
+    ];
+    if cents.abs() >= 0.05 {
+        spans.push(Span::styled(
+            format!(" {cents:>+7.1}¢"),
+            Style::default().fg(colors::highlight()).bold(),
+        ));
+    }
+    spans.push(Span::raw(" "));
+
+    Line::from(spans)
+}
+
+/// Ten cells filled in steps of 0.1, dimmed while muted
+fn volume_bar(ui: &UiState) -> Line<'static> {
+    let filled = (ui.volume.clamp(0.0, 1.0) * 10.0).round() as usize;
+    let style = if ui.muted {
+        Style::default().fg(colors::muted())
+    } else {
+        Style::default().fg(colors::highlight())
+    };
+
+    Line::from(vec![
+        Span::styled(" vol ", Style::default().fg(colors::muted())),
+        Span::styled("▮".repeat(filled), style),
+        Span::styled(
+            "▯".repeat(10 - filled),
+            Style::default().fg(colors::border()),
+        ),
+        Span::styled(
+            if ui.muted { " muted " } else { " " },
+            Style::default().fg(colors::accent()).bold(),
+        ),
+    ])
+}
+
+/// Joins as many items as fit in `width` columns, then " +n" for the rest
+fn fit_list(items: &[&str], sep: &str, width: usize) -> String {
+    let mut out = String::new();
+    let mut used = 0;
+
+    for (i, item) in items.iter().enumerate() {
+        let sep_w = if i == 0 { 0 } else { sep.chars().count() };
+        let left = items.len() - i - 1;
+        let more_w = if left == 0 {
+            0
+        } else {
+            format!(" +{left}").len()
+        };
+
+        if used + sep_w + item.chars().count() + more_w > width {
+            out.push_str(&format!(" +{}", items.len() - i));
+            break;
+        }
+
+        if i > 0 {
+            out.push_str(sep);
+        }
+        out.push_str(item);
+        used += sep_w + item.chars().count();
+    }
+
+    out
+}
 
 fn draw_keyboard(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
     let bounds = area;
@@ -1365,7 +5647,7 @@ fn draw_keyboard(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
         bounds.y,
         bounds.width,
         bounds.height,
-        Style::default().bg(kdr::BG0),
+        Style::default().bg(colors::bg()),
     );
 
     draw_keyboard_white_keys(buf, bounds, ui, focused, &layout);
@@ -1402,9 +5684,13 @@ fn draw_keyboard_white_keys(
     focused: bool,
     layout: &KeyboardLayout,
 ) {
-    let white_bg = if focused { kdr::FG } else { kdr::BORDER };
-    let white_fill = Style::default().bg(white_bg).fg(kdr::BG0);
-    let orange_fill = Style::default().bg(kdr::ORANGE).fg(kdr::BG0);
+    let white_bg = if focused {
+        colors::fg()
+    } else {
+        colors::border()
+    };
+    let white_fill = Style::default().bg(white_bg).fg(colors::bg());
+    let orange_fill = Style::default().bg(colors::accent()).fg(colors::bg());
 
     for (i, key) in WHITE_KEYS.iter().enumerate() {
         let x = usize_to_u16(layout.x0 + i * layout.white_w);
@@ -1433,9 +5719,13 @@ fn draw_keyboard_separators(
     focused: bool,
     layout: &KeyboardLayout,
 ) {
-    let white_bg = if focused { kdr::FG } else { kdr::BORDER };
-    let orange_fill = Style::default().bg(kdr::ORANGE).fg(kdr::BG0);
-    let sep_style = Style::default().fg(kdr::BG0).bg(white_bg);
+    let white_bg = if focused {
+        colors::fg()
+    } else {
+        colors::border()
+    };
+    let orange_fill = Style::default().bg(colors::accent()).fg(colors::bg());
+    let sep_style = Style::default().fg(colors::bg()).bg(white_bg);
 
     for i in 0..layout.white_count.saturating_sub(1) {
         let x = usize_to_u16(layout.x0 + (i + 1) * layout.white_w - 1);
@@ -1459,7 +5749,11 @@ fn draw_keyboard_white_labels(
     focused: bool,
     layout: &KeyboardLayout,
 ) {
-    let white_bg = if focused { kdr::FG } else { kdr::BORDER };
+    let white_bg = if focused {
+        colors::fg()
+    } else {
+        colors::border()
+    };
     let label_y = bounds.y + bounds.height.saturating_sub(1);
     let half_white = usize_to_u16(layout.white_w) / 2;
 
@@ -1470,9 +5764,12 @@ fn draw_keyboard_white_labels(
         if lx >= bounds.x && lx < bounds.x + bounds.width {
             let pressed = ui.held_keys.contains(&key.code);
             let style = if pressed {
-                Style::default().fg(kdr::BG0).bg(kdr::ORANGE).bold()
+                Style::default()
+                    .fg(colors::bg())
+                    .bg(colors::accent())
+                    .bold()
             } else {
-                Style::default().fg(kdr::BG0).bg(white_bg)
+                Style::default().fg(colors::bg()).bg(white_bg)
             };
 
             buf[(lx, label_y)]
@@ -1488,8 +5785,8 @@ fn draw_keyboard_black_keys(
     ui: &UiState,
     layout: &KeyboardLayout,
 ) {
-    let black_fill = Style::default().bg(kdr::BG0).fg(kdr::FG);
-    let black_pressed = Style::default().bg(kdr::ORANGE).fg(kdr::BG0);
+    let black_fill = Style::default().bg(colors::bg()).fg(colors::fg());
+    let black_pressed = Style::default().bg(colors::accent()).fg(colors::bg());
 
     let w = usize_to_u16(layout.black_w);
     let h = usize_to_u16(layout.black_h);
@@ -1520,9 +5817,12 @@ fn draw_keyboard_black_keys(
             && ly < bounds.y + bounds.height
         {
             let style = if pressed {
-                Style::default().fg(kdr::BG0).bg(kdr::ORANGE).bold()
+                Style::default()
+                    .fg(colors::bg())
+                    .bg(colors::accent())
+                    .bold()
             } else {
-                Style::default().fg(kdr::FG).bg(kdr::BG0)
+                Style::default().fg(colors::fg()).bg(colors::bg())
             };
 
             buf[(lx, ly)]
@@ -1532,6 +5832,115 @@ fn draw_keyboard_black_keys(
     }
 }
 
+/// Sounding notes on `PIANO_OCTAVES` of piano, from an octave under the
+/// computer keyboard's range, each key labelled with the computer key that
+/// plays it. Too narrow a terminal drops up to that lower octave, then keys
+/// off the top
+fn draw_piano(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
+    let base = Key::new(Note::C, KEYBOARD_BASE_OCTAVE + ui.octave - 1);
+    let whites: Vec<Key> = (0..=PIANO_OCTAVES * SEMITONES_PER_OCTAVE)
+        .map(|semitones| base.transpose(semitones))
+        .filter(|key| !key.note.is_black())
+        .collect();
+
+    let width = u16_to_usize(area.width);
+    let white_w = if whites.len() * 4 <= width { 4 } else { 3 };
+    let shown = (width / white_w).min(whites.len());
+    let skip = (whites.len() - shown).min(7);
+    let whites = &whites[skip..skip + shown];
+    let x0 = u16_to_usize(area.x) + (width - shown * white_w) / 2;
+
+    let shift = ui.octave * SEMITONES_PER_OCTAVE + ui.transpose;
+    let labels: HashMap<Key, char> = mapped_keys()
+        .into_iter()
+        .filter_map(|(code, key)| Some((key.transpose(shift), keycode_char(code)?)))
+        .collect();
+
+    let white_bg = if ui.pane == Pane::Keyboard {
+        colors::fg()
+    } else {
+        colors::border()
+    };
+    let style_for = |key: &Key| {
+        if ui.active_keys.contains(key) {
+            Style::default()
+                .bg(colors::accent())
+                .fg(colors::bg())
+                .bold()
+        } else if key.note.is_black() {
+            Style::default().bg(colors::bg()).fg(colors::fg())
+        } else {
+            Style::default().bg(white_bg).fg(colors::bg())
+        }
+    };
+
+    let buf = f.buffer_mut();
+    fill_rect(
+        buf,
+        area,
+        area.x,
+        area.y,
+        area.width,
+        area.height,
+        Style::default().bg(colors::bg()),
+    );
+
+    let label_y = area.y + area.height.saturating_sub(1);
+    for (i, key) in whites.iter().enumerate() {
+        let x = usize_to_u16(x0 + i * white_w);
+        let style = style_for(key);
+
+        fill_rect(
+            buf,
+            area,
+            x,
+            area.y,
+            usize_to_u16(white_w),
+            area.height,
+            style,
+        );
+        vline(
+            buf,
+            area,
+            x + usize_to_u16(white_w) - 1,
+            area.y,
+            area.height,
+            '│',
+            style.fg(colors::bg()),
+        );
+
+        let label = labels.get(key).map_or_else(
+            || match key.note {
+                Note::C => key.to_string(),
+                _ => String::new(),
+            },
+            char::to_string,
+        );
+        buf.set_string(x, label_y, label, style);
+    }
+
+    let black_w = usize_to_u16(white_w - 1);
+    let black_h = area.height.saturating_sub(1).max(1);
+    for (i, white) in whites.iter().enumerate().take(shown.saturating_sub(1)) {
+        let key = white.transpose(1);
+        if !key.note.is_black() {
+            continue;
+        }
+
+        // centred on the gap between the two white keys
+        let gap = usize_to_u16(x0 + (i + 1) * white_w - 1);
+        let x = gap - (black_w - 1) / 2;
+        let style = style_for(&key);
+
+        fill_rect(buf, area, x, area.y, black_w, black_h, style);
+        if let Some(label) = labels.get(&key) {
+            buf[(x + black_w / 2, area.y + black_h - 1)]
+                .set_char(*label)
+                .set_style(style);
+        }
+    }
+}
+
 fn draw_help(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
     let focus_name = if ui.show_presets {
         "Presets"
@@ -1541,15 +5950,37 @@ fn draw_help(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
             Pane::Adsr => "ADSR",
             Pane::Mod => match ui.mod_tab {
                 ModTab::Lfo => "LFO",
+                ModTab::LfoRoute => "LFO Route",
+                ModTab::Am => "AM",
+                ModTab::RingMod => "Ring Mod",
+                ModTab::Bitcrush => "Bitcrush",
+                ModTab::SampleHold => "S&H",
+                ModTab::HighPass => "HighPass",
                 ModTab::LowPass => "LowPass",
+                ModTab::Delay => "Delay",
+                ModTab::Reverb => "Reverb",
+                ModTab::NoiseGate => "Gate",
+                ModTab::Pan => "Pan",
+                ModTab::Layers => "Layers",
+                ModTab::Sub => "Sub",
+                ModTab::Fm => "FM",
+                ModTab::Unison => "Unison",
+                ModTab::Glide => "Glide",
+                ModTab::Bend => "Bend",
+                ModTab::Scale => "Scale",
+                ModTab::Chord => "Chord",
+                ModTab::Velocity => "Velocity",
+                ModTab::Arp => "Arp",
+                ModTab::Seq => "Seq",
             },
+            Pane::Effects => "Effects",
             Pane::Keyboard => "Keyboard",
         }
     };
 
-    let key_style = Style::default().fg(kdr::ORANGE).bold();
-    let dim = Style::default().fg(kdr::MUTED);
-    let strong = Style::default().fg(kdr::FG).bold();
+    let key_style = Style::default().fg(colors::accent()).bold();
+    let dim = Style::default().fg(colors::muted());
+    let strong = Style::default().fg(colors::fg()).bold();
 
     let line1 = if ui.show_presets {
         Line::from(vec![
@@ -1567,7 +5998,25 @@ fn draw_help(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
             Span::styled(" quit", dim),
         ])
     } else {
-        Line::from(vec![
+        let pane_keys: &[(&'static str, &'static str)] = match ui.pane {
+            Pane::Waveforms if ui.split => &[
+                ("+/-", " layer  "),
+                ("</>", " crossfade  "),
+                ("Enter", " split row  "),
+            ],
+            Pane::Waveforms => &[("+/-", " layer  "), ("</>", " crossfade  ")],
+            Pane::Mod => &[("Enter", " next tab  ")],
+            Pane::Effects => &[
+                ("Enter", " param  "),
+                ("Shift+↑/↓", " move  "),
+                ("PgUp/PgDn", " pick  "),
+                ("Ins/Del", " add/remove  "),
+            ],
+            Pane::Keyboard => &[("↑/↓", " tuning  ")],
+            Pane::Adsr => &[],
+        };
+
+        let mut spans = vec![
             Span::styled("Space", key_style),
             Span::styled(" presets  ", dim),
             Span::styled("Tab", key_style),
@@ -1576,29 +6025,123 @@ fn draw_help(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
             Span::styled(" select  ", dim),
             Span::styled("←/→", key_style),
             Span::styled(" change  ", dim),
-            Span::styled("Enter", key_style),
-            Span::styled(" toggle mod tab  ", dim),
+        ];
+        for (key, label) in pane_keys {
+            spans.push(Span::styled(*key, key_style));
+            spans.push(Span::styled(*label, dim));
+        }
+        spans.extend([
+            Span::styled("i", key_style),
+            Span::styled(" input  ", dim),
+            Span::styled("z", key_style),
+            Span::styled(" split  ", dim),
+            Span::styled("c", key_style),
+            Span::styled(" copy scope  ", dim),
+            Span::styled("v", key_style),
+            Span::styled(" scope  ", dim),
+            Span::styled("x", key_style),
+            Span::styled(" trigger  ", dim),
+            Span::styled("r", key_style),
+            Span::styled(" record  ", dim),
+            Span::styled("m", key_style),
+            Span::styled(" mono  ", dim),
+            Span::styled("9/0", key_style),
+            Span::styled(" volume  ", dim),
+            Span::styled("M", key_style),
+            Span::styled(" mute  ", dim),
+            Span::styled("n", key_style),
+            Span::styled(" sustain  ", dim),
+            Span::styled("/", key_style),
+            Span::styled(" arp  ", dim),
+            Span::styled("\\", key_style),
+            Span::styled(" sequencer  ", dim),
+            Span::styled("`", key_style),
+            Span::styled(" tap tempo  ", dim),
+            Span::styled("[/]", key_style),
+            Span::styled(" octave  ", dim),
+            Span::styled("{/}", key_style),
+            Span::styled(" bend  ", dim),
+            Span::styled(",/.", key_style),
+            Span::styled(" transpose  ", dim),
+            Span::styled("Ctrl+S/O", key_style),
+            Span::styled(" save/load patch  ", dim),
+            Span::styled("Ctrl+N/P", key_style),
+            Span::styled(" next/prev patch  ", dim),
+            Span::styled("Ctrl+F", key_style),
+            Span::styled(" fps  ", dim),
+            Span::styled("Ctrl+T", key_style),
+            Span::styled(" theme  ", dim),
+            Span::styled("Ctrl+D", key_style),
+            Span::styled(" scope DC  ", dim),
+            Span::styled("Ctrl+A", key_style),
+            Span::styled(" scope auto-scale  ", dim),
+            Span::styled("Ctrl+↑/↓", key_style),
+            Span::styled(" scope zoom  ", dim),
+            Span::styled("wheel/drag", key_style),
+            Span::styled(" scope zoom/pan  ", dim),
+            Span::styled("Ctrl+L", key_style),
+            Span::styled(" scope synth/input  ", dim),
+            Span::styled("Ctrl+R", key_style),
+            Span::styled(" scope grid  ", dim),
+            Span::styled("Ctrl+G", key_style),
+            Span::styled(" afterglow  ", dim),
+            Span::styled("Ctrl+←/→", key_style),
+            Span::styled(" afterglow depth  ", dim),
+            Span::styled("Ctrl+E/K", key_style),
+            Span::styled(" scope shades/contrast  ", dim),
+            Span::styled("Ctrl+B", key_style),
+            Span::styled(" scope black and white  ", dim),
+            Span::styled("Ctrl+V", key_style),
+            Span::styled(" scope voice  ", dim),
+            Span::styled("Ctrl+Y", key_style),
+            Span::styled(" scope pitches  ", dim),
+            Span::styled("Ctrl+X", key_style),
+            Span::styled(" freeze spectrum to wavetable  ", dim),
+            Span::styled("Ctrl+W", key_style),
+            Span::styled(" spectrum weighting  ", dim),
+            Span::styled("End", key_style),
+            Span::styled(" panic  ", dim),
             Span::styled("q", key_style),
             Span::styled(" quit  ", dim),
             Span::styled("Ctrl+C", key_style),
             Span::styled(" quit", dim),
-        ])
+        ]);
+
+        Line::from(spans)
     };
 
     let line2 = Line::from(vec![
         Span::styled("Focus: ", dim),
         Span::styled(focus_name, strong),
+        Span::styled(
+            if ui.preset_name.is_some() {
+                "  |  Preset: "
+            } else {
+                ""
+            },
+            dim,
+        ),
+        Span::styled(ui.preset_name.clone().unwrap_or_default(), strong),
         Span::styled("  |  Patch: ", dim),
         Span::styled(ui.patch_name.clone(), strong),
         Span::styled("  |  Wave: ", dim),
-        Span::styled(ui.wave.name().to_string(), strong),
+        Span::styled(ui.wave.label(ui.pulse_duty), strong),
+        Span::styled("  |  Split ", dim),
+        Span::styled(
+            if ui.split {
+                ui.split_wave.label(ui.pulse_duty)
+            } else {
+                "off".to_string()
+            },
+            strong,
+        ),
         Span::styled("  |  LP ", dim),
         Span::styled(format!("{:.0}Hz", ui.lowpass.cutoff_hz), strong),
         Span::styled("  |  Vol ", dim),
         Span::styled(format!("{:.2}", ui.volume), strong),
         Span::styled(
             if ui.muted { " Muted" } else { "" },
-            Style::default().fg(kdr::ORANGE).bold(),
+            Style::default().fg(colors::accent()).bold(),
         ),
         Span::styled("  |  Oct ", dim),
         Span::styled(
@@ -1606,16 +6149,161 @@ fn draw_help(f: &mut ratatui::Frame, area: Rect, ui: &UiState) {
             if ui.octave == 0 {
                 strong
             } else {
-                Style::default().fg(kdr::YELLOW).bold()
+                Style::default().fg(colors::highlight()).bold()
+            },
+        ),
+        Span::styled("  |  Tr ", dim),
+        Span::styled(
+            format!("{:+}", ui.transpose),
+            if ui.transpose == 0 {
+                strong
+            } else {
+                Style::default().fg(colors::highlight()).bold()
+            },
+        ),
+        Span::styled(
+            if ui.bend == 0.0 {
+                String::new()
+            } else {
+                format!(" Bend {:+.1}", ui.bend * ui.bend_range_st)
+            },
+            Style::default().fg(colors::highlight()).bold(),
+        ),
+        Span::styled("  |  A4 ", dim),
+        Span::styled(
+            format!("{:.0}Hz", ui.a4_hz),
+            if (ui.a4_hz - BASE_FREQ).abs() < f32::EPSILON {
+                strong
+            } else {
+                Style::default().fg(colors::highlight()).bold()
+            },
+        ),
+        Span::styled("  |  Input ", dim),
+        Span::styled(ui.input.name(), strong),
+        Span::styled("  |  ", dim),
+        Span::styled(
+            ui.voice_mode.name(),
+            if ui.voice_mode == VoiceMode::Poly {
+                strong
+            } else {
+                Style::default().fg(colors::highlight()).bold()
+            },
+        ),
+        Span::styled(
+            if ui.sustain { " Sus" } else { "" },
+            Style::default().fg(colors::highlight()).bold(),
+        ),
+        Span::styled(
+            if ui.chord == Chord::Off {
+                String::new()
+            } else {
+                format!(" Chord {}", ui.chord.name())
+            },
+            Style::default().fg(colors::highlight()).bold(),
+        ),
+        Span::styled(
+            if ui.quantizer.enabled() {
+                format!(" {}", ui.quantizer.label())
+            } else {
+                String::new()
+            },
+            Style::default().fg(colors::highlight()).bold(),
+        ),
+        Span::styled(
+            if ui.tuning == Tuning::Equal {
+                String::new()
+            } else {
+                format!(" {} {}", ui.quantizer.root.name(), ui.tuning.label())
+            },
+            Style::default().fg(colors::highlight()).bold(),
+        ),
+        Span::styled(
+            if ui.arp.enabled { " Arp" } else { "" },
+            Style::default().fg(colors::highlight()).bold(),
+        ),
+        Span::styled("  |  Tempo ", dim),
+        Span::styled(format!("{:.0}", ui.tempo.bpm), strong),
+        Span::styled(
+            format!(" {}/{BEATS_PER_BAR}", ui.beat % BEATS_PER_BAR + 1),
+            dim,
+        ),
+        Span::styled("  |  Voices ", dim),
+        Span::styled(
+            format!("{}/{}", ui.active_voices, ui.max_voices),
+            if ui.active_voices >= ui.max_voices {
+                Style::default().fg(colors::accent()).bold()
+            } else {
+                strong
+            },
+        ),
+        Span::styled("  |  FPS ", dim),
+        Span::styled(
+            ui.measured_fps.map_or_else(
+                || format!("-/{}", ui.fps),
+                |fps| format!("{fps:.0}/{}", ui.fps),
+            ),
+            strong,
+        ),
+        Span::styled(
+            ui.frame_times.map_or_else(String::new, |t| {
+                format!(
+                    " ({:.1}ms avg, {:.0}-{:.0}ms)",
+                    t.avg.as_secs_f64() * 1000.0,
+                    t.min.as_secs_f64() * 1000.0,
+                    t.max.as_secs_f64() * 1000.0
+                )
+            }),
+            dim,
+        ),
+        Span::styled("  |  Audio ", dim),
+        Span::styled(
+            ui.audio_hz
+                .map_or_else(|| "-".to_string(), |hz| format!("{hz:.0}/s")),
+            strong,
+        ),
+        Span::styled("  |  DSP ", dim),
+        Span::styled(
+            format!("{:.0}%", ui.audio_load.load() * 100.0),
+            if ui.audio_load.load() >= AUDIO_LOAD_WARN {
+                Style::default().fg(colors::accent()).bold()
+            } else {
+                strong
+            },
+        ),
+        Span::styled(
+            match ui.audio_load.underruns() {
+                0 => String::new(),
+                n => format!(" {n} xruns"),
             },
+            Style::default().fg(colors::accent()).bold(),
+        ),
+        Span::styled(
+            ui.recording.as_ref().map_or_else(String::new, |rec| {
+                let elapsed = rec.started.elapsed();
+                let left = rec
+                    .limit
+                    .map(|limit| limit.saturating_sub(elapsed).as_secs())
+                    .filter(|&left| left < RECORD_WARN_S);
+                match left {
+                    Some(left) => format!(
+                        "  ● REC {} stops in {}s",
+                        clock(elapsed.as_secs()),
+                        left + 1
+                    ),
+                    None => format!("  ● REC {}", clock(elapsed.as_secs())),
+                }
+            }),
+            Style::default().fg(colors::accent()).bold(),
         ),
     ]);
 
+    let status = Line::from(Span::styled(ui.status.clone(), dim));
+
     f.render_widget(
-        Paragraph::new(vec![Line::from(""), line1, line2])
+        Paragraph::new(vec![status, line1, line2])
             .alignment(Alignment::Center)
             .wrap(Wrap { trim: true })
-            .style(Style::default().bg(kdr::BG0)),
+            .style(Style::default().bg(colors::bg())),
         area,
     );
 }
@@ -1647,8 +6335,8 @@ fn centered_rect(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
 fn outer_block() -> Block<'static> {
     Block::default()
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(kdr::BORDER))
-        .style(Style::default().bg(kdr::BG0).fg(kdr::FG))
+        .border_style(Style::default().fg(colors::border()))
+        .style(Style::default().bg(colors::bg()).fg(colors::fg()))
 }
 
 #[must_use]
@@ -1657,19 +6345,19 @@ fn panel_block(title: &'static str, focused: bool) -> Block<'static> {
         .borders(Borders::ALL)
         .title(tab_title(title, focused))
         .border_style(if focused {
-            Style::default().fg(kdr::FG)
+            Style::default().fg(colors::fg())
         } else {
-            Style::default().fg(kdr::BORDER)
+            Style::default().fg(colors::border())
         })
-        .style(Style::default().bg(kdr::BG0))
+        .style(Style::default().bg(colors::bg()))
 }
 
 #[must_use]
 fn panel_style(focused: bool) -> Style {
     if focused {
-        Style::default().fg(kdr::FG).bg(kdr::BG0)
+        Style::default().fg(colors::fg()).bg(colors::bg())
     } else {
-        Style::default().fg(kdr::MUTED).bg(kdr::BG0)
+        Style::default().fg(colors::muted()).bg(colors::bg())
     }
 }
 
@@ -1677,9 +6365,9 @@ fn panel_style(focused: bool) -> Style {
 fn tab_title(name: &'static str, focused: bool) -> Span<'static> {
     let title = format!(" {name} ");
     if focused {
-        Span::styled(title, Style::default().fg(kdr::ORANGE).bold())
+        Span::styled(title, Style::default().fg(colors::accent()).bold())
     } else {
-        Span::styled(title, Style::default().fg(kdr::MUTED))
+        Span::styled(title, Style::default().fg(colors::muted()))
     }
 }
 
@@ -1687,13 +6375,13 @@ fn tab_title(name: &'static str, focused: bool) -> Span<'static> {
 fn simple_select_line(selected: bool, text: &str) -> Line<'static> {
     if selected {
         Line::from(vec![
-            Span::styled("› ", Style::default().fg(kdr::ORANGE).bold()),
-            Span::styled(text.to_string(), Style::default().fg(kdr::FG).bold()),
+            Span::styled("› ", Style::default().fg(colors::accent()).bold()),
+            Span::styled(text.to_string(), Style::default().fg(colors::fg()).bold()),
         ])
     } else {
         Line::from(vec![
-            Span::styled("  ", Style::default().fg(kdr::MUTED)),
-            Span::styled(text.to_string(), Style::default().fg(kdr::FG)),
+            Span::styled("  ", Style::default().fg(colors::muted())),
+            Span::styled(text.to_string(), Style::default().fg(colors::fg())),
         ])
     }
 }
@@ -1712,27 +6400,27 @@ fn kv_line(width: usize, selected: bool, label: &str, hint: &str, value: &str) -
     let pad = " ".repeat(pad_len + min_gap);
 
     let prefix_style = if selected {
-        Style::default().fg(kdr::ORANGE).bold()
+        Style::default().fg(colors::accent()).bold()
     } else {
-        Style::default().fg(kdr::MUTED)
+        Style::default().fg(colors::muted())
     };
 
     let label_style = if selected {
-        Style::default().fg(kdr::FG).bold()
+        Style::default().fg(colors::fg()).bold()
     } else {
-        Style::default().fg(kdr::FG)
+        Style::default().fg(colors::fg())
     };
 
     let value_style = if selected {
-        Style::default().fg(kdr::FG).bold()
+        Style::default().fg(colors::fg()).bold()
     } else {
-        Style::default().fg(kdr::FG)
+        Style::default().fg(colors::fg())
     };
 
     Line::from(vec![
         Span::styled(prefix, prefix_style),
         Span::styled(left_label, label_style),
-        Span::styled(hint.to_string(), Style::default().fg(kdr::MUTED)),
+        Span::styled(hint.to_string(), Style::default().fg(colors::muted())),
         Span::raw(pad),
         Span::styled(value.to_string(), value_style),
         Span::raw(" ".repeat(right_padding)),
@@ -1808,3 +6496,257 @@ fn i32_to_usize(value: i32) -> usize {
 fn u16_to_usize(value: u16) -> usize {
     usize::from(value)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::MugenError;
+    use crate::play::capture::{CaptureWriter, VoiceTap};
+    use std::collections::HashSet;
+    use std::f32::consts::TAU;
+    use std::sync::atomic::AtomicUsize;
+
+    static RESTORED: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_restore() {
+        RESTORED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn tui_guard_restores_on_an_error_and_on_a_panic_elsewhere() {
+        // the UI giving up once the engine found no output device
+        let ui = || -> Result<()> {
+            let _guard = TuiGuard::new(count_restore);
+            Err(MugenError::AudioDevice("no output device".into()))
+        };
+        let before = RESTORED.load(Ordering::SeqCst);
+        assert!(ui().is_err());
+        assert!(RESTORED.load(Ordering::SeqCst) > before);
+
+        let _guard = TuiGuard::new(count_restore);
+        let before = RESTORED.load(Ordering::SeqCst);
+        let audio = std::thread::spawn(|| panic!("the audio thread fell over"));
+        assert!(audio.join().is_err());
+        assert!(RESTORED.load(Ordering::SeqCst) > before, "from the hook");
+    }
+
+    fn shading(shades: usize, contrast: f32) -> ScopeShading {
+        ScopeShading {
+            shades,
+            contrast,
+            mono: false,
+        }
+    }
+
+    #[test]
+    fn shading_uses_the_configured_number_of_shades() {
+        for shades in SCOPE_SHADE_STEPS {
+            let steps: HashSet<usize> = (0..=1000)
+                .map(|i| shading(shades, 1.0).step(i as f32 / 1000.0))
+                .collect();
+            assert_eq!(steps.len(), shades);
+            assert_eq!(steps.iter().max(), Some(&(shades - 1)));
+        }
+    }
+
+    #[test]
+    fn contrast_bends_the_levels() {
+        // 0.5 through x^c onto 16 shades
+        assert_eq!(shading(16, 1.0).step(0.5), 8);
+        assert_eq!(shading(16, 2.0).step(0.5), 4);
+        assert_eq!(shading(16, 0.5).step(0.5), 11);
+
+        // the ends stay put whatever the curve
+        for contrast in SCOPE_CONTRAST_STEPS {
+            assert_eq!(shading(8, contrast).step(0.0), 0);
+            assert_eq!(shading(8, contrast).step(1.0), 7);
+        }
+    }
+
+    #[test]
+    fn mono_is_black_and_white() {
+        let mono = ScopeShading {
+            shades: 16,
+            contrast: 1.0,
+            mono: true,
+        };
+        let colors: HashSet<Color> = (0..=100)
+            .map(|i| mono.heat(i as f32 / 100.0))
+            .chain((0..=100).map(|i| mono.trace(Color::Red, i as f32 / 100.0)))
+            .collect();
+
+        assert_eq!(colors, HashSet::from([Color::Black, Color::White]));
+    }
+
+    #[test]
+    fn intro_modes_always_and_never() {
+        let now = Instant::now();
+        assert!(!Intro::new(IntroMode::Never, None, now, 1).visible(now));
+
+        let mut intro = Intro::new(IntroMode::Always, None, now, 1);
+        assert!(intro.visible(now));
+        intro.dismiss();
+        assert!(!intro.visible(now));
+    }
+
+    #[test]
+    fn intro_ends_after_its_duration() {
+        let now = Instant::now();
+        let mut intro = Intro::new(IntroMode::Always, None, now, 1);
+        let duration = Duration::from_millis(INTRO_DURATION_MS);
+
+        assert!(intro.visible(now + duration / 2));
+        assert!(!intro.visible(now + duration));
+    }
+
+    #[test]
+    fn daily_intro_plays_once_a_day() {
+        let stamp = std::env::temp_dir()
+            .join(format!("mugen-intro-{}", std::process::id()))
+            .join("intro_stamp");
+        let _ = fs::remove_file(&stamp);
+        let now = Instant::now();
+
+        assert!(Intro::new(IntroMode::Daily, Some(&stamp), now, 100).visible(now));
+        assert!(!Intro::new(IntroMode::Daily, Some(&stamp), now, 100).visible(now));
+        assert!(Intro::new(IntroMode::Daily, Some(&stamp), now, 101).visible(now));
+
+        let _ = fs::remove_dir_all(stamp.parent().unwrap());
+    }
+
+    #[test]
+    fn sparkline_maps_levels_to_bars() {
+        assert_eq!(
+            sparkline(&[-1.0, -0.5, 0.0, 0.5, 1.0, 2.0, -2.0]),
+            "▁▃▅▆██▁"
+        );
+    }
+
+    #[test]
+    fn sparkline_points_pick_evenly_and_normalize() {
+        let samples: Vec<f32> = (0..64).map(|i| if i % 8 < 4 { 0.0 } else { 0.5 }).collect();
+        let points = sparkline_points(&samples, 8).unwrap();
+
+        assert_eq!(points.len(), 8);
+        assert!(points.iter().all(|p| p.abs() < 1e-6), "{points:?}");
+        assert_eq!(
+            sparkline(&sparkline_points(&samples, 16).unwrap()),
+            "▅█".repeat(8)
+        );
+        assert!(sparkline_points(&[0.0; 64], 8).is_none());
+    }
+
+    #[test]
+    fn copied_sparkline_comes_from_the_capture() {
+        let capture = Capture::new();
+        capture.set_dc_block(false);
+        let mut writer = CaptureWriter::new(capture.clone(), 1, 48_000);
+        // period of 48 frames, so the evenly spaced picks land on both halves
+        for i in 0..SCOPE_FRAMES {
+            writer.push(if i % 48 < 24 { 0.25 } else { -0.25 });
+        }
+
+        let trace = scope_trace(&capture, Trigger::Off, 0);
+        let samples: Vec<f32> = trace[0].iter().map(|&(_, s)| s as f32).collect();
+        let line = sparkline(&sparkline_points(&samples, SPARKLINE_W).unwrap());
+
+        assert_eq!(line.chars().count(), SPARKLINE_W);
+        assert!(line.chars().all(|c| c == '█' || c == '▁'), "{line}");
+    }
+
+    #[test]
+    fn just_third_reports_its_pitch_and_cents() {
+        let e4 = Key::new(Note::E, 4);
+        let (hz, cents) = voice_pitch(e4, &Tuning::Just, Note::C, 440.0, 1.0);
+
+        // a pure 5:4 above equal-tempered C4
+        let c4 = Key::new(Note::C, 4).frequency_at(440.0);
+        assert!((hz - c4 * 1.25).abs() < 1e-3, "{hz}");
+        assert!((cents + 13.686).abs() < 0.01, "{cents}");
+
+        let (hz, cents) = voice_pitch(e4, &Tuning::Equal, Note::C, 440.0, 1.0);
+        assert!((hz - 329.628).abs() < 1e-2, "{hz}");
+        assert!(cents.abs() < 1e-3, "{cents}");
+    }
+
+    #[test]
+    fn note_trigger_starts_the_window_at_the_onset() {
+        let capture = Capture::new();
+        capture.set_dc_block(false);
+        let mut writer = CaptureWriter::new(capture.clone(), 1, 48_000);
+        for _ in 0..1000 {
+            writer.push(0.0);
+        }
+
+        // a note starting mid-capture, its first sample marks the onset
+        let tap = VoiceTap::new(Capture::new(), capture.clone());
+        let note: Vec<f32> = (1..=1500).map(|i| i as f32 / 1500.0).collect();
+        let voice = tap.apply(
+            Box::new(rodio::buffer::SamplesBuffer::new(1, 48_000, note.clone())),
+            true,
+        );
+        for s in voice {
+            writer.push(s);
+        }
+
+        let trace = scope_trace(&capture, Trigger::NoteOn, 0);
+        let samples: Vec<f32> = trace[0].iter().map(|&(_, s)| s as f32).collect();
+        assert_eq!(samples.len(), SCOPE_FRAMES);
+        assert_eq!(samples, note[..SCOPE_FRAMES]);
+
+        // free running shows the newest window instead
+        let edge = scope_trace(&capture, Trigger::Off, 0);
+        assert_ne!(edge[0][0].1, f64::from(note[0]));
+    }
+
+    #[test]
+    fn rising_trigger_starts_the_trace_on_the_edge() {
+        let capture = Capture::new();
+        capture.set_dc_block(false);
+        let mut writer = CaptureWriter::new(capture.clone(), 1, 48_000);
+        // a saw climbing through the trigger level every 100 frames
+        for i in 0..SCOPE_FRAMES * 2 {
+            writer.push((i % 100) as f32 / 50.0 - 1.0);
+        }
+
+        let trace = scope_trace(&capture, Trigger::Rising, 0);
+        assert_eq!(trace[0].len(), SCOPE_FRAMES);
+        let first = trace[0][0].1 as f32;
+        assert!(first >= SCOPE_TRIGGER_LEVEL && first - SCOPE_TRIGGER_LEVEL < 0.02);
+    }
+
+    #[test]
+    fn mono_frames_average_the_channels() {
+        let capture = Capture::new();
+        capture.set_dc_block(false);
+        let mut writer = CaptureWriter::new(capture.clone(), 2, 48_000);
+        for _ in 0..16 {
+            writer.push(1.0);
+            writer.push(0.5);
+        }
+
+        assert_eq!(mono_frames(&capture, 8), vec![0.75; 8]);
+    }
+
+    #[test]
+    fn spectrogram_peaks_in_the_column_of_the_tone() {
+        let capture = Capture::new();
+        capture.set_dc_block(false);
+        let mut writer = CaptureWriter::new(capture.clone(), 1, 48_000);
+        for i in 0..SPECTRUM_FRAMES {
+            writer.push((TAU * 1000.0 * i as f32 / 48_000.0).sin());
+        }
+
+        let cols = 64;
+        let row = spectrogram_row(&capture, 48_000, cols, Weighting::Flat);
+        assert_eq!(row.len(), cols);
+        assert!(row.iter().all(|level| (0.0..=1.0).contains(level)));
+
+        // 1 kHz sits this far along the log axis from 20 Hz to 24 kHz
+        let at = (1000f32 / 20.0).log10() / (24_000f32 / 20.0).log10() * cols as f32;
+        let loudest = (0..cols)
+            .max_by(|&a, &b| row[a].total_cmp(&row[b]))
+            .unwrap();
+        assert!(loudest.abs_diff(at as usize) <= 1, "{loudest} vs {at}");
+    }
+}
@@ -0,0 +1,59 @@
+//! Launching on a patch file sets the engine up from the file
+
+use std::fs;
+use synth_rs::audio::State;
+use synth_rs::patch::chain::Fx;
+use synth_rs::patch::effects::adsr::EnvelopeMode;
+use synth_rs::patch::oscilators::basic::Wave;
+use synth_rs::presets::start_patch;
+
+const PATCH: &str = r#"
+wave = "square"
+effects = ["lowpass", "bitcrush", "gain"]
+
+[adsr]
+attack_s = 0.25
+decay_s = 0.5
+sustain = 0.4
+release_s = 1.5
+mode = "trigger"
+curve = "linear"
+
+[lowpass]
+cutoff_hz = 900.0
+"#;
+
+#[tokio::test]
+async fn patch_file_sets_the_source_chain_and_envelope() {
+    let dir = std::env::temp_dir().join(format!("mugen-start-patch-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("lead.patch");
+    fs::write(&path, PATCH).unwrap();
+
+    let snapshot = start_patch(path.to_str().unwrap()).await.unwrap();
+    let state = State::from_snapshot(snapshot);
+    let _ = fs::remove_dir_all(&dir);
+
+    assert_eq!(state.wave(), Wave::Square);
+    assert_eq!(state.zone().effects, [Fx::LowPass, Fx::Bitcrush, Fx::Gain]);
+    assert_eq!(
+        state.zone().patch.fx_names(),
+        ["LowPass", "Bitcrush", "Gain", "ADSR", "Gate", "Pan"]
+    );
+
+    let adsr = state.adsr();
+    assert_eq!(
+        (adsr.attack_s, adsr.decay_s, adsr.sustain, adsr.release_s),
+        (0.25, 0.5, 0.4, 1.5)
+    );
+    assert_eq!(adsr.mode, EnvelopeMode::Trigger);
+    assert!((state.lowpass().cutoff_hz - 900.0).abs() < 1e-3);
+}
+
+#[tokio::test]
+async fn missing_patch_file_is_an_error() {
+    let err = start_patch("/nonexistent/mugen/none.patch")
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("none.patch"), "{err}");
+}